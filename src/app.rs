@@ -1,22 +1,158 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use chrono::Local;
 use notify_rust::Notification;
+use std::path::{Path, PathBuf};
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use crate::backend;
+use crate::beautify;
 use crate::capture;
-use crate::cli::{Args, Mode, default_filename, resolve_delay, resolve_notif_timeout};
+use crate::cli::{
+    Action, Args, ListTarget, Mode, apply_profile, detached_args, output_filename, resolve_delay,
+    resolve_filename_template, resolve_format, resolve_freeze, resolve_notif_timeout,
+    resolve_record_format, stdout_flags,
+};
+use crate::completions;
+use crate::compositor;
 use crate::config;
 use crate::config_cmds::{
     handle_config_path, handle_init_config, handle_set_config, handle_show_config,
 };
+use crate::doctor;
+use crate::filename;
 use crate::freeze;
+use crate::geometry::Geometry;
+use crate::hooks;
 use crate::hyprland_cmds::{
-    handle_generate_hyprland_config, handle_install_binds, handle_setup_hotkeys,
+    handle_generate_hyprland_config, handle_install_binds, handle_restore_binds,
+    handle_setup_hotkeys,
 };
+use crate::last_geometry;
+use crate::list_cmds;
+use crate::metadata::Metadata;
+use crate::notify;
+use crate::paste_cmds;
+use crate::record;
 use crate::save;
+use crate::selector;
+use crate::upload;
 use crate::utils;
 
+/// Environment that decides which compositor paths are taken; logged under
+/// `--debug` so bug reports include it.
+const SESSION_ENV_VARS: &[&str] = &[
+    "WAYLAND_DISPLAY",
+    "XDG_CURRENT_DESKTOP",
+    "XDG_SESSION_TYPE",
+    "HYPRLAND_INSTANCE_SIGNATURE",
+    "SWAYSOCK",
+    "I3SOCK",
+];
+
+fn log_session_env() {
+    for name in SESSION_ENV_VARS {
+        match std::env::var(name) {
+            Ok(value) => eprintln!("{}={}", name, value),
+            Err(_) => eprintln!("{} is not set", name),
+        }
+    }
+}
+
+/// Re-run hyprshot-rs without `--detach-delay` in its own process group with
+/// no stdio, so the keybind's exec returns while the child selects, waits and
+/// captures. Notifications come from the child.
+fn spawn_detached(debug: bool) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    let exe = std::env::current_exe().context("Failed to locate the hyprshot-rs executable")?;
+    let child = Command::new(exe)
+        .args(detached_args(std::env::args_os().skip(1)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(if debug {
+            Stdio::inherit()
+        } else {
+            Stdio::null()
+        })
+        .process_group(0)
+        .spawn()
+        .context("Failed to start the background capture")?;
+    if debug {
+        eprintln!("Delayed capture continues in process {}", child.id());
+    }
+    Ok(())
+}
+
+/// `%mode` in filename templates: the `-m` value, with `active-` for `-m active`.
+pub(crate) fn mode_label(mode: &Mode, current: bool) -> &'static str {
+    match (mode, current) {
+        (Mode::Output, true) => "active-output",
+        (Mode::Window, true) => "active-window",
+        (Mode::Output | Mode::OutputName(_) | Mode::Active, _) => "output",
+        (Mode::Window, false) => "window",
+        (Mode::WindowPair, _) => "window-pair",
+        (Mode::Region, _) => "region",
+        (Mode::Layer, _) => "layer",
+        (Mode::All, _) => "all",
+    }
+}
+
+/// The geometry given with `--geometry`, else the one remembered for `--last`.
+fn preset(
+    geometry: Option<Geometry>,
+    last_path: Option<&Path>,
+    mode_key: &str,
+    debug: bool,
+) -> Result<Geometry> {
+    if let Some(geometry) = geometry {
+        if debug {
+            eprintln!("Geometry from --geometry: {}", geometry);
+        }
+        return Ok(geometry);
+    }
+    let path =
+        last_path.context("--last needs a state directory ($XDG_STATE_HOME or ~/.local/state)")?;
+    let geometry = last_geometry::load(path, mode_key)?;
+    if debug {
+        eprintln!("Repeating the last {} capture: {}", mode_key, geometry);
+    }
+    Ok(geometry)
+}
+
+/// Hint for the freeze overlay; only modes that wait for a selection get one.
+pub(crate) fn freeze_hint_text(
+    mode: &Mode,
+    current: bool,
+    named_output: bool,
+) -> Option<&'static str> {
+    match mode {
+        Mode::Region => Some("Drag to select a region - Esc to cancel"),
+        Mode::Window if !current => Some("Click a window - Esc to cancel"),
+        Mode::WindowPair => Some("Click two windows - Esc to cancel"),
+        Mode::Output if !current && !named_output => Some("Click an output - Esc to cancel"),
+        Mode::Layer => Some("Click a bar or panel - Esc to cancel"),
+        _ => None,
+    }
+}
+
+/// The config file, or the defaults with `--no-config` or when it can't be read.
+fn load_config(no_config: bool, debug: bool) -> config::Config {
+    if no_config {
+        if debug {
+            eprintln!("Config loading disabled (--no-config flag)");
+        }
+        return config::Config::default();
+    }
+    config::Config::load().unwrap_or_else(|e| {
+        if debug {
+            eprintln!("Failed to load config, using defaults: {}", e);
+        }
+        config::Config::default()
+    })
+}
+
 pub fn run(mut args: Args) -> Result<()> {
     // Handle config management commands first
     if args.init_config {
@@ -35,6 +171,31 @@ pub fn run(mut args: Args) -> Result<()> {
         return handle_set_config(set_args);
     }
 
+    if args.show_recent {
+        return save::handle_show_recent();
+    }
+
+    if let Some(action) = args.action.clone() {
+        return match action {
+            Action::List {
+                target: ListTarget::Outputs { json },
+            } => list_cmds::handle_list_outputs(json, args.debug),
+            Action::List {
+                target: ListTarget::Windows { json, client_area },
+            } => list_cmds::handle_list_windows(json, client_area, args.debug),
+            Action::Completions { shell } => completions::handle_completions(shell),
+            Action::Doctor => doctor::handle_doctor(args.debug),
+            // Saved like a capture, so the config and --profile apply.
+            Action::Paste { path } => {
+                let mut config = load_config(args.no_config, args.debug);
+                if let Some(name) = args.profile.clone() {
+                    apply_profile(&mut args, &mut config, &name)?;
+                }
+                paste_cmds::handle_paste(&args, &config, path.as_deref())
+            }
+        };
+    }
+
     // Handle Hyprland integration commands
     if args.generate_hyprland_config {
         return handle_generate_hyprland_config(args.with_clipboard);
@@ -44,16 +205,35 @@ pub fn run(mut args: Args) -> Result<()> {
         return handle_install_binds(args.with_clipboard);
     }
 
-    if args.setup_hotkeys {
-        return handle_setup_hotkeys();
+    if args.restore_binds {
+        return handle_restore_binds(args.yes);
+    }
+
+    if args.setup || args.setup_hotkeys {
+        return handle_setup_hotkeys(args.setup);
+    }
+
+    let started = Instant::now();
+    let debug = args.debug;
+    let mut config = load_config(args.no_config, debug);
+    if let Some(name) = args.profile.clone() {
+        apply_profile(&mut args, &mut config, &name)?;
+    }
+
+    // A second `--record` stops the running recording instead of starting one.
+    if args.record && record::stop_running(debug)? {
+        eprintln!("Stopping the running recording");
+        return Ok(());
     }
 
-    if args.mode.is_empty() {
+    if args.mode.is_empty() && !args.print_geometry && args.geometry.is_none() {
         print_help();
         return Ok(());
     }
 
-    let debug = args.debug;
+    if debug {
+        log_session_env();
+    }
     let clipboard_only = args.clipboard_only;
     let raw = args.raw;
     let command = if args.command.is_empty() {
@@ -69,7 +249,12 @@ pub fn run(mut args: Args) -> Result<()> {
     let modes = std::mem::take(&mut args.mode);
     for mode in modes {
         match mode {
-            Mode::Output | Mode::Window | Mode::Region => {
+            Mode::Output
+            | Mode::Window
+            | Mode::WindowPair
+            | Mode::Region
+            | Mode::Layer
+            | Mode::All => {
                 option = Some(mode);
             }
             Mode::Active => {
@@ -81,21 +266,53 @@ pub fn run(mut args: Args) -> Result<()> {
         }
     }
 
-    let option = option.context("A mode is required (output, region, window)")?;
+    // --window-title/--window-class/--address/--pid pick the window without
+    // the selector.
+    let window_filter = capture::WindowFilter {
+        title: args.window_title.take(),
+        class: args.window_class.take(),
+        address: args.address.take(),
+        pid: args.pid,
+    };
+    if !window_filter.is_empty() {
+        if current || !matches!(option, None | Some(Mode::Window)) {
+            bail!("--window-title, --window-class, --address and --pid only work with -m window");
+        }
+        option = Some(Mode::Window);
+    }
 
-    let config = if args.no_config {
-        if debug {
-            eprintln!("Config loading disabled (--no-config flag)");
+    // --geometry is a region that needs no selection.
+    if args.geometry.is_some() {
+        if current || selected_monitor.is_some() || !matches!(option, None | Some(Mode::Region)) {
+            bail!("--geometry only works with -m region");
         }
-        config::Config::default()
-    } else {
-        config::Config::load().unwrap_or_else(|e| {
-            if debug {
-                eprintln!("Failed to load config, using defaults: {}", e);
-            }
-            config::Config::default()
-        })
-    };
+        option = Some(Mode::Region);
+    }
+
+    // --print-geometry on its own is a plain region selection.
+    if args.print_geometry && option.is_none() && !current {
+        option = Some(Mode::Region);
+    }
+    let option =
+        option.context("A mode is required (output, region, window, window-pair, layer, all)")?;
+    if args.split && !matches!(option, Mode::All) {
+        bail!("--split only works with -m all");
+    }
+    if (!args.only_output.is_empty() || !args.exclude_output.is_empty())
+        && !matches!(option, Mode::All)
+    {
+        bail!("--only-output and --exclude-output only work with -m all");
+    }
+    if (args.decorations || args.no_rounding) && !matches!(option, Mode::Window | Mode::WindowPair)
+    {
+        bail!("--decorations and --no-rounding only work with -m window and -m window-pair");
+    }
+    if args.transparent && !matches!(option, Mode::Window) {
+        bail!("--transparent only works with -m window");
+    }
+    if args.padding.is_some() && matches!(option, Mode::Output | Mode::All | Mode::OutputName(_)) {
+        bail!("--padding doesn't work with whole outputs");
+    }
 
     // Apply settings with priority: CLI > config > default
     let silent = if args.silent {
@@ -106,36 +323,184 @@ pub fn run(mut args: Args) -> Result<()> {
 
     let notif_timeout = resolve_notif_timeout(&args, &config);
 
-    let freeze = if args.freeze {
-        true
-    } else {
-        config.advanced.freeze_on_region
-    };
+    // --last and --geometry have nothing to select, so there is nothing to freeze for.
+    let preset_geometry = args.last || args.geometry.is_some();
+    let freeze = resolve_freeze(&args, &config) && !preset_geometry;
 
     let delay = resolve_delay(&args, &config);
 
-    let save_dir = config::get_screenshots_dir(args.output_folder.clone(), &config, debug)?;
+    let format = resolve_format(&args, &config);
+    if args.raw_header
+        && args
+            .raw_format
+            .and_then(save::RawFormat::image_format)
+            .is_some()
+    {
+        bail!("--raw-header only works with --raw-format ppm or bgra");
+    }
+    let quality = args.quality.unwrap_or(config.capture.quality);
+
+    // Fail before the selection rather than after it.
+    if let Some(scale) = config.capture.force_scale {
+        save::validate_scale(scale)?;
+    }
+    if !(1..=100).contains(&quality) {
+        bail!("Invalid capture.quality {}: expected 1-100", quality);
+    }
+    if let Some(fps) = config.record.fps
+        && !(1..=120).contains(&fps)
+    {
+        bail!("Invalid record.fps {}: expected 1-120", fps);
+    }
+    let flatten_background = config::parse_hex_color(&config.capture.flatten_background)
+        .context("Invalid capture.flatten_background")?;
+    let filename_template = resolve_filename_template(&args, &config).to_string();
+    filename::validate(&filename_template)?;
+    let upload = match args.upload.as_deref() {
+        Some(name) => {
+            let (name, target) = upload::resolve_target(&config.upload, name)?;
+            Some((name.to_string(), target.clone()))
+        }
+        None => None,
+    };
+    let beautify = args
+        .beautify
+        .then(|| beautify::Style::from_config(&config.beautify))
+        .transpose()?;
+    let editor = match args.edit_with.as_deref() {
+        Some("") if config.hooks.editor.trim().is_empty() => {
+            bail!("--edit-with needs a command; pass one or set hooks.editor")
+        }
+        Some("") => Some(config.hooks.editor.clone()),
+        Some(command) => Some(command.to_string()),
+        None => None,
+    };
+
+    if args.detach_delay && delay > Duration::from_secs(0) {
+        let printing = stdout_flags(&args);
+        if !printing.is_empty() {
+            bail!(
+                "--detach-delay can't be combined with {}: the background capture has no stdout",
+                printing.join(", ")
+            );
+        }
+        return spawn_detached(debug);
+    }
+
+    hooks::run_pre_capture(
+        &config.hooks.pre_capture,
+        mode_label(&option, current),
+        debug,
+    )?;
+
+    let mut compositor = compositor::detect(debug);
+
+    // Read and changed before the freeze, so the frozen frames show the result.
+    if (args.decorations || args.no_rounding)
+        && compositor::detect_kind(|name| std::env::var_os(name).is_some_and(|v| !v.is_empty()))
+            != compositor::CompositorKind::Hyprland
+    {
+        bail!("--decorations and --no-rounding need Hyprland");
+    }
+    let decorations = args
+        .decorations
+        .then(|| compositor::hyprland_decoration_extents(debug))
+        .transpose()?;
+    let _flat_corners = args
+        .no_rounding
+        .then(|| compositor::FlatCorners::apply(debug))
+        .transpose()?;
+
+    // Pick the output before freezing, so the menu isn't hidden under the overlay.
+    if args.menu
+        && !preset_geometry
+        && matches!(option, Mode::Output)
+        && !current
+        && selected_monitor.is_none()
+    {
+        if config.capture.menu_command.trim().is_empty() {
+            if debug {
+                eprintln!("--menu without capture.menu_command; selecting with the pointer");
+            }
+        } else {
+            let names = capture::output_names(compositor.as_mut(), debug)?;
+            let _timeout = args
+                .selection_timeout
+                .map(selector::SelectionTimeout::start);
+            selected_monitor = Some(selector::select_from_menu(
+                &config.capture.menu_command,
+                &names,
+                debug,
+            )?);
+        }
+    }
 
-    let save_dir = if !clipboard_only && !raw {
-        config::ensure_directory(&save_dir.to_string_lossy())?
+    // One timestamp for the directory tokens and the filename.
+    let now = Local::now();
+    let save_dir = filename::expand_directory(
+        &config::get_screenshots_dir(args.output_folder.clone(), &config, debug)?,
+        now,
+    );
+
+    let save_dir = if !clipboard_only && !raw && !args.print_geometry {
+        config::ensure_directory(
+            &save_dir.to_string_lossy(),
+            config.paths.create_missing_dirs,
+        )?
     } else {
         save_dir
     };
 
-    let filename = args
-        .filename
-        .unwrap_or_else(|| default_filename(Local::now()));
-    let save_fullpath = save_dir.join(&filename);
-
-    if debug && !clipboard_only {
-        eprintln!("Saving in: {}", save_fullpath.display());
+    // Without a selection to wait for (-m active, a named output, --window-title,
+    // ...) the delay comes first, so the geometry and any frozen frames are
+    // both from after it.
+    let selects = !preset_geometry
+        && window_filter.is_empty()
+        && freeze_hint_text(&option, current, selected_monitor.is_some()).is_some();
+    let wait = delay > Duration::from_secs(0) && !args.print_geometry;
+    if wait && !selects {
+        sleep(delay);
     }
 
     let freeze_guard: Option<freeze::FreezeGuard> = if freeze {
         if debug {
             eprintln!("Freeze requested: starting overlay thread");
         }
-        let guard = freeze::start_freeze(selected_monitor.as_deref(), debug)?;
+        let hint = if config.advanced.freeze_hint && window_filter.is_empty() {
+            freeze_hint_text(&option, current, selected_monitor.is_some()).map(|text| {
+                freeze::FreezeHint {
+                    text: text.to_string(),
+                    position: config.advanced.freeze_hint_position,
+                    // Draw on the targeted output, else the one with focus.
+                    output: match selected_monitor.as_deref() {
+                        Some(monitor) => {
+                            capture::grab_selected_output(compositor.as_mut(), monitor, debug).ok()
+                        }
+                        None => capture::grab_active_output(compositor.as_mut(), debug)
+                            .ok()
+                            .map(|(_, geometry)| geometry),
+                    },
+                }
+            })
+        } else {
+            None
+        };
+        let debug_boxes = if config.advanced.freeze_debug_boxes
+            && matches!(option, Mode::Window | Mode::WindowPair)
+            && !current
+        {
+            capture::window_candidates(compositor.as_mut(), args.client_area, debug).unwrap_or_else(
+                |err| {
+                    if debug {
+                        eprintln!("No window boxes to outline: {:#}", err);
+                    }
+                    Vec::new()
+                },
+            )
+        } else {
+            Vec::new()
+        };
+        let guard = freeze::start_freeze(selected_monitor.as_deref(), hint, debug_boxes, debug)?;
         if debug {
             eprintln!("Freeze guard acquired");
         }
@@ -144,65 +509,557 @@ pub fn run(mut args: Args) -> Result<()> {
         None
     };
 
-    if delay > Duration::from_secs(0) {
+    if wait && selects {
         sleep(delay);
     }
 
-    let mut hyprctl_cache = capture::HyprctlCache::new();
+    // Only output captures (and regions clipped to one) know their output.
+    let mut output_name = match option {
+        Mode::Output if !current => selected_monitor.clone(),
+        Mode::Region => selected_monitor.clone(),
+        _ => None,
+    };
+    let output_physical_pixels = matches!(option, Mode::Output)
+        && config.capture.output_physical_pixels
+        && args.aspect.is_none();
 
+    // Untrimmed, so %title and %class can find the window again.
+    let mut window_geometry = None;
+    // -m all with --only-output/--exclude-output: the outputs to keep.
+    let mut kept_outputs = None;
+    let mode_key = mode_label(&option, current);
+    let last_path = last_geometry::state_path();
+    let selection_timeout = args
+        .selection_timeout
+        .filter(|_| !preset_geometry)
+        .map(selector::SelectionTimeout::start);
     let geometry = match option {
+        _ if preset_geometry => preset(args.geometry, last_path.as_deref(), mode_key, debug)?,
         Mode::Output => {
             if current {
-                capture::grab_active_output(debug, &mut hyprctl_cache)?
-            } else if let Some(monitor) = selected_monitor {
-                capture::grab_selected_output(&monitor, debug)?
+                let (name, geometry) = capture::grab_active_output(compositor.as_mut(), debug)?;
+                if output_physical_pixels {
+                    output_name = Some(name);
+                }
+                geometry
+            } else if let Some(monitor) = selected_monitor.as_deref() {
+                capture::grab_selected_output(compositor.as_mut(), monitor, debug)?
             } else {
-                capture::grab_output(debug)?
+                let (geometry, name) = capture::grab_output(debug)?;
+                output_name = name;
+                geometry
             }
         }
-        Mode::Region => match capture::grab_region(debug) {
-            Ok(geo) => geo,
-            Err(err) => {
-                if !silent && capture::is_region_selection_cancelled(&err) {
-                    let _ = Notification::new()
-                        .summary("Region mode")
-                        .body("Drag to select an area (not a window/output).")
-                        .appname("Hyprshot-rs")
-                        .timeout(notif_timeout as i32)
-                        .show();
+        Mode::Region => {
+            let constrain = args.constrain_output || config.advanced.constrain_to_output;
+            match capture::grab_region(
+                compositor.as_mut(),
+                selected_monitor.as_deref(),
+                constrain,
+                debug,
+            ) {
+                Ok(geo) => geo,
+                Err(err) => {
+                    if !silent
+                        && capture::is_region_selection_cancelled(&err)
+                        && notify::daemon_available(debug)
+                    {
+                        let _ = Notification::new()
+                            .summary("Region mode")
+                            .body("Drag to select an area (not a window/output).")
+                            .appname("Hyprshot-rs")
+                            .timeout(notif_timeout as i32)
+                            .show();
+                    }
+                    return Err(err);
                 }
-                return Err(err);
             }
-        },
+        }
         Mode::Window => {
             let geo = if current {
-                capture::grab_active_window(debug)?
+                capture::grab_active_window(compositor.as_mut(), args.client_area, debug)?
+            } else if !window_filter.is_empty() {
+                capture::find_window(compositor.as_mut(), &window_filter, args.client_area, debug)?
             } else {
-                capture::grab_window(debug, &mut hyprctl_cache)?
+                capture::grab_window(compositor.as_mut(), args.client_area, debug)?
+            };
+            window_geometry = Some(geo);
+            match decorations {
+                Some(extents) => utils::trim(&extents.expand(geo)?, debug)?,
+                None => utils::trim(&geo, debug)?,
+            }
+        }
+        Mode::WindowPair => {
+            let first = capture::grab_window(compositor.as_mut(), args.client_area, debug)?;
+            let second = capture::grab_window(compositor.as_mut(), args.client_area, debug)?;
+            let bounds = first.union(second);
+            let bounds = match decorations {
+                Some(extents) => extents.expand(bounds)?,
+                None => bounds,
             };
-            utils::trim(&geo, debug)?
+            if debug {
+                eprintln!("Window pair bounding box: {}", bounds);
+            }
+            utils::trim(&bounds, debug)?
+        }
+        Mode::Layer => capture::grab_layer(compositor.as_mut(), debug)?,
+        Mode::All => {
+            let (geometry, outputs) =
+                capture::grab_all_outputs(&args.only_output, &args.exclude_output, debug)?;
+            if !args.only_output.is_empty() || !args.exclude_output.is_empty() {
+                kept_outputs = Some(outputs);
+            }
+            geometry
         }
         _ => unreachable!(),
     };
+    drop(selection_timeout);
+    if !preset_geometry
+        && let Some(path) = &last_path
+        && let Err(err) = last_geometry::store(path, mode_key, &geometry)
+        && debug
+    {
+        eprintln!("Failed to remember the geometry for --last: {:#}", err);
+    }
+
+    let geometry = match args.padding {
+        Some(padding) => {
+            let padded = geometry.grow(padding)?;
+            if debug {
+                eprintln!("Padded geometry: {}", padded);
+            }
+            utils::trim(&padded, debug)?
+        }
+        None => geometry,
+    };
+
+    let geometry = match args.aspect {
+        Some(aspect) => {
+            let adjusted = geometry.fit_aspect(aspect, args.aspect_mode)?;
+            if debug {
+                eprintln!("Aspect-adjusted geometry: {}", adjusted);
+            }
+            utils::trim(&adjusted, debug)?
+        }
+        None => geometry,
+    };
+
+    // Dragged while the screen is still frozen, so the boxes line up with it.
+    let obscure = match args.obscure {
+        Some(style) => {
+            if !silent && notify::daemon_available(debug) {
+                let _ = Notification::new()
+                    .summary("Obscure")
+                    .body("Drag boxes to hide, then press Escape.")
+                    .appname("Hyprshot-rs")
+                    .timeout(notif_timeout as i32)
+                    .show();
+            }
+            let boxes = selector::select_boxes_within(&geometry, debug)?;
+            if debug {
+                eprintln!("Obscuring {} box(es)", boxes.len());
+            }
+            Some((style, boxes))
+        }
+        None => None,
+    };
 
-    if let Some(guard) = freeze_guard {
-        guard.stop()?;
+    // The frozen frames are kept even if the overlay ended early; a freeze
+    // failure is only a warning.
+    let mut frozen_frames = Vec::new();
+    if let Some(mut guard) = freeze_guard {
+        frozen_frames = guard.take_frames();
+        if let Err(err) = guard.stop() {
+            eprintln!("Warning: freeze ended early: {:#}", err);
+        }
     }
 
-    save::save_geometry(
-        &geometry,
-        &save_fullpath,
+    if args.print_geometry {
+        println!("{}", geometry);
+        return Ok(());
+    }
+
+    let mut metadata = Metadata::new(args.metadata.unwrap_or(config.capture.metadata), now);
+    let names_window = args.filename.is_none()
+        && (filename::uses(&filename_template, "title")
+            || filename::uses(&filename_template, "class"));
+    let wants_window = names_window
+        || metadata
+            .as_ref()
+            .is_some_and(|metadata| metadata.level == config::MetadataLevel::Full);
+    let window = window_geometry
+        .filter(|_| wants_window)
+        .and_then(|geometry| {
+            capture::window_with_geometry(compositor.as_mut(), &geometry, args.client_area, debug)
+        });
+    if let (Some(metadata), Some(window)) = (metadata.as_mut(), &window) {
+        metadata.title = Some(window.title.clone());
+        metadata.class = Some(window.class.clone());
+        metadata.workspace = window.workspace.clone();
+    }
+
+    let mut fields = filename::Fields::new(now);
+    fields.mode = Some(mode_label(&option, current).to_string());
+    if args.filename.is_none() {
+        if filename::uses(&filename_template, "output") {
+            fields.output = output_name
+                .clone()
+                .or_else(|| capture::output_at(compositor.as_mut(), &geometry, debug));
+        }
+        if names_window && let Some(window) = window {
+            fields.title = Some(window.title);
+            fields.class = Some(window.class);
+        }
+    }
+    let filename = output_filename(&args, format, &filename_template, &save_dir, &mut fields);
+    let mut save_fullpath = save_dir.join(&filename);
+    if !clipboard_only && !raw && !args.record && !args.split {
+        save_fullpath = filename::resolve_conflict(&save_fullpath, config.paths.on_conflict)?;
+    }
+
+    if debug && !clipboard_only {
+        eprintln!("Saving in: {}", save_fullpath.display());
+    }
+
+    let include_cursor = args.cursor || config.capture.include_cursor;
+    #[cfg(feature = "grim")]
+    let mut backend = backend::create_backend(include_cursor, debug)?;
+    #[cfg(not(feature = "grim"))]
+    compile_error!("Feature 'grim' must be enabled to save screenshots");
+
+    if args.record && args.still {
+        let dir = filename::resolve_conflict(
+            &save_fullpath.with_extension(""),
+            config.paths.on_conflict,
+        )?;
+        let max_duration = args.max_duration.or_else(|| {
+            (config.record.max_duration_ms > 0)
+                .then(|| Duration::from_millis(config.record.max_duration_ms))
+        });
+        let frames = record::record_stills(
+            backend.as_mut(),
+            &geometry,
+            &dir,
+            args.every.unwrap_or(record::STILL_INTERVAL),
+            max_duration,
+            args.png_compression
+                .unwrap_or(config.capture.png_compression),
+            debug,
+        )?;
+        if args.json {
+            let outcome = save::SaveOutcome {
+                path: Some(dir.clone()),
+                ..save::SaveOutcome::default()
+            };
+            let output = output_name
+                .clone()
+                .or_else(|| capture::output_at(compositor.as_mut(), &geometry, debug));
+            println!(
+                "{}",
+                save::json_report(
+                    &outcome,
+                    &geometry,
+                    mode_label(&option, current),
+                    output.as_deref(),
+                    "png",
+                    started.elapsed(),
+                )
+            );
+        }
+        if !silent
+            && notify::daemon_available(debug)
+            && let Err(err) = Notification::new()
+                .summary("Timelapse saved")
+                .body(&format!(
+                    "{} frame(s) saved in <i>{}</i>.",
+                    frames,
+                    dir.display()
+                ))
+                .timeout(notif_timeout as i32)
+                .appname("Hyprshot-rs")
+                .show()
+        {
+            eprintln!("Warning: failed to show notification: {}", err);
+        }
+        return Ok(());
+    }
+
+    if args.record {
+        let record_format = resolve_record_format(&args, &config);
+        let video_path = filename::resolve_conflict(
+            &record::video_path(&save_fullpath, record_format),
+            config.paths.on_conflict,
+        )?;
+        let max_duration = args.max_duration.or_else(|| {
+            (config.record.max_duration_ms > 0)
+                .then(|| Duration::from_millis(config.record.max_duration_ms))
+        });
+        let options = record::RecordOptions {
+            fps: args
+                .fps
+                .or(config.record.fps)
+                .unwrap_or_else(|| record::default_fps(record_format)),
+            max_duration,
+        };
+        record::record(backend.as_mut(), &geometry, &video_path, &options, debug)?;
+        if args.json {
+            let outcome = save::SaveOutcome {
+                path: Some(video_path.clone()),
+                ..save::SaveOutcome::default()
+            };
+            let output = output_name
+                .clone()
+                .or_else(|| capture::output_at(compositor.as_mut(), &geometry, debug));
+            println!(
+                "{}",
+                save::json_report(
+                    &outcome,
+                    &geometry,
+                    mode_label(&option, current),
+                    output.as_deref(),
+                    record_format.extension(),
+                    started.elapsed(),
+                )
+            );
+        }
+        if !silent
+            && notify::daemon_available(debug)
+            && let Err(err) = Notification::new()
+                .summary("Recording saved")
+                .body(&format!("Video saved in <i>{}</i>.", video_path.display()))
+                .timeout(notif_timeout as i32)
+                .appname("Hyprshot-rs")
+                .show()
+        {
+            eprintln!("Warning: failed to show notification: {}", err);
+        }
+        return Ok(());
+    }
+
+    // Crop the screenshot from what the freeze overlay showed. The frozen
+    // frames have no pointer, so cursor captures stay live.
+    if !frozen_frames.is_empty() && !include_cursor {
+        if debug {
+            eprintln!("Capturing from {} frozen frame(s)", frozen_frames.len());
+        }
+        backend = Box::new(backend::FrozenBackend::new(frozen_frames, backend, debug));
+    }
+
+    // Outputs left out of -m all are transparent, not just cropped around.
+    if let Some(outputs) = kept_outputs {
+        backend = Box::new(backend::MaskedBackend::new(outputs, backend));
+    }
+
+    // The window's own buffer has nothing of the screen behind it to crop.
+    if args.transparent {
+        let window = window_geometry
+            .and_then(|geometry| {
+                capture::window_with_geometry(
+                    compositor.as_mut(),
+                    &geometry,
+                    args.client_area,
+                    debug,
+                )
+            })
+            .context("Couldn't look up the window for --transparent")?;
+        if debug {
+            eprintln!(
+                "Copying '{}' ({}) from its own buffer",
+                window.title, window.class
+            );
+        }
+        backend = backend::create_window_backend(&window, include_cursor, backend)?;
+    }
+
+    let mode = mode_label(&option, current);
+    let options = save::SaveOptions {
         clipboard_only,
+        clipboard: config.capture.clipboard_on_capture,
+        flash: config.capture.flash,
+        clipboard_uri: config.capture.clipboard_uri,
+        png_compression: args
+            .png_compression
+            .unwrap_or(config.capture.png_compression),
+        optimize: args.optimize,
+        format,
+        quality,
+        flatten_background,
+        output_physical_pixels,
+        force_scale: config.capture.force_scale,
         raw,
+        raw_format: args.raw_format,
+        raw_header: args.raw_header,
+        stdout_encoding: args.stdout_encoding.unwrap_or_default(),
+        term_preview: args.term_preview,
         command,
+        command_timeout_ms: config.capture.command_timeout_ms,
         silent,
         notif_timeout,
+        skip_unchanged: args.skip_unchanged,
+        also_save: std::mem::take(&mut args.also_save),
+        create_missing_dirs: config.paths.create_missing_dirs,
+        keep_recent: config.paths.keep_recent,
+        set_wallpaper: args.set_wallpaper,
+        upload,
+        editor,
+        obscure,
+        beautify,
+        resize: args.resize,
+        metadata,
+        post_capture: config.hooks.post_capture.clone(),
+        context: save::CaptureContext {
+            mode: Some(option),
+            current,
+        },
         debug,
+    };
+
+    let report = Report {
+        json: args.json,
+        mode,
+        extension: if clipboard_only {
+            config::ImageFormat::Png.extension()
+        } else {
+            format.extension()
+        },
+        started,
+    };
+
+    if args.split {
+        let outputs = capture::filter_outputs(
+            capture::wayland_outputs(debug)?,
+            &args.only_output,
+            &args.exclude_output,
+        )?;
+        let template = if filename::uses(&filename_template, "output") {
+            filename_template.clone()
+        } else {
+            format!("{}_%output", filename_template)
+        };
+        let path_for = |name: &str| {
+            let mut fields = fields.clone();
+            fields.output = Some(name.to_string());
+            let filename = output_filename(&args, format, &template, &save_dir, &mut fields);
+            filename::resolve_conflict(&save_dir.join(&filename), config.paths.on_conflict)
+        };
+        return save_split(
+            backend.as_mut(),
+            &outputs,
+            &save_dir,
+            path_for,
+            options,
+            &report,
+        );
+    }
+
+    save_and_report(
+        backend.as_mut(),
+        &geometry,
+        output_name.as_deref(),
+        &save_fullpath,
+        &options,
+        &report,
+        || {
+            fields
+                .output
+                .clone()
+                .or_else(|| capture::output_at(compositor.as_mut(), &geometry, debug))
+        },
     )?;
 
     Ok(())
 }
 
+/// What is printed on stdout for each saved capture.
+struct Report<'a> {
+    json: bool,
+    mode: &'a str,
+    extension: &'static str,
+    started: Instant,
+}
+
+/// Save one capture and print its `--json` document, or the `--upload` URL
+/// without `--json`. `lookup_output` names the output for the document when
+/// `output_name` doesn't.
+fn save_and_report(
+    backend: &mut dyn backend::CaptureBackend,
+    geometry: &Geometry,
+    output_name: Option<&str>,
+    path: &PathBuf,
+    options: &save::SaveOptions,
+    report: &Report,
+    lookup_output: impl FnOnce() -> Option<String>,
+) -> Result<save::SaveOutcome> {
+    let outcome = save::save_geometry(backend, geometry, output_name, path, options)?;
+    if report.json {
+        let output = output_name.map(String::from).or_else(lookup_output);
+        println!(
+            "{}",
+            save::json_report(
+                &outcome,
+                geometry,
+                report.mode,
+                output.as_deref(),
+                report.extension,
+                report.started.elapsed(),
+            )
+        );
+    } else if let Some(url) = &outcome.url {
+        println!("{}", url);
+    }
+    Ok(outcome)
+}
+
+/// `-m all --split`: every output to the file `path_for` names, through the
+/// same backend, with one notification for the lot.
+fn save_split(
+    backend: &mut dyn backend::CaptureBackend,
+    outputs: &[(String, Geometry)],
+    dir: &Path,
+    path_for: impl Fn(&str) -> Result<PathBuf>,
+    mut options: save::SaveOptions,
+    report: &Report,
+) -> Result<()> {
+    let silent = options.silent;
+    options.output_physical_pixels = true;
+    options.silent = true;
+    // Each copy would replace the previous one.
+    options.clipboard = false;
+    let mut saved = Vec::new();
+    for (name, geometry) in outputs {
+        let path = path_for(name)?;
+        if options.debug {
+            eprintln!("Saving {} in: {}", name, path.display());
+        }
+        let outcome = save_and_report(
+            backend,
+            geometry,
+            Some(name),
+            &path,
+            &options,
+            report,
+            || None,
+        )?;
+        saved.extend(outcome.path);
+    }
+    if !silent
+        && notify::daemon_available(options.debug)
+        && let Err(err) = Notification::new()
+            .summary(&options.context.summary(None))
+            .body(&format!(
+                "{} image(s) saved in <i>{}</i>.",
+                saved.len(),
+                dir.display()
+            ))
+            .timeout(options.notif_timeout as i32)
+            .appname("Hyprshot-rs")
+            .show()
+    {
+        eprintln!("Warning: failed to show notification: {}", err);
+    }
+    Ok(())
+}
+
 fn print_help() {
     println!(
         r#"
@@ -219,40 +1076,107 @@ Examples:
 
 Options:
   -h, --help                show help message
-  -m, --mode                one of: output, window, region, active, OUTPUT_NAME
+  -m, --mode                one of: output, window, window-pair, region, layer, all, active, OUTPUT_NAME
+  --profile NAME            use the options in [profiles.NAME] of the config (flags still win)
   -o, --output-folder       directory in which to save screenshot
   -f, --filename            the file name of the resulting screenshot
-  -D, --delay               how long to delay taking the screenshot after selection (seconds)
+  --filename-template T     name screenshots from placeholders such as %Y %m %d %mode %title %counter
+  --padding N               grow the selection by N logical pixels on each side (kept on its monitor)
+  --aspect W:H              adjust the selection to an aspect ratio (e.g. 16:9)
+  --aspect-mode MODE        expand (default) or crop the selection for --aspect
+  -D, --delay               how long to delay taking the screenshot after selection
+                            (e.g. 3, 0.5, 500ms, 1.5s, 1m; bare numbers are seconds)
+  --detach-delay            with a delay, capture in a background process and return immediately
+  --selection-timeout DURATION
+                            give up if nothing is selected within DURATION (exit code 11)
+  --png-compression LEVEL   fast, default, best or a zlib level 0-9 (speed vs. file size)
+  --optimize                shrink saved PNGs losslessly (palette/gray/no alpha, best filters; slower)
+  --metadata LEVEL          none, basic (time, tool) or full (also window, output, geometry, workspace)
+  --format FORMAT           png, jpeg or webp (a -f extension such as .jpg wins)
+  --force-format FORMAT     use FORMAT whatever -f and the config say; rewrites the extension
+  --quality N               JPEG/WebP quality, 1-100
+  --cursor                  include the mouse pointer in the screenshot
+  --menu                    with -m output, pick the output from capture.menu_command (fuzzel, wofi, dmenu)
+  --split                   with -m all, save every output to its own file (named with %output)
+  --only-output NAME        with -m all, capture only this output (repeatable)
+  --exclude-output NAME     with -m all, leave this output out (repeatable)
+  --client-area             with -m window, leave out title bar and borders (Sway; no-op on Hyprland)
+  --decorations             with -m window, include the Hyprland border and drop shadow
+  --no-rounding             with -m window, square the window corners for the capture (Hyprland)
+  --transparent             with -m window, copy the window's own buffer with real alpha
+  --constrain-output        with -m region, keep the selection on the monitor showing most of it
+  --window-title REGEX      capture the window whose title matches REGEX, without selecting it
+  --window-class NAME       capture the window of class/app_id NAME, without selecting it
+  --address 0xADDRESS       capture the Hyprland client with this address, without selecting it
+  --pid PID                 capture the window of process PID, without selecting it
+  --last                    capture the same area as the previous capture in this mode
+  --geometry "X,Y WxH"      capture this region without selecting (implies -m region)
+  --print-geometry          print the selection as "x,y WxH" and exit (defaults to region)
+  --record                  record a video of the selection with ffmpeg (mp4, or -f NAME.webm/.mkv/.gif/.webp);
+                            run with --record again or press Ctrl+C to stop
+  --record-format FORMAT    mp4, webm, mkv, gif or webp
+  --fps N                   frames per second of the recording (30, or 15 for gif/webp)
+  --max-duration DURATION   stop the recording after DURATION (e.g. 10s); also --for
+  --still                   with --record, save numbered PNG frames instead of a video (timelapse)
+  --every DURATION          time between --still frames (default 30s)
   --freeze                  freeze the screen on initialization
+  --no-freeze               don't freeze, even if advanced.freeze_on_region is set
   -d, --debug               print debug information
   -s, --silent              don't send notification when screenshot is saved
   -r, --raw                 output raw image data to stdout
+  --raw-format FORMAT       with --raw, write png, jpeg, ppm or bgra (bare pixels, e.g. for ffmpeg)
+  --raw-header              with --raw-format ppm/bgra, start with a "WIDTH HEIGHT STRIDE" line
+  --stdout-encoding ENC     with --raw, write binary (default) or base64 text
+  --term-preview            show the capture in the terminal (kitty graphics or sixel)
   -n, --notif-timeout       notification timeout in milliseconds (default 5000)
+  --skip-unchanged          don't save if the capture is identical to the previous one
+  --also-save DIR           also save a copy into DIR (repeatable)
+  --set-wallpaper           set the saved screenshot as wallpaper (swww, hyprpaper or swaybg)
+  --upload [TARGET]         upload to an [upload.targets] endpoint and copy the URL (needs curl)
+  --edit-with [CMD]         open the capture in CMD (default: hooks.editor) and save the edited file
+  --beautify                frame the capture: padding, rounded corners, shadow, background ([beautify])
+  --resize WxH|N%           scale the capture to fit in WxH or to N% (Lanczos), e.g. 1920x1080 or 50%
+  --obscure [STYLE]         after the selection, drag boxes to pixelate (default) or blur; Escape ends
   --clipboard-only          copy screenshot to clipboard and don't save image in disk
+  --json                    print the saved path, geometry, output, format, duration and clipboard status as JSON
   --no-config               don't load config file (use defaults and CLI args only)
   -- [command]              open screenshot with a command of your choosing. e.g. hyprshot-rs -m window -- mirage
 
+Commands:
+  list outputs [--json]     list output names, logical geometry, scale and transform
+  list windows [--json]     list the windows -m window offers: geometry, class, address/id, workspace, title
+  completions SHELL         print a completion script for bash, zsh, fish or nushell
+  doctor                    check protocols, IPC, clipboard, notifications and the screenshots dir
+  paste [PATH]              save the clipboard image like a capture, to a file or directory
+
 Config Management:
   --init-config             initialize default config file (~/.config/hyprshot-rs/config.toml)
   --show-config             show current configuration
   --config-path             show path to config file
   --set KEY VALUE           set config value (e.g., --set paths.screenshots_dir ~/Screenshots)
+  --show-recent             print the path of the most recent screenshot (needs paths.keep_recent)
 
 Hyprland Integration:
   --generate-hyprland-config    generate keybindings for Hyprland
   --install-binds               install keybindings to hyprland.conf (creates backup)
   --with-clipboard              include clipboard-only variants (use with above commands)
+  --restore-binds               restore hyprland.conf from the latest backup (--yes skips confirmation)
   --setup-hotkeys               interactive wizard to configure hotkeys
+  --setup                       interactive wizard for directory, notifications, freeze and hotkeys
 
 Modes:
   output        take screenshot of an entire monitor
   window        take screenshot of an open window
+  window-pair   take screenshot of the bounding box of two selected windows
   region        take screenshot of selected region
+  layer         take screenshot of a layer surface such as a bar (experimental, Hyprland;
+                falls back to region elsewhere)
   active        take screenshot of active window|output
                 (you must use --mode again with the intended selection)
   OUTPUT_NAME   take screenshot of output with OUTPUT_NAME
-                (you must use --mode again with the intended selection)
-                (you can get this from `hyprctl monitors`)
+                (you must use --mode again with the intended selection;
+                with region, the selection is limited to that output)
+                (you can get this from `hyprshot-rs list outputs`)
 "#
     );
 }
@@ -0,0 +1,467 @@
+//! Capture backend abstraction so save/encode paths don't depend on a live compositor.
+
+use anyhow::{Context, Result, bail};
+use std::borrow::Borrow;
+
+use crate::compositor::Window;
+use crate::config::PngCompression;
+use crate::geometry::Geometry;
+
+/// Raw RGBA pixels produced by a capture backend.
+#[derive(Clone)]
+pub struct CapturedImage {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Size and row layout of a capture written into a caller-provided buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Bytes per row. Rows are tightly packed, so this is always `width * 4`.
+    pub stride: usize,
+}
+
+pub trait CaptureBackend {
+    /// Capture a region in logical coordinates. The region may span several
+    /// outputs; areas not covered by any output must be transparent.
+    fn capture_region(&mut self, geometry: &Geometry) -> Result<CapturedImage>;
+
+    /// Capture a whole output at its native (physical) resolution.
+    fn capture_output(&mut self, name: &str) -> Result<CapturedImage>;
+
+    fn to_png(&self, image: &CapturedImage, compression: PngCompression) -> Result<Vec<u8>>;
+
+    /// Capture a region straight into `buffer` without PNG encoding.
+    ///
+    /// Pixels are 8-bit RGBA (non-premultiplied), top row first, with no row
+    /// padding. `buffer` must hold at least `width * height * 4` bytes; only
+    /// that prefix is written. The backends in this crate receive an owned
+    /// buffer from the compositor, so this costs one copy rather than none.
+    fn capture_region_into(
+        &mut self,
+        geometry: &Geometry,
+        buffer: &mut [u8],
+    ) -> Result<BufferInfo> {
+        let image = self.capture_region(geometry)?;
+        let stride = image.width as usize * 4;
+        let needed = stride * image.height as usize;
+        if buffer.len() < needed {
+            bail!(
+                "Buffer too small for {}x{} capture: need {} bytes, got {}",
+                image.width,
+                image.height,
+                needed,
+                buffer.len()
+            );
+        }
+        buffer[..needed].copy_from_slice(&image.data[..needed]);
+        Ok(BufferInfo {
+            width: image.width,
+            height: image.height,
+            stride,
+        })
+    }
+}
+
+/// Paste whole-output captures into a `geometry`-sized image. Each entry is an
+/// output's logical bounds, the part of `geometry` it covers and its pixels.
+/// Like grim, the result uses the highest scale among the outputs; uncovered
+/// areas stay transparent.
+pub(crate) fn composite_region<P: Borrow<CapturedImage>>(
+    geometry: &Geometry,
+    captures: &[(Geometry, Geometry, P)],
+) -> CapturedImage {
+    let scale = captures
+        .iter()
+        .map(|(bounds, _, pixels)| pixels.borrow().width as f64 / bounds.width as f64)
+        .fold(1.0, f64::max);
+    let width = (geometry.width as f64 * scale).round() as u32;
+    let height = (geometry.height as f64 * scale).round() as u32;
+    let mut data = vec![0u8; width as usize * height as usize * 4];
+
+    for (bounds, overlap, pixels) in captures {
+        let pixels = pixels.borrow();
+        let src_scale_x = pixels.width as f64 / bounds.width as f64;
+        let src_scale_y = pixels.height as f64 / bounds.height as f64;
+        let dst_x0 = ((overlap.x - geometry.x) as f64 * scale).round() as u32;
+        let dst_y0 = ((overlap.y - geometry.y) as f64 * scale).round() as u32;
+        let dst_x1 =
+            (((overlap.x + overlap.width - geometry.x) as f64 * scale).round() as u32).min(width);
+        let dst_y1 =
+            (((overlap.y + overlap.height - geometry.y) as f64 * scale).round() as u32).min(height);
+        for dy in dst_y0..dst_y1 {
+            let ly = geometry.y as f64 + (dy as f64 + 0.5) / scale - bounds.y as f64;
+            let sy = ((ly * src_scale_y) as u32).min(pixels.height - 1);
+            for dx in dst_x0..dst_x1 {
+                let lx = geometry.x as f64 + (dx as f64 + 0.5) / scale - bounds.x as f64;
+                let sx = ((lx * src_scale_x) as u32).min(pixels.width - 1);
+                let src = (sy as usize * pixels.width as usize + sx as usize) * 4;
+                let dst = (dy as usize * width as usize + dx as usize) * 4;
+                data[dst..dst + 4].copy_from_slice(&pixels.data[src..src + 4]);
+            }
+        }
+    }
+
+    CapturedImage {
+        data,
+        width,
+        height,
+    }
+}
+
+/// Serves captures from the frames the freeze overlay showed, so the screenshot
+/// matches what was on screen while selecting. Regions the frames don't fully
+/// cover, and outputs that weren't frozen, go to the live backend.
+pub struct FrozenBackend {
+    frames: Vec<crate::freeze::FrozenFrame>,
+    live: Box<dyn CaptureBackend>,
+    debug: bool,
+}
+
+impl FrozenBackend {
+    pub fn new(
+        frames: Vec<crate::freeze::FrozenFrame>,
+        live: Box<dyn CaptureBackend>,
+        debug: bool,
+    ) -> Self {
+        Self {
+            frames,
+            live,
+            debug,
+        }
+    }
+}
+
+impl CaptureBackend for FrozenBackend {
+    fn capture_region(&mut self, geometry: &Geometry) -> Result<CapturedImage> {
+        let captures: Vec<_> = self
+            .frames
+            .iter()
+            .filter_map(|frame| {
+                let overlap = frame.bounds.intersect(*geometry)?;
+                Some((frame.bounds, overlap, &frame.image))
+            })
+            .collect();
+        let covered: i64 = captures
+            .iter()
+            .map(|(_, overlap, _)| overlap.width as i64 * overlap.height as i64)
+            .sum();
+        if covered < geometry.width as i64 * geometry.height as i64 {
+            if self.debug {
+                eprintln!("Frozen frames don't cover {}; capturing it live", geometry);
+            }
+            return self.live.capture_region(geometry);
+        }
+        Ok(composite_region(geometry, &captures))
+    }
+
+    fn capture_output(&mut self, name: &str) -> Result<CapturedImage> {
+        match self.frames.iter().find(|frame| frame.output == name) {
+            Some(frame) => Ok(CapturedImage {
+                data: frame.image.data.clone(),
+                width: frame.image.width,
+                height: frame.image.height,
+            }),
+            None => self.live.capture_output(name),
+        }
+    }
+
+    fn to_png(&self, image: &CapturedImage, compression: PngCompression) -> Result<Vec<u8>> {
+        self.live.to_png(image, compression)
+    }
+}
+
+/// `-m all` with `--only-output`/`--exclude-output`: only the kept outputs
+/// are captured, so the rest of their bounding box stays transparent.
+pub struct MaskedBackend {
+    outputs: Vec<Geometry>,
+    live: Box<dyn CaptureBackend>,
+}
+
+impl MaskedBackend {
+    pub fn new(outputs: Vec<Geometry>, live: Box<dyn CaptureBackend>) -> Self {
+        Self { outputs, live }
+    }
+}
+
+impl CaptureBackend for MaskedBackend {
+    fn capture_region(&mut self, geometry: &Geometry) -> Result<CapturedImage> {
+        let mut captures = Vec::new();
+        for overlap in self.outputs.iter().filter_map(|o| o.intersect(*geometry)) {
+            captures.push((overlap, overlap, self.live.capture_region(&overlap)?));
+        }
+        if captures.is_empty() {
+            bail!("Region {} does not intersect a kept output", geometry);
+        }
+        Ok(composite_region(geometry, &captures))
+    }
+
+    fn capture_output(&mut self, name: &str) -> Result<CapturedImage> {
+        self.live.capture_output(name)
+    }
+
+    fn to_png(&self, image: &CapturedImage, compression: PngCompression) -> Result<Vec<u8>> {
+        self.live.to_png(image, compression)
+    }
+}
+
+/// Backend for `--transparent`: copies `window` from its own buffer instead of
+/// cropping the screen. Hyprland windows are found by address through
+/// hyprland-toplevel-export; elsewhere, by title and class through
+/// ext-image-copy-capture (`ext-capture` builds only). Whole outputs still
+/// come from `live`.
+pub fn create_window_backend(
+    window: &Window,
+    include_cursor: bool,
+    live: Box<dyn CaptureBackend>,
+) -> Result<Box<dyn CaptureBackend>> {
+    if let Some(address) = &window.address {
+        #[cfg(feature = "freeze")]
+        return Ok(Box::new(crate::toplevel_export::WindowBackend::new(
+            address,
+            include_cursor,
+            live,
+        )?));
+        #[cfg(not(feature = "freeze"))]
+        {
+            let _ = address;
+            bail!("--transparent on Hyprland needs a build with the 'freeze' feature")
+        }
+    }
+    #[cfg(feature = "ext-capture")]
+    return Ok(Box::new(crate::ext_capture::WindowBackend::new(
+        &window.title,
+        &window.class,
+        include_cursor,
+    )?));
+    #[cfg(not(feature = "ext-capture"))]
+    {
+        let _ = (include_cursor, live);
+        bail!(
+            "--transparent outside Hyprland needs a build with the 'ext-capture' feature; \
+{} windows have no address to export them by",
+            window.class
+        )
+    }
+}
+
+/// Pick the capture backend: grim-rs (wlr-screencopy) unless the compositor
+/// only offers ext-image-copy-capture and the `ext-capture` feature is on.
+/// `include_cursor` composites the pointer into every capture.
+#[cfg(feature = "grim")]
+pub fn create_backend(include_cursor: bool, debug: bool) -> Result<Box<dyn CaptureBackend>> {
+    #[cfg(feature = "ext-capture")]
+    match crate::ext_capture::ExtCaptureBackend::connect_if_needed(include_cursor, debug) {
+        Ok(Some(backend)) => return Ok(Box::new(backend)),
+        Ok(None) => {}
+        Err(err) => {
+            if debug {
+                eprintln!("ext-image-copy-capture unavailable: {:#}", err);
+            }
+        }
+    }
+    #[cfg(not(feature = "ext-capture"))]
+    let _ = debug;
+
+    #[cfg(feature = "freeze")]
+    {
+        let mut backend = GrimBackend::new()?;
+        backend.cursor = include_cursor;
+        match crate::shm::probe_outputs() {
+            Ok(outputs) => {
+                let deep: Vec<&str> = outputs
+                    .iter()
+                    .filter(|output| output.is_deep_color())
+                    .map(|output| output.name.as_str())
+                    .collect();
+                if debug && !deep.is_empty() {
+                    eprintln!(
+                        "10-bit outputs, converted after capture: {}",
+                        deep.join(", ")
+                    );
+                }
+                backend.outputs = outputs;
+            }
+            // Without the outputs the cursor can't be captured; 10-bit ones
+            // just keep grim-rs's colors.
+            Err(err) if include_cursor => return Err(err),
+            Err(err) => {
+                if debug {
+                    eprintln!("Could not check output buffer formats: {:#}", err);
+                }
+            }
+        }
+        if debug && include_cursor {
+            eprintln!("Capturing with the cursor via grim-rs");
+        }
+        Ok(Box::new(backend))
+    }
+    #[cfg(not(feature = "freeze"))]
+    {
+        if include_cursor {
+            bail!("Capturing the cursor needs the 'freeze' feature");
+        }
+        Ok(Box::new(GrimBackend::new()?))
+    }
+}
+
+#[cfg(feature = "grim")]
+pub struct GrimBackend {
+    grim: grim_rs::Grim,
+    /// Composite the pointer into every capture (`--cursor`).
+    #[cfg(feature = "freeze")]
+    cursor: bool,
+    /// Outputs with their screencopy formats, for the captures that go
+    /// through [`capture_outputs`]: all of them with the cursor, otherwise
+    /// those touching a 10-bit output.
+    #[cfg(feature = "freeze")]
+    outputs: Vec<crate::shm::ScreencopyOutput>,
+}
+
+#[cfg(feature = "grim")]
+impl GrimBackend {
+    pub fn new() -> Result<Self> {
+        let grim = grim_rs::Grim::new().context("Failed to initialize grim-rs")?;
+        Ok(Self {
+            grim,
+            #[cfg(feature = "freeze")]
+            cursor: false,
+            #[cfg(feature = "freeze")]
+            outputs: Vec::new(),
+        })
+    }
+}
+
+/// Capture whole outputs through `Grim::capture_outputs`, which can overlay
+/// the cursor but, unlike grim-rs's region path, leaves 10-bit buffers and
+/// output transforms as the compositor sent them.
+#[cfg(feature = "freeze")]
+pub(crate) fn capture_outputs(
+    grim: &mut grim_rs::Grim,
+    outputs: &[&crate::shm::ScreencopyOutput],
+    cursor: bool,
+) -> Result<Vec<CapturedImage>> {
+    let parameters = outputs
+        .iter()
+        .map(|output| grim_rs::CaptureParameters::new(&output.name).overlay_cursor(cursor))
+        .collect();
+    let mut captures = grim
+        .capture_outputs(parameters)
+        .context("Failed to capture outputs")?
+        .into_outputs();
+    outputs
+        .iter()
+        .map(|output| {
+            let capture = captures
+                .remove(&output.name)
+                .with_context(|| format!("No capture returned for output '{}'", output.name))?;
+            let (width, height) = (capture.width(), capture.height());
+            let mut data = capture.into_data();
+            if let Some(format) = output
+                .format
+                .filter(|format| crate::shm::is_deep_color(*format))
+            {
+                data = crate::shm::to_rgba(&data, format);
+            }
+            Ok(crate::shm::untransform(
+                CapturedImage {
+                    data,
+                    width,
+                    height,
+                },
+                output.transform,
+            ))
+        })
+        .collect()
+}
+
+#[cfg(feature = "grim")]
+impl CaptureBackend for GrimBackend {
+    fn capture_region(&mut self, geometry: &Geometry) -> Result<CapturedImage> {
+        #[cfg(feature = "freeze")]
+        {
+            let targets: Vec<_> = self
+                .outputs
+                .iter()
+                .filter_map(|output| Some((output, geometry.intersect(output.bounds)?)))
+                .collect();
+            if self.cursor || targets.iter().any(|(output, _)| output.is_deep_color()) {
+                if targets.is_empty() {
+                    bail!("Region {} does not intersect any output", geometry);
+                }
+                let outputs: Vec<_> = targets.iter().map(|(output, _)| *output).collect();
+                let pixels = capture_outputs(&mut self.grim, &outputs, self.cursor)?;
+                let captures: Vec<_> = targets
+                    .iter()
+                    .zip(pixels)
+                    .map(|((output, overlap), pixels)| (output.bounds, *overlap, pixels))
+                    .collect();
+                return Ok(composite_region(geometry, &captures));
+            }
+        }
+        // grim-rs captures each intersecting output separately and blits it into a
+        // zeroed RGBA buffer, so gaps between outputs are already transparent.
+        let capture = self
+            .grim
+            .capture_region(crate::save::to_grim_box(geometry))
+            .context("Failed to capture screenshot region")?;
+        let (width, height) = (capture.width(), capture.height());
+        Ok(CapturedImage {
+            data: capture.into_data(),
+            width,
+            height,
+        })
+    }
+
+    fn capture_output(&mut self, name: &str) -> Result<CapturedImage> {
+        #[cfg(feature = "freeze")]
+        if let Some(output) = self
+            .outputs
+            .iter()
+            .find(|output| output.name == name && (self.cursor || output.is_deep_color()))
+        {
+            let mut captures = capture_outputs(&mut self.grim, &[output], self.cursor)
+                .with_context(|| format!("Failed to capture output '{}'", name))?;
+            return captures.pop().context("No capture returned");
+        }
+        let capture = self
+            .grim
+            .capture_output(name)
+            .with_context(|| format!("Failed to capture output '{}'", name))?;
+        let (width, height) = (capture.width(), capture.height());
+        Ok(CapturedImage {
+            data: capture.into_data(),
+            width,
+            height,
+        })
+    }
+
+    fn to_png(&self, image: &CapturedImage, compression: PngCompression) -> Result<Vec<u8>> {
+        crate::png_encoder::encode(image, compression.level())
+            .context("Failed to encode screenshot as PNG")
+    }
+}
+
+/// A PNG chunk: length, type, body and CRC.
+pub(crate) fn png_chunk(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut chunk = (body.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(body);
+    chunk.extend_from_slice(&crc32fast::hash(&chunk[4..]).to_be_bytes());
+    chunk
+}
+
+/// Insert encoded `chunks` right after IHDR; anything that doesn't start
+/// with a PNG header is returned unchanged.
+pub(crate) fn insert_png_chunks(mut png: Vec<u8>, chunks: &[u8]) -> Vec<u8> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    const IHDR_END: usize = 8 + 4 + 4 + 13 + 4;
+    if png.len() < IHDR_END || !png.starts_with(SIGNATURE) || &png[12..16] != b"IHDR" {
+        return png;
+    }
+    png.splice(IHDR_END..IHDR_END, chunks.iter().copied());
+    png
+}
@@ -0,0 +1,168 @@
+//! `--beautify`: frame a capture for READMEs and posts. The image gets
+//! rounded corners and a soft drop shadow, centered on a padded solid or
+//! gradient background (`[beautify]`).
+
+use anyhow::{Context, Result};
+
+use crate::backend::CapturedImage;
+use crate::config::{self, BeautifyConfig};
+use crate::obscure::box_filter;
+
+/// Darkest the shadow gets, right under the capture.
+const SHADOW_OPACITY: f32 = 0.45;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Background {
+    Solid([u8; 3]),
+    /// From the top-left corner to the bottom-right one.
+    Gradient([u8; 3], [u8; 3]),
+}
+
+/// Sizes in logical pixels; they are scaled with the capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Style {
+    pub padding: u32,
+    pub corner_radius: u32,
+    pub shadow_blur: u32,
+    pub background: Background,
+}
+
+impl Style {
+    pub(crate) fn from_config(config: &BeautifyConfig) -> Result<Self> {
+        Ok(Self {
+            padding: config.padding,
+            corner_radius: config.corner_radius,
+            shadow_blur: config.shadow_blur,
+            background: parse_background(&config.background)
+                .context("Invalid beautify.background")?,
+        })
+    }
+}
+
+/// `#rrggbb`, or `#rrggbb:#rrggbb` for a diagonal gradient.
+pub(crate) fn parse_background(value: &str) -> Result<Background> {
+    match value.split_once(':') {
+        Some((from, to)) => Ok(Background::Gradient(
+            config::parse_hex_color(from)?,
+            config::parse_hex_color(to)?,
+        )),
+        None => Ok(Background::Solid(config::parse_hex_color(value)?)),
+    }
+}
+
+/// The framed image. `scale` is image pixels per logical pixel.
+pub(crate) fn apply(image: &CapturedImage, style: &Style, scale: f64) -> CapturedImage {
+    let px = |logical: u32| (f64::from(logical) * scale).round() as u32;
+    let (padding, shadow_blur) = (px(style.padding), px(style.shadow_blur));
+    let radius = px(style.corner_radius)
+        .min(image.width / 2)
+        .min(image.height / 2) as f32;
+    let width = image.width + 2 * padding;
+    let height = image.height + 2 * padding;
+
+    let mut canvas: Vec<[f32; 3]> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| background_at(style.background, x, y, width, height)))
+        .collect();
+
+    // The capture's rounded outline, shifted down a little and blurred.
+    if shadow_blur > 0 {
+        let offset = shadow_blur / 3;
+        let mut shadow: Vec<f32> = (0..height)
+            .flat_map(|y| {
+                (0..width).map(move |x| {
+                    let (Some(x), Some(y)) =
+                        (x.checked_sub(padding), y.checked_sub(padding + offset))
+                    else {
+                        return 0.0;
+                    };
+                    coverage(x, y, image.width, image.height, radius)
+                })
+            })
+            .collect();
+        blur(
+            &mut shadow,
+            width as usize,
+            height as usize,
+            shadow_blur as usize / 2,
+        );
+        for (pixel, alpha) in canvas.iter_mut().zip(&shadow) {
+            let keep = 1.0 - alpha * SHADOW_OPACITY;
+            pixel.iter_mut().for_each(|channel| *channel *= keep);
+        }
+    }
+
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let i = (y * image.width + x) as usize * 4;
+            let source = &image.data[i..i + 4];
+            let alpha =
+                coverage(x, y, image.width, image.height, radius) * f32::from(source[3]) / 255.0;
+            let pixel = &mut canvas[((y + padding) * width + x + padding) as usize];
+            for (channel, &value) in pixel.iter_mut().zip(source) {
+                *channel = *channel * (1.0 - alpha) + f32::from(value) * alpha;
+            }
+        }
+    }
+
+    CapturedImage {
+        data: canvas
+            .into_iter()
+            .flat_map(|[r, g, b]| [r, g, b].map(|v| v.round() as u8).into_iter().chain([255]))
+            .collect(),
+        width,
+        height,
+    }
+}
+
+fn background_at(background: Background, x: u32, y: u32, width: u32, height: u32) -> [f32; 3] {
+    match background {
+        Background::Solid(color) => color.map(f32::from),
+        Background::Gradient(from, to) => {
+            let t = (x + y) as f32 / (width + height).saturating_sub(2).max(1) as f32;
+            [0, 1, 2].map(|c| f32::from(from[c]) * (1.0 - t) + f32::from(to[c]) * t)
+        }
+    }
+}
+
+/// How much of pixel (`x`, `y`) lies inside a `width`x`height` rectangle
+/// with corners rounded by `radius`, from 0 to 1 (antialiased edge).
+fn coverage(x: u32, y: u32, width: u32, height: u32, radius: f32) -> f32 {
+    if x >= width || y >= height {
+        return 0.0;
+    }
+    let (cx, cy) = (x as f32 + 0.5, y as f32 + 0.5);
+    let dx = (radius - cx).max(cx - (width as f32 - radius)).max(0.0);
+    let dy = (radius - cy).max(cy - (height as f32 - radius)).max(0.0);
+    if dx == 0.0 || dy == 0.0 {
+        return 1.0;
+    }
+    (radius + 0.5 - (dx * dx + dy * dy).sqrt()).clamp(0.0, 1.0)
+}
+
+/// Three box-blur passes each way, close to a gaussian.
+fn blur(values: &mut [f32], width: usize, height: usize, radius: usize) {
+    if radius == 0 {
+        return;
+    }
+    let mut line = Vec::new();
+    for _ in 0..3 {
+        for row in 0..height {
+            line.clear();
+            line.extend(
+                values[row * width..(row + 1) * width]
+                    .iter()
+                    .map(|&v| [f64::from(v)]),
+            );
+            for (i, [v]) in box_filter(&line, radius).into_iter().enumerate() {
+                values[row * width + i] = v as f32;
+            }
+        }
+        for col in 0..width {
+            line.clear();
+            line.extend((0..height).map(|row| [f64::from(values[row * width + col])]));
+            for (i, [v]) in box_filter(&line, radius).into_iter().enumerate() {
+                values[i * width + col] = v as f32;
+            }
+        }
+    }
+}
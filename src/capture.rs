@@ -1,199 +1,627 @@
-use anyhow::{Context, Result};
-use serde_json::Value;
-use std::{collections::HashSet, process::Command, time::Duration};
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use std::fmt;
 
+use crate::compositor::{CompositorBackend, Window};
 use crate::geometry::Geometry;
 use crate::selector;
-use crate::utils::output_with_timeout;
 
+#[cfg(feature = "freeze")]
+use crate::outputs::{Outputs, delegate_outputs};
 #[cfg(feature = "freeze")]
 use wayland_client::{
     Connection, Dispatch, QueueHandle,
-    protocol::{wl_output::Mode as WlOutputMode, wl_output::WlOutput, wl_registry::WlRegistry},
-};
-#[cfg(feature = "freeze")]
-use wayland_protocols::xdg::xdg_output::zv1::client::{
-    zxdg_output_manager_v1::ZxdgOutputManagerV1, zxdg_output_v1::ZxdgOutputV1,
+    protocol::{wl_output::Transform as WlTransform, wl_output::WlOutput, wl_registry::WlRegistry},
 };
 
-pub struct HyprctlCache {
-    monitors: Option<Value>,
+pub fn grab_output(debug: bool) -> Result<(Geometry, Option<String>)> {
+    selector::select_output(debug)
 }
 
-impl HyprctlCache {
-    pub fn new() -> Self {
-        Self { monitors: None }
+/// Bounding box of every output (`-m all`), or of the ones `only`/`exclude`
+/// leave, with the kept outputs' geometry. Capturing it stitches the outputs
+/// at their logical positions, with gaps between them left transparent.
+pub fn grab_all_outputs(
+    only: &[String],
+    exclude: &[String],
+    debug: bool,
+) -> Result<(Geometry, Vec<Geometry>)> {
+    #[cfg(feature = "freeze")]
+    {
+        let outputs = if only.is_empty() && exclude.is_empty() {
+            crate::utils::wayland_monitors()?
+        } else {
+            filter_outputs(wayland_outputs(debug)?, only, exclude)?
+                .into_iter()
+                .map(|(_, geometry)| geometry)
+                .collect()
+        };
+        let geometry = outputs_bounding_box(&outputs).context("No outputs found")?;
+        if debug {
+            eprintln!("All outputs ({}): {}", outputs.len(), geometry);
+        }
+        Ok((geometry, outputs))
+    }
+    #[cfg(not(feature = "freeze"))]
+    {
+        let _ = (only, exclude, debug);
+        Err(anyhow::anyhow!(
+            "Listing outputs needs the 'freeze' feature"
+        ))
     }
 }
 
-fn hyprctl_monitors_json(cache: &mut HyprctlCache, timeout: Duration) -> Result<&Value> {
-    if cache.monitors.is_none() {
-        let output = output_with_timeout(
-            {
-                let mut cmd = Command::new("hyprctl");
-                cmd.arg("monitors").arg("-j");
-                cmd
-            },
-            timeout,
-        )
-        .context("Failed to run hyprctl monitors")?;
-        let monitors: Value =
-            serde_json::from_slice(&output.stdout).context("Failed to parse hyprctl monitors")?;
-        cache.monitors = Some(monitors);
+/// Apply `--only-output`/`--exclude-output` to `outputs`. Every name must be
+/// one of them, so a typo is an error rather than a capture of everything.
+pub(crate) fn filter_outputs(
+    outputs: Vec<(String, Geometry)>,
+    only: &[String],
+    exclude: &[String],
+) -> Result<Vec<(String, Geometry)>> {
+    if let Some(unknown) = only
+        .iter()
+        .chain(exclude)
+        .find(|name| !outputs.iter().any(|(output, _)| output == *name))
+    {
+        let names: Vec<&str> = outputs.iter().map(|(name, _)| name.as_str()).collect();
+        bail!(
+            "Output '{}' not found (outputs: {})",
+            unknown,
+            names.join(", ")
+        );
+    }
+    let kept: Vec<_> = outputs
+        .into_iter()
+        .filter(|(name, _)| (only.is_empty() || only.contains(name)) && !exclude.contains(name))
+        .collect();
+    if kept.is_empty() {
+        bail!("--exclude-output leaves no outputs to capture");
     }
-
-    cache
-        .monitors
-        .as_ref()
-        .context("Hyprctl monitors cache missing")
+    Ok(kept)
 }
 
-pub fn grab_output(debug: bool) -> Result<Geometry> {
-    selector::select_output(debug)
+pub(crate) fn outputs_bounding_box(outputs: &[Geometry]) -> Option<Geometry> {
+    outputs.iter().copied().reduce(Geometry::union)
 }
 
 // Support matrix:
 // - region/output: Wayland-wide via slurp-rs API
-// - output by name: Wayland enumeration (no hyprctl)
-// - window/active: Hyprland and Sway (hyprctl/swaymsg)
-pub fn grab_active_output(debug: bool, cache: &mut HyprctlCache) -> Result<Geometry> {
-    if let Ok(geometry) = grab_active_output_hyprctl(debug, cache) {
-        return Ok(geometry);
+// - output by name: Wayland enumeration (no compositor IPC)
+// - window/active/layer: the detected compositor backend (see compositor.rs)
+
+/// Name and geometry of the output showing the focused workspace.
+pub fn grab_active_output(
+    compositor: &mut dyn CompositorBackend,
+    debug: bool,
+) -> Result<(String, Geometry)> {
+    let (name, geometry) = compositor.active_output(debug)?;
+    if debug {
+        eprintln!(
+            "Active output ({}): {} {}",
+            compositor.name(),
+            name,
+            geometry
+        );
+    }
+    Ok((name, geometry))
+}
+
+pub fn grab_selected_output(
+    compositor: &mut dyn CompositorBackend,
+    monitor: &str,
+    debug: bool,
+) -> Result<Geometry> {
+    let geometry = compositor
+        .list_outputs(debug)?
+        .into_iter()
+        .find(|(name, _)| name == monitor)
+        .map(|(_, geometry)| geometry)
+        .with_context(|| {
+            format!(
+                "Output '{}' not found. Use '-m output' to select interactively.",
+                monitor
+            )
+        })?;
+    if debug {
+        eprintln!("Selected output geometry: {}", geometry);
     }
-    if let Ok(geometry) = grab_active_output_sway(debug) {
-        return Ok(geometry);
+    Ok(geometry)
+}
+
+/// Names of the current outputs, e.g. for `--menu`.
+pub fn output_names(compositor: &mut dyn CompositorBackend, debug: bool) -> Result<Vec<String>> {
+    Ok(compositor
+        .list_outputs(debug)?
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect())
+}
+
+/// Outputs from Wayland enumeration, with their names and logical geometry.
+pub(crate) fn wayland_outputs(debug: bool) -> Result<Vec<(String, Geometry)>> {
+    #[cfg(feature = "freeze")]
+    {
+        let outputs: Vec<(String, Geometry)> = wayland_named_outputs()?
+            .into_iter()
+            .map(|output| (output.name, output.geometry))
+            .collect();
+        if debug {
+            let names: Vec<&str> = outputs.iter().map(|(name, _)| name.as_str()).collect();
+            eprintln!("Outputs: {}", names.join(", "));
+        }
+        Ok(outputs)
     }
+    #[cfg(not(feature = "freeze"))]
+    {
+        let _ = debug;
+        Err(anyhow::anyhow!(
+            "Listing outputs needs the 'freeze' feature"
+        ))
+    }
+}
 
-    Err(anyhow::anyhow!(
-        "Active output is only supported on Hyprland or Sway"
-    ))
+/// An output as `hyprshot-rs list outputs` shows it.
+#[derive(Debug, Clone)]
+pub(crate) struct OutputInfo {
+    pub name: String,
+    pub description: Option<String>,
+    /// Logical geometry, as used by `--geometry` and the selectors.
+    pub geometry: Geometry,
+    /// Physical pixels per logical pixel; fractional scales show up here.
+    pub scale: f64,
+    /// `normal`, `90`, `flipped-270`, ... as in the Sway and wlr-randr configs.
+    pub transform: &'static str,
 }
 
-fn grab_active_output_hyprctl(debug: bool, cache: &mut HyprctlCache) -> Result<Geometry> {
-    const IPC_TIMEOUT: Duration = Duration::from_secs(3);
-    let active_workspace: Value = serde_json::from_slice(
-        &output_with_timeout(
-            {
-                let mut cmd = Command::new("hyprctl");
-                cmd.arg("activeworkspace").arg("-j");
-                cmd
-            },
-            IPC_TIMEOUT,
+/// Outputs from Wayland enumeration with their description, scale and transform.
+pub(crate) fn wayland_output_info(debug: bool) -> Result<Vec<OutputInfo>> {
+    #[cfg(feature = "freeze")]
+    {
+        let outputs = wayland_named_outputs()?;
+        if debug {
+            eprintln!("Found {} output(s) via Wayland", outputs.len());
+        }
+        Ok(outputs)
+    }
+    #[cfg(not(feature = "freeze"))]
+    {
+        let _ = debug;
+        Err(anyhow::anyhow!(
+            "Listing outputs needs the 'freeze' feature"
+        ))
+    }
+}
+
+/// Name of a `wl_output` transform in Sway/wlr-randr terms.
+#[cfg(feature = "freeze")]
+pub(crate) fn transform_name(transform: WlTransform) -> &'static str {
+    match transform {
+        WlTransform::_90 => "90",
+        WlTransform::_180 => "180",
+        WlTransform::_270 => "270",
+        WlTransform::Flipped => "flipped",
+        WlTransform::Flipped90 => "flipped-90",
+        WlTransform::Flipped180 => "flipped-180",
+        WlTransform::Flipped270 => "flipped-270",
+        _ => "normal",
+    }
+}
+
+/// Whether `transform` turns the output a quarter, so its mode and buffers
+/// are sideways compared to its logical geometry.
+#[cfg(feature = "freeze")]
+pub(crate) fn is_rotated(transform: WlTransform) -> bool {
+    matches!(
+        transform,
+        WlTransform::_90 | WlTransform::_270 | WlTransform::Flipped90 | WlTransform::Flipped270
+    )
+}
+
+/// Logical size from the current mode and integer scale, for outputs without
+/// xdg-output; a quarter turn swaps the mode's sides.
+pub(crate) fn mode_logical_size(mode: (i32, i32), scale: i32, rotated: bool) -> (i32, i32) {
+    let (width, height) = if rotated { (mode.1, mode.0) } else { mode };
+    let scale = f64::from(scale.max(1));
+    (
+        (f64::from(width) / scale).round() as i32,
+        (f64::from(height) / scale).round() as i32,
+    )
+}
+
+/// Scale from the current mode and the logical size, to two decimals; the
+/// mode is in buffer orientation, so a quarter turn swaps its sides.
+pub(crate) fn output_scale(mode: (i32, i32), logical: (i32, i32), rotated: bool) -> f64 {
+    let mode_width = if rotated { mode.1 } else { mode.0 };
+    if logical.0 <= 0 || mode_width <= 0 {
+        return 1.0;
+    }
+    (mode_width as f64 / logical.0 as f64 * 100.0).round() / 100.0
+}
+
+/// Outputs that report both a name and a logical geometry.
+#[cfg(feature = "freeze")]
+fn wayland_named_outputs() -> Result<Vec<OutputInfo>> {
+    Ok(crate::outputs::enumerate()?
+        .iter()
+        .filter_map(crate::outputs::OutputEntry::info)
+        .collect())
+}
+
+/// Select a region, clipped to `monitor` when one was named with `-m <output>`.
+/// Otherwise `constrain` clips it to the output showing most of it, so it never
+/// spans outputs with different scales. slurp-rs can't keep the drag on one
+/// output, so a clipped selection is reported on stderr.
+pub fn grab_region(
+    compositor: &mut dyn CompositorBackend,
+    monitor: Option<&str>,
+    constrain: bool,
+    debug: bool,
+) -> Result<Geometry> {
+    let Some(monitor) = monitor else {
+        let selection = selector::select_region(debug)?;
+        if !constrain {
+            return Ok(selection);
+        }
+        let outputs = compositor.list_outputs(debug)?;
+        return Ok(constrain_to_output(selection, &outputs));
+    };
+    let bounds = grab_selected_output(compositor, monitor, debug)?;
+    let selection = selector::select_region(debug)?;
+    let clipped = selection.intersect(bounds).with_context(|| {
+        format!(
+            "Selected region {} is outside output '{}' ({})",
+            selection, monitor, bounds
         )
-        .context("Failed to run hyprctl activeworkspace")?
-        .stdout,
-    )?;
-    let monitors = hyprctl_monitors_json(cache, IPC_TIMEOUT)?;
+    })?;
+    if clipped != selection {
+        eprintln!(
+            "Region clipped to output '{}': {} (selected {})",
+            monitor, clipped, selection
+        );
+    }
+    Ok(clipped)
+}
 
-    if debug {
-        eprintln!("Monitors: {}", monitors);
-        eprintln!("Active workspace: {}", active_workspace);
+/// Clip `selection` to the output showing most of it; unchanged when it
+/// touches no output.
+pub(crate) fn constrain_to_output(selection: Geometry, outputs: &[(String, Geometry)]) -> Geometry {
+    let Some(name) = output_with_most_overlap(&selection, outputs) else {
+        return selection;
+    };
+    let Some(clipped) = outputs
+        .iter()
+        .find(|(output, _)| output == name)
+        .and_then(|(_, bounds)| selection.intersect(*bounds))
+    else {
+        return selection;
+    };
+    if clipped != selection {
+        eprintln!(
+            "Region clipped to output '{}': {} (selected {})",
+            name, clipped, selection
+        );
+    }
+    clipped
+}
+
+pub fn is_region_selection_cancelled(err: &anyhow::Error) -> bool {
+    selector::is_cancelled(err, selector::SelectionTarget::Region)
+}
+
+/// `client_area` drops server-side decorations on Sway. Hyprland clients draw
+/// their own, so it has no effect there.
+pub fn grab_window(
+    compositor: &mut dyn CompositorBackend,
+    client_area: bool,
+    debug: bool,
+) -> Result<Geometry> {
+    let boxes = window_boxes(compositor, client_area, debug)?;
+    selector::select_from_boxes(&boxes, debug)
+}
+
+/// Window boxes offered to the selector, in logical coordinates. Used to draw
+/// them on the freeze overlay when checking alignment.
+pub fn window_candidates(
+    compositor: &mut dyn CompositorBackend,
+    client_area: bool,
+    debug: bool,
+) -> Result<Vec<Geometry>> {
+    Ok(compositor
+        .list_windows(client_area, debug)?
+        .into_iter()
+        .map(|window| window.geometry)
+        .collect())
+}
+
+/// The listed window with exactly `geometry`, for `%title` and `%class` in
+/// filename templates. `None` (logged under `--debug`) when it can't be found.
+pub fn window_with_geometry(
+    compositor: &mut dyn CompositorBackend,
+    geometry: &Geometry,
+    client_area: bool,
+    debug: bool,
+) -> Option<Window> {
+    match compositor.list_windows(client_area, debug) {
+        Ok(windows) => {
+            let window = windows.into_iter().find(|w| w.geometry == *geometry);
+            if debug && window.is_none() {
+                eprintln!("No window at {} for the filename template", geometry);
+            }
+            window
+        }
+        Err(err) => {
+            if debug {
+                eprintln!("Window lookup for the filename template failed: {:#}", err);
+            }
+            None
+        }
+    }
+}
+
+/// Name of the output showing most of `geometry`, for `%output` in filename
+/// templates.
+pub fn output_at(
+    compositor: &mut dyn CompositorBackend,
+    geometry: &Geometry,
+    debug: bool,
+) -> Option<String> {
+    match compositor.list_outputs(debug) {
+        Ok(outputs) => output_with_most_overlap(geometry, &outputs).map(str::to_string),
+        Err(err) => {
+            if debug {
+                eprintln!("Output lookup for the filename template failed: {:#}", err);
+            }
+            None
+        }
     }
+}
+
+/// Ties go to the first output, as in [`crate::utils::crop_to_monitors`].
+pub(crate) fn output_with_most_overlap<'a>(
+    geometry: &Geometry,
+    outputs: &'a [(String, Geometry)],
+) -> Option<&'a str> {
+    let area = |g: &Geometry| g.width as i64 * g.height as i64;
+    let mut best: Option<(&str, i64)> = None;
+    for (name, bounds) in outputs {
+        if let Some(overlap) = geometry.intersect(*bounds)
+            && best.is_none_or(|(_, best_area)| area(&overlap) > best_area)
+        {
+            best = Some((name, area(&overlap)));
+        }
+    }
+    best.map(|(name, _)| name)
+}
 
-    let current_monitor = monitors
-        .as_array()
-        .and_then(|arr| {
-            arr.iter()
-                .find(|m| m["activeWorkspace"]["id"] == active_workspace["id"])
-        })
-        .context("No matching monitor found")?;
+/// `--window-title`, `--window-class`, `--address` and `--pid`: pick a window
+/// without the selector. A window must match every criterion given.
+#[derive(Debug, Default)]
+pub struct WindowFilter {
+    pub title: Option<Regex>,
+    /// Compared case-insensitively.
+    pub class: Option<String>,
+    /// Hyprland client address, with or without the `0x` prefix.
+    pub address: Option<String>,
+    pub pid: Option<u32>,
+}
 
-    if debug {
-        eprintln!("Current output: {}", current_monitor);
+impl WindowFilter {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.class.is_none() && self.address.is_none() && self.pid.is_none()
+    }
+
+    fn matches(&self, window: &Window) -> bool {
+        self.title
+            .as_ref()
+            .is_none_or(|title| title.is_match(&window.title))
+            && self
+                .class
+                .as_ref()
+                .is_none_or(|class| window.class.eq_ignore_ascii_case(class))
+            && self.address.as_deref().is_none_or(|address| {
+                window
+                    .address
+                    .as_deref()
+                    .is_some_and(|own| same_address(own, address))
+            })
+            && self.pid.is_none_or(|pid| window.pid == Some(pid))
     }
+}
 
-    let x = current_monitor["x"].as_i64().unwrap_or(0) as i32;
-    let y = current_monitor["y"].as_i64().unwrap_or(0) as i32;
-    let width = current_monitor["width"].as_i64().unwrap_or(0) as f64;
-    let height = current_monitor["height"].as_i64().unwrap_or(0) as f64;
-    let scale = current_monitor["scale"].as_f64().unwrap_or(1.0);
+fn same_address(a: &str, b: &str) -> bool {
+    let strip = |s: &str| {
+        s.strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .unwrap_or(s)
+            .trim_start_matches('0')
+            .to_ascii_lowercase()
+    };
+    strip(a) == strip(b)
+}
+
+impl fmt::Display for WindowFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(title) = &self.title {
+            parts.push(format!("title /{}/", title));
+        }
+        if let Some(class) = &self.class {
+            parts.push(format!("class '{}'", class));
+        }
+        if let Some(address) = &self.address {
+            parts.push(format!("address {}", address));
+        }
+        if let Some(pid) = self.pid {
+            parts.push(format!("pid {}", pid));
+        }
+        if parts.is_empty() {
+            return write!(f, "any window");
+        }
+        write!(f, "{}", parts.join(" and "))
+    }
+}
 
-    let geometry = Geometry::new(
-        x,
-        y,
-        (width / scale).round() as i32,
-        (height / scale).round() as i32,
-    )?;
+/// Geometry of the one visible window matching `filter`.
+pub fn find_window(
+    compositor: &mut dyn CompositorBackend,
+    filter: &WindowFilter,
+    client_area: bool,
+    debug: bool,
+) -> Result<Geometry> {
+    let windows = compositor.list_windows(client_area, debug)?;
+    let window = match_window(&windows, filter)?;
     if debug {
-        eprintln!("Active output geometry: {}", geometry);
+        eprintln!("Window matching {}: {}", filter, describe_window(window));
+    }
+    Ok(window.geometry)
+}
+
+/// The single window matching `filter`. No match or several matches is an
+/// error listing the candidates, so a script never captures the wrong one.
+pub(crate) fn match_window<'a>(windows: &'a [Window], filter: &WindowFilter) -> Result<&'a Window> {
+    let matches: Vec<&Window> = windows.iter().filter(|w| filter.matches(w)).collect();
+    let list = |windows: &[&Window]| {
+        windows
+            .iter()
+            .map(|w| format!("  {}", describe_window(w)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    match matches.as_slice() {
+        [window] => Ok(window),
+        [] if windows.is_empty() => bail!("No window matches {}: no windows are visible", filter),
+        [] => bail!(
+            "No window matches {}. Visible windows:\n{}",
+            filter,
+            list(&windows.iter().collect::<Vec<_>>())
+        ),
+        _ => bail!(
+            "{} windows match {}; narrow it down:\n{}",
+            matches.len(),
+            filter,
+            list(&matches)
+        ),
     }
-    Ok(geometry)
 }
 
-fn grab_active_output_sway(debug: bool) -> Result<Geometry> {
-    let workspaces = sway_msg(&["-t", "get_workspaces"])?;
-    let focused_output = workspaces
-        .as_array()
-        .and_then(|arr| arr.iter().find(|w| w["focused"].as_bool() == Some(true)))
-        .and_then(|w| w["output"].as_str())
-        .context("Failed to find focused workspace output")?;
-
-    let outputs = sway_msg(&["-t", "get_outputs"])?;
-    let output_data = outputs
-        .as_array()
-        .and_then(|arr| {
-            arr.iter()
-                .find(|o| o["name"].as_str() == Some(focused_output))
-        })
-        .context("Focused output not found in sway outputs")?;
-
-    let rect = output_data["rect"]
-        .as_object()
-        .context("Invalid output rect data")?;
-
-    let x = rect.get("x").and_then(|v| v.as_i64()).unwrap_or(0);
-    let y = rect.get("y").and_then(|v| v.as_i64()).unwrap_or(0);
-    let width = rect.get("width").and_then(|v| v.as_i64()).unwrap_or(0);
-    let height = rect.get("height").and_then(|v| v.as_i64()).unwrap_or(0);
-
-    let geometry = Geometry::new(x as i32, y as i32, width as i32, height as i32)?;
+fn describe_window(window: &Window) -> String {
+    let mut ids = Vec::new();
+    if let Some(address) = &window.address {
+        ids.push(address.clone());
+    }
+    if let Some(pid) = window.pid {
+        ids.push(format!("pid {}", pid));
+    }
+    let ids = if ids.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", ids.join(", "))
+    };
+    format!(
+        "{} [{}]{} {}",
+        window.geometry, window.class, ids, window.title
+    )
+}
+
+/// Visible windows as selector boxes (`x,y WxH title` per line).
+fn window_boxes(
+    compositor: &mut dyn CompositorBackend,
+    client_area: bool,
+    debug: bool,
+) -> Result<String> {
+    let boxes = compositor
+        .list_windows(client_area, debug)?
+        .iter()
+        .map(Window::selector_box)
+        .collect::<Vec<_>>()
+        .join("\n");
+
     if debug {
-        eprintln!("Active output geometry (sway): {}", geometry);
+        eprintln!("Window boxes ({}):\n{}", compositor.name(), boxes);
     }
-    Ok(geometry)
+
+    if boxes.is_empty() {
+        return Err(anyhow::anyhow!("No valid windows found to capture"));
+    }
+
+    Ok(boxes)
 }
 
-pub fn grab_selected_output(monitor: &str, debug: bool) -> Result<Geometry> {
-    #[cfg(feature = "freeze")]
-    if let Ok(geometry) = grab_selected_output_wayland(monitor, debug) {
-        return Ok(geometry);
+// Experimental: layer-shell surfaces (bars, docks, ...) are only listed by
+// Hyprland. Elsewhere we fall back to a plain region selection.
+pub fn grab_layer(compositor: &mut dyn CompositorBackend, debug: bool) -> Result<Geometry> {
+    match select_layer(compositor, debug) {
+        Ok(geometry) => Ok(geometry),
+        Err(err) if selector::is_cancelled(&err, selector::SelectionTarget::Window) => Err(err),
+        Err(err) => {
+            if debug {
+                eprintln!(
+                    "Layer listing unavailable ({:#}); falling back to region",
+                    err
+                );
+            }
+            grab_region(compositor, None, false, debug)
+        }
     }
+}
 
-    Err(anyhow::anyhow!(
-        "Output '{}' not found via Wayland. Use '-m output' to select interactively.",
-        monitor
-    ))
+fn select_layer(compositor: &mut dyn CompositorBackend, debug: bool) -> Result<Geometry> {
+    let boxes = compositor.list_layers(debug)?.join("\n");
+    if debug {
+        eprintln!("Layer boxes:\n{}", boxes);
+    }
+    if boxes.is_empty() {
+        return Err(anyhow::anyhow!("No layer surfaces found to capture"));
+    }
+
+    selector::select_from_boxes(&boxes, debug)
 }
 
+pub fn grab_active_window(
+    compositor: &mut dyn CompositorBackend,
+    client_area: bool,
+    debug: bool,
+) -> Result<Geometry> {
+    let geometry = compositor.active_window(client_area, debug)?;
+    if debug {
+        eprintln!(
+            "Active window geometry ({}): {}",
+            compositor.name(),
+            geometry
+        );
+    }
+    Ok(geometry)
+}
+
+// wlr-foreign-toplevel-management reports which toplevel is activated and which
+// outputs it is on, but not its position or size. Only a fullscreen toplevel
+// has a known geometry (its output), so anything else is reported as an error.
 #[cfg(feature = "freeze")]
-fn grab_selected_output_wayland(monitor: &str, debug: bool) -> Result<Geometry> {
+pub(crate) fn grab_active_window_foreign_toplevel(debug: bool) -> Result<Geometry> {
+    use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+        zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+        zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+    };
+
     let conn = Connection::connect_to_env().context("Failed to connect to Wayland")?;
     let mut event_queue = conn.new_event_queue();
     let qh = event_queue.handle();
 
     let _registry = conn.display().get_registry(&qh, ());
 
-    #[derive(Debug)]
-    struct OutputKey(usize);
-
-    struct OutputEntry {
-        output: WlOutput,
-        name: Option<String>,
-        xdg_output: Option<ZxdgOutputV1>,
-        pos_x: Option<i32>,
-        pos_y: Option<i32>,
-        mode_width: Option<i32>,
-        mode_height: Option<i32>,
-        scale: i32,
-        logical_x: Option<i32>,
-        logical_y: Option<i32>,
-        logical_width: Option<i32>,
-        logical_height: Option<i32>,
+    struct ToplevelEntry {
+        handle: ZwlrForeignToplevelHandleV1,
+        title: Option<String>,
+        activated: bool,
+        fullscreen: bool,
+        outputs: Vec<WlOutput>,
+        closed: bool,
     }
 
     struct State {
-        outputs: Vec<OutputEntry>,
-        xdg_output_manager: Option<ZxdgOutputManagerV1>,
+        outputs: Outputs,
+        toplevel_manager: Option<ZwlrForeignToplevelManagerV1>,
+        toplevels: Vec<ToplevelEntry>,
     }
 
     impl Dispatch<WlRegistry, ()> for State {
@@ -211,499 +639,153 @@ fn grab_selected_output_wayland(monitor: &str, debug: bool) -> Result<Geometry>
                 version,
             } = event
             {
-                match interface.as_str() {
-                    "wl_output" => {
-                        let idx = state.outputs.len();
-                        let output = registry.bind::<WlOutput, _, _>(
-                            name,
-                            version.min(4),
-                            qh,
-                            OutputKey(idx),
-                        );
-                        state.outputs.push(OutputEntry {
-                            output,
-                            name: None,
-                            xdg_output: None,
-                            pos_x: None,
-                            pos_y: None,
-                            mode_width: None,
-                            mode_height: None,
-                            scale: 1,
-                            logical_x: None,
-                            logical_y: None,
-                            logical_width: None,
-                            logical_height: None,
-                        });
-                    }
-                    "zxdg_output_manager_v1" => {
-                        state.xdg_output_manager =
-                            Some(registry.bind(name, version.min(3), qh, ()));
-                    }
-                    _ => {}
+                if interface == "zwlr_foreign_toplevel_manager_v1" {
+                    state.toplevel_manager = Some(registry.bind(name, version.min(3), qh, ()));
+                } else {
+                    state.outputs.bind(registry, name, &interface, version, qh);
                 }
             }
         }
     }
 
-    impl Dispatch<WlOutput, OutputKey> for State {
+    impl AsMut<Outputs> for State {
+        fn as_mut(&mut self) -> &mut Outputs {
+            &mut self.outputs
+        }
+    }
+
+    delegate_outputs!(State);
+
+    impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for State {
         fn event(
             state: &mut Self,
-            _: &WlOutput,
-            event: wayland_client::protocol::wl_output::Event,
-            data: &OutputKey,
+            _: &ZwlrForeignToplevelManagerV1,
+            event: zwlr_foreign_toplevel_manager_v1::Event,
+            _: &(),
             _: &Connection,
             _: &QueueHandle<Self>,
         ) {
-            let Some(entry) = state.outputs.get_mut(data.0) else {
-                return;
-            };
-            match event {
-                wayland_client::protocol::wl_output::Event::Geometry { x, y, .. } => {
-                    entry.pos_x = Some(x);
-                    entry.pos_y = Some(y);
-                }
-                wayland_client::protocol::wl_output::Event::Mode {
-                    flags,
-                    width,
-                    height,
-                    ..
-                } => {
-                    let is_current = match flags {
-                        wayland_client::WEnum::Value(f) => f.contains(WlOutputMode::Current),
-                        wayland_client::WEnum::Unknown(_) => false,
-                    };
-                    if is_current {
-                        entry.mode_width = Some(width);
-                        entry.mode_height = Some(height);
-                    }
-                }
-                wayland_client::protocol::wl_output::Event::Scale { factor } => {
-                    entry.scale = factor.max(1);
-                }
-                wayland_client::protocol::wl_output::Event::Name { name } => {
-                    entry.name = Some(name);
-                }
-                _ => {}
+            if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+                state.toplevels.push(ToplevelEntry {
+                    handle: toplevel,
+                    title: None,
+                    activated: false,
+                    fullscreen: false,
+                    outputs: Vec::new(),
+                    closed: false,
+                });
             }
         }
+
+        wayland_client::event_created_child!(State, ZwlrForeignToplevelManagerV1, [
+            zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+        ]);
     }
 
-    impl Dispatch<ZxdgOutputV1, OutputKey> for State {
+    impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for State {
         fn event(
             state: &mut Self,
-            _: &ZxdgOutputV1,
-            event: wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1::Event,
-            data: &OutputKey,
+            handle: &ZwlrForeignToplevelHandleV1,
+            event: zwlr_foreign_toplevel_handle_v1::Event,
+            _: &(),
             _: &Connection,
             _: &QueueHandle<Self>,
         ) {
-            let Some(entry) = state.outputs.get_mut(data.0) else {
+            let Some(entry) = state.toplevels.iter_mut().find(|t| &t.handle == handle) else {
                 return;
             };
             match event {
-                wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1::Event::LogicalPosition { x, y } => {
-                    entry.logical_x = Some(x);
-                    entry.logical_y = Some(y);
+                zwlr_foreign_toplevel_handle_v1::Event::Title { title } => {
+                    entry.title = Some(title);
                 }
-                wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1::Event::LogicalSize { width, height } => {
-                    entry.logical_width = Some(width);
-                    entry.logical_height = Some(height);
+                zwlr_foreign_toplevel_handle_v1::Event::State { state: raw } => {
+                    let states = raw
+                        .chunks_exact(4)
+                        .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]));
+                    entry.activated = false;
+                    entry.fullscreen = false;
+                    for value in states {
+                        match value {
+                            v if v == zwlr_foreign_toplevel_handle_v1::State::Activated as u32 => {
+                                entry.activated = true;
+                            }
+                            v if v == zwlr_foreign_toplevel_handle_v1::State::Fullscreen as u32 => {
+                                entry.fullscreen = true;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                zwlr_foreign_toplevel_handle_v1::Event::OutputEnter { output } => {
+                    entry.outputs.push(output);
+                }
+                zwlr_foreign_toplevel_handle_v1::Event::OutputLeave { output } => {
+                    entry.outputs.retain(|o| o != &output);
                 }
-                wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1::Event::Name {
-                    name,
-                } => {
-                    entry.name = Some(name);
+                zwlr_foreign_toplevel_handle_v1::Event::Closed => {
+                    entry.closed = true;
                 }
                 _ => {}
             }
         }
     }
 
-    impl Dispatch<ZxdgOutputManagerV1, ()> for State {
-        fn event(
-            _: &mut Self,
-            _: &ZxdgOutputManagerV1,
-            _: wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_manager_v1::Event,
-            _: &(),
-            _: &Connection,
-            _: &QueueHandle<Self>,
-        ) {
-        }
-    }
-
     let mut state = State {
-        outputs: Vec::new(),
-        xdg_output_manager: None,
+        outputs: Outputs::default(),
+        toplevel_manager: None,
+        toplevels: Vec::new(),
     };
 
     event_queue
         .roundtrip(&mut state)
-        .context("Failed to initialize Wayland outputs")?;
+        .context("Failed to initialize Wayland globals")?;
 
-    if let Some(manager) = &state.xdg_output_manager {
-        for (idx, entry) in state.outputs.iter_mut().enumerate() {
-            let xdg_output = manager.get_xdg_output(&entry.output, &qh, OutputKey(idx));
-            entry.xdg_output = Some(xdg_output);
-        }
-        event_queue
-            .roundtrip(&mut state)
-            .context("Failed to receive output names")?;
-    }
-
-    let Some(output) = state
-        .outputs
-        .iter()
-        .find(|o| o.name.as_deref() == Some(monitor))
-    else {
+    let Some(toplevel_manager) = state.toplevel_manager.clone() else {
         return Err(anyhow::anyhow!(
-            "Output names are unavailable or '{}' was not found",
-            monitor
+            "Compositor does not support wlr-foreign-toplevel-management"
         ));
     };
 
-    fn output_logical_size(output: &OutputEntry) -> Option<(i32, i32)> {
-        if let (Some(width), Some(height)) = (output.logical_width, output.logical_height) {
-            return Some((width, height));
-        }
-
-        let mode_width = output.mode_width?;
-        let mode_height = output.mode_height?;
-        let scale = output.scale.max(1);
-        Some((
-            ((mode_width as f64) / (scale as f64)).round() as i32,
-            ((mode_height as f64) / (scale as f64)).round() as i32,
-        ))
-    }
-
-    fn output_geometry(output: &OutputEntry) -> Option<Geometry> {
-        let x = output.logical_x.or(output.pos_x)?;
-        let y = output.logical_y.or(output.pos_y)?;
-        let (width, height) = output_logical_size(output)?;
-        Geometry::new(x, y, width, height).ok()
-    }
-
-    let geometry = output_geometry(output).context("Output geometry not available")?;
-    if debug {
-        eprintln!("Selected output geometry: {}", geometry);
-    }
-    Ok(geometry)
-}
-
-pub fn grab_region(debug: bool) -> Result<Geometry> {
-    selector::select_region(debug)
-}
-
-pub fn is_region_selection_cancelled(err: &anyhow::Error) -> bool {
-    selector::is_cancelled(err, selector::SelectionTarget::Region)
-}
-
-pub fn grab_window(debug: bool, cache: &mut HyprctlCache) -> Result<Geometry> {
-    if let Ok(geometry) = grab_window_hyprctl(debug, cache) {
-        return Ok(geometry);
-    }
-    if let Ok(geometry) = grab_window_sway(debug) {
-        return Ok(geometry);
-    }
-
-    Err(anyhow::anyhow!(
-        "Window selection is only supported on Hyprland or Sway"
-    ))
-}
-
-fn grab_window_hyprctl(debug: bool, cache: &mut HyprctlCache) -> Result<Geometry> {
-    const IPC_TIMEOUT: Duration = Duration::from_secs(3);
-    let monitors = hyprctl_monitors_json(cache, IPC_TIMEOUT)?;
-    let clients: Value = serde_json::from_slice(
-        &output_with_timeout(
-            {
-                let mut cmd = Command::new("hyprctl");
-                cmd.arg("clients").arg("-j");
-                cmd
-            },
-            IPC_TIMEOUT,
-        )
-        .context("Failed to run hyprctl clients")?
-        .stdout,
-    )?;
-
-    let workspace_ids: HashSet<i64> = monitors
-        .as_array()
-        .map(|arr| {
-            arr.iter()
-                .filter_map(|m| m["activeWorkspace"]["id"].as_i64())
-                .collect::<HashSet<_>>()
-        })
-        .unwrap_or_default();
-
-    let filtered_clients: Vec<Value> = clients
-        .as_array()
-        .map(|arr| {
-            arr.iter()
-                .filter(|c| {
-                    c["workspace"]["id"]
-                        .as_i64()
-                        .map(|id| workspace_ids.contains(&id))
-                        .unwrap_or(false)
-                })
-                .cloned()
-                .collect()
-        })
-        .unwrap_or_default();
-
-    if debug {
-        eprintln!("Monitors: {}", monitors);
-        eprintln!("Clients: {}", serde_json::to_string(&filtered_clients)?);
-    }
-
-    let boxes: String = filtered_clients
-        .into_iter()
-        .filter_map(|c| {
-            let at = c["at"].as_array()?;
-            let size = c["size"].as_array()?;
-            let x = at[0].as_i64()?;
-            let y = at[1].as_i64()?;
-            let width = size[0].as_i64()?;
-            let height = size[1].as_i64()?;
-            if width <= 0 || height <= 0 {
-                return None;
-            }
-            Some(format!(
-                "{},{} {}x{} {}",
-                x,
-                y,
-                width,
-                height,
-                c["title"].as_str().unwrap_or("")
-            ))
-        })
-        .collect::<Vec<_>>()
-        .join("\n");
-
-    if debug {
-        eprintln!("Window boxes:\n{}", boxes);
-    }
-
-    if boxes.is_empty() {
-        return Err(anyhow::anyhow!("No valid windows found to capture"));
-    }
-
-    selector::select_from_boxes(&boxes, debug)
-}
-
-pub fn grab_active_window(debug: bool) -> Result<Geometry> {
-    if let Ok(geometry) = grab_active_window_hyprctl(debug) {
-        return Ok(geometry);
-    }
-    if let Ok(geometry) = grab_active_window_sway(debug) {
-        return Ok(geometry);
-    }
-
-    Err(anyhow::anyhow!(
-        "Active window is only supported on Hyprland or Sway"
-    ))
-}
+    state.outputs.request_logical(&qh);
+    // First roundtrip delivers the toplevel handles, the second their state.
+    event_queue
+        .roundtrip(&mut state)
+        .context("Failed to receive toplevels")?;
+    event_queue
+        .roundtrip(&mut state)
+        .context("Failed to receive toplevel state")?;
+    toplevel_manager.stop();
 
-fn grab_active_window_hyprctl(debug: bool) -> Result<Geometry> {
-    const IPC_TIMEOUT: Duration = Duration::from_secs(3);
-    let active_window: Value = serde_json::from_slice(
-        &output_with_timeout(
-            {
-                let mut cmd = Command::new("hyprctl");
-                cmd.arg("activewindow").arg("-j");
-                cmd
-            },
-            IPC_TIMEOUT,
-        )
-        .context("Failed to run hyprctl activewindow")?
-        .stdout,
-    )?;
+    let active = state
+        .toplevels
+        .iter()
+        .find(|t| t.activated && !t.closed)
+        .context("No activated toplevel reported by the compositor")?;
 
     if debug {
-        eprintln!("Active window: {}", active_window);
+        eprintln!(
+            "Active toplevel (foreign-toplevel): {} fullscreen={}",
+            active.title.as_deref().unwrap_or(""),
+            active.fullscreen
+        );
     }
 
-    let at = active_window["at"]
-        .as_array()
-        .context("Invalid active window data: missing 'at' field")?;
-    let size = active_window["size"]
-        .as_array()
-        .context("Invalid active window data: missing 'size' field")?;
-
-    let x = at[0].as_i64().context("Invalid x coordinate")?;
-    let y = at[1].as_i64().context("Invalid y coordinate")?;
-    let width = size[0].as_i64().context("Invalid width")?;
-    let height = size[1].as_i64().context("Invalid height")?;
-
-    if width <= 0 || height <= 0 {
+    if !active.fullscreen {
         return Err(anyhow::anyhow!(
-            "Invalid window dimensions: width={} or height={}",
-            width,
-            height
+            "wlr-foreign-toplevel-management does not expose window geometry; \
+only fullscreen windows can be captured this way"
         ));
     }
 
-    let geometry = Geometry::new(x as i32, y as i32, width as i32, height as i32)?;
-    if debug {
-        eprintln!("Active window geometry: {}", geometry);
-    }
-    Ok(geometry)
-}
-
-fn grab_window_sway(debug: bool) -> Result<Geometry> {
-    let workspaces = sway_msg(&["-t", "get_workspaces"])?;
-    let visible_workspaces: HashSet<String> = workspaces
-        .as_array()
-        .map(|arr| {
-            arr.iter()
-                .filter(|w| w["visible"].as_bool() == Some(true))
-                .filter_map(|w| w["name"].as_str().map(|s| s.to_string()))
-                .collect::<HashSet<_>>()
-        })
-        .unwrap_or_default();
-
-    let tree = sway_msg(&["-t", "get_tree"])?;
-    let mut boxes = Vec::new();
-    collect_visible_windows(&tree, &visible_workspaces, false, &mut boxes);
-
-    if debug {
-        eprintln!("Sway window boxes:\n{}", boxes.join("\n"));
-    }
-
-    if boxes.is_empty() {
-        return Err(anyhow::anyhow!("No valid windows found to capture (sway)"));
-    }
-
-    selector::select_from_boxes(&boxes.join("\n"), debug)
-}
-
-fn grab_active_window_sway(debug: bool) -> Result<Geometry> {
-    let tree = sway_msg(&["-t", "get_tree"])?;
-    let focused = find_focused_window(&tree).context("Focused window not found (sway)")?;
-
-    let rect = focused["rect"]
-        .as_object()
-        .context("Invalid focused window rect")?;
-    let x = rect.get("x").and_then(|v| v.as_i64()).unwrap_or(0);
-    let y = rect.get("y").and_then(|v| v.as_i64()).unwrap_or(0);
-    let width = rect.get("width").and_then(|v| v.as_i64()).unwrap_or(0);
-    let height = rect.get("height").and_then(|v| v.as_i64()).unwrap_or(0);
-
-    if width <= 0 || height <= 0 {
-        return Err(anyhow::anyhow!(
-            "Invalid focused window dimensions: width={} height={}",
-            width,
-            height
-        ));
-    }
+    let output = active
+        .outputs
+        .first()
+        .and_then(|wl| state.outputs.entries.iter().find(|o| &o.output == wl))
+        .context("Active fullscreen window is not on a known output")?;
 
-    let geometry = Geometry::new(x as i32, y as i32, width as i32, height as i32)?;
+    let geometry = output.geometry().context("Output geometry not available")?;
     if debug {
-        eprintln!("Active window geometry (sway): {}", geometry);
+        eprintln!("Active window geometry (foreign-toplevel): {}", geometry);
     }
     Ok(geometry)
 }
-
-fn collect_visible_windows(
-    node: &Value,
-    visible_workspaces: &HashSet<String>,
-    mut visible: bool,
-    boxes: &mut Vec<String>,
-) {
-    if node["type"].as_str() == Some("workspace") {
-        visible = node
-            .get("name")
-            .and_then(|v| v.as_str())
-            .map(|name| visible_workspaces.contains(name))
-            .unwrap_or(false);
-    }
-
-    if visible
-        && is_window_node(node)
-        && let Some(line) = format_window_box(node)
-    {
-        boxes.push(line);
-    }
-
-    if let Some(nodes) = node.get("nodes").and_then(|v| v.as_array()) {
-        for child in nodes {
-            collect_visible_windows(child, visible_workspaces, visible, boxes);
-        }
-    }
-    if let Some(nodes) = node.get("floating_nodes").and_then(|v| v.as_array()) {
-        for child in nodes {
-            collect_visible_windows(child, visible_workspaces, visible, boxes);
-        }
-    }
-}
-
-fn is_window_node(node: &Value) -> bool {
-    if node["type"].as_str() != Some("con") {
-        return false;
-    }
-    let has_app = node["app_id"].is_string();
-    let has_props = node
-        .get("window_properties")
-        .map(|v| v.is_object())
-        .unwrap_or(false);
-    has_app || has_props
-}
-
-fn format_window_box(node: &Value) -> Option<String> {
-    let rect = node.get("rect")?.as_object()?;
-    let x = rect.get("x")?.as_i64()? as i32;
-    let y = rect.get("y")?.as_i64()? as i32;
-    let width = rect.get("width")?.as_i64()? as i32;
-    let height = rect.get("height")?.as_i64()? as i32;
-    if width <= 0 || height <= 0 {
-        return None;
-    }
-    let title = node
-        .get("name")
-        .and_then(|v| v.as_str())
-        .unwrap_or("")
-        .replace('\n', " ");
-    Some(format!("{},{} {}x{} {}", x, y, width, height, title))
-}
-
-fn find_focused_window(node: &Value) -> Option<&Value> {
-    if node.get("focused").and_then(|v| v.as_bool()) == Some(true) && is_window_node(node) {
-        return Some(node);
-    }
-
-    if let Some(nodes) = node.get("nodes").and_then(|v| v.as_array()) {
-        for child in nodes {
-            if let Some(found) = find_focused_window(child) {
-                return Some(found);
-            }
-        }
-    }
-    if let Some(nodes) = node.get("floating_nodes").and_then(|v| v.as_array()) {
-        for child in nodes {
-            if let Some(found) = find_focused_window(child) {
-                return Some(found);
-            }
-        }
-    }
-
-    None
-}
-
-fn sway_msg(args: &[&str]) -> Result<Value> {
-    const IPC_TIMEOUT: Duration = Duration::from_secs(3);
-    let output = output_with_timeout(
-        {
-            let mut cmd = Command::new("swaymsg");
-            cmd.args(args);
-            cmd
-        },
-        IPC_TIMEOUT,
-    )
-    .context("Failed to run swaymsg")?;
-    if !output.status.success() {
-        return Err(anyhow::anyhow!(
-            "swaymsg failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        ));
-    }
-    serde_json::from_slice(&output.stdout).context("Failed to parse swaymsg JSON")
-}
@@ -1,9 +1,14 @@
-use chrono::{DateTime, Local};
-use clap::Parser;
-use std::path::PathBuf;
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueHint};
+use regex::Regex;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use crate::config;
+use crate::filename;
+use crate::geometry::{AspectMode, AspectRatio, Geometry};
+use crate::resize::Resize;
+use crate::save::{RawFormat, StdoutEncoding};
 
 #[derive(Parser)]
 #[command(
@@ -14,32 +19,348 @@ pub struct Args {
     #[arg(
         short = 'm',
         long,
-        value_parser = clap::builder::ValueParser::new(
-            |s: &str| -> std::result::Result<Mode, String> {
-            match s.to_ascii_lowercase().as_str() {
-                "output" => Ok(Mode::Output),
-                "window" => Ok(Mode::Window),
-                "region" => Ok(Mode::Region),
-                "active" => Ok(Mode::Active),
-                _ => Ok(Mode::OutputName(s.to_string())),
-            }
-        }),
-        help = "Mode: output, window, region, active, or OUTPUT_NAME"
+        value_parser = ModeParser,
+        hide_possible_values = true,
+        help = "Mode: output, window, window-pair, region, layer, all, active, or OUTPUT_NAME"
     )]
     pub mode: Vec<Mode>,
 
-    #[arg(short, long, help = "Directory to save screenshot")]
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Use the options bundled in [profiles.NAME] of the config"
+    )]
+    pub profile: Option<String>,
+
+    #[arg(short, long, value_hint = ValueHint::DirPath, help = "Directory to save screenshot")]
     pub output_folder: Option<PathBuf>,
 
-    #[arg(short, long, help = "Filename of the screenshot")]
+    #[arg(short, long, value_hint = ValueHint::FilePath, help = "Filename of the screenshot")]
     pub filename: Option<String>,
 
-    #[arg(short = 'D', long, help = "Delay before taking screenshot (seconds)")]
-    pub delay: Option<u64>,
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        help = "Name new screenshots from TEMPLATE, e.g. '%Y-%m-%d_%mode_%counter' (overrides paths.filename_template)"
+    )]
+    pub filename_template: Option<String>,
+
+    #[arg(
+        short = 'D',
+        long,
+        value_parser = parse_delay,
+        help = "Delay before taking screenshot (e.g. 3, 0.5, 500ms, 1.5s, 1m; bare numbers are seconds)"
+    )]
+    pub delay: Option<Duration>,
+
+    #[arg(
+        long,
+        help = "Run the delayed capture in the background and return immediately"
+    )]
+    pub detach_delay: bool,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = parse_delay,
+        help = "Give up with exit code 11 if nothing is selected within DURATION (e.g. 30, 1m)"
+    )]
+    pub selection_timeout: Option<Duration>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Grow the selection by N logical pixels on each side, within its monitor"
+    )]
+    pub padding: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "W:H",
+        value_parser = parse_aspect,
+        help = "Adjust the selection to an aspect ratio (e.g. 16:9)"
+    )]
+    pub aspect: Option<AspectRatio>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = AspectMode::Expand,
+        help = "How --aspect adjusts the selection: expand or crop"
+    )]
+    pub aspect_mode: AspectMode,
+
+    #[arg(
+        long,
+        value_parser = parse_png_compression,
+        value_name = "LEVEL",
+        help = "PNG compression: fast, default, best or a level 0-9 (overrides capture.png_compression)"
+    )]
+    pub png_compression: Option<config::PngCompression>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["record", "print_geometry", "clipboard_only"],
+        help = "Shrink saved PNGs losslessly (smaller color type, best filters; slower)"
+    )]
+    pub optimize: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "LEVEL",
+        help = "Details embedded in saved files: none, basic or full (overrides capture.metadata)"
+    )]
+    pub metadata: Option<config::MetadataLevel>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Image format: png, jpeg or webp (overrides capture.default_format; a -f extension wins)"
+    )]
+    pub format: Option<config::ImageFormat>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        help = "Image format regardless of the -f extension and config; the extension is rewritten to match"
+    )]
+    pub force_format: Option<config::ImageFormat>,
+
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u8).range(1..=100),
+        help = "JPEG/WebP quality, 1-100 (overrides capture.quality)"
+    )]
+    pub quality: Option<u8>,
+
+    #[arg(
+        long,
+        help = "Include the mouse pointer in the screenshot (same as capture.include_cursor)"
+    )]
+    pub cursor: bool,
+
+    #[arg(
+        long,
+        help = "With -m output, pick the output from capture.menu_command instead of clicking"
+    )]
+    pub menu: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["filename", "record", "print_geometry", "raw", "clipboard_only", "skip_unchanged"],
+        help = "With -m all, save every output to its own file (named with %output)"
+    )]
+    pub split: bool,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "With -m all, capture only this output (repeatable)"
+    )]
+    pub only_output: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with = "only_output",
+        help = "With -m all, leave this output out (repeatable)"
+    )]
+    pub exclude_output: Vec<String>,
+
+    #[arg(
+        long,
+        help = "With -m window, capture only the window content without its title bar and borders (Sway)"
+    )]
+    pub client_area: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "client_area",
+        help = "With -m window, include the border and drop shadow Hyprland draws around the window"
+    )]
+    pub decorations: bool,
+
+    #[arg(
+        long,
+        help = "With -m window, square the window corners for the capture (Hyprland)"
+    )]
+    pub no_rounding: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["decorations", "record"],
+        help = "With -m window, copy the window's own buffer, keeping transparent corners (Hyprland, or an ext-capture build)"
+    )]
+    pub transparent: bool,
+
+    #[arg(
+        long,
+        help = "With -m region, keep the selection on one monitor (same as advanced.constrain_to_output)"
+    )]
+    pub constrain_output: bool,
+
+    #[arg(
+        long,
+        value_name = "REGEX",
+        value_parser = parse_regex,
+        help = "Capture the window whose title matches REGEX, without selecting it (implies -m window)"
+    )]
+    pub window_title: Option<Regex>,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Capture the window of class/app_id NAME, without selecting it (implies -m window)"
+    )]
+    pub window_class: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "0xADDRESS",
+        help = "Capture the Hyprland client with this address (from `hyprctl clients`; implies -m window)"
+    )]
+    pub address: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PID",
+        help = "Capture the window of process PID (implies -m window)"
+    )]
+    pub pid: Option<u32>,
+
+    #[arg(
+        long,
+        help = "Capture the same area as the previous capture in this mode, without selecting"
+    )]
+    pub last: bool,
+
+    #[arg(
+        long,
+        value_name = "TARGET",
+        num_args = 0..=1,
+        default_missing_value = "",
+        conflicts_with_all = ["raw", "clipboard_only", "record", "print_geometry"],
+        help = "Upload the screenshot to TARGET from [upload.targets] (default: upload.default) and copy its URL"
+    )]
+    pub upload: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "CMD",
+        num_args = 0..=1,
+        default_missing_value = "",
+        conflicts_with_all = ["record", "print_geometry"],
+        help = "Open the capture in CMD (default: hooks.editor) and save what it leaves behind"
+    )]
+    pub edit_with: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STYLE",
+        num_args = 0..=1,
+        default_missing_value = "pixelate",
+        conflicts_with_all = ["record", "print_geometry"],
+        help = "After the selection, drag boxes to pixelate (default) or blur; Escape when done"
+    )]
+    pub obscure: Option<config::ObscureStyle>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["record", "print_geometry"],
+        help = "Frame the capture with padding, rounded corners and a shadow on a background ([beautify])"
+    )]
+    pub beautify: bool,
+
+    #[arg(
+        long,
+        value_name = "WxH|N%",
+        value_parser = parse_resize,
+        conflicts_with_all = ["record", "print_geometry"],
+        help = "Scale the capture to fit in WxH or to N% with a Lanczos filter (e.g. 1920x1080, 50%)"
+    )]
+    pub resize: Option<Resize>,
+
+    #[arg(
+        long,
+        value_name = "X,Y WxH",
+        value_parser = parse_geometry,
+        allow_hyphen_values = true,
+        conflicts_with = "last",
+        help = "Capture this region without selecting (implies -m region)"
+    )]
+    pub geometry: Option<Geometry>,
+
+    #[arg(
+        long,
+        help = "Print the selected geometry (x,y WxH) and exit without capturing"
+    )]
+    pub print_geometry: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["raw", "clipboard_only", "print_geometry"],
+        help = "Record a video of the selection with ffmpeg; run with --record again to stop"
+    )]
+    pub record: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        requires = "record",
+        help = "mp4, webm, mkv, gif or webp (overrides -f and record.format)"
+    )]
+    pub record_format: Option<config::RecordFormat>,
+
+    #[arg(
+        long,
+        value_name = "FPS",
+        value_parser = clap::value_parser!(u32).range(1..=120),
+        requires = "record",
+        help = "Frames per second of the recording (overrides record.fps)"
+    )]
+    pub fps: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = parse_delay,
+        requires = "record",
+        visible_alias = "for",
+        help = "Stop recording after DURATION, e.g. 10s (overrides record.max_duration_ms)"
+    )]
+    pub max_duration: Option<Duration>,
+
+    #[arg(
+        long,
+        requires = "record",
+        conflicts_with_all = ["record_format", "fps"],
+        help = "Save numbered PNG frames into a directory instead of encoding a video (timelapse)"
+    )]
+    pub still: bool,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = parse_delay,
+        requires = "still",
+        help = "Time between --still frames, e.g. 30s (default 30s)"
+    )]
+    pub every: Option<Duration>,
 
     #[arg(long, help = "Freeze the screen on initialization")]
     pub freeze: bool,
 
+    #[arg(
+        long,
+        conflicts_with = "freeze",
+        help = "Don't freeze the screen, even if advanced.freeze_on_region is set"
+    )]
+    pub no_freeze: bool,
+
     #[arg(short, long, help = "Print debug information")]
     pub debug: bool,
 
@@ -49,15 +370,77 @@ pub struct Args {
     #[arg(short, long, help = "Output raw image data to stdout")]
     pub raw: bool,
 
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        requires = "raw",
+        help = "With --raw, write png, jpeg, ppm or bgra (bare pixels) instead of the chosen format"
+    )]
+    pub raw_format: Option<RawFormat>,
+
+    #[arg(
+        long,
+        requires = "raw_format",
+        help = "With --raw-format ppm or bgra, start with a 'WIDTH HEIGHT STRIDE' line"
+    )]
+    pub raw_header: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ENCODING",
+        requires = "raw",
+        help = "With --raw, write binary (default) or base64 text"
+    )]
+    pub stdout_encoding: Option<StdoutEncoding>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["raw", "json", "record", "print_geometry"],
+        help = "Show the capture in the terminal (kitty graphics or sixel) when stdout is one"
+    )]
+    pub term_preview: bool,
+
     #[arg(short, long, help = "Notification timeout (ms)")]
     pub notif_timeout: Option<u32>,
 
     #[arg(long, help = "Copy to clipboard and don't save to disk")]
     pub clipboard_only: bool,
 
+    #[arg(
+        long,
+        conflicts_with_all = ["raw", "print_geometry"],
+        help = "Print the result (path, geometry, output, format, duration, clipboard) as JSON"
+    )]
+    pub json: bool,
+
+    #[arg(
+        long,
+        help = "Skip saving when the capture is identical to the previous one"
+    )]
+    pub skip_unchanged: bool,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        help = "Also save a copy into DIR (repeatable)"
+    )]
+    pub also_save: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Set the saved screenshot as wallpaper (swww, hyprpaper or swaybg)"
+    )]
+    pub set_wallpaper: bool,
+
     #[arg(last = true, help = "Command to open screenshot (e.g., 'mirage')")]
     pub command: Vec<String>,
 
+    #[command(subcommand)]
+    pub action: Option<Action>,
+
     #[arg(long, help = "Initialize default config file")]
     pub init_config: bool,
 
@@ -75,18 +458,33 @@ pub struct Args {
     )]
     pub set: Option<Vec<String>>,
 
+    #[arg(long, help = "Print the path of the most recent screenshot")]
+    pub show_recent: bool,
+
     #[arg(long, help = "Generate Hyprland keybindings")]
     pub generate_hyprland_config: bool,
 
     #[arg(long, help = "Install keybindings to hyprland.conf (creates backup)")]
     pub install_binds: bool,
 
+    #[arg(long, help = "Restore hyprland.conf from the latest backup")]
+    pub restore_binds: bool,
+
+    #[arg(long, help = "Don't ask for confirmation (with --restore-binds)")]
+    pub yes: bool,
+
     #[arg(long, help = "Include clipboard-only bindings when generating")]
     pub with_clipboard: bool,
 
     #[arg(long, help = "Interactive hotkeys setup wizard")]
     pub setup_hotkeys: bool,
 
+    #[arg(
+        long,
+        help = "Interactive setup wizard for general settings and hotkeys"
+    )]
+    pub setup: bool,
+
     #[arg(
         long,
         help = "Don't load configuration file (use defaults and CLI args only)"
@@ -98,48 +496,424 @@ impl std::fmt::Debug for Args {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Args")
             .field("mode", &self.mode)
+            .field("profile", &self.profile)
             .field("output_folder", &self.output_folder)
             .field("filename", &self.filename)
+            .field("filename_template", &self.filename_template)
             .field("delay", &self.delay)
+            .field("detach_delay", &self.detach_delay)
+            .field("selection_timeout", &self.selection_timeout)
+            .field("padding", &self.padding)
+            .field("aspect", &self.aspect)
+            .field("aspect_mode", &self.aspect_mode)
+            .field("png_compression", &self.png_compression)
+            .field("optimize", &self.optimize)
+            .field("metadata", &self.metadata)
+            .field("format", &self.format)
+            .field("force_format", &self.force_format)
+            .field("quality", &self.quality)
+            .field("cursor", &self.cursor)
+            .field("menu", &self.menu)
+            .field("split", &self.split)
+            .field("only_output", &self.only_output)
+            .field("exclude_output", &self.exclude_output)
+            .field("client_area", &self.client_area)
+            .field("decorations", &self.decorations)
+            .field("no_rounding", &self.no_rounding)
+            .field("transparent", &self.transparent)
+            .field("constrain_output", &self.constrain_output)
+            .field("window_title", &self.window_title)
+            .field("window_class", &self.window_class)
+            .field("address", &self.address)
+            .field("pid", &self.pid)
+            .field("last", &self.last)
+            .field("upload", &self.upload)
+            .field("edit_with", &self.edit_with)
+            .field("obscure", &self.obscure)
+            .field("beautify", &self.beautify)
+            .field("resize", &self.resize)
+            .field("geometry", &self.geometry)
+            .field("print_geometry", &self.print_geometry)
+            .field("record", &self.record)
+            .field("record_format", &self.record_format)
+            .field("fps", &self.fps)
+            .field("max_duration", &self.max_duration)
+            .field("still", &self.still)
+            .field("every", &self.every)
             .field("freeze", &self.freeze)
+            .field("no_freeze", &self.no_freeze)
             .field("debug", &self.debug)
             .field("silent", &self.silent)
             .field("raw", &self.raw)
+            .field("raw_format", &self.raw_format)
+            .field("raw_header", &self.raw_header)
+            .field("stdout_encoding", &self.stdout_encoding)
+            .field("term_preview", &self.term_preview)
             .field("notif_timeout", &self.notif_timeout)
             .field("clipboard_only", &self.clipboard_only)
+            .field("json", &self.json)
+            .field("skip_unchanged", &self.skip_unchanged)
+            .field("also_save", &self.also_save)
+            .field("set_wallpaper", &self.set_wallpaper)
             .field("command", &self.command)
+            .field("action", &self.action)
             .finish()
     }
 }
 
+/// Fold `[profiles.NAME]` into the base config and fill in what the command
+/// line left unset, giving CLI > profile > base config.
+pub fn apply_profile(args: &mut Args, config: &mut config::Config, name: &str) -> Result<()> {
+    let profile = config
+        .profiles
+        .get(name)
+        .cloned()
+        .with_context(|| format!("Profile '{}' not found in config", name))?;
+
+    if args.mode.is_empty()
+        && let Some(modes) = &profile.mode
+    {
+        for mode in modes {
+            args.mode
+                .push(parse_mode(mode).map_err(|err| anyhow::anyhow!(err))?);
+        }
+    }
+    if args.command.is_empty()
+        && let Some(command) = profile.command
+    {
+        args.command = command;
+    }
+    args.clipboard_only |= profile.clipboard_only.unwrap_or(false);
+    // Skipped when a flag that --upload conflicts with was given.
+    if args.upload.is_none()
+        && !(args.raw || args.clipboard_only || args.record || args.print_geometry)
+        && let Some(target) = profile.upload
+    {
+        args.upload = Some(target);
+    }
+
+    if let Some(dir) = profile.screenshots_dir {
+        config.paths.screenshots_dir = dir;
+    }
+    if let Some(compression) = profile.png_compression {
+        config.capture.png_compression = compression;
+    }
+    if let Some(clipboard) = profile.clipboard_on_capture {
+        config.capture.clipboard_on_capture = clipboard;
+    }
+    if let Some(clipboard_uri) = profile.clipboard_uri {
+        config.capture.clipboard_uri = clipboard_uri;
+    }
+    if let Some(notification) = profile.notification {
+        config.capture.notification = notification;
+    }
+    if let Some(freeze) = profile.freeze {
+        config.advanced.freeze_on_region = freeze;
+    }
+    if let Some(delay_ms) = profile.delay_ms {
+        config.advanced.delay_ms = delay_ms;
+    }
+    if let Some(format) = profile.format {
+        config.capture.default_format = format;
+    }
+    if let Some(quality) = profile.quality {
+        config.capture.quality = quality;
+    }
+    Ok(())
+}
+
 pub fn resolve_notif_timeout(args: &Args, config: &config::Config) -> u32 {
     args.notif_timeout
         .unwrap_or(config.capture.notification_timeout)
 }
 
+/// `--freeze`/`--no-freeze` win over `advanced.freeze_on_region`.
+pub fn resolve_freeze(args: &Args, config: &config::Config) -> bool {
+    if args.no_freeze {
+        false
+    } else {
+        args.freeze || config.advanced.freeze_on_region
+    }
+}
+
+/// Image format: `--force-format` > `-f` extension > `--format` > config.
+pub fn resolve_format(args: &Args, config: &config::Config) -> config::ImageFormat {
+    args.raw_format
+        .and_then(RawFormat::image_format)
+        .or(args.force_format)
+        .or_else(|| {
+            args.filename
+                .as_deref()
+                .and_then(|name| config::ImageFormat::from_extension(Path::new(name)))
+        })
+        .or(args.format)
+        .unwrap_or(config.capture.default_format)
+}
+
+/// `--filename-template` wins over `paths.filename_template`.
+pub fn resolve_filename_template<'a>(args: &'a Args, config: &'a config::Config) -> &'a str {
+    args.filename_template
+        .as_deref()
+        .unwrap_or(&config.paths.filename_template)
+}
+
+/// Name to save as in `dir`: `-f` as given, or `template` expanded with
+/// `fields` (see [`filename::free_name`]), with the extension of `format`. `-f`
+/// keeps its extension unless `--force-format` overrides it.
+pub fn output_filename(
+    args: &Args,
+    format: config::ImageFormat,
+    template: &str,
+    dir: &Path,
+    fields: &mut filename::Fields,
+) -> String {
+    let with_extension = |name: &str| {
+        let path = Path::new(name);
+        if config::ImageFormat::from_extension(path) == Some(format) {
+            return name.to_string();
+        }
+        path.with_extension(format.extension())
+            .to_string_lossy()
+            .into_owned()
+    };
+    match &args.filename {
+        Some(name) if args.force_format.is_some() => with_extension(name),
+        Some(name) => name.clone(),
+        None => filename::free_name(dir, template, format.extension(), fields),
+    }
+}
+
+/// `--record-format`, then a container named by `-f`, then `record.format`.
+pub fn resolve_record_format(args: &Args, config: &config::Config) -> config::RecordFormat {
+    args.record_format
+        .or_else(|| {
+            args.filename
+                .as_deref()
+                .and_then(|name| config::RecordFormat::from_extension(Path::new(name)))
+        })
+        .unwrap_or(config.record.format)
+}
+
+/// `--delay`, or else `advanced.delay_ms` (applied from the profile already).
 pub fn resolve_delay(args: &Args, config: &config::Config) -> Duration {
-    if let Some(d) = args.delay {
-        Duration::from_secs(d)
-    } else if config.advanced.delay_ms > 0 {
-        Duration::from_millis(config.advanced.delay_ms as u64)
+    args.delay
+        .unwrap_or_else(|| Duration::from_millis(u64::from(config.advanced.delay_ms)))
+}
+
+/// Flags that print to stdout. The `--detach-delay` child has no stdout, so
+/// they would print nothing.
+pub fn stdout_flags(args: &Args) -> Vec<&'static str> {
+    [
+        (args.raw, "--raw"),
+        (args.print_geometry, "--print-geometry"),
+        (args.json, "--json"),
+        (args.stdout_encoding.is_some(), "--stdout-encoding"),
+        (args.term_preview, "--term-preview"),
+    ]
+    .into_iter()
+    .filter_map(|(set, flag)| set.then_some(flag))
+    .collect()
+}
+
+/// Arguments for the background process started by `--detach-delay`: the same
+/// command line without the flag, so the child runs the capture itself.
+pub fn detached_args<I>(args: I) -> Vec<std::ffi::OsString>
+where
+    I: IntoIterator<Item = std::ffi::OsString>,
+{
+    let mut out = Vec::new();
+    let mut after_separator = false;
+    for arg in args {
+        if arg == "--" {
+            after_separator = true;
+        } else if !after_separator && arg == "--detach-delay" {
+            continue;
+        }
+        out.push(arg);
+    }
+    out
+}
+
+/// Parse a delay value with an optional unit suffix.
+/// Supported units: `ms`, `s`, `m`, `h`. A bare number means seconds. Decimals
+/// (`0.5`, `1.5s`, `0.25m`) are exact down to the nanosecond.
+pub fn parse_delay(s: &str) -> std::result::Result<Duration, String> {
+    let input = s.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+    let (value, unit) = input.split_at(split);
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if value.is_empty()
+        || (whole.is_empty() && fraction.is_empty())
+        || (value.contains('.') && fraction.is_empty())
+        || fraction.contains('.')
+    {
+        return Err(format!("invalid delay '{}': expected a number", s));
+    }
+    let nanos_per_unit: u128 = match unit {
+        "" | "s" => 1_000_000_000,
+        "ms" => 1_000_000,
+        "m" => 60_000_000_000,
+        "h" => 3_600_000_000_000,
+        _ => {
+            return Err(format!(
+                "invalid delay '{}': unknown unit '{}' (use ms, s, m or h)",
+                s, unit
+            ));
+        }
+    };
+    let too_large = || format!("invalid delay '{}': number is too large", s);
+    let whole: u128 = if whole.is_empty() {
+        0
     } else {
-        Duration::from_secs(0)
+        whole.parse().map_err(|_| too_large())?
+    };
+    // Digits past the nanosecond can't change the result.
+    let fraction = &fraction[..fraction.len().min(11)];
+    let scale = 10u128.pow(fraction.len() as u32);
+    let fraction: u128 = if fraction.is_empty() {
+        0
+    } else {
+        fraction.parse().map_err(|_| too_large())?
+    };
+    let nanos = whole
+        .checked_mul(nanos_per_unit)
+        .and_then(|n| n.checked_add(fraction * nanos_per_unit / scale))
+        .ok_or_else(too_large)?;
+    let secs = u64::try_from(nanos / 1_000_000_000).map_err(|_| too_large())?;
+    Ok(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+}
+
+/// Parse a `-m` value; anything that isn't a known mode is an output name.
+pub fn parse_mode(s: &str) -> std::result::Result<Mode, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "output" => Ok(Mode::Output),
+        "window" => Ok(Mode::Window),
+        "window-pair" => Ok(Mode::WindowPair),
+        "region" => Ok(Mode::Region),
+        "active" => Ok(Mode::Active),
+        "layer" => Ok(Mode::Layer),
+        "all" => Ok(Mode::All),
+        _ => Ok(Mode::OutputName(s.to_string())),
+    }
+}
+
+/// `-m` goes through [`parse_mode`]; the modes are listed for shell completion.
+#[derive(Clone)]
+struct ModeParser;
+
+impl clap::builder::TypedValueParser for ModeParser {
+    type Value = Mode;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &std::ffi::OsStr,
+    ) -> std::result::Result<Mode, clap::Error> {
+        parse_mode.parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(
+        &self,
+    ) -> Option<Box<dyn Iterator<Item = clap::builder::PossibleValue> + '_>> {
+        let modes = [
+            "output",
+            "window",
+            "window-pair",
+            "region",
+            "layer",
+            "all",
+            "active",
+        ];
+        Some(Box::new(
+            modes.into_iter().map(clap::builder::PossibleValue::new),
+        ))
     }
 }
 
-pub fn default_filename(now: DateTime<Local>) -> String {
-    format!(
-        "{}-{:03}_hyprshot.png",
-        now.format("%Y-%m-%d-%H%M%S"),
-        now.timestamp_subsec_millis()
-    )
+fn parse_aspect(s: &str) -> std::result::Result<AspectRatio, String> {
+    s.parse().map_err(|err: anyhow::Error| err.to_string())
+}
+
+fn parse_png_compression(s: &str) -> std::result::Result<config::PngCompression, String> {
+    s.parse().map_err(|err: anyhow::Error| err.to_string())
+}
+
+fn parse_resize(s: &str) -> std::result::Result<Resize, String> {
+    s.parse().map_err(|err: anyhow::Error| err.to_string())
+}
+
+fn parse_geometry(s: &str) -> std::result::Result<Geometry, String> {
+    s.parse().map_err(|err: anyhow::Error| err.to_string())
+}
+
+fn parse_regex(s: &str) -> std::result::Result<Regex, String> {
+    Regex::new(s).map_err(|err| err.to_string())
+}
+
+/// Subcommands that report on the session instead of capturing.
+#[derive(Subcommand, Clone, Debug)]
+pub enum Action {
+    #[command(about = "List what can be captured")]
+    List {
+        #[command(subcommand)]
+        target: ListTarget,
+    },
+    #[command(about = "Print a shell completion script")]
+    Completions {
+        #[arg(value_enum, help = "bash, zsh, fish or nushell")]
+        shell: Shell,
+    },
+    #[command(about = "Check the environment and say how to fix what is missing")]
+    Doctor,
+    #[command(about = "Save the image on the clipboard like a screenshot")]
+    Paste {
+        #[arg(
+            value_hint = ValueHint::AnyPath,
+            help = "File to save to, or directory to name it in (default: the screenshots directory)"
+        )]
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Nushell,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum ListTarget {
+    #[command(about = "Outputs with their description, logical geometry, scale and transform")]
+    Outputs {
+        #[arg(long, help = "Print as JSON")]
+        json: bool,
+    },
+    #[command(
+        about = "Windows the window selector offers, with class, address or id, and workspace"
+    )]
+    Windows {
+        #[arg(long, help = "Print as JSON")]
+        json: bool,
+        #[arg(long, help = "Geometry without title bar and borders (Sway)")]
+        client_area: bool,
+    },
 }
 
 #[derive(Clone, Debug)]
 pub enum Mode {
     Output,
     Window,
+    /// Two window selections, captured as their bounding box.
+    WindowPair,
     Region,
+    Layer,
+    /// Every output, stitched into one image.
+    All,
     Active,
     OutputName(String),
 }
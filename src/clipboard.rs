@@ -0,0 +1,535 @@
+//! Clipboard copies and pastes. The selection is set over
+//! ext-data-control-v1 (or wlr-data-control) and served by a forked child
+//! until another client takes it over, the way `wl-copy` does, and read over
+//! the same protocols; `wl-copy` and `wl-paste` themselves are only the
+//! fallback for compositors without either protocol.
+
+use anyhow::{Context, Result};
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::utils::{output_with_timeout, wait_with_timeout};
+
+/// MIME types a plain-text payload is offered under; `text/plain` alone is
+/// missed by X11 apps going through XWayland.
+const TEXT_MIME_TYPES: [&str; 5] = [
+    "text/plain;charset=utf-8",
+    "text/plain",
+    "UTF8_STRING",
+    "STRING",
+    "TEXT",
+];
+
+/// `text` under every plain-text MIME type.
+pub(crate) fn text_offers(text: &str) -> Vec<(&'static str, &[u8])> {
+    TEXT_MIME_TYPES
+        .iter()
+        .map(|mime| (*mime, text.as_bytes()))
+        .collect()
+}
+
+/// Put `offers` (MIME type and payload, preferred first) on the clipboard.
+pub(crate) fn copy(offers: &[(&str, &[u8])], debug: bool) -> Result<()> {
+    let Some((mime, payload)) = offers.first() else {
+        return Ok(());
+    };
+    #[cfg(all(target_os = "linux", feature = "clipboard"))]
+    match imp::copy(offers, debug) {
+        Ok(()) => return Ok(()),
+        Err(err) if debug => eprintln!("Falling back to wl-copy: {:#}", err),
+        Err(_) => {}
+    }
+    wl_copy(mime, payload, debug)
+}
+
+/// `wl-copy` forks to serve the selection, so waiting for it only covers the
+/// hand-over.
+fn wl_copy(mime: &str, payload: &[u8], debug: bool) -> Result<()> {
+    if debug {
+        eprintln!("Copying {} via wl-copy", mime);
+    }
+    let mut child = Command::new("wl-copy")
+        .arg("--type")
+        .arg(mime)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|err| match err.kind() {
+            ErrorKind::NotFound => anyhow::anyhow!(
+                "the compositor offers no data-control protocol and wl-copy is not installed"
+            ),
+            _ => anyhow::Error::new(err).context("Failed to start wl-copy"),
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(payload)
+            .context("Failed to write to wl-copy stdin")?;
+    }
+    let status = wait_with_timeout(&mut child, Duration::from_secs(3))
+        .context("Failed to wait for wl-copy")?;
+    if !status.success() {
+        anyhow::bail!("wl-copy exited with {}", status);
+    }
+    Ok(())
+}
+
+/// The clipboard content of type `mime`; `None` when the clipboard is empty
+/// or doesn't offer that type.
+pub(crate) fn paste(mime: &str, debug: bool) -> Result<Option<Vec<u8>>> {
+    #[cfg(all(target_os = "linux", feature = "clipboard"))]
+    match imp::paste(mime, debug) {
+        Ok(content) => return Ok(content),
+        Err(err) if debug => eprintln!("Falling back to wl-paste: {:#}", err),
+        Err(_) => {}
+    }
+    wl_paste(mime, debug)
+}
+
+fn wl_paste(mime: &str, debug: bool) -> Result<Option<Vec<u8>>> {
+    if debug {
+        eprintln!("Pasting {} via wl-paste", mime);
+    }
+    let mut list = Command::new("wl-paste");
+    list.arg("--list-types");
+    let types = output_with_timeout(list, Duration::from_secs(3))
+        .context("the compositor offers no data-control protocol and wl-paste could not be run")?;
+    // Fails with an empty clipboard.
+    if !types.status.success()
+        || !String::from_utf8_lossy(&types.stdout)
+            .lines()
+            .any(|offered| offered == mime)
+    {
+        return Ok(None);
+    }
+    let mut paste = Command::new("wl-paste");
+    paste.arg("--no-newline").arg("--type").arg(mime);
+    let output =
+        output_with_timeout(paste, Duration::from_secs(5)).context("Failed to run wl-paste")?;
+    if !output.status.success() {
+        anyhow::bail!("wl-paste exited with {}", output.status);
+    }
+    Ok(Some(output.stdout))
+}
+
+#[cfg(all(target_os = "linux", feature = "clipboard"))]
+mod imp {
+    use anyhow::{Context, Result, bail};
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::os::fd::AsFd;
+    use wayland_client::{
+        Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+        backend::ObjectId,
+        event_created_child,
+        protocol::{wl_registry::WlRegistry, wl_seat::WlSeat},
+    };
+    use wayland_protocols::ext::data_control::v1::client::{
+        ext_data_control_device_v1::{self, ExtDataControlDeviceV1},
+        ext_data_control_manager_v1::{self, ExtDataControlManagerV1},
+        ext_data_control_offer_v1::{self, ExtDataControlOfferV1},
+        ext_data_control_source_v1::{self, ExtDataControlSourceV1},
+    };
+    use wayland_protocols_wlr::data_control::v1::client::{
+        zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+        zwlr_data_control_manager_v1::{self, ZwlrDataControlManagerV1},
+        zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+        zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+    };
+
+    enum Manager {
+        Ext(ExtDataControlManagerV1),
+        Wlr(ZwlrDataControlManagerV1),
+    }
+
+    /// Another client's selection, as announced to our data device.
+    enum Offer {
+        Ext(ExtDataControlOfferV1),
+        Wlr(ZwlrDataControlOfferV1),
+    }
+
+    struct State {
+        seat: Option<WlSeat>,
+        ext_manager: Option<ExtDataControlManagerV1>,
+        wlr_manager: Option<ZwlrDataControlManagerV1>,
+        offers: Vec<(String, Vec<u8>)>,
+        /// MIME types announced by each offer we were sent.
+        offered: Vec<(ObjectId, String)>,
+        selection: Option<Offer>,
+        /// Another client took the selection, or the seat went away.
+        done: bool,
+        debug: bool,
+    }
+
+    impl State {
+        fn send(&self, mime: &str, mut file: File) {
+            let Some((_, payload)) = self.offers.iter().find(|(offered, _)| offered == mime) else {
+                return;
+            };
+            // A paste the target app abandons must not take the selection down.
+            if let Err(err) = file.write_all(payload)
+                && self.debug
+            {
+                eprintln!("Failed to send {} to the clipboard reader: {}", mime, err);
+            }
+        }
+    }
+
+    /// Connect and bind the seat and the best data-control manager.
+    fn connect(
+        offers: Vec<(String, Vec<u8>)>,
+        debug: bool,
+    ) -> Result<(Connection, EventQueue<State>, State, WlSeat, Manager)> {
+        let conn = Connection::connect_to_env().context("Failed to connect to Wayland")?;
+        let mut queue = conn.new_event_queue();
+        let qh = queue.handle();
+        let _registry = conn.display().get_registry(&qh, ());
+
+        let mut state = State {
+            seat: None,
+            ext_manager: None,
+            wlr_manager: None,
+            offers,
+            offered: Vec::new(),
+            selection: None,
+            done: false,
+            debug,
+        };
+        queue
+            .roundtrip(&mut state)
+            .context("Failed to initialize Wayland globals")?;
+
+        let Some(seat) = state.seat.clone() else {
+            bail!("no wl_seat with a clipboard");
+        };
+        let manager = match (state.ext_manager.clone(), state.wlr_manager.clone()) {
+            (Some(manager), _) => Manager::Ext(manager),
+            (None, Some(manager)) => Manager::Wlr(manager),
+            (None, None) => bail!("the compositor offers no data-control protocol"),
+        };
+        Ok((conn, queue, state, seat, manager))
+    }
+
+    pub(super) fn paste(mime: &str, debug: bool) -> Result<Option<Vec<u8>>> {
+        let (conn, mut queue, mut state, seat, manager) = connect(Vec::new(), debug)?;
+        let qh = queue.handle();
+        // A new device is told about the current selection right away.
+        match &manager {
+            Manager::Ext(manager) => {
+                manager.get_data_device(&seat, &qh, ());
+            }
+            Manager::Wlr(manager) => {
+                manager.get_data_device(&seat, &qh, ());
+            }
+        }
+        queue
+            .roundtrip(&mut state)
+            .context("Failed to read the clipboard selection")?;
+        let Some(offer) = state.selection.take() else {
+            return Ok(None);
+        };
+        let id = match &offer {
+            Offer::Ext(offer) => offer.id(),
+            Offer::Wlr(offer) => offer.id(),
+        };
+        if !state
+            .offered
+            .iter()
+            .any(|(offer, offered)| *offer == id && offered == mime)
+        {
+            if debug {
+                eprintln!("The clipboard offers no {}", mime);
+            }
+            return Ok(None);
+        }
+
+        let (mut reader, writer) = std::io::pipe().context("Failed to create a pipe")?;
+        match &offer {
+            Offer::Ext(offer) => offer.receive(mime.to_string(), writer.as_fd()),
+            Offer::Wlr(offer) => offer.receive(mime.to_string(), writer.as_fd()),
+        }
+        conn.flush()
+            .context("Failed to request the clipboard content")?;
+        // Once only the source holds the write end, the read ends when it is done.
+        drop(writer);
+        let mut content = Vec::new();
+        reader
+            .read_to_end(&mut content)
+            .context("Failed to read the clipboard")?;
+        if debug {
+            eprintln!("Pasted {} bytes of {}", content.len(), mime);
+        }
+        Ok(Some(content))
+    }
+
+    pub(super) fn copy(offers: &[(&str, &[u8])], debug: bool) -> Result<()> {
+        let offers = offers
+            .iter()
+            .map(|(mime, payload)| (mime.to_string(), payload.to_vec()))
+            .collect();
+        let (_conn, mut queue, mut state, seat, manager) = connect(offers, debug)?;
+        let qh = queue.handle();
+        match &manager {
+            Manager::Ext(manager) => {
+                let source = manager.create_data_source(&qh, ());
+                for (mime, _) in &state.offers {
+                    source.offer(mime.clone());
+                }
+                manager
+                    .get_data_device(&seat, &qh, ())
+                    .set_selection(Some(&source));
+            }
+            Manager::Wlr(manager) => {
+                let source = manager.create_data_source(&qh, ());
+                for (mime, _) in &state.offers {
+                    source.offer(mime.clone());
+                }
+                manager
+                    .get_data_device(&seat, &qh, ())
+                    .set_selection(Some(&source));
+            }
+        }
+        queue
+            .roundtrip(&mut state)
+            .context("Failed to set the clipboard selection")?;
+        if state.done {
+            bail!("the clipboard selection was cancelled right away");
+        }
+        if debug {
+            let protocol = match manager {
+                Manager::Ext(_) => "ext-data-control",
+                Manager::Wlr(_) => "wlr-data-control",
+            };
+            let types: Vec<&str> = state.offers.iter().map(|(mime, _)| mime.as_str()).collect();
+            eprintln!("Clipboard set via {}: {}", protocol, types.join(", "));
+        }
+
+        // SAFETY: the child only serves the selection on this thread and then
+        // leaves through _exit, skipping the parent's destructors.
+        match unsafe { libc::fork() } {
+            -1 => {
+                Err(std::io::Error::last_os_error()).context("Failed to fork the clipboard server")
+            }
+            0 => {
+                detach(debug);
+                serve(&mut queue, &mut state);
+                unsafe { libc::_exit(0) }
+            }
+            pid => {
+                if debug {
+                    eprintln!("Clipboard served by process {}", pid);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Leave the caller's session and stdio, so a pipe reading hyprshot-rs'
+    /// output isn't held open until the next copy.
+    fn detach(debug: bool) {
+        unsafe {
+            libc::setsid();
+            libc::chdir(c"/".as_ptr());
+            let null = libc::open(c"/dev/null".as_ptr(), libc::O_RDWR);
+            if null >= 0 {
+                libc::dup2(null, libc::STDIN_FILENO);
+                libc::dup2(null, libc::STDOUT_FILENO);
+                if !debug {
+                    libc::dup2(null, libc::STDERR_FILENO);
+                }
+                if null > libc::STDERR_FILENO {
+                    libc::close(null);
+                }
+            }
+        }
+    }
+
+    fn serve(queue: &mut EventQueue<State>, state: &mut State) {
+        while !state.done {
+            if let Err(err) = queue.blocking_dispatch(state) {
+                if state.debug {
+                    eprintln!("Clipboard server stopped: {}", err);
+                }
+                break;
+            }
+        }
+    }
+
+    impl Dispatch<WlRegistry, ()> for State {
+        fn event(
+            state: &mut Self,
+            registry: &WlRegistry,
+            event: wayland_client::protocol::wl_registry::Event,
+            _: &(),
+            _: &Connection,
+            qh: &QueueHandle<Self>,
+        ) {
+            if let wayland_client::protocol::wl_registry::Event::Global {
+                name, interface, ..
+            } = event
+            {
+                match interface.as_str() {
+                    "wl_seat" if state.seat.is_none() => {
+                        state.seat = Some(registry.bind(name, 1, qh, ()));
+                    }
+                    "ext_data_control_manager_v1" => {
+                        state.ext_manager = Some(registry.bind(name, 1, qh, ()));
+                    }
+                    "zwlr_data_control_manager_v1" => {
+                        state.wlr_manager = Some(registry.bind(name, 1, qh, ()));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    impl Dispatch<WlSeat, ()> for State {
+        fn event(
+            _: &mut Self,
+            _: &WlSeat,
+            _: wayland_client::protocol::wl_seat::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<ExtDataControlManagerV1, ()> for State {
+        fn event(
+            _: &mut Self,
+            _: &ExtDataControlManagerV1,
+            _: ext_data_control_manager_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<ExtDataControlSourceV1, ()> for State {
+        fn event(
+            state: &mut Self,
+            _: &ExtDataControlSourceV1,
+            event: ext_data_control_source_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            match event {
+                ext_data_control_source_v1::Event::Send { mime_type, fd } => {
+                    state.send(&mime_type, File::from(fd));
+                }
+                ext_data_control_source_v1::Event::Cancelled => state.done = true,
+                _ => {}
+            }
+        }
+    }
+
+    impl Dispatch<ExtDataControlDeviceV1, ()> for State {
+        fn event(
+            state: &mut Self,
+            _: &ExtDataControlDeviceV1,
+            event: ext_data_control_device_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            match event {
+                ext_data_control_device_v1::Event::Selection { id } => {
+                    state.selection = id.map(Offer::Ext);
+                }
+                ext_data_control_device_v1::Event::Finished => state.done = true,
+                _ => {}
+            }
+        }
+
+        event_created_child!(State, ExtDataControlDeviceV1, [
+            ext_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ExtDataControlOfferV1, ()),
+        ]);
+    }
+
+    impl Dispatch<ExtDataControlOfferV1, ()> for State {
+        fn event(
+            state: &mut Self,
+            offer: &ExtDataControlOfferV1,
+            event: ext_data_control_offer_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            if let ext_data_control_offer_v1::Event::Offer { mime_type } = event {
+                state.offered.push((offer.id(), mime_type));
+            }
+        }
+    }
+
+    impl Dispatch<ZwlrDataControlManagerV1, ()> for State {
+        fn event(
+            _: &mut Self,
+            _: &ZwlrDataControlManagerV1,
+            _: zwlr_data_control_manager_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<ZwlrDataControlSourceV1, ()> for State {
+        fn event(
+            state: &mut Self,
+            _: &ZwlrDataControlSourceV1,
+            event: zwlr_data_control_source_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            match event {
+                zwlr_data_control_source_v1::Event::Send { mime_type, fd } => {
+                    state.send(&mime_type, File::from(fd));
+                }
+                zwlr_data_control_source_v1::Event::Cancelled => state.done = true,
+                _ => {}
+            }
+        }
+    }
+
+    impl Dispatch<ZwlrDataControlDeviceV1, ()> for State {
+        fn event(
+            state: &mut Self,
+            _: &ZwlrDataControlDeviceV1,
+            event: zwlr_data_control_device_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            match event {
+                zwlr_data_control_device_v1::Event::Selection { id } => {
+                    state.selection = id.map(Offer::Wlr);
+                }
+                zwlr_data_control_device_v1::Event::Finished => state.done = true,
+                _ => {}
+            }
+        }
+
+        event_created_child!(State, ZwlrDataControlDeviceV1, [
+            zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+        ]);
+    }
+
+    impl Dispatch<ZwlrDataControlOfferV1, ()> for State {
+        fn event(
+            state: &mut Self,
+            offer: &ZwlrDataControlOfferV1,
+            event: zwlr_data_control_offer_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            if let zwlr_data_control_offer_v1::Event::Offer { mime_type } = event {
+                state.offered.push((offer.id(), mime_type));
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! `hyprshot-rs completions SHELL`: completion scripts generated by
+//! `clap_complete` from the clap definition, so new flags show up without
+//! touching this file.
+//!
+//! Output names can't be known when the script is generated, so each script
+//! gets a small hook that asks `hyprshot-rs list outputs` while completing
+//! `-m`, `--only-output` and `--exclude-output`.
+
+use clap::CommandFactory;
+use clap_complete::Generator;
+
+use crate::cli::{Args, Shell};
+
+const BIN: &str = "hyprshot-rs";
+
+/// Prints one output name per line; silent where no compositor answers.
+const LIST_OUTPUTS: &str = "hyprshot-rs list outputs 2>/dev/null | cut -d ' ' -f 1";
+
+/// Flags that take nothing but an output name.
+const OUTPUT_FLAGS: [&str; 2] = ["only-output", "exclude-output"];
+
+pub fn handle_completions(shell: Shell) -> anyhow::Result<()> {
+    print!("{}", generate(shell));
+    Ok(())
+}
+
+pub(crate) fn generate(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash_outputs(script(clap_complete::Shell::Bash)),
+        Shell::Zsh => zsh_outputs(script(clap_complete::Shell::Zsh)),
+        Shell::Fish => fish_outputs(script(clap_complete::Shell::Fish)),
+        Shell::Nushell => nushell_outputs(script(clap_complete_nushell::Nushell)),
+    }
+}
+
+fn script(generator: impl Generator) -> String {
+    let mut out = Vec::new();
+    clap_complete::generate(generator, &mut Args::command(), BIN, &mut out);
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Wrap the generated function: output flags complete only output names, and
+/// `-m` gets them next to the modes.
+fn bash_outputs(script: String) -> String {
+    let flags: Vec<String> = OUTPUT_FLAGS
+        .iter()
+        .map(|flag| format!("--{flag}"))
+        .collect();
+    let hook = format!(
+        r#"
+_hyprshot__rs_outputs() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    _hyprshot__rs "$@"
+    case "${{prev}}" in
+        {flags})
+            COMPREPLY=($(compgen -W "$({LIST_OUTPUTS})" -- "${{cur}}"))
+            ;;
+        -m|--mode)
+            COMPREPLY+=($(compgen -W "$({LIST_OUTPUTS})" -- "${{cur}}"))
+            ;;
+    esac
+}}
+"#,
+        flags = flags.join("|"),
+    );
+    let script = script.replace(
+        "complete -F _hyprshot__rs ",
+        "complete -F _hyprshot__rs_outputs ",
+    );
+    match script.find("\nif [[ \"${BASH_VERSINFO[0]}\"") {
+        Some(at) => format!("{}{}{}", &script[..at], hook, &script[at..]),
+        None => script,
+    }
+}
+
+/// Point the output flags at `_hyprshot-rs_outputs`, and `-m` at a function
+/// offering the modes and the outputs.
+fn zsh_outputs(script: String) -> String {
+    let mut modes = String::new();
+    let mut lines: Vec<String> = Vec::new();
+    for line in script.lines() {
+        let mut line = line.to_string();
+        if OUTPUT_FLAGS
+            .iter()
+            .any(|flag| line.contains(&format!("--{flag}=[")))
+        {
+            line = line.replace(":_default'", ":_hyprshot-rs_outputs'");
+        }
+        if let Some(start) = line.find(":MODE:(") {
+            let list = &line[start + ":MODE:(".len()..];
+            if let Some(end) = list.find(")'") {
+                modes = list[..end].to_string();
+                line = format!(
+                    "{}:MODE:_hyprshot-rs_modes{}",
+                    &line[..start],
+                    &list[end + 1..]
+                );
+            }
+        }
+        lines.push(line);
+    }
+    let mut script = lines.join("\n") + "\n";
+    let hook = format!(
+        r#"(( $+functions[_hyprshot-rs_outputs] )) ||
+_hyprshot-rs_outputs() {{
+    local outputs; outputs=(${{(f)"$({LIST_OUTPUTS})"}})
+    _describe -t outputs 'outputs' outputs "$@"
+}}
+(( $+functions[_hyprshot-rs_modes] )) ||
+_hyprshot-rs_modes() {{
+    _alternative 'modes:mode:({modes})' 'outputs:output:_hyprshot-rs_outputs'
+}}
+
+"#
+    );
+    if let Some(at) = script.find("if [ \"$funcstack[1]\" = \"_hyprshot-rs\" ]") {
+        script.insert_str(at, &hook);
+    }
+    script
+}
+
+/// fish merges several `complete` lines for one flag, so the outputs are
+/// simply added.
+fn fish_outputs(mut script: String) -> String {
+    let candidates = format!("-x -a \"({LIST_OUTPUTS})\"");
+    let condition = "-n \"__fish_hyprshot_rs_needs_command\"";
+    script.push_str("# Output names come from the running compositor.\n");
+    script.push_str(&format!(
+        "complete -c {BIN} {condition} -s m -l mode {candidates}\n"
+    ));
+    for flag in OUTPUT_FLAGS {
+        script.push_str(&format!(
+            "complete -c {BIN} {condition} -l {flag} {candidates}\n"
+        ));
+    }
+    script
+}
+
+/// Add a `nu-complete hyprshot-rs outputs` completer, use it for the output
+/// flags and append it to the modes.
+fn nushell_outputs(script: String) -> String {
+    let completer = "\"nu-complete hyprshot-rs outputs\"";
+    let hook = format!(
+        r#"module completions {{
+
+  def {completer} [] {{
+    ^sh -c "{LIST_OUTPUTS}" | lines
+  }}
+"#
+    );
+    let mut script = script.replacen("module completions {\n", &hook, 1);
+    for flag in OUTPUT_FLAGS {
+        script = script.replace(
+            &format!("--{flag}: string "),
+            &format!("--{flag}: string@{completer} "),
+        );
+    }
+    let modes = "def \"nu-complete hyprshot-rs mode\" [] {";
+    if let Some(start) = script.find(modes)
+        && let Some(end) = script[start..].find("]\n")
+    {
+        let at = start + end + 1;
+        script.insert_str(at, " | append (nu-complete hyprshot-rs outputs)");
+    }
+    script
+}
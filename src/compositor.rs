@@ -0,0 +1,767 @@
+//! Compositor IPC: the window, layer and focused-output queries that have no
+//! Wayland protocol, answered by the Hyprland and Sway IPC sockets.
+//!
+//! [`detect`] picks one backend per run from the environment, so errors come
+//! from the compositor that is actually running. Supporting another compositor
+//! means implementing [`CompositorBackend`] and teaching [`detect_kind`] how
+//! to recognise it.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::capture;
+use crate::geometry::Geometry;
+use crate::hyprland_ipc;
+use crate::sway_ipc::{self, MessageType};
+
+pub trait CompositorBackend {
+    /// Shown in debug output and error messages.
+    fn name(&self) -> &'static str;
+
+    /// Windows on visible workspaces. `client_area` drops server-side
+    /// decorations where the compositor draws them.
+    fn list_windows(&mut self, client_area: bool, debug: bool) -> Result<Vec<Window>>;
+
+    /// Geometry of the focused window.
+    fn active_window(&mut self, client_area: bool, debug: bool) -> Result<Geometry>;
+
+    /// Name and logical geometry of the output showing the focused workspace.
+    fn active_output(&mut self, debug: bool) -> Result<(String, Geometry)>;
+
+    /// Outputs with their names and logical geometry. Wayland enumeration
+    /// works everywhere, so backends rarely need to override this.
+    fn list_outputs(&mut self, debug: bool) -> Result<Vec<(String, Geometry)>> {
+        capture::wayland_outputs(debug)
+    }
+
+    /// Layer-shell surfaces (bars, docks, ...) as selector boxes.
+    fn list_layers(&mut self, debug: bool) -> Result<Vec<String>> {
+        let _ = debug;
+        Err(anyhow::anyhow!(
+            "Layer surfaces can't be listed on {}",
+            self.name()
+        ))
+    }
+}
+
+/// A window on a visible workspace, in logical coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Window {
+    pub geometry: Geometry,
+    pub title: String,
+    /// Hyprland class, Sway `app_id` or the X11 class of an Xwayland window.
+    pub class: String,
+    /// Hyprland client address (`0x...`); unknown on other compositors.
+    pub address: Option<String>,
+    /// Sway container id; unknown on other compositors.
+    pub id: Option<i64>,
+    pub pid: Option<u32>,
+    /// Name of the workspace showing the window.
+    pub workspace: Option<String>,
+}
+
+impl Window {
+    /// Line for the selector: `x,y WxH title`.
+    pub fn selector_box(&self) -> String {
+        format!("{} {}", self.geometry, self.title.replace('\n', " "))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CompositorKind {
+    Hyprland,
+    Sway,
+    /// Anything else: only Wayland protocols are available.
+    Generic,
+}
+
+/// Recognise the compositor from the IPC socket variables it exports.
+/// `is_set` reports whether an environment variable is set and non-empty.
+pub(crate) fn detect_kind(is_set: impl Fn(&str) -> bool) -> CompositorKind {
+    if is_set("HYPRLAND_INSTANCE_SIGNATURE") {
+        CompositorKind::Hyprland
+    } else if is_set("SWAYSOCK") || is_set("I3SOCK") {
+        CompositorKind::Sway
+    } else {
+        CompositorKind::Generic
+    }
+}
+
+/// Backend for the running compositor.
+pub fn detect(debug: bool) -> Box<dyn CompositorBackend> {
+    let kind = detect_kind(|name| std::env::var_os(name).is_some_and(|value| !value.is_empty()));
+    let backend: Box<dyn CompositorBackend> = match kind {
+        CompositorKind::Hyprland => Box::new(Hyprland::new()),
+        CompositorKind::Sway => Box::new(Sway),
+        CompositorKind::Generic => Box::new(Generic),
+    };
+    if debug {
+        eprintln!("Compositor backend: {}", backend.name());
+    }
+    backend
+}
+
+/// Hyprland through its request socket. The `monitors` reply is cached, since
+/// window selection and the freeze overlay both need it.
+pub struct Hyprland {
+    monitors: Option<Value>,
+}
+
+impl Hyprland {
+    pub fn new() -> Self {
+        Self { monitors: None }
+    }
+
+    fn monitors(&mut self) -> Result<&Value> {
+        if self.monitors.is_none() {
+            self.monitors = Some(hyprland_ipc::query("monitors")?);
+        }
+
+        self.monitors
+            .as_ref()
+            .context("Hyprland monitors cache missing")
+    }
+}
+
+impl CompositorBackend for Hyprland {
+    fn name(&self) -> &'static str {
+        "Hyprland"
+    }
+
+    /// Hyprland clients draw their own decorations, so `client_area` has no
+    /// effect here.
+    fn list_windows(&mut self, _client_area: bool, debug: bool) -> Result<Vec<Window>> {
+        let clients = hyprland_ipc::query("clients")?;
+        let monitors = self.monitors()?;
+
+        if debug {
+            eprintln!("Monitors: {}", monitors);
+            eprintln!("Clients: {}", clients);
+        }
+
+        Ok(hyprland_windows(monitors, &clients))
+    }
+
+    fn active_window(&mut self, _client_area: bool, debug: bool) -> Result<Geometry> {
+        let active_window = hyprland_ipc::query("activewindow")?;
+
+        if debug {
+            eprintln!("Active window: {}", active_window);
+        }
+
+        hyprland_active_window_geometry(&active_window)
+    }
+
+    fn active_output(&mut self, debug: bool) -> Result<(String, Geometry)> {
+        let active_workspace = hyprland_ipc::query("activeworkspace")?;
+        let monitors = self.monitors()?;
+
+        if debug {
+            eprintln!("Monitors: {}", monitors);
+            eprintln!("Active workspace: {}", active_workspace);
+        }
+
+        let current_monitor = monitors
+            .as_array()
+            .and_then(|arr| {
+                arr.iter()
+                    .find(|m| m["activeWorkspace"]["id"] == active_workspace["id"])
+            })
+            .context("No matching monitor found")?;
+
+        if debug {
+            eprintln!("Current output: {}", current_monitor);
+        }
+
+        let name = current_monitor["name"]
+            .as_str()
+            .or_else(|| active_workspace["monitor"].as_str())
+            .context("Active monitor has no name")?
+            .to_string();
+        let x = current_monitor["x"].as_i64().unwrap_or(0) as i32;
+        let y = current_monitor["y"].as_i64().unwrap_or(0) as i32;
+        let width = current_monitor["width"].as_i64().unwrap_or(0) as f64;
+        let height = current_monitor["height"].as_i64().unwrap_or(0) as f64;
+        let scale = current_monitor["scale"].as_f64().unwrap_or(1.0);
+
+        let geometry = Geometry::new(
+            x,
+            y,
+            (width / scale).round() as i32,
+            (height / scale).round() as i32,
+        )?;
+        Ok((name, geometry))
+    }
+
+    fn list_layers(&mut self, _debug: bool) -> Result<Vec<String>> {
+        Ok(hyprland_layer_boxes(&hyprland_ipc::query("layers")?))
+    }
+}
+
+/// How far Hyprland draws past a client's geometry on each side: the border,
+/// then the drop shadow around it, shifted by its offset. Blur only changes
+/// what shows through the window, so it adds nothing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Extents {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Extents {
+    /// `shadow` is `(range, (offset_x, offset_y))` when shadows are enabled.
+    pub(crate) fn new(border: i32, shadow: Option<(i32, (i32, i32))>) -> Self {
+        let (range, (dx, dy)) = shadow.unwrap_or_default();
+        let past_border = |extent: i32| border + extent.max(0);
+        Self {
+            left: past_border(range - dx),
+            top: past_border(range - dy),
+            right: past_border(range + dx),
+            bottom: past_border(range + dy),
+        }
+    }
+
+    pub(crate) fn expand(self, geometry: Geometry) -> Result<Geometry> {
+        Geometry::new(
+            geometry.x - self.left,
+            geometry.y - self.top,
+            geometry.width + self.left + self.right,
+            geometry.height + self.top + self.bottom,
+        )
+    }
+}
+
+/// The first of `names` Hyprland knows, from `hyprctl getoption`; options
+/// were renamed across releases (e.g. `decoration:shadow_range` became
+/// `decoration:shadow:range` in 0.45).
+fn hyprland_option(names: &[&str]) -> Result<Value> {
+    let mut last_error = None;
+    for name in names {
+        match hyprland_ipc::query(&format!("getoption {}", name)) {
+            Ok(value) => return Ok(value),
+            Err(err) => last_error = Some(err),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No option name given")))
+}
+
+pub(crate) fn option_int(value: &Value) -> Option<i64> {
+    value["int"]
+        .as_i64()
+        .or_else(|| value["float"].as_f64().map(|v| v.round() as i64))
+}
+
+/// A `vec2` option, reported as `[x, y]` or, by older releases, as `"x y"`.
+pub(crate) fn option_vec2(value: &Value) -> Option<(f64, f64)> {
+    if let Some([x, y]) = value["vec2"].as_array().map(Vec::as_slice) {
+        return Some((x.as_f64()?, y.as_f64()?));
+    }
+    let mut parts = value["custom"].as_str()?.split_whitespace();
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+/// `--decorations`: the extents from the running Hyprland's options.
+pub(crate) fn hyprland_decoration_extents(debug: bool) -> Result<Extents> {
+    let border = option_int(&hyprland_option(&["general:border_size"])?).unwrap_or(0);
+    let shadow_enabled = hyprland_option(&["decoration:shadow:enabled", "decoration:drop_shadow"])
+        .ok()
+        .and_then(|value| option_int(&value))
+        .is_some_and(|enabled| enabled != 0);
+    let shadow = if shadow_enabled {
+        let range = hyprland_option(&["decoration:shadow:range", "decoration:shadow_range"])
+            .ok()
+            .and_then(|value| option_int(&value))
+            .unwrap_or(0);
+        let (dx, dy) = hyprland_option(&["decoration:shadow:offset", "decoration:shadow_offset"])
+            .ok()
+            .and_then(|value| option_vec2(&value))
+            .unwrap_or_default();
+        Some((range as i32, (dx.round() as i32, dy.round() as i32)))
+    } else {
+        None
+    };
+    let extents = Extents::new(border as i32, shadow);
+    if debug {
+        eprintln!(
+            "Decorations: border {}, shadow {:?} -> {:?}",
+            border, shadow, extents
+        );
+    }
+    Ok(extents)
+}
+
+/// `--no-rounding`: square window corners until dropped, then restore
+/// `decoration:rounding`. Window rules that set rounding still win.
+///
+/// SIGINT and SIGTERM (Ctrl+C during `--delay` or the selection) skip `Drop`,
+/// so while the guard lives a handler sends the restoring request itself
+/// before letting the signal end the process.
+pub(crate) struct FlatCorners {
+    rounding: i64,
+    previous_handlers: [libc::sighandler_t; 2],
+}
+
+/// What the signal handler sends, prepared up front because a handler can't
+/// allocate. Null outside a [`FlatCorners`] guard.
+static RESTORE: AtomicPtr<RestoreRequest> = AtomicPtr::new(ptr::null_mut());
+
+struct RestoreRequest {
+    address: libc::sockaddr_un,
+    message: Vec<u8>,
+}
+
+const RESTORE_SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+impl RestoreRequest {
+    fn new(socket: &Path, rounding: i64) -> Result<Self> {
+        // SAFETY: sockaddr_un is plain data; all zeroes is a valid value.
+        let mut address: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+        address.sun_family = libc::AF_UNIX as libc::sa_family_t;
+        let path = socket.as_os_str().as_bytes();
+        if path.len() >= address.sun_path.len() {
+            anyhow::bail!("Hyprland socket path is too long: {}", socket.display());
+        }
+        for (slot, byte) in address.sun_path.iter_mut().zip(path) {
+            *slot = *byte as libc::c_char;
+        }
+        Ok(Self {
+            address,
+            message: format!("keyword decoration:rounding {}", rounding).into_bytes(),
+        })
+    }
+
+    /// Only async-signal-safe calls: this runs inside the signal handler.
+    fn send(&self) {
+        unsafe {
+            let fd = libc::socket(libc::AF_UNIX, libc::SOCK_STREAM, 0);
+            if fd < 0 {
+                return;
+            }
+            let address = &self.address as *const libc::sockaddr_un as *const libc::sockaddr;
+            let length = std::mem::size_of::<libc::sockaddr_un>() as libc::socklen_t;
+            if libc::connect(fd, address, length) == 0
+                && libc::write(fd, self.message.as_ptr().cast(), self.message.len()) >= 0
+            {
+                // Hyprland applies the keyword before it answers.
+                let mut reply = [0u8; 16];
+                libc::read(fd, reply.as_mut_ptr().cast(), reply.len());
+            }
+            libc::close(fd);
+        }
+    }
+}
+
+extern "C" fn restore_rounding(signal: libc::c_int) {
+    let request = RESTORE.swap(ptr::null_mut(), Ordering::SeqCst);
+    // SAFETY: a non-null pointer came from Box::into_raw in `apply` and was
+    // taken out of RESTORE exactly once. It is leaked; the process is ending.
+    if let Some(request) = unsafe { request.as_ref() } {
+        request.send();
+    }
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
+
+impl FlatCorners {
+    pub(crate) fn apply(debug: bool) -> Result<Self> {
+        let rounding = option_int(&hyprland_option(&["decoration:rounding"])?)
+            .context("Hyprland reported no decoration:rounding")?;
+        let request = RestoreRequest::new(&hyprland_ipc::socket_path()?, rounding)?;
+        RESTORE.store(Box::into_raw(Box::new(request)), Ordering::SeqCst);
+        let handler = restore_rounding as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        let previous_handlers =
+            RESTORE_SIGNALS.map(|signal| unsafe { libc::signal(signal, handler) });
+        // From here on Drop restores the handlers and the rounding.
+        let guard = Self {
+            rounding,
+            previous_handlers,
+        };
+        hyprland_ipc::keyword("decoration:rounding", "0")?;
+        if debug {
+            eprintln!("Window rounding {} -> 0 for the capture", rounding);
+        }
+        Ok(guard)
+    }
+}
+
+impl Drop for FlatCorners {
+    fn drop(&mut self) {
+        for (signal, handler) in RESTORE_SIGNALS.into_iter().zip(self.previous_handlers) {
+            unsafe {
+                libc::signal(signal, handler);
+            }
+        }
+        let request = RESTORE.swap(ptr::null_mut(), Ordering::SeqCst);
+        if !request.is_null() {
+            // SAFETY: see `restore_rounding`; the handler no longer runs.
+            drop(unsafe { Box::from_raw(request) });
+        }
+        if let Err(err) = hyprland_ipc::keyword("decoration:rounding", &self.rounding.to_string()) {
+            eprintln!(
+                "Warning: failed to restore decoration:rounding = {}: {:#}",
+                self.rounding, err
+            );
+        }
+    }
+}
+
+/// Sway (and i3-compatible IPC) through the socket in `SWAYSOCK`.
+pub struct Sway;
+
+impl CompositorBackend for Sway {
+    fn name(&self) -> &'static str {
+        "Sway"
+    }
+
+    fn list_windows(&mut self, client_area: bool, _debug: bool) -> Result<Vec<Window>> {
+        let workspaces = sway_ipc::query(MessageType::Workspaces)?;
+        let tree = sway_ipc::query(MessageType::Tree)?;
+        Ok(sway_windows(&workspaces, &tree, client_area))
+    }
+
+    fn active_window(&mut self, client_area: bool, _debug: bool) -> Result<Geometry> {
+        let tree = sway_ipc::query(MessageType::Tree)?;
+        sway_focused_window_geometry(&tree, client_area)
+    }
+
+    fn active_output(&mut self, _debug: bool) -> Result<(String, Geometry)> {
+        let workspaces = sway_ipc::query(MessageType::Workspaces)?;
+        let focused_output = workspaces
+            .as_array()
+            .and_then(|arr| arr.iter().find(|w| w["focused"].as_bool() == Some(true)))
+            .and_then(|w| w["output"].as_str())
+            .context("Failed to find focused workspace output")?;
+
+        let outputs = sway_ipc::query(MessageType::Outputs)?;
+        let output_data = outputs
+            .as_array()
+            .and_then(|arr| {
+                arr.iter()
+                    .find(|o| o["name"].as_str() == Some(focused_output))
+            })
+            .context("Focused output not found in sway outputs")?;
+
+        let rect = output_data["rect"]
+            .as_object()
+            .context("Invalid output rect data")?;
+
+        let x = rect.get("x").and_then(|v| v.as_i64()).unwrap_or(0);
+        let y = rect.get("y").and_then(|v| v.as_i64()).unwrap_or(0);
+        let width = rect.get("width").and_then(|v| v.as_i64()).unwrap_or(0);
+        let height = rect.get("height").and_then(|v| v.as_i64()).unwrap_or(0);
+
+        let geometry = Geometry::new(x as i32, y as i32, width as i32, height as i32)?;
+        Ok((focused_output.to_string(), geometry))
+    }
+}
+
+/// Compositors without a supported IPC. The active window can still be found
+/// when it is fullscreen, through wlr-foreign-toplevel-management.
+pub struct Generic;
+
+impl CompositorBackend for Generic {
+    fn name(&self) -> &'static str {
+        "generic Wayland"
+    }
+
+    fn list_windows(&mut self, _client_area: bool, _debug: bool) -> Result<Vec<Window>> {
+        Err(anyhow::anyhow!(
+            "Window selection is only supported on Hyprland or Sway"
+        ))
+    }
+
+    fn active_window(&mut self, _client_area: bool, debug: bool) -> Result<Geometry> {
+        #[cfg(feature = "freeze")]
+        {
+            capture::grab_active_window_foreign_toplevel(debug).context(
+                "Active window is only supported on Hyprland, Sway, or for fullscreen windows \
+via wlr-foreign-toplevel-management",
+            )
+        }
+        #[cfg(not(feature = "freeze"))]
+        {
+            let _ = debug;
+            Err(anyhow::anyhow!(
+                "Active window is only supported on Hyprland or Sway"
+            ))
+        }
+    }
+
+    fn active_output(&mut self, _debug: bool) -> Result<(String, Geometry)> {
+        Err(anyhow::anyhow!(
+            "Active output is only supported on Hyprland or Sway"
+        ))
+    }
+}
+
+/// Clients on any monitor's active workspace, from `hyprctl monitors -j` and
+/// `hyprctl clients -j`. Zero-sized clients are skipped.
+pub(crate) fn hyprland_windows(monitors: &Value, clients: &Value) -> Vec<Window> {
+    let workspace_ids: HashSet<i64> = monitors
+        .as_array()
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|m| m["activeWorkspace"]["id"].as_i64())
+                .collect::<HashSet<_>>()
+        })
+        .unwrap_or_default();
+
+    clients
+        .as_array()
+        .map(|arr| arr.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter(|c| {
+            c["workspace"]["id"]
+                .as_i64()
+                .is_some_and(|id| workspace_ids.contains(&id))
+        })
+        .filter_map(|c| {
+            let at = c["at"].as_array()?;
+            let size = c["size"].as_array()?;
+            let x = at.first()?.as_i64()?;
+            let y = at.get(1)?.as_i64()?;
+            let width = size.first()?.as_i64()?;
+            let height = size.get(1)?.as_i64()?;
+            Some(Window {
+                geometry: Geometry::new(x as i32, y as i32, width as i32, height as i32).ok()?,
+                title: c["title"].as_str().unwrap_or("").to_string(),
+                class: c["class"].as_str().unwrap_or("").to_string(),
+                address: c["address"].as_str().map(str::to_string),
+                id: None,
+                pid: pid(&c["pid"]),
+                workspace: c["workspace"]["name"].as_str().map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+/// Turn `hyprctl layers -j` output (output -> levels -> surfaces) into
+/// selector boxes labelled with the layer namespace.
+pub(crate) fn hyprland_layer_boxes(layers: &Value) -> Vec<String> {
+    let mut boxes = Vec::new();
+    let Some(outputs) = layers.as_object() else {
+        return boxes;
+    };
+    for output in outputs.values() {
+        let Some(levels) = output["levels"].as_object() else {
+            continue;
+        };
+        for surfaces in levels.values().filter_map(|v| v.as_array()) {
+            for surface in surfaces {
+                let namespace = surface["namespace"].as_str().unwrap_or("");
+                // Skip our own freeze overlay.
+                if namespace == "hyprshot-freeze" {
+                    continue;
+                }
+                let (Some(x), Some(y), Some(w), Some(h)) = (
+                    surface["x"].as_i64(),
+                    surface["y"].as_i64(),
+                    surface["w"].as_i64(),
+                    surface["h"].as_i64(),
+                ) else {
+                    continue;
+                };
+                if w <= 0 || h <= 0 {
+                    continue;
+                }
+                boxes.push(format!("{},{} {}x{} {}", x, y, w, h, namespace));
+            }
+        }
+    }
+    boxes
+}
+
+/// Geometry of `hyprctl activewindow -j`.
+pub(crate) fn hyprland_active_window_geometry(active_window: &Value) -> Result<Geometry> {
+    let at = active_window["at"]
+        .as_array()
+        .context("Invalid active window data: missing 'at' field")?;
+    let size = active_window["size"]
+        .as_array()
+        .context("Invalid active window data: missing 'size' field")?;
+
+    let coord = |values: &[Value], i: usize, what: &str| {
+        values
+            .get(i)
+            .and_then(Value::as_i64)
+            .with_context(|| format!("Invalid {}", what))
+    };
+    let x = coord(at, 0, "x coordinate")?;
+    let y = coord(at, 1, "y coordinate")?;
+    let width = coord(size, 0, "width")?;
+    let height = coord(size, 1, "height")?;
+
+    if width <= 0 || height <= 0 {
+        return Err(anyhow::anyhow!(
+            "Invalid window dimensions: width={} or height={}",
+            width,
+            height
+        ));
+    }
+
+    Geometry::new(x as i32, y as i32, width as i32, height as i32)
+}
+
+/// Windows on visible workspaces, from the Sway `get_workspaces` and
+/// `get_tree` replies.
+pub(crate) fn sway_windows(workspaces: &Value, tree: &Value, client_area: bool) -> Vec<Window> {
+    let visible_workspaces: HashSet<String> = workspaces
+        .as_array()
+        .map(|arr| {
+            arr.iter()
+                .filter(|w| w["visible"].as_bool() == Some(true))
+                .filter_map(|w| w["name"].as_str().map(|s| s.to_string()))
+                .collect::<HashSet<_>>()
+        })
+        .unwrap_or_default();
+
+    let mut windows = Vec::new();
+    collect_visible_windows(tree, &visible_workspaces, None, client_area, &mut windows);
+    windows
+}
+
+/// Geometry of the focused window in a Sway `get_tree` reply.
+pub(crate) fn sway_focused_window_geometry(tree: &Value, client_area: bool) -> Result<Geometry> {
+    let focused = find_focused_window(tree).context("Focused window not found (sway)")?;
+
+    let (x, y, width, height) =
+        sway_window_rect(focused, client_area).context("Invalid focused window rect")?;
+
+    if width <= 0 || height <= 0 {
+        return Err(anyhow::anyhow!(
+            "Invalid focused window dimensions: width={} height={}",
+            width,
+            height
+        ));
+    }
+
+    Geometry::new(x as i32, y as i32, width as i32, height as i32)
+}
+
+const SWAY_SCRATCHPAD: &str = "__i3_scratch";
+
+/// `workspace` is the visible workspace `node` is on, if any.
+fn collect_visible_windows<'a>(
+    node: &'a Value,
+    visible_workspaces: &HashSet<String>,
+    mut workspace: Option<&'a str>,
+    client_area: bool,
+    windows: &mut Vec<Window>,
+) {
+    if node["type"].as_str() == Some("workspace") {
+        // Hidden scratchpad windows stay on `__i3_scratch`; a shown one is moved
+        // to the current workspace's floating nodes and is collected there.
+        workspace = node
+            .get("name")
+            .and_then(|v| v.as_str())
+            .filter(|name| *name != SWAY_SCRATCHPAD && visible_workspaces.contains(*name));
+    }
+
+    // Background tabs of tabbed/stacked containers report `"visible": false`.
+    if let Some(name) = workspace
+        && is_window_node(node)
+        && node["visible"].as_bool() != Some(false)
+        && let Some(mut window) = sway_window(node, client_area)
+    {
+        window.workspace = Some(name.to_string());
+        windows.push(window);
+    }
+
+    if let Some(nodes) = node.get("nodes").and_then(|v| v.as_array()) {
+        for child in nodes {
+            collect_visible_windows(child, visible_workspaces, workspace, client_area, windows);
+        }
+    }
+    if let Some(nodes) = node.get("floating_nodes").and_then(|v| v.as_array()) {
+        for child in nodes {
+            collect_visible_windows(child, visible_workspaces, workspace, client_area, windows);
+        }
+    }
+}
+
+fn is_window_node(node: &Value) -> bool {
+    // Floating windows are `floating_con` nodes under `floating_nodes`.
+    if !matches!(node["type"].as_str(), Some("con" | "floating_con")) {
+        return false;
+    }
+    let has_app = node["app_id"].is_string();
+    let has_props = node
+        .get("window_properties")
+        .map(|v| v.is_object())
+        .unwrap_or(false);
+    has_app || has_props
+}
+
+/// Absolute `(x, y, width, height)` of a Sway window. `rect` includes the
+/// borders; `window_rect` is the client content, relative to `rect`.
+fn sway_window_rect(node: &Value, client_area: bool) -> Option<(i64, i64, i64, i64)> {
+    let field = |value: &Value, key: &str| value.get(key).and_then(Value::as_i64);
+    let rect = node.get("rect")?;
+    let (x, y) = (field(rect, "x")?, field(rect, "y")?);
+    if client_area && let Some(content) = node.get("window_rect") {
+        return Some((
+            x + field(content, "x")?,
+            y + field(content, "y")?,
+            field(content, "width")?,
+            field(content, "height")?,
+        ));
+    }
+    Some((x, y, field(rect, "width")?, field(rect, "height")?))
+}
+
+fn sway_window(node: &Value, client_area: bool) -> Option<Window> {
+    let (x, y, width, height) = sway_window_rect(node, client_area)?;
+    let class = node["app_id"]
+        .as_str()
+        .or_else(|| node["window_properties"]["class"].as_str())
+        .unwrap_or("");
+    Some(Window {
+        geometry: Geometry::new(x as i32, y as i32, width as i32, height as i32).ok()?,
+        title: node["name"].as_str().unwrap_or("").to_string(),
+        class: class.to_string(),
+        address: None,
+        id: node["id"].as_i64(),
+        pid: pid(&node["pid"]),
+        workspace: None,
+    })
+}
+
+/// Process id of a window; Hyprland reports `-1` when there is none.
+fn pid(value: &Value) -> Option<u32> {
+    value
+        .as_i64()
+        .and_then(|pid| u32::try_from(pid).ok())
+        .filter(|&pid| pid > 0)
+}
+
+fn find_focused_window(node: &Value) -> Option<&Value> {
+    if node.get("focused").and_then(|v| v.as_bool()) == Some(true) && is_window_node(node) {
+        return Some(node);
+    }
+
+    if let Some(nodes) = node.get("nodes").and_then(|v| v.as_array()) {
+        for child in nodes {
+            if let Some(found) = find_focused_window(child) {
+                return Some(found);
+            }
+        }
+    }
+    if let Some(nodes) = node.get("floating_nodes").and_then(|v| v.as_array()) {
+        for child in nodes {
+            if let Some(found) = find_focused_window(child) {
+                return Some(found);
+            }
+        }
+    }
+
+    None
+}
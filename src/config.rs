@@ -1,9 +1,11 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 /// Main configuration structure for hyprshot-rs
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -16,6 +18,43 @@ pub struct Config {
     pub capture: CaptureConfig,
     #[serde(default)]
     pub advanced: AdvancedConfig,
+    #[serde(default)]
+    pub record: RecordConfig,
+    #[serde(default)]
+    pub upload: UploadConfig,
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    #[serde(default)]
+    pub beautify: BeautifyConfig,
+    /// Named option bundles selected with `--profile NAME`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+}
+
+/// Options bundled under `[profiles.NAME]`. Unset fields fall back to the
+/// base config; command-line flags override both. Unknown keys are rejected
+/// so a misspelled option doesn't silently do nothing.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    /// Modes as passed to `-m`, e.g. `["window", "active"]`
+    pub mode: Option<Vec<String>>,
+    pub screenshots_dir: Option<String>,
+    pub png_compression: Option<PngCompression>,
+    pub clipboard_only: Option<bool>,
+    pub clipboard_on_capture: Option<bool>,
+    pub clipboard_uri: Option<bool>,
+    pub notification: Option<bool>,
+    pub freeze: Option<bool>,
+    pub delay_ms: Option<u32>,
+    /// Like `capture.default_format`
+    pub format: Option<ImageFormat>,
+    /// Like `capture.quality`
+    pub quality: Option<u8>,
+    /// Target to upload to, like `--upload TARGET`; "" for `upload.default`
+    pub upload: Option<String>,
+    /// Command run with the saved file, like `-- command`
+    pub command: Option<Vec<String>>,
 }
 
 /// Configuration for paths
@@ -25,6 +64,28 @@ pub struct PathsConfig {
     /// Default: ~/Pictures
     #[serde(default = "default_screenshots_dir")]
     pub screenshots_dir: String,
+
+    /// Create the screenshots directory if it doesn't exist
+    /// Default: true
+    #[serde(default = "default_create_missing_dirs")]
+    pub create_missing_dirs: bool,
+
+    /// Keep copies of the last N screenshots as recent-0.png (newest) .. recent-(N-1).png
+    /// Default: 0 (disabled)
+    #[serde(default)]
+    pub keep_recent: usize,
+
+    /// Name of new screenshots without the extension; see doc/CONFIGURATION.md
+    /// for the placeholders
+    /// Default: "%Y-%m-%d-%H%M%S-%ms_hyprshot"
+    #[serde(default = "default_filename_template")]
+    pub filename_template: String,
+
+    /// What to do when the file to save already exists: "overwrite",
+    /// "increment" (shot-1.png, shot-2.png, ...) or "fail"
+    /// Default: "increment"
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
 }
 
 /// Configuration for hotkeys (for Hyprland)
@@ -63,6 +124,282 @@ pub struct CaptureConfig {
     /// Default: 3000
     #[serde(default = "default_notification_timeout")]
     pub notification_timeout: u32,
+
+    /// Flash the captured outputs white once the capture is taken, e.g. as
+    /// confirmation when notifications are off
+    /// Default: false
+    #[serde(default)]
+    pub flash: bool,
+
+    /// Copy saved captures (or their URI or upload link) to the clipboard;
+    /// `--clipboard-only` copies regardless
+    /// Default: true
+    #[serde(default = "default_clipboard_on_capture")]
+    pub clipboard_on_capture: bool,
+
+    /// Copy a `file://` URI (text/uri-list) instead of the image after saving
+    /// Default: false
+    #[serde(default)]
+    pub clipboard_uri: bool,
+
+    /// PNG compression: "fast", "default", "best" or a zlib level 0-9
+    /// Default: "default"
+    #[serde(default)]
+    pub png_compression: PngCompression,
+
+    /// Details embedded in saved files: "none", "basic" (time and tool) or
+    /// "full" (also window, output, geometry and workspace)
+    /// Default: "none"
+    #[serde(default)]
+    pub metadata: MetadataLevel,
+
+    /// Format when the filename doesn't name one: "png", "jpeg" or "webp"
+    /// Default: "png"
+    #[serde(default)]
+    pub default_format: ImageFormat,
+
+    /// JPEG and WebP quality, 1-100
+    /// Default: 90
+    #[serde(default = "default_quality")]
+    pub quality: u8,
+
+    /// Color that transparent areas are blended onto for JPEG, as "#rrggbb"
+    /// Default: "#ffffff"
+    #[serde(default = "default_flatten_background")]
+    pub flatten_background: String,
+
+    /// Capture whole outputs at their native resolution instead of the
+    /// logical (scaled) size
+    /// Default: false
+    #[serde(default)]
+    pub output_physical_pixels: bool,
+
+    /// Composite the mouse pointer into screenshots
+    /// Default: false
+    #[serde(default)]
+    pub include_cursor: bool,
+
+    /// Resample every capture to this many pixels per logical pixel, whatever
+    /// the output scale (e.g. 1.0 for uniform sizes across monitors)
+    /// Default: unset (keep the captured size)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub force_scale: Option<f64>,
+
+    /// How long to wait for the `-- command` before exiting, in milliseconds;
+    /// the command is left running (0 = wait until it exits)
+    /// Default: 0
+    #[serde(default)]
+    pub command_timeout_ms: u64,
+
+    /// dmenu-style program used by `-m output --menu`, e.g. "fuzzel --dmenu"
+    /// (empty = click the output with the pointer instead)
+    /// Default: ""
+    #[serde(default)]
+    pub menu_command: String,
+}
+
+/// What happens when the file a capture would be saved to already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    Overwrite,
+    /// Append `-1`, `-2`, ... before the extension.
+    #[default]
+    Increment,
+    Fail,
+}
+
+/// How `--obscure` hides the boxes dragged after the selection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ObscureStyle {
+    #[default]
+    Pixelate,
+    Blur,
+}
+
+/// PNG encoder speed/size tradeoff.
+/// `fast` encodes quickest with larger files, `best` is slowest and smallest.
+/// Written as a name or as a zlib level, 0-9.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "PngCompressionValue", into = "PngCompressionValue")]
+pub enum PngCompression {
+    Fast,
+    #[default]
+    Default,
+    Best,
+    /// zlib level; 0 stores the pixels uncompressed.
+    Level(u8),
+}
+
+impl PngCompression {
+    /// zlib level (1 = fast, 6 = default, 9 = best).
+    pub fn level(self) -> u8 {
+        match self {
+            Self::Fast => 1,
+            Self::Default => 6,
+            Self::Best => 9,
+            Self::Level(level) => level,
+        }
+    }
+}
+
+impl FromStr for PngCompression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "fast" => Ok(Self::Fast),
+            "default" => Ok(Self::Default),
+            "best" => Ok(Self::Best),
+            level => match level.parse() {
+                Ok(level @ 0..=9) => Ok(Self::Level(level)),
+                _ => bail!(
+                    "Invalid PNG compression '{}': use fast, default, best or a level 0-9",
+                    s
+                ),
+            },
+        }
+    }
+}
+
+/// `png_compression` as written in the config: a name or a bare level.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum PngCompressionValue {
+    Level(u8),
+    Name(String),
+}
+
+impl TryFrom<PngCompressionValue> for PngCompression {
+    type Error = anyhow::Error;
+
+    fn try_from(value: PngCompressionValue) -> Result<Self> {
+        match value {
+            PngCompressionValue::Level(level) => level.to_string().parse(),
+            PngCompressionValue::Name(name) => name.parse(),
+        }
+    }
+}
+
+impl From<PngCompression> for PngCompressionValue {
+    fn from(compression: PngCompression) -> Self {
+        match compression {
+            PngCompression::Fast => Self::Name("fast".to_string()),
+            PngCompression::Default => Self::Name("default".to_string()),
+            PngCompression::Best => Self::Name("best".to_string()),
+            PngCompression::Level(level) => Self::Level(level),
+        }
+    }
+}
+
+/// What is written into the PNG text chunks or JPEG EXIF of saved files.
+/// WebP files never carry any.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum MetadataLevel {
+    #[default]
+    None,
+    /// Capture time and tool name.
+    Basic,
+    /// Also the window title and class, output, geometry and workspace.
+    Full,
+}
+
+/// Encoding of saved screenshots. The clipboard always receives PNG.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    #[serde(alias = "jpg")]
+    #[value(alias = "jpg")]
+    Jpeg,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+        }
+    }
+
+    /// Format named by the extension of `path` (case-insensitive), if any.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "webp" => Some(Self::Webp),
+            _ => None,
+        }
+    }
+}
+
+/// Container of `--record` output. GIF and WebP are animated images for short
+/// clips; the others are videos.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordFormat {
+    #[default]
+    Mp4,
+    Webm,
+    Mkv,
+    Gif,
+    Webp,
+}
+
+impl RecordFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::Webm => "webm",
+            Self::Mkv => "mkv",
+            Self::Gif => "gif",
+            Self::Webp => "webp",
+        }
+    }
+
+    /// Format named by the extension of `path` (case-insensitive), if any.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "mp4" => Some(Self::Mp4),
+            "webm" => Some(Self::Webm),
+            "mkv" => Some(Self::Mkv),
+            "gif" => Some(Self::Gif),
+            "webp" => Some(Self::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn is_animated_image(self) -> bool {
+        matches!(self, Self::Gif | Self::Webp)
+    }
+}
+
+/// Parse a `#rrggbb` color (the `#` is optional).
+pub fn parse_hex_color(value: &str) -> Result<[u8; 3]> {
+    let hex = value.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow::anyhow!(
+            "Invalid color '{}': expected #rrggbb",
+            value
+        ));
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+    Ok([channel(0)?, channel(2)?, channel(4)?])
 }
 
 /// Advanced configuration options
@@ -77,13 +414,156 @@ pub struct AdvancedConfig {
     /// Default: 0
     #[serde(default)]
     pub delay_ms: u32,
+
+    /// Show a short usage hint on the freeze overlay
+    /// Default: false
+    #[serde(default)]
+    pub freeze_hint: bool,
+
+    /// Where the freeze hint is drawn: "top", "center" or "bottom"
+    /// Default: "top"
+    #[serde(default)]
+    pub freeze_hint_position: FreezeHintPosition,
+
+    /// Outline the window boxes offered for selection on the freeze overlay
+    /// (Hyprland, `-m window`), to check that they line up with the frozen image
+    /// Default: false
+    #[serde(default)]
+    pub freeze_debug_boxes: bool,
+
+    /// Clip region selections to the output showing most of them
+    /// Default: false
+    #[serde(default)]
+    pub constrain_to_output: bool,
+}
+
+/// `--record` settings
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RecordConfig {
+    /// Container when `-f` doesn't name one: mp4, webm, mkv, gif or webp
+    /// Default: "mp4"
+    #[serde(default)]
+    pub format: RecordFormat,
+
+    /// Frames per second
+    /// Default: unset (30 for videos, 15 for GIF/WebP)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fps: Option<u32>,
+
+    /// Stop the recording after this many milliseconds
+    /// Default: 0 (record until stopped)
+    #[serde(default)]
+    pub max_duration_ms: u64,
+}
+
+/// `--beautify` frame; sizes are logical pixels
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct BeautifyConfig {
+    /// Space around the capture
+    /// Default: 64
+    #[serde(default = "default_beautify_padding")]
+    pub padding: u32,
+
+    /// Radius of the capture's corners (0 = square)
+    /// Default: 12
+    #[serde(default = "default_beautify_corner_radius")]
+    pub corner_radius: u32,
+
+    /// Softness of the drop shadow (0 = no shadow)
+    /// Default: 24
+    #[serde(default = "default_beautify_shadow_blur")]
+    pub shadow_blur: u32,
+
+    /// "#rrggbb", or "#rrggbb:#rrggbb" for a diagonal gradient
+    /// Default: "#8ec5fc:#e0c3fc"
+    #[serde(default = "default_beautify_background")]
+    pub background: String,
+}
+
+/// Shell commands run around a capture
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct HooksConfig {
+    /// Run in order before selecting and capturing; a failing command aborts
+    /// the capture
+    /// Default: none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_capture: Vec<String>,
+
+    /// Run in order after the screenshot is saved, with `HYPRSHOT_PATH` and
+    /// friends set
+    /// Default: none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_capture: Vec<String>,
+
+    /// Annotator run by a bare `--edit-with`, e.g. "gimp"; the capture is
+    /// appended unless the command uses `$HYPRSHOT_PATH`
+    /// Default: none
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub editor: String,
+}
+
+/// `--upload` endpoints
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct UploadConfig {
+    /// Target used by a bare `--upload`
+    /// Default: unset (the only target, if there is exactly one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+
+    /// Endpoints under `[upload.targets.NAME]`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub targets: BTreeMap<String, UploadTarget>,
+}
+
+/// An HTTP endpoint screenshots can be sent to.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct UploadTarget {
+    pub url: String,
+
+    /// Default: "POST"
+    #[serde(default = "default_upload_method")]
+    pub method: String,
+
+    /// Extra request headers, e.g. `Authorization = "Client-ID ..."`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+
+    /// Send the file as this multipart form field; unset sends it as the raw body
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub form_field: Option<String>,
+
+    /// Dotted path to the URL in a JSON reply, e.g. `data.link`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_path: Option<String>,
+
+    /// Regex for the URL in the reply; the first group if it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url_regex: Option<String>,
+}
+
+/// Vertical placement of the freeze overlay hint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum FreezeHintPosition {
+    #[default]
+    Top,
+    Center,
+    Bottom,
 }
 
 // Default value functions for serde
+fn default_upload_method() -> String {
+    "POST".to_string()
+}
+
 fn default_screenshots_dir() -> String {
     "~/Pictures".to_string()
 }
 
+fn default_create_missing_dirs() -> bool {
+    true
+}
+
 fn default_hotkey_window() -> String {
     "SUPER, Print".to_string()
 }
@@ -104,10 +584,42 @@ fn default_notification() -> bool {
     true
 }
 
+fn default_clipboard_on_capture() -> bool {
+    true
+}
+
+fn default_beautify_padding() -> u32 {
+    64
+}
+
+fn default_beautify_corner_radius() -> u32 {
+    12
+}
+
+fn default_beautify_shadow_blur() -> u32 {
+    24
+}
+
+fn default_beautify_background() -> String {
+    "#8ec5fc:#e0c3fc".to_string()
+}
+
 fn default_notification_timeout() -> u32 {
     3000
 }
 
+fn default_quality() -> u8 {
+    90
+}
+
+fn default_flatten_background() -> String {
+    "#ffffff".to_string()
+}
+
+fn default_filename_template() -> String {
+    crate::filename::DEFAULT_TEMPLATE.to_string()
+}
+
 fn default_freeze() -> bool {
     true
 }
@@ -116,6 +628,10 @@ impl Default for PathsConfig {
     fn default() -> Self {
         Self {
             screenshots_dir: default_screenshots_dir(),
+            create_missing_dirs: default_create_missing_dirs(),
+            keep_recent: 0,
+            filename_template: default_filename_template(),
+            on_conflict: ConflictPolicy::default(),
         }
     }
 }
@@ -136,6 +652,30 @@ impl Default for CaptureConfig {
         Self {
             notification: default_notification(),
             notification_timeout: default_notification_timeout(),
+            flash: false,
+            clipboard_on_capture: default_clipboard_on_capture(),
+            clipboard_uri: false,
+            png_compression: PngCompression::default(),
+            metadata: MetadataLevel::default(),
+            default_format: ImageFormat::default(),
+            quality: default_quality(),
+            flatten_background: default_flatten_background(),
+            output_physical_pixels: false,
+            include_cursor: false,
+            force_scale: None,
+            command_timeout_ms: 0,
+            menu_command: String::new(),
+        }
+    }
+}
+
+impl Default for BeautifyConfig {
+    fn default() -> Self {
+        Self {
+            padding: default_beautify_padding(),
+            corner_radius: default_beautify_corner_radius(),
+            shadow_blur: default_beautify_shadow_blur(),
+            background: default_beautify_background(),
         }
     }
 }
@@ -145,6 +685,10 @@ impl Default for AdvancedConfig {
         Self {
             freeze_on_region: default_freeze(),
             delay_ms: 0,
+            freeze_hint: false,
+            freeze_hint_position: FreezeHintPosition::default(),
+            freeze_debug_boxes: false,
+            constrain_to_output: false,
         }
     }
 }
@@ -157,10 +701,34 @@ impl Default for Config {
             hotkeys: HotkeysConfig::default(),
             capture: CaptureConfig::default(),
             advanced: AdvancedConfig::default(),
+            record: RecordConfig::default(),
+            upload: UploadConfig::default(),
+            hooks: HooksConfig::default(),
+            beautify: BeautifyConfig::default(),
+            profiles: BTreeMap::new(),
         }
     }
 }
 
+/// Normalize a hotkey for comparison
+/// Examples:
+///   "shift super, print" -> "SHIFT SUPER, PRINT"
+///   "SUPER  SHIFT,Print" -> "SHIFT SUPER, PRINT"
+fn normalize_hotkey(hotkey: &str) -> String {
+    let (modifiers, key) = hotkey.split_once(',').unwrap_or(("", hotkey));
+    let mut modifiers: Vec<String> = modifiers
+        .split_whitespace()
+        .map(|m| m.to_ascii_uppercase())
+        .collect();
+    modifiers.sort();
+    modifiers.dedup();
+    format!(
+        "{}, {}",
+        modifiers.join(" "),
+        key.trim().to_ascii_uppercase()
+    )
+}
+
 // Utility functions for path expansion and validation
 
 /// Expand path with support for:
@@ -235,12 +803,18 @@ pub fn expand_path(path: &str) -> Result<PathBuf> {
 
 /// Validate and prepare directory for saving screenshots
 /// - Expands path variables
-/// - Creates directory if it doesn't exist
+/// - Creates directory if it doesn't exist (errors instead when `create_missing` is false)
 /// - Returns error if path is not writable
-pub fn ensure_directory(path: &str) -> Result<PathBuf> {
+pub fn ensure_directory(path: &str, create_missing: bool) -> Result<PathBuf> {
     let expanded_path = expand_path(path)?;
 
     let existed = expanded_path.exists();
+    if !existed && !create_missing {
+        return Err(anyhow::anyhow!(
+            "Directory does not exist: {} (paths.create_missing_dirs is false)",
+            expanded_path.display()
+        ));
+    }
     if !existed {
         fs::create_dir_all(&expanded_path).context(format!(
             "Failed to create directory: {}",
@@ -287,7 +861,7 @@ pub fn get_screenshots_dir(
         if debug {
             eprintln!("Using screenshot directory from CLI: {}", path.display());
         }
-        return Ok(path);
+        return reject_non_directory(path, "-o/--output-folder");
     }
 
     if let Ok(env_path) = env::var("HYPRSHOT_DIR") {
@@ -298,7 +872,7 @@ pub fn get_screenshots_dir(
                 expanded.display()
             );
         }
-        return Ok(expanded);
+        return reject_non_directory(expanded, "HYPRSHOT_DIR");
     }
 
     let config_path = expand_path(&config.paths.screenshots_dir)?;
@@ -308,7 +882,20 @@ pub fn get_screenshots_dir(
             config_path.display()
         );
     }
-    Ok(config_path)
+    reject_non_directory(config_path, "paths.screenshots_dir")
+}
+
+/// Fail early, naming where the path came from, when it exists but isn't a
+/// directory (otherwise the screenshot would be saved as `file/filename`).
+fn reject_non_directory(path: PathBuf, source: &str) -> Result<PathBuf> {
+    if path.exists() && !path.is_dir() {
+        return Err(anyhow::anyhow!(
+            "Screenshot directory from {} is not a directory: {}",
+            source,
+            path.display()
+        ));
+    }
+    Ok(path)
 }
 
 impl Config {
@@ -412,6 +999,8 @@ impl Config {
                 result.push_str("\n# Capture settings\n");
             } else if line.starts_with("[advanced]") {
                 result.push_str("\n# Advanced settings\n");
+            } else if line.starts_with("[profiles.") {
+                result.push_str("\n# Profile (use with --profile NAME)\n");
             }
 
             result.push_str(line);
@@ -478,6 +1067,46 @@ impl Config {
         binds
     }
 
+    /// Find hotkeys that are bound more than once
+    /// Returns (normalized hotkey, names of the bindings that use it)
+    /// Clipboard variants (ALT added) are included when `with_clipboard` is set
+    pub fn hotkey_conflicts(&self, with_clipboard: bool) -> Vec<(String, Vec<String>)> {
+        let mut bindings = vec![
+            ("window".to_string(), self.hotkeys.window.clone()),
+            ("region".to_string(), self.hotkeys.region.clone()),
+            ("output".to_string(), self.hotkeys.output.clone()),
+            (
+                "active_output".to_string(),
+                self.hotkeys.active_output.clone(),
+            ),
+        ];
+        if with_clipboard {
+            bindings.push((
+                "window (clipboard)".to_string(),
+                self.add_alt_modifier(&self.hotkeys.window),
+            ));
+            bindings.push((
+                "region (clipboard)".to_string(),
+                self.add_alt_modifier(&self.hotkeys.region),
+            ));
+            bindings.push((
+                "output (clipboard)".to_string(),
+                self.add_alt_modifier(&self.hotkeys.output),
+            ));
+        }
+
+        let mut conflicts: Vec<(String, Vec<String>)> = Vec::new();
+        for (name, hotkey) in bindings {
+            let normalized = normalize_hotkey(&hotkey);
+            match conflicts.iter_mut().find(|(key, _)| *key == normalized) {
+                Some((_, names)) => names.push(name),
+                None => conflicts.push((normalized, vec![name])),
+            }
+        }
+        conflicts.retain(|(_, names)| names.len() > 1);
+        conflicts
+    }
+
     /// Add ALT modifier to a hotkey string
     /// Examples:
     ///   "SUPER, Print" -> "SUPER ALT, Print"
@@ -501,8 +1130,8 @@ impl Config {
     }
 
     /// Install Hyprland bindings to hyprland.conf
-    /// Returns the path where bindings were installed
-    pub fn install_hyprland_binds(&self, with_clipboard: bool) -> Result<PathBuf> {
+    /// Returns the path where bindings were installed and the backup path
+    pub fn install_hyprland_binds(&self, with_clipboard: bool) -> Result<(PathBuf, PathBuf)> {
         let hyprland_conf = dirs::home_dir()
             .context("Failed to get home directory")?
             .join(".config/hypr/hyprland.conf");
@@ -537,13 +1166,31 @@ impl Config {
         new_config.push('\n');
         new_config.push_str(&binds);
 
-        let backup_path = hyprland_conf.with_extension("conf.backup");
+        let backup_path =
+            Self::hyprland_backup_path(&hyprland_conf, chrono::Local::now().timestamp());
+        if backup_path.exists() {
+            anyhow::bail!(
+                "Backup already exists: {}\nRefusing to overwrite it; try again in a second.",
+                backup_path.display()
+            );
+        }
         fs::copy(&hyprland_conf, &backup_path)
             .context("Failed to create backup of hyprland.conf")?;
 
         fs::write(&hyprland_conf, new_config).context("Failed to write to hyprland.conf")?;
 
-        Ok(hyprland_conf)
+        Ok((hyprland_conf, backup_path))
+    }
+
+    /// Timestamped backup path for hyprland.conf
+    /// Example: hyprland.conf -> hyprland.conf.backup.1700000000
+    pub fn hyprland_backup_path(hyprland_conf: &Path, epoch: i64) -> PathBuf {
+        let mut name = hyprland_conf
+            .file_name()
+            .map(|n| n.to_os_string())
+            .unwrap_or_else(|| "hyprland.conf".into());
+        name.push(format!(".backup.{}", epoch));
+        hyprland_conf.with_file_name(name)
     }
 
     /// Get the path to Hyprland config file
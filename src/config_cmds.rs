@@ -1,4 +1,5 @@
 use anyhow::{Context, Result};
+use clap::ValueEnum;
 
 use crate::config;
 
@@ -72,6 +73,78 @@ pub fn handle_set_config(args: &[String]) -> Result<()> {
     Ok(())
 }
 
+/// Wizard prompts for the non-hotkey settings. Every answer goes through
+/// `set_config_value`, so the wizard accepts exactly what `--set` accepts.
+pub(crate) fn prompt_general_settings(
+    config: &mut config::Config,
+    theme: &dialoguer::theme::ColorfulTheme,
+) -> Result<()> {
+    use clap::ValueEnum;
+    use dialoguer::{Confirm, Input, Select};
+
+    let text_fields = [
+        (
+            "paths.screenshots_dir",
+            "Screenshots directory",
+            config.paths.screenshots_dir.clone(),
+        ),
+        (
+            "capture.notification_timeout",
+            "Notification timeout (ms)",
+            config.capture.notification_timeout.to_string(),
+        ),
+    ];
+    for (key, prompt, current) in text_fields {
+        let value: String = Input::with_theme(theme)
+            .with_prompt(prompt)
+            .default(current)
+            .validate_with(|input: &String| -> Result<(), String> {
+                set_config_value(&mut config.clone(), key, input).map_err(|e| e.to_string())
+            })
+            .interact_text()?;
+        set_config_value(config, key, &value)?;
+    }
+
+    let formats: Vec<String> = config::ImageFormat::value_variants()
+        .iter()
+        .filter_map(|format| format.to_possible_value())
+        .map(|value| value.get_name().to_string())
+        .collect();
+    let current = config::ImageFormat::value_variants()
+        .iter()
+        .position(|format| *format == config.capture.default_format)
+        .unwrap_or(0);
+    let choice = Select::with_theme(theme)
+        .with_prompt("Default image format")
+        .items(&formats)
+        .default(current)
+        .interact()?;
+    set_config_value(config, "capture.default_format", &formats[choice])?;
+
+    let bool_fields = [
+        (
+            "capture.notification",
+            "Show a notification after each screenshot?",
+            config.capture.notification,
+        ),
+        (
+            "advanced.freeze_on_region",
+            "Freeze the screen while selecting?",
+            config.advanced.freeze_on_region,
+        ),
+    ];
+    for (key, prompt, current) in bool_fields {
+        let value = Confirm::with_theme(theme)
+            .with_prompt(prompt)
+            .default(current)
+            .interact()?;
+        set_config_value(config, key, &value.to_string())?;
+    }
+    println!();
+
+    Ok(())
+}
+
 fn set_config_value(config: &mut config::Config, key: &str, value: &str) -> Result<()> {
     let parts: Vec<&str> = key.split('.').collect();
 
@@ -90,6 +163,23 @@ fn set_config_value(config: &mut config::Config, key: &str, value: &str) -> Resu
         ("paths", "screenshots_dir") => {
             config.paths.screenshots_dir = value.to_string();
         }
+        ("paths", "create_missing_dirs") => {
+            config.paths.create_missing_dirs =
+                value.parse().context("Value must be 'true' or 'false'")?;
+        }
+        ("paths", "keep_recent") => {
+            config.paths.keep_recent = value
+                .parse()
+                .context("Value must be a number (0 disables)")?;
+        }
+        ("paths", "filename_template") => {
+            crate::filename::validate(value)?;
+            config.paths.filename_template = value.to_string();
+        }
+        ("paths", "on_conflict") => {
+            config.paths.on_conflict = config::ConflictPolicy::from_str(value, true)
+                .map_err(|_| anyhow::anyhow!("Value must be 'overwrite', 'increment' or 'fail'"))?;
+        }
 
         // [hotkeys] section
         ("hotkeys", "window") => {
@@ -115,6 +205,92 @@ fn set_config_value(config: &mut config::Config, key: &str, value: &str) -> Resu
                 .parse()
                 .context("Value must be a number (milliseconds)")?;
         }
+        ("capture", "flash") => {
+            config.capture.flash = value.parse().context("Value must be 'true' or 'false'")?;
+        }
+        ("capture", "clipboard_on_capture") => {
+            config.capture.clipboard_on_capture =
+                value.parse().context("Value must be 'true' or 'false'")?;
+        }
+        ("capture", "clipboard_uri") => {
+            config.capture.clipboard_uri =
+                value.parse().context("Value must be 'true' or 'false'")?;
+        }
+        ("capture", "png_compression") => {
+            config.capture.png_compression = value.parse()?;
+        }
+        ("capture", "metadata") => {
+            config.capture.metadata = config::MetadataLevel::from_str(value, true)
+                .map_err(|_| anyhow::anyhow!("Value must be 'none', 'basic' or 'full'"))?;
+        }
+        ("capture", "default_format") => {
+            config.capture.default_format = config::ImageFormat::from_str(value, true)
+                .map_err(|_| anyhow::anyhow!("Value must be 'png', 'jpeg' or 'webp'"))?;
+        }
+        ("capture", "quality") => {
+            let quality: u8 = value
+                .parse()
+                .context("Value must be a number from 1 to 100")?;
+            if !(1..=100).contains(&quality) {
+                return Err(anyhow::anyhow!("Value must be a number from 1 to 100"));
+            }
+            config.capture.quality = quality;
+        }
+        ("capture", "flatten_background") => {
+            config::parse_hex_color(value)?;
+            config.capture.flatten_background = value.to_string();
+        }
+        ("capture", "command_timeout_ms") => {
+            config.capture.command_timeout_ms = value
+                .parse()
+                .context("Value must be a number (milliseconds)")?;
+        }
+        ("capture", "menu_command") => {
+            config.capture.menu_command = value.to_string();
+        }
+        ("capture", "force_scale") => {
+            config.capture.force_scale = match value {
+                "" | "none" => None,
+                _ => {
+                    let scale: f64 = value
+                        .parse()
+                        .context("Value must be a number (e.g. 1 or 1.5) or 'none'")?;
+                    crate::save::validate_scale(scale)?;
+                    Some(scale)
+                }
+            };
+        }
+        ("capture", "output_physical_pixels") => {
+            config.capture.output_physical_pixels =
+                value.parse().context("Value must be 'true' or 'false'")?;
+        }
+        ("capture", "include_cursor") => {
+            config.capture.include_cursor =
+                value.parse().context("Value must be 'true' or 'false'")?;
+        }
+
+        // [hooks] section
+        ("hooks", "editor") => {
+            config.hooks.editor = value.to_string();
+        }
+
+        // [beautify] section
+        ("beautify", "padding") => {
+            config.beautify.padding = value.parse().context("Value must be a number (pixels)")?;
+        }
+        ("beautify", "corner_radius") => {
+            config.beautify.corner_radius =
+                value.parse().context("Value must be a number (pixels)")?;
+        }
+        ("beautify", "shadow_blur") => {
+            config.beautify.shadow_blur = value
+                .parse()
+                .context("Value must be a number (pixels, 0 = none)")?;
+        }
+        ("beautify", "background") => {
+            crate::beautify::parse_background(value)?;
+            config.beautify.background = value.to_string();
+        }
 
         // [advanced] section
         ("advanced", "freeze_on_region") => {
@@ -126,12 +302,63 @@ fn set_config_value(config: &mut config::Config, key: &str, value: &str) -> Resu
                 .parse()
                 .context("Value must be a number (milliseconds)")?;
         }
+        ("advanced", "freeze_hint") => {
+            config.advanced.freeze_hint =
+                value.parse().context("Value must be 'true' or 'false'")?;
+        }
+        ("advanced", "freeze_hint_position") => {
+            config.advanced.freeze_hint_position =
+                config::FreezeHintPosition::from_str(value, true)
+                    .map_err(|_| anyhow::anyhow!("Value must be 'top', 'center' or 'bottom'"))?;
+        }
+        ("advanced", "freeze_debug_boxes") => {
+            config.advanced.freeze_debug_boxes =
+                value.parse().context("Value must be 'true' or 'false'")?;
+        }
+        ("advanced", "constrain_to_output") => {
+            config.advanced.constrain_to_output =
+                value.parse().context("Value must be 'true' or 'false'")?;
+        }
+        ("record", "format") => {
+            config.record.format = config::RecordFormat::from_str(value, true).map_err(|_| {
+                anyhow::anyhow!("Value must be 'mp4', 'webm', 'mkv', 'gif' or 'webp'")
+            })?;
+        }
+        ("record", "fps") => {
+            config.record.fps = match value {
+                "" | "none" => None,
+                _ => {
+                    let fps: u32 = value
+                        .parse()
+                        .context("Value must be a number (1-120) or 'none'")?;
+                    if !(1..=120).contains(&fps) {
+                        return Err(anyhow::anyhow!("Value must be between 1 and 120"));
+                    }
+                    Some(fps)
+                }
+            };
+        }
+        ("record", "max_duration_ms") => {
+            config.record.max_duration_ms = value
+                .parse()
+                .context("Value must be a number (milliseconds)")?;
+        }
+        ("upload", "default") => {
+            config.upload.default = match value {
+                "" | "none" => None,
+                _ => Some(value.to_string()),
+            };
+        }
 
         _ => {
             return Err(anyhow::anyhow!(
                 "Unknown config key: {}.{}\n\nAvailable keys:\n\
                  Paths:\n\
                    - paths.screenshots_dir\n\
+                   - paths.create_missing_dirs (true, false)\n\
+                   - paths.keep_recent (number of screenshots, 0 disables)\n\
+                   - paths.filename_template (e.g. %Y-%m-%d_%mode_%counter)\n\
+                   - paths.on_conflict (overwrite, increment, fail)\n\
                  Hotkeys:\n\
                    - hotkeys.window\n\
                    - hotkeys.region\n\
@@ -140,9 +367,39 @@ fn set_config_value(config: &mut config::Config, key: &str, value: &str) -> Resu
                  Capture:\n\
                    - capture.notification (true, false)\n\
                    - capture.notification_timeout (milliseconds)\n\
+                   - capture.flash (true, false)\n\
+                   - capture.clipboard_on_capture (true, false)\n\
+                   - capture.clipboard_uri (true, false)\n\
+                   - capture.png_compression (fast, default, best, 0-9)\n\
+                   - capture.metadata (none, basic, full)\n\
+                   - capture.default_format (png, jpeg, webp)\n\
+                   - capture.quality (1-100, JPEG/WebP)\n\
+                   - capture.flatten_background (#rrggbb, JPEG background)\n\
+                   - capture.output_physical_pixels (true, false)\n\
+                   - capture.include_cursor (true, false)\n\
+                   - capture.force_scale (pixels per logical pixel, none = unset)\n\
+                   - capture.command_timeout_ms (milliseconds, 0 = wait)\n\
+                   - capture.menu_command (e.g. \"fuzzel --dmenu\")\n\
+                 Hooks:\n\
+                   - hooks.editor (command for a bare --edit-with)\n\
+                 Beautify:\n\
+                   - beautify.padding (pixels)\n\
+                   - beautify.corner_radius (pixels, 0 = square)\n\
+                   - beautify.shadow_blur (pixels, 0 = none)\n\
+                   - beautify.background (#rrggbb or #rrggbb:#rrggbb)\n\
                  Advanced:\n\
                    - advanced.freeze_on_region (true, false)\n\
-                   - advanced.delay_ms (milliseconds)",
+                   - advanced.delay_ms (milliseconds)\n\
+                   - advanced.freeze_hint (true, false)\n\
+                   - advanced.freeze_hint_position (top, center, bottom)\n\
+                   - advanced.freeze_debug_boxes (true, false)\n\
+                   - advanced.constrain_to_output (true, false)\n\
+                 Record:\n\
+                   - record.format (mp4, webm, mkv, gif, webp)\n\
+                   - record.fps (1-120, none = 30 for video, 15 for GIF/WebP)\n\
+                   - record.max_duration_ms (milliseconds, 0 = until stopped)\n\
+                 Upload:\n\
+                   - upload.default (target name, none = unset; targets are edited in the file)",
                 section,
                 field
             ));
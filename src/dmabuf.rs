@@ -0,0 +1,156 @@
+//! GBM buffers for ext-image-copy-capture (feature `dmabuf`): the compositor
+//! blits the frame into GPU memory instead of reading it back into a shared
+//! memory file, which is what stalls full-output captures on 4K monitors.
+
+use anyhow::{Context, Result};
+use gbm::{BufferObject, BufferObjectFlags, Device, Format, Modifier};
+use std::fs::File;
+use std::os::fd::OwnedFd;
+use std::os::unix::fs::MetadataExt;
+use wayland_client::protocol::wl_shm;
+
+use crate::shm::SUPPORTED_FORMATS;
+
+/// `DRM_FORMAT_MOD_LINEAR`: plain rows, mappable without detiling.
+pub(crate) const LINEAR: u64 = 0;
+
+/// A `dev_t` as sent by `dmabuf_device` (native-endian bytes).
+pub(crate) fn device_id(bytes: &[u8]) -> Option<u64> {
+    Some(u64::from_ne_bytes(bytes.try_into().ok()?))
+}
+
+/// Modifiers of a `dmabuf_format` event (an array of native-endian u64).
+pub(crate) fn modifiers(bytes: &[u8]) -> Vec<u64> {
+    bytes
+        .chunks_exact(8)
+        .filter_map(|chunk| Some(u64::from_ne_bytes(chunk.try_into().ok()?)))
+        .collect()
+}
+
+/// The shm format with the same memory layout as DRM `fourcc`. wl_shm keeps
+/// the fourcc codes except for its two original formats.
+pub(crate) fn shm_format(fourcc: u32) -> Option<wl_shm::Format> {
+    let format = match fourcc {
+        0x3432_5241 => wl_shm::Format::Argb8888,
+        0x3432_5258 => wl_shm::Format::Xrgb8888,
+        other => wl_shm::Format::try_from(other).ok()?,
+    };
+    SUPPORTED_FORMATS.contains(&format).then_some(format)
+}
+
+/// First offered format we can convert, with `alpha` preferring the ones that
+/// keep transparency, and its modifiers with linear first.
+pub(crate) fn pick_format(
+    offered: &[(u32, Vec<u64>)],
+    alpha: bool,
+) -> Option<(u32, wl_shm::Format, Vec<u64>)> {
+    let mut candidates: Vec<(u32, wl_shm::Format, &Vec<u64>)> = offered
+        .iter()
+        .filter(|(_, modifiers)| !modifiers.is_empty())
+        .filter_map(|(fourcc, modifiers)| Some((*fourcc, shm_format(*fourcc)?, modifiers)))
+        .collect();
+    candidates.sort_by_key(|(_, format, _)| {
+        let keeps_alpha = matches!(format, wl_shm::Format::Argb8888 | wl_shm::Format::Abgr8888);
+        (
+            !(alpha && keeps_alpha),
+            SUPPORTED_FORMATS.iter().position(|f| f == format),
+        )
+    });
+    let (fourcc, format, modifiers) = candidates.into_iter().next()?;
+    let mut modifiers = modifiers.clone();
+    modifiers.sort_by_key(|&modifier| modifier != LINEAR);
+    Some((fourcc, format, modifiers))
+}
+
+/// A GBM device on the DRM node the compositor named.
+pub(crate) struct Allocator {
+    device: Device<File>,
+}
+
+impl Allocator {
+    /// Open the node in `/dev/dri` whose device number is `id`.
+    pub(crate) fn open(id: u64) -> Result<Self> {
+        let node = std::fs::read_dir("/dev/dri")
+            .context("Failed to list /dev/dri")?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .find(|path| std::fs::metadata(path).is_ok_and(|meta| meta.rdev() == id))
+            .with_context(|| format!("No DRM node in /dev/dri has device number {:#x}", id))?;
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .open(&node)
+            .with_context(|| format!("Failed to open {}", node.display()))?;
+        let device = Device::new(file)
+            .with_context(|| format!("Failed to create a GBM device on {}", node.display()))?;
+        Ok(Self { device })
+    }
+
+    /// A `width` x `height` buffer in `fourcc` with one of `modifiers`;
+    /// linear ones are allocated as such so mapping needs no detiling.
+    pub(crate) fn allocate(
+        &self,
+        width: u32,
+        height: u32,
+        fourcc: u32,
+        modifiers: &[u64],
+    ) -> Result<Buffer> {
+        let format = Format::try_from(fourcc).context("Unknown DRM format")?;
+        let bo = if modifiers.first() == Some(&LINEAR) {
+            self.device.create_buffer_object(
+                width,
+                height,
+                format,
+                BufferObjectFlags::RENDERING | BufferObjectFlags::LINEAR,
+            )
+        } else {
+            self.device.create_buffer_object_with_modifiers(
+                width,
+                height,
+                format,
+                modifiers.iter().map(|&modifier| Modifier::from(modifier)),
+            )
+        }
+        .context("Failed to allocate a GBM buffer")?;
+        Ok(Buffer { bo })
+    }
+}
+
+pub(crate) struct Buffer {
+    bo: BufferObject<()>,
+}
+
+impl Buffer {
+    pub(crate) fn modifier(&self) -> u64 {
+        self.bo.modifier().into()
+    }
+
+    /// File descriptor, offset and stride of every plane.
+    pub(crate) fn planes(&self) -> Result<Vec<(OwnedFd, u32, u32)>> {
+        (0..self.bo.plane_count() as i32)
+            .map(|plane| {
+                let fd = self
+                    .bo
+                    .fd_for_plane(plane)
+                    .context("Failed to export a GBM buffer plane")?;
+                Ok((fd, self.bo.offset(plane), self.bo.stride_for_plane(plane)))
+            })
+            .collect()
+    }
+
+    /// The pixels, rows packed to 4 bytes a pixel, in the buffer's format.
+    pub(crate) fn read(&self) -> Result<Vec<u8>> {
+        let (width, height) = (self.bo.width(), self.bo.height());
+        let row = width as usize * 4;
+        self.bo
+            .map(0, 0, width, height, |mapped| {
+                let stride = mapped.stride() as usize;
+                let mut pixels = Vec::with_capacity(row * height as usize);
+                for line in mapped.buffer().chunks(stride).take(height as usize) {
+                    pixels.extend_from_slice(&line[..row]);
+                }
+                pixels
+            })
+            .context("Failed to map the GBM buffer")
+    }
+}
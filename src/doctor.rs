@@ -0,0 +1,420 @@
+//! `hyprshot-rs doctor`: check what a capture depends on and say how to fix
+//! what is missing, instead of failing halfway through a keybind.
+
+use anyhow::{Result, bail};
+use chrono::Local;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::compositor::{self, CompositorKind};
+use crate::config;
+use crate::hyprland_ipc;
+use crate::notify;
+use crate::sway_ipc::{self, MessageType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Status {
+    Ok,
+    /// Something optional is missing; captures still work.
+    Warn,
+    Fail,
+}
+
+#[derive(Debug)]
+pub(crate) struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    /// What to do about a warning or failure.
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn problem(
+        name: &'static str,
+        status: Status,
+        detail: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+pub fn handle_doctor(debug: bool) -> Result<()> {
+    let checks = run_checks(debug);
+    print!("{}", report(&checks));
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == Status::Fail)
+        .count();
+    if failed > 0 {
+        bail!("{} check(s) failed", failed);
+    }
+    Ok(())
+}
+
+fn run_checks(debug: bool) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let globals = wayland_checks(debug, &mut checks);
+    checks.push(compositor_ipc());
+    checks.push(clipboard(globals.as_deref()));
+    checks.push(notifications(debug));
+    let config = config::Config::load();
+    if let Ok(path) = config::Config::config_path() {
+        checks.push(config_file(&path, &config));
+    }
+    checks.push(screenshots_dir(&config.unwrap_or_default(), debug));
+    checks.push(optional_tool(
+        "ffmpeg",
+        "--record",
+        "install ffmpeg to record videos and GIFs",
+    ));
+    checks.push(optional_tool(
+        "curl",
+        "--upload",
+        "install curl to upload screenshots",
+    ));
+    checks
+}
+
+/// `[ok]`/`[warn]`/`[FAIL]` lines, each problem followed by its fix.
+pub(crate) fn report(checks: &[Check]) -> String {
+    let width = checks
+        .iter()
+        .map(|check| check.name.len())
+        .max()
+        .unwrap_or(0);
+    let mut text = String::new();
+    for check in checks {
+        let label = match check.status {
+            Status::Ok => "[ok]  ",
+            Status::Warn => "[warn]",
+            Status::Fail => "[FAIL]",
+        };
+        text.push_str(&format!(
+            "{} {:<width$}  {}\n",
+            label,
+            check.name,
+            check.detail,
+            width = width
+        ));
+        if let Some(fix) = &check.fix {
+            text.push_str(&format!(
+                "       {:<width$}  -> {}\n",
+                "",
+                fix,
+                width = width
+            ));
+        }
+    }
+    text
+}
+
+/// The protocols a capture needs, from the advertised globals.
+pub(crate) fn protocol_checks(globals: &[String]) -> Vec<Check> {
+    let has = |interface: &str| globals.iter().any(|global| global == interface);
+    let mut checks = Vec::new();
+    checks.push(
+        match (
+            has("zwlr_screencopy_manager_v1"),
+            has("ext_image_copy_capture_manager_v1"),
+        ) {
+            (true, _) => Check::ok("screencopy", "wlr-screencopy"),
+            (false, true) if cfg!(feature = "ext-capture") => {
+                Check::ok("screencopy", "ext-image-copy-capture")
+            }
+            (false, true) => Check::problem(
+                "screencopy",
+                Status::Fail,
+                "only ext-image-copy-capture is offered",
+                "rebuild with `--features ext-capture`",
+            ),
+            (false, false) => Check::problem(
+                "screencopy",
+                Status::Fail,
+                "the compositor offers no screencopy protocol",
+                "use a wlroots-based compositor (Hyprland, Sway, River, ...) or update it",
+            ),
+        },
+    );
+    checks.push(if has("zwlr_layer_shell_v1") {
+        Check::ok("layer-shell", "zwlr_layer_shell_v1")
+    } else {
+        Check::problem(
+            "layer-shell",
+            Status::Fail,
+            "not offered; selection and --freeze can't draw their overlay",
+            "use a compositor with wlr-layer-shell (Hyprland, Sway, ...)",
+        )
+    });
+    checks.push(if has("zxdg_output_manager_v1") {
+        Check::ok("xdg-output", "zxdg_output_manager_v1")
+    } else {
+        Check::problem(
+            "xdg-output",
+            Status::Warn,
+            "not offered; output positions fall back to wl_output and may be off with scaling",
+            "update the compositor",
+        )
+    });
+    checks
+}
+
+/// Connect to Wayland and check its globals; `None` when there is no session.
+fn wayland_checks(debug: bool, checks: &mut Vec<Check>) -> Option<Vec<String>> {
+    let display = env::var("WAYLAND_DISPLAY").unwrap_or_default();
+    if display.is_empty() {
+        checks.push(Check::problem(
+            "Wayland socket",
+            Status::Fail,
+            "WAYLAND_DISPLAY is not set",
+            "run hyprshot-rs inside a Wayland session (not from a TTY or over SSH)",
+        ));
+        return None;
+    }
+    match wayland_globals(debug) {
+        Ok(globals) => {
+            checks.push(Check::ok("Wayland socket", display));
+            checks.extend(protocol_checks(&globals));
+            Some(globals)
+        }
+        Err(err) => {
+            checks.push(Check::problem(
+                "Wayland socket",
+                Status::Fail,
+                format!("{}: {:#}", display, err),
+                "check that WAYLAND_DISPLAY and XDG_RUNTIME_DIR match the running compositor",
+            ));
+            None
+        }
+    }
+}
+
+#[cfg(feature = "freeze")]
+fn wayland_globals(debug: bool) -> Result<Vec<String>> {
+    use anyhow::Context;
+    use wayland_client::{Connection, Dispatch, QueueHandle, protocol::wl_registry};
+
+    struct Globals(Vec<String>);
+
+    impl Dispatch<wl_registry::WlRegistry, ()> for Globals {
+        fn event(
+            state: &mut Self,
+            _: &wl_registry::WlRegistry,
+            event: wl_registry::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            if let wl_registry::Event::Global { interface, .. } = event {
+                state.0.push(interface);
+            }
+        }
+    }
+
+    let conn = Connection::connect_to_env().context("Failed to connect to Wayland")?;
+    let mut queue = conn.new_event_queue();
+    let _registry = conn.display().get_registry(&queue.handle(), ());
+    let mut globals = Globals(Vec::new());
+    queue
+        .roundtrip(&mut globals)
+        .context("Failed to list Wayland globals")?;
+    if debug {
+        eprintln!("Wayland globals: {}", globals.0.join(", "));
+    }
+    Ok(globals.0)
+}
+
+#[cfg(not(feature = "freeze"))]
+fn wayland_globals(_debug: bool) -> Result<Vec<String>> {
+    bail!("checking Wayland protocols needs the 'freeze' feature")
+}
+
+fn compositor_ipc() -> Check {
+    let kind = compositor::detect_kind(|name| env::var_os(name).is_some_and(|v| !v.is_empty()));
+    match kind {
+        CompositorKind::Hyprland => match hyprland_ipc::query("version") {
+            Ok(version) => Check::ok(
+                "compositor IPC",
+                format!(
+                    "Hyprland {}",
+                    version["tag"].as_str().unwrap_or("(unknown version)")
+                ),
+            ),
+            Err(err) => Check::problem(
+                "compositor IPC",
+                Status::Fail,
+                format!("Hyprland socket: {:#}", err),
+                "HYPRLAND_INSTANCE_SIGNATURE may be stale; restart the shell from inside Hyprland",
+            ),
+        },
+        CompositorKind::Sway => match sway_ipc::query(MessageType::Version) {
+            Ok(version) => Check::ok(
+                "compositor IPC",
+                version["human_readable"]
+                    .as_str()
+                    .map(|text| format!("Sway {}", text))
+                    .unwrap_or_else(|| "Sway".to_string()),
+            ),
+            Err(err) => Check::problem(
+                "compositor IPC",
+                Status::Fail,
+                format!("Sway socket: {:#}", err),
+                "SWAYSOCK may be stale; restart the shell from inside Sway",
+            ),
+        },
+        CompositorKind::Generic => Check::problem(
+            "compositor IPC",
+            Status::Warn,
+            "neither Hyprland nor Sway detected",
+            "-m window and -m active need Hyprland or Sway; output and region captures still work",
+        ),
+    }
+}
+
+/// Data-control sets the clipboard natively; `wl-copy` is the fallback.
+fn clipboard(globals: Option<&[String]>) -> Check {
+    let native = cfg!(feature = "clipboard")
+        && globals.is_some_and(|globals| {
+            globals.iter().any(|global| {
+                global == "ext_data_control_manager_v1" || global == "zwlr_data_control_manager_v1"
+            })
+        });
+    match (native, find_in_path("wl-copy")) {
+        (true, _) => Check::ok("clipboard", "data-control"),
+        (false, Some(path)) => Check::ok("clipboard", format!("{}", path.display())),
+        (false, None) => Check::problem(
+            "clipboard",
+            Status::Fail,
+            "no data-control protocol and wl-copy not found",
+            "install wl-clipboard",
+        ),
+    }
+}
+
+fn notifications(debug: bool) -> Check {
+    if notify::daemon_available(debug) {
+        Check::ok("notifications", "daemon running")
+    } else {
+        Check::problem(
+            "notifications",
+            Status::Warn,
+            "no notification daemon answers on D-Bus",
+            "start one (mako, dunst, swaync), or pass --silent",
+        )
+    }
+}
+
+/// Captures fall back to the defaults when config.toml doesn't load, so this is
+/// the one place that says so.
+pub(crate) fn config_file(path: &Path, loaded: &Result<config::Config>) -> Check {
+    const NAME: &str = "config";
+    match loaded {
+        Ok(_) if path.exists() => Check::ok(NAME, path.display().to_string()),
+        Ok(_) => Check::ok(
+            NAME,
+            format!("{} not found; using defaults", path.display()),
+        ),
+        Err(err) => Check::problem(
+            NAME,
+            Status::Fail,
+            format!("{}: {:#}; captures use the defaults", path.display(), err),
+            format!(
+                "fix the TOML in {}, or move it aside and run hyprshot-rs --init-config",
+                path.display()
+            ),
+        ),
+    }
+}
+
+fn screenshots_dir(config: &config::Config, debug: bool) -> Check {
+    const NAME: &str = "screenshots dir";
+    let dir = config::get_screenshots_dir(None, config, debug)
+        .map(|dir| crate::filename::expand_directory(&dir, Local::now()));
+    let dir = match dir {
+        Ok(dir) => dir,
+        Err(err) => {
+            return Check::problem(
+                NAME,
+                Status::Fail,
+                format!("{:#}", err),
+                "fix paths.screenshots_dir (hyprshot-rs --set paths.screenshots_dir DIR)",
+            );
+        }
+    };
+    if !dir.exists() {
+        let parent = dir.ancestors().find(|ancestor| ancestor.exists());
+        return match parent {
+            Some(parent) if config.paths.create_missing_dirs && is_writable(parent) => Check::ok(
+                NAME,
+                format!("{} (created on first capture)", dir.display()),
+            ),
+            _ if !config.paths.create_missing_dirs => Check::problem(
+                NAME,
+                Status::Fail,
+                format!("{} does not exist", dir.display()),
+                format!(
+                    "mkdir -p '{}', or set paths.create_missing_dirs = true",
+                    dir.display()
+                ),
+            ),
+            _ => Check::problem(
+                NAME,
+                Status::Fail,
+                format!("{} can't be created", dir.display()),
+                "point paths.screenshots_dir at a directory you own",
+            ),
+        };
+    }
+    if is_writable(&dir) {
+        Check::ok(NAME, dir.display().to_string())
+    } else {
+        Check::problem(
+            NAME,
+            Status::Fail,
+            format!("{} is not writable", dir.display()),
+            "fix its permissions or point paths.screenshots_dir elsewhere",
+        )
+    }
+}
+
+/// Write and remove a probe file; permission bits alone miss read-only mounts.
+fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".hyprshot-rs-doctor-{}", std::process::id()));
+    let writable = fs::write(&probe, b"").is_ok();
+    let _ = fs::remove_file(&probe);
+    writable
+}
+
+fn optional_tool(tool: &'static str, feature: &str, fix: &str) -> Check {
+    match find_in_path(tool) {
+        Some(path) => Check::ok(tool, path.display().to_string()),
+        None => Check::problem(
+            tool,
+            Status::Warn,
+            format!("not found (only needed for {})", feature),
+            fix,
+        ),
+    }
+}
+
+fn find_in_path(program: &str) -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+}
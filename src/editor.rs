@@ -0,0 +1,86 @@
+//! `--edit-with`: hand the capture to an annotator (swappy, satty, gimp, ...)
+//! through a private temp file and carry on with whatever it saved there.
+
+use anyhow::{Context, Result, bail};
+use std::io::Write;
+use std::process::Command;
+
+use crate::backend::{CaptureBackend, CapturedImage};
+use crate::config::PngCompression;
+
+/// The shell line for `command`: as written when it refers to
+/// `$HYPRSHOT_PATH`, otherwise with the file appended as the last argument.
+pub(crate) fn editor_command(command: &str) -> String {
+    if command.contains("HYPRSHOT_PATH") {
+        command.to_string()
+    } else {
+        format!("{} \"$HYPRSHOT_PATH\"", command)
+    }
+}
+
+/// Open `capture` in `command`, wait for it to exit and return the edited
+/// image. The temp file is created 0600 and removed afterwards.
+pub(crate) fn edit(
+    backend: &dyn CaptureBackend,
+    capture: CapturedImage,
+    command: &str,
+    debug: bool,
+) -> Result<CapturedImage> {
+    let mut file = tempfile::Builder::new()
+        .prefix("hyprshot-rs-edit-")
+        .suffix(".png")
+        .tempfile()
+        .context("Failed to create a temp file for the editor")?;
+    file.write_all(&backend.to_png(&capture, PngCompression::Fast)?)
+        .and_then(|_| file.flush())
+        .context("Failed to write the capture for the editor")?;
+
+    let line = editor_command(command);
+    if debug {
+        eprintln!("Editing {} with: {}", file.path().display(), line);
+    }
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(&line)
+        .env("HYPRSHOT_PATH", file.path())
+        .status()
+        .with_context(|| format!("Failed to run editor '{}'", command))?;
+    if !status.success() {
+        bail!(
+            "Editor '{}' failed ({}); nothing was saved",
+            command,
+            status
+        );
+    }
+
+    let bytes = std::fs::read(file.path()).context("Failed to read the edited capture")?;
+    decode_png(&bytes).context("The editor didn't leave a PNG behind")
+}
+
+/// Decode a PNG of any color type into the RGBA layout captures use.
+pub(crate) fn decode_png(bytes: &[u8]) -> Result<CapturedImage> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+    let pixels = &buffer[..info.buffer_size()];
+    let data = match info.color_type {
+        png::ColorType::Rgba => pixels.to_vec(),
+        png::ColorType::Rgb => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => pixels
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        png::ColorType::Indexed => bail!("Unexpanded palette image"),
+    };
+    Ok(CapturedImage {
+        data,
+        width: info.width,
+        height: info.height,
+    })
+}
@@ -0,0 +1,44 @@
+//! Exit codes, so keybind wrappers and scripts can tell a cancelled selection
+//! from a capture that failed.
+
+use std::fmt;
+
+use crate::selector::SelectorError;
+
+pub const CANCELLED: u8 = 10;
+/// `--selection-timeout` ran out before anything was selected.
+pub const TIMED_OUT: u8 = 11;
+pub const CAPTURE_FAILED: u8 = 20;
+pub const SAVE_FAILED: u8 = 30;
+/// Anything else: bad arguments or config, a missing tool, a selection error.
+pub const OTHER: u8 = 1;
+
+/// Context marking the stage an error came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stage {
+    Capture,
+    Save,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Capture => write!(f, "Capture failed"),
+            Self::Save => write!(f, "Failed to save the capture"),
+        }
+    }
+}
+
+/// The exit code for an error returned by `run`.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    let selector = err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<SelectorError>());
+    match err.downcast_ref::<Stage>() {
+        _ if matches!(selector, Some(SelectorError::Cancelled(_))) => CANCELLED,
+        _ if matches!(selector, Some(SelectorError::TimedOut(_))) => TIMED_OUT,
+        Some(Stage::Capture) => CAPTURE_FAILED,
+        Some(Stage::Save) => SAVE_FAILED,
+        None => OTHER,
+    }
+}
@@ -0,0 +1,825 @@
+//! Capture backend for compositors that implement `ext-image-copy-capture-v1`
+//! but not wlr-screencopy (which grim-rs needs).
+//!
+//! The same protocol copies a single window's own buffer for `--transparent`,
+//! through `ext-foreign-toplevel-image-capture-source`.
+
+use anyhow::{Context, Result};
+use std::os::fd::{AsRawFd, BorrowedFd};
+use wayland_client::{
+    Connection, Dispatch, EventQueue, QueueHandle, WEnum, event_created_child,
+    protocol::{
+        wl_buffer::WlBuffer,
+        wl_output,
+        wl_registry::WlRegistry,
+        wl_shm::{self, WlShm},
+        wl_shm_pool::WlShmPool,
+    },
+};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::{
+    ext_foreign_toplevel_handle_v1::{self, ExtForeignToplevelHandleV1},
+    ext_foreign_toplevel_list_v1::{self, ExtForeignToplevelListV1},
+};
+use wayland_protocols::ext::image_capture_source::v1::client::{
+    ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1,
+    ext_image_capture_source_v1::ExtImageCaptureSourceV1,
+    ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1,
+};
+use wayland_protocols::ext::image_copy_capture::v1::client::{
+    ext_image_copy_capture_frame_v1::{self, ExtImageCopyCaptureFrameV1},
+    ext_image_copy_capture_manager_v1::{ExtImageCopyCaptureManagerV1, Options},
+    ext_image_copy_capture_session_v1::{self, ExtImageCopyCaptureSessionV1},
+};
+#[cfg(feature = "dmabuf")]
+use wayland_protocols::wp::linux_dmabuf::zv1::client::{
+    zwp_linux_buffer_params_v1::{self, ZwpLinuxBufferParamsV1},
+    zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1,
+};
+
+use crate::backend::{self, CaptureBackend, CapturedImage};
+use crate::config::PngCompression;
+use crate::geometry::Geometry;
+use crate::outputs::{Outputs, delegate_outputs};
+use crate::shm::{SUPPORTED_FORMATS, to_rgba, untransform};
+
+#[derive(Default)]
+struct SessionInfo {
+    width: u32,
+    height: u32,
+    formats: Vec<wl_shm::Format>,
+    /// `dev_t` of the DRM node dmabufs must be allocated on.
+    #[cfg(feature = "dmabuf")]
+    dmabuf_device: Option<u64>,
+    /// DRM fourcc codes with their modifiers.
+    #[cfg(feature = "dmabuf")]
+    dmabuf_formats: Vec<(u32, Vec<u64>)>,
+    done: bool,
+    stopped: bool,
+}
+
+/// A window from ext-foreign-toplevel-list.
+struct Toplevel {
+    handle: ExtForeignToplevelHandleV1,
+    title: String,
+    app_id: String,
+}
+
+enum FrameStatus {
+    Pending,
+    Ready,
+    Failed(String),
+}
+
+struct State {
+    outputs: Outputs,
+    shm: Option<WlShm>,
+    /// Cleared after a dmabuf capture fails, so later ones go straight to shm.
+    #[cfg(feature = "dmabuf")]
+    dmabuf: Option<ZwpLinuxDmabufV1>,
+    source_manager: Option<ExtOutputImageCaptureSourceManagerV1>,
+    capture_manager: Option<ExtImageCopyCaptureManagerV1>,
+    toplevel_list: Option<ExtForeignToplevelListV1>,
+    toplevel_source_manager: Option<ExtForeignToplevelImageCaptureSourceManagerV1>,
+    toplevels: Vec<Toplevel>,
+    has_wlr_screencopy: bool,
+    session: SessionInfo,
+    frame: FrameStatus,
+    transform: wl_output::Transform,
+}
+
+impl AsMut<Outputs> for State {
+    fn as_mut(&mut self) -> &mut Outputs {
+        &mut self.outputs
+    }
+}
+
+delegate_outputs!(State);
+
+pub struct ExtCaptureBackend {
+    _conn: Connection,
+    queue: EventQueue<State>,
+    state: State,
+    paint_cursors: bool,
+    #[cfg(feature = "dmabuf")]
+    allocator: Option<crate::dmabuf::Allocator>,
+    debug: bool,
+}
+
+impl ExtCaptureBackend {
+    /// Connect only when this backend is needed: the compositor offers
+    /// ext-image-copy-capture but no wlr-screencopy. Returns `None` otherwise,
+    /// so the caller keeps using grim-rs.
+    pub fn connect_if_needed(paint_cursors: bool, debug: bool) -> Result<Option<Self>> {
+        let mut backend = Self::connect(paint_cursors)?;
+        backend.debug = debug;
+        let state = &backend.state;
+        // With dmabufs, ext-image-copy-capture skips the readback grim-rs's
+        // wlr-screencopy shm buffers need, so it wins when both exist.
+        #[cfg(feature = "dmabuf")]
+        let prefer_ext = state.dmabuf.is_some()
+            && state.source_manager.is_some()
+            && state.capture_manager.is_some();
+        #[cfg(not(feature = "dmabuf"))]
+        let prefer_ext = false;
+        if state.has_wlr_screencopy && !prefer_ext {
+            return Ok(None);
+        }
+        if state.source_manager.is_none() || state.capture_manager.is_none() {
+            if debug {
+                eprintln!("Neither wlr-screencopy nor ext-image-copy-capture is available");
+            }
+            return Ok(None);
+        }
+        backend.bind_outputs()?;
+
+        if debug && prefer_ext {
+            eprintln!("Capturing via ext-image-copy-capture into dmabufs");
+        } else if debug {
+            eprintln!("wlr-screencopy missing; capturing via ext-image-copy-capture");
+        }
+        Ok(Some(backend))
+    }
+
+    /// Connect for `--transparent`, whether or not wlr-screencopy exists.
+    pub fn connect_for_windows(paint_cursors: bool) -> Result<Self> {
+        let mut backend = Self::connect(paint_cursors)?;
+        let state = &backend.state;
+        if state.capture_manager.is_none()
+            || state.toplevel_source_manager.is_none()
+            || state.toplevel_list.is_none()
+        {
+            anyhow::bail!(
+                "--transparent needs ext-image-copy-capture, ext-foreign-toplevel-list and \
+ext-foreign-toplevel-image-capture-source, which this compositor doesn't offer"
+            );
+        }
+        backend.bind_outputs()?;
+        Ok(backend)
+    }
+
+    fn connect(paint_cursors: bool) -> Result<Self> {
+        let conn = Connection::connect_to_env().context("Failed to connect to Wayland")?;
+        let mut queue = conn.new_event_queue();
+        let qh = queue.handle();
+        let _registry = conn.display().get_registry(&qh, ());
+
+        let mut state = State {
+            outputs: Outputs::default(),
+            shm: None,
+            #[cfg(feature = "dmabuf")]
+            dmabuf: None,
+            source_manager: None,
+            capture_manager: None,
+            toplevel_list: None,
+            toplevel_source_manager: None,
+            toplevels: Vec::new(),
+            has_wlr_screencopy: false,
+            session: SessionInfo::default(),
+            frame: FrameStatus::Pending,
+            transform: wl_output::Transform::Normal,
+        };
+        queue
+            .roundtrip(&mut state)
+            .context("Failed to initialize Wayland globals")?;
+        if state.shm.is_none() {
+            return Err(anyhow::anyhow!("wl_shm not available"));
+        }
+        Ok(Self {
+            _conn: conn,
+            queue,
+            state,
+            paint_cursors,
+            #[cfg(feature = "dmabuf")]
+            allocator: None,
+            debug: false,
+        })
+    }
+
+    /// Ask for logical output geometry; the roundtrip also delivers the
+    /// toplevel list when it was bound.
+    fn bind_outputs(&mut self) -> Result<()> {
+        let qh = self.queue.handle();
+        self.state.outputs.request_logical(&qh);
+        self.queue
+            .roundtrip(&mut self.state)
+            .context("Failed to receive output geometry")?;
+        Ok(())
+    }
+
+    /// Capture one output at its native buffer size.
+    fn capture_output_pixels(&mut self, idx: usize) -> Result<CapturedImage> {
+        let qh = self.queue.handle();
+        let source_manager = self
+            .state
+            .source_manager
+            .clone()
+            .context("ext-output-image-capture-source is missing")?;
+        let output = self.state.outputs.entries[idx].output.clone();
+        let source = source_manager.create_source(&output, &qh, ());
+        let image = self.copy_source(&source, false);
+        source.destroy();
+        Ok(untransform(image?, self.state.transform))
+    }
+
+    /// Copy the window whose title and app id are `title` and `app_id` from
+    /// its own buffer, alpha included.
+    pub fn capture_window(&mut self, title: &str, app_id: &str) -> Result<CapturedImage> {
+        let matches: Vec<&Toplevel> = self
+            .state
+            .toplevels
+            .iter()
+            .filter(|toplevel| toplevel.title == title && toplevel.app_id == app_id)
+            .collect();
+        let handle = match matches.as_slice() {
+            [toplevel] => toplevel.handle.clone(),
+            [] => anyhow::bail!("Window '{}' ({}) isn't in the toplevel list", title, app_id),
+            _ => anyhow::bail!(
+                "{} windows are titled '{}' ({}); can't tell which to capture",
+                matches.len(),
+                title,
+                app_id
+            ),
+        };
+        let qh = self.queue.handle();
+        let source_manager = self
+            .state
+            .toplevel_source_manager
+            .clone()
+            .context("ext-foreign-toplevel-image-capture-source is missing")?;
+        let source = source_manager.create_source(&handle, &qh, ());
+        let image = self.copy_source(&source, true);
+        source.destroy();
+        image
+    }
+
+    fn copy_source(
+        &mut self,
+        source: &ExtImageCaptureSourceV1,
+        alpha: bool,
+    ) -> Result<CapturedImage> {
+        let qh = self.queue.handle();
+        let (Some(capture_manager), Some(shm)) =
+            (self.state.capture_manager.clone(), self.state.shm.clone())
+        else {
+            return Err(anyhow::anyhow!(
+                "ext-image-copy-capture globals are missing"
+            ));
+        };
+        let options = if self.paint_cursors {
+            Options::PaintCursors
+        } else {
+            Options::empty()
+        };
+        let session = capture_manager.create_session(source, options, &qh, ());
+        self.state.session = SessionInfo::default();
+        while !self.state.session.done && !self.state.session.stopped {
+            self.queue
+                .blocking_dispatch(&mut self.state)
+                .context("Failed to receive capture session constraints")?;
+        }
+        if self.state.session.stopped {
+            session.destroy();
+            return Err(anyhow::anyhow!(
+                "Capture session was stopped by the compositor"
+            ));
+        }
+
+        #[cfg(feature = "dmabuf")]
+        if let Some(image) = self.copy_dmabuf(&session, alpha) {
+            session.destroy();
+            return Ok(image);
+        }
+
+        let width = self.state.session.width;
+        let height = self.state.session.height;
+        let Some(format) = pick_format(&self.state.session.formats, alpha) else {
+            session.destroy();
+            return Err(anyhow::anyhow!(
+                "No supported shm format offered (got {:?})",
+                self.state.session.formats
+            ));
+        };
+
+        let stride = width as usize * 4;
+        let size = stride * height as usize;
+        let tmp_file = tempfile::NamedTempFile::new()
+            .context("Failed to create temporary file for shm buffer")?;
+        tmp_file
+            .as_file()
+            .set_len(size as u64)
+            .context("Failed to resize shm buffer file")?;
+        let mmap =
+            unsafe { memmap2::Mmap::map(&tmp_file).context("Failed to memory-map shm buffer")? };
+        let pool = shm.create_pool(
+            unsafe { BorrowedFd::borrow_raw(tmp_file.as_file().as_raw_fd()) },
+            size as i32,
+            &qh,
+            (),
+        );
+        let buffer = pool.create_buffer(
+            0,
+            width as i32,
+            height as i32,
+            stride as i32,
+            format,
+            &qh,
+            (),
+        );
+        pool.destroy();
+
+        self.state.frame = FrameStatus::Pending;
+        self.state.transform = wl_output::Transform::Normal;
+        let frame = session.create_frame(&qh, ());
+        frame.attach_buffer(&buffer);
+        frame.damage_buffer(0, 0, width as i32, height as i32);
+        frame.capture();
+        while matches!(self.state.frame, FrameStatus::Pending) {
+            self.queue
+                .blocking_dispatch(&mut self.state)
+                .context("Failed to wait for captured frame")?;
+        }
+
+        frame.destroy();
+        session.destroy();
+        buffer.destroy();
+
+        if let FrameStatus::Failed(reason) = &self.state.frame {
+            return Err(anyhow::anyhow!("Frame capture failed: {}", reason));
+        }
+
+        Ok(CapturedImage {
+            data: to_rgba(&mmap[..size], format),
+            width,
+            height,
+        })
+    }
+}
+
+#[cfg(feature = "dmabuf")]
+impl ExtCaptureBackend {
+    /// Copy a frame of `session` into a GBM buffer if the compositor offers
+    /// dmabufs in a format we convert. `None` means use shm; after a failure
+    /// dmabufs aren't tried again.
+    fn copy_dmabuf(
+        &mut self,
+        session: &ExtImageCopyCaptureSessionV1,
+        alpha: bool,
+    ) -> Option<CapturedImage> {
+        let linux_dmabuf = self.state.dmabuf.clone()?;
+        let (fourcc, format, modifiers) =
+            crate::dmabuf::pick_format(&self.state.session.dmabuf_formats, alpha)?;
+        match self.dmabuf_frame(session, &linux_dmabuf, fourcc, format, &modifiers) {
+            Ok(image) => Some(image),
+            Err(err) => {
+                if self.debug {
+                    eprintln!("dmabuf capture failed, falling back to shm: {:#}", err);
+                }
+                self.state.dmabuf = None;
+                None
+            }
+        }
+    }
+
+    fn dmabuf_frame(
+        &mut self,
+        session: &ExtImageCopyCaptureSessionV1,
+        linux_dmabuf: &ZwpLinuxDmabufV1,
+        fourcc: u32,
+        format: wl_shm::Format,
+        modifiers: &[u64],
+    ) -> Result<CapturedImage> {
+        use std::os::fd::AsFd;
+
+        let qh = self.queue.handle();
+        let width = self.state.session.width;
+        let height = self.state.session.height;
+        if self.allocator.is_none() {
+            let device = self
+                .state
+                .session
+                .dmabuf_device
+                .context("The compositor named no dmabuf device")?;
+            self.allocator = Some(crate::dmabuf::Allocator::open(device)?);
+        }
+        let allocator = self
+            .allocator
+            .as_ref()
+            .context("No GBM device to allocate on")?;
+        let gbm_buffer = allocator.allocate(width, height, fourcc, modifiers)?;
+        let modifier = gbm_buffer.modifier();
+        // Kept open until the compositor is done with the buffer.
+        let planes = gbm_buffer.planes()?;
+
+        let params = linux_dmabuf.create_params(&qh, ());
+        for (idx, (fd, offset, stride)) in planes.iter().enumerate() {
+            params.add(
+                fd.as_fd(),
+                idx as u32,
+                *offset,
+                *stride,
+                (modifier >> 32) as u32,
+                modifier as u32,
+            );
+        }
+        let buffer = params.create_immed(
+            width as i32,
+            height as i32,
+            fourcc,
+            zwp_linux_buffer_params_v1::Flags::empty(),
+            &qh,
+            (),
+        );
+        params.destroy();
+
+        self.state.frame = FrameStatus::Pending;
+        self.state.transform = wl_output::Transform::Normal;
+        let frame = session.create_frame(&qh, ());
+        frame.attach_buffer(&buffer);
+        frame.damage_buffer(0, 0, width as i32, height as i32);
+        frame.capture();
+        while matches!(self.state.frame, FrameStatus::Pending) {
+            self.queue
+                .blocking_dispatch(&mut self.state)
+                .context("Failed to wait for captured frame")?;
+        }
+        frame.destroy();
+        buffer.destroy();
+        drop(planes);
+
+        if let FrameStatus::Failed(reason) = &self.state.frame {
+            return Err(anyhow::anyhow!("Frame capture failed: {}", reason));
+        }
+        Ok(CapturedImage {
+            data: to_rgba(&gbm_buffer.read()?, format),
+            width,
+            height,
+        })
+    }
+}
+
+impl CaptureBackend for ExtCaptureBackend {
+    fn capture_region(&mut self, geometry: &Geometry) -> Result<CapturedImage> {
+        let targets: Vec<(usize, Geometry, Geometry)> = self
+            .state
+            .outputs
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, entry)| {
+                let bounds = entry.geometry()?;
+                let overlap = geometry.intersect(bounds)?;
+                Some((idx, bounds, overlap))
+            })
+            .collect();
+        if targets.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Region {} does not intersect any output",
+                geometry
+            ));
+        }
+
+        let mut captures = Vec::with_capacity(targets.len());
+        for (idx, bounds, overlap) in targets {
+            let pixels = self.capture_output_pixels(idx)?;
+            captures.push((bounds, overlap, pixels));
+        }
+
+        Ok(backend::composite_region(geometry, &captures))
+    }
+
+    fn capture_output(&mut self, name: &str) -> Result<CapturedImage> {
+        let idx = self
+            .state
+            .outputs
+            .entries
+            .iter()
+            .position(|entry| entry.name.as_deref() == Some(name))
+            .with_context(|| format!("Output '{}' not found", name))?;
+        self.capture_output_pixels(idx)
+    }
+
+    fn to_png(&self, image: &CapturedImage, compression: PngCompression) -> Result<Vec<u8>> {
+        crate::png_encoder::encode(image, compression.level())
+            .context("Failed to encode screenshot as PNG")
+    }
+}
+
+/// `--transparent`: every region capture is the one window's own buffer, so
+/// the geometry only decides which window it is, not what gets cropped.
+pub struct WindowBackend {
+    inner: ExtCaptureBackend,
+    title: String,
+    app_id: String,
+}
+
+impl WindowBackend {
+    pub fn new(title: &str, app_id: &str, paint_cursors: bool) -> Result<Self> {
+        Ok(Self {
+            inner: ExtCaptureBackend::connect_for_windows(paint_cursors)?,
+            title: title.to_string(),
+            app_id: app_id.to_string(),
+        })
+    }
+}
+
+impl CaptureBackend for WindowBackend {
+    fn capture_region(&mut self, _geometry: &Geometry) -> Result<CapturedImage> {
+        self.inner.capture_window(&self.title, &self.app_id)
+    }
+
+    fn capture_output(&mut self, name: &str) -> Result<CapturedImage> {
+        self.inner.capture_output(name)
+    }
+
+    fn to_png(&self, image: &CapturedImage, compression: PngCompression) -> Result<Vec<u8>> {
+        self.inner.to_png(image, compression)
+    }
+}
+
+/// Prefer 8-bit RGB formats we can convert without extra work; with
+/// `alpha` (window buffers), the ones that keep transparency first.
+fn pick_format(formats: &[wl_shm::Format], alpha: bool) -> Option<wl_shm::Format> {
+    SUPPORTED_FORMATS
+        .into_iter()
+        .filter(|f| alpha && matches!(f, wl_shm::Format::Argb8888 | wl_shm::Format::Abgr8888))
+        .chain(SUPPORTED_FORMATS)
+        .find(|f| formats.contains(f))
+}
+
+impl Dispatch<WlRegistry, ()> for State {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: wayland_client::protocol::wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wayland_client::protocol::wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+        {
+            if state.outputs.bind(registry, name, &interface, version, qh) {
+                return;
+            }
+            match interface.as_str() {
+                "wl_shm" => {
+                    state.shm = Some(registry.bind(name, 1, qh, ()));
+                }
+                // create_immed arrived in version 2.
+                #[cfg(feature = "dmabuf")]
+                "zwp_linux_dmabuf_v1" if version >= 2 => {
+                    state.dmabuf = Some(registry.bind(name, version.min(3), qh, ()));
+                }
+                "ext_output_image_capture_source_manager_v1" => {
+                    state.source_manager = Some(registry.bind(name, 1, qh, ()));
+                }
+                "ext_image_copy_capture_manager_v1" => {
+                    state.capture_manager = Some(registry.bind(name, 1, qh, ()));
+                }
+                "ext_foreign_toplevel_list_v1" => {
+                    state.toplevel_list = Some(registry.bind(name, 1, qh, ()));
+                }
+                "ext_foreign_toplevel_image_capture_source_manager_v1" => {
+                    state.toplevel_source_manager = Some(registry.bind(name, 1, qh, ()));
+                }
+                "zwlr_screencopy_manager_v1" => {
+                    state.has_wlr_screencopy = true;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Dispatch<ExtImageCopyCaptureSessionV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ExtImageCopyCaptureSessionV1,
+        event: ext_image_copy_capture_session_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_image_copy_capture_session_v1::Event::BufferSize { width, height } => {
+                state.session.width = width;
+                state.session.height = height;
+            }
+            ext_image_copy_capture_session_v1::Event::ShmFormat {
+                format: WEnum::Value(format),
+            } => {
+                state.session.formats.push(format);
+            }
+            #[cfg(feature = "dmabuf")]
+            ext_image_copy_capture_session_v1::Event::DmabufDevice { device } => {
+                state.session.dmabuf_device = crate::dmabuf::device_id(&device);
+            }
+            #[cfg(feature = "dmabuf")]
+            ext_image_copy_capture_session_v1::Event::DmabufFormat { format, modifiers } => {
+                state
+                    .session
+                    .dmabuf_formats
+                    .push((format, crate::dmabuf::modifiers(&modifiers)));
+            }
+            ext_image_copy_capture_session_v1::Event::Done => {
+                state.session.done = true;
+            }
+            ext_image_copy_capture_session_v1::Event::Stopped => {
+                state.session.stopped = true;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtImageCopyCaptureFrameV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ExtImageCopyCaptureFrameV1,
+        event: ext_image_copy_capture_frame_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_image_copy_capture_frame_v1::Event::Transform {
+                transform: WEnum::Value(transform),
+            } => {
+                state.transform = transform;
+            }
+            ext_image_copy_capture_frame_v1::Event::Ready => {
+                state.frame = FrameStatus::Ready;
+            }
+            ext_image_copy_capture_frame_v1::Event::Failed { reason } => {
+                state.frame = FrameStatus::Failed(format!("{:?}", reason));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlShm, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlShm,
+        _: wl_shm::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlShmPool, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlShmPool,
+        _: wayland_client::protocol::wl_shm_pool::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlBuffer, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlBuffer,
+        _: wayland_client::protocol::wl_buffer::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtOutputImageCaptureSourceManagerV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ExtOutputImageCaptureSourceManagerV1,
+        _: wayland_protocols::ext::image_capture_source::v1::client::ext_output_image_capture_source_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtImageCaptureSourceV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ExtImageCaptureSourceV1,
+        _: wayland_protocols::ext::image_capture_source::v1::client::ext_image_capture_source_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtImageCopyCaptureManagerV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ExtImageCopyCaptureManagerV1,
+        _: wayland_protocols::ext::image_copy_capture::v1::client::ext_image_copy_capture_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtForeignToplevelListV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ExtForeignToplevelListV1,
+        event: ext_foreign_toplevel_list_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel } = event {
+            state.toplevels.push(Toplevel {
+                handle: toplevel,
+                title: String::new(),
+                app_id: String::new(),
+            });
+        }
+    }
+
+    event_created_child!(State, ExtForeignToplevelListV1, [
+        ext_foreign_toplevel_list_v1::EVT_TOPLEVEL_OPCODE => (ExtForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ExtForeignToplevelHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ExtForeignToplevelHandleV1,
+        event: ext_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let ext_foreign_toplevel_handle_v1::Event::Closed = event {
+            state
+                .toplevels
+                .retain(|toplevel| &toplevel.handle != handle);
+            return;
+        }
+        let Some(toplevel) = state
+            .toplevels
+            .iter_mut()
+            .find(|toplevel| &toplevel.handle == handle)
+        else {
+            return;
+        };
+        match event {
+            ext_foreign_toplevel_handle_v1::Event::Title { title } => toplevel.title = title,
+            ext_foreign_toplevel_handle_v1::Event::AppId { app_id } => toplevel.app_id = app_id,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtForeignToplevelImageCaptureSourceManagerV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ExtForeignToplevelImageCaptureSourceManagerV1,
+        _: wayland_protocols::ext::image_capture_source::v1::client::ext_foreign_toplevel_image_capture_source_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+#[cfg(feature = "dmabuf")]
+impl Dispatch<ZwpLinuxDmabufV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ZwpLinuxDmabufV1,
+        _: wayland_protocols::wp::linux_dmabuf::zv1::client::zwp_linux_dmabuf_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+#[cfg(feature = "dmabuf")]
+impl Dispatch<ZwpLinuxBufferParamsV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ZwpLinuxBufferParamsV1,
+        _: zwp_linux_buffer_params_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
@@ -0,0 +1,257 @@
+//! Filename templates (`paths.filename_template`, `--filename-template`).
+//!
+//! Placeholders start with `%`: `%Y %m %d %H %M %S %ms` for the capture time,
+//! `%mode %output %title %class` for what was captured and `%counter` for the
+//! first number that doesn't overwrite an existing file. `%%` is a literal `%`.
+//! The extension is added from the image format, so templates leave it out.
+//!
+//! The screenshots directory takes the time placeholders too, e.g.
+//! `~/Pictures/%Y/%m`.
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use std::path::{Path, PathBuf};
+
+use crate::config::ConflictPolicy;
+
+/// Reproduces the historical `2024-05-06-070809-123_hyprshot` names.
+pub const DEFAULT_TEMPLATE: &str = "%Y-%m-%d-%H%M%S-%ms_hyprshot";
+
+/// Stand-in for a placeholder whose value isn't known for this capture.
+const UNKNOWN: &str = "unknown";
+/// Window titles can be long; keep names well under the 255-byte limit.
+const MAX_TITLE_CHARS: usize = 64;
+
+/// Longest names first, so `%mode` and `%ms` aren't read as `%m`.
+const PLACEHOLDERS: [&str; 12] = [
+    "counter", "output", "title", "class", "mode", "ms", "Y", "m", "d", "H", "M", "S",
+];
+
+/// The placeholders a directory may use: the capture time.
+const TIME_PLACEHOLDERS: [&str; 7] = ["ms", "Y", "m", "d", "H", "M", "S"];
+
+/// Values substituted into a template.
+#[derive(Debug, Clone)]
+pub struct Fields {
+    pub now: DateTime<Local>,
+    /// Mode as given to `-m`, e.g. `region` or `active-window`.
+    pub mode: Option<String>,
+    pub output: Option<String>,
+    pub title: Option<String>,
+    pub class: Option<String>,
+    pub counter: u32,
+}
+
+impl Fields {
+    pub fn new(now: DateTime<Local>) -> Self {
+        Self {
+            now,
+            mode: None,
+            output: None,
+            title: None,
+            class: None,
+            counter: 1,
+        }
+    }
+}
+
+enum Piece<'a> {
+    Text(&'a str),
+    Placeholder(&'static str),
+}
+
+fn parse(template: &str) -> Result<Vec<Piece<'_>>> {
+    let mut pieces = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('%') {
+        if start > 0 {
+            pieces.push(Piece::Text(&rest[..start]));
+        }
+        let after = &rest[start + 1..];
+        if let Some(literal) = after.strip_prefix('%') {
+            pieces.push(Piece::Text("%"));
+            rest = literal;
+            continue;
+        }
+        let Some(name) = PLACEHOLDERS.iter().find(|name| after.starts_with(*name)) else {
+            bail!(
+                "Unknown placeholder '%{}' in filename template '{}' (use %% for a literal %)",
+                after.chars().next().map(String::from).unwrap_or_default(),
+                template
+            );
+        };
+        pieces.push(Piece::Placeholder(name));
+        rest = &after[name.len()..];
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest));
+    }
+    Ok(pieces)
+}
+
+/// Reject unknown placeholders and templates that can't name a file.
+pub fn validate(template: &str) -> Result<()> {
+    parse(template)?;
+    if template.trim().is_empty() {
+        bail!("Filename template is empty");
+    }
+    if template.contains('/') {
+        bail!(
+            "Filename template '{}' can't contain '/'; set the directory with -o or paths.screenshots_dir",
+            template
+        );
+    }
+    Ok(())
+}
+
+/// Whether `template` contains `%name`.
+pub fn uses(template: &str, name: &str) -> bool {
+    parse(template).is_ok_and(|pieces| {
+        pieces
+            .iter()
+            .any(|piece| matches!(piece, Piece::Placeholder(p) if *p == name))
+    })
+}
+
+/// Expand `template` without an extension. Unknown placeholders are kept as
+/// written; [`validate`] reports them up front.
+pub fn expand(template: &str, fields: &Fields) -> String {
+    let Ok(pieces) = parse(template) else {
+        return template.to_string();
+    };
+    let mut out = String::new();
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) => out.push_str(text),
+            Piece::Placeholder(name) => out.push_str(&value(name, fields)),
+        }
+    }
+    out
+}
+
+/// `template` expanded with the `extension`, as a name that is free in `dir`:
+/// with `%counter`, the counter is raised past names that are taken.
+pub fn free_name(dir: &Path, template: &str, extension: &str, fields: &mut Fields) -> String {
+    let name = |fields: &Fields| format!("{}.{}", expand(template, fields), extension);
+    let mut filename = name(fields);
+    if uses(template, "counter") {
+        while dir.join(&filename).exists() {
+            fields.counter += 1;
+            filename = name(fields);
+        }
+    }
+    filename
+}
+
+/// Expand the time placeholders in a screenshots directory (`-o`,
+/// `paths.screenshots_dir` or `HYPRSHOT_DIR`). Directories named before they
+/// existed may hold a `%` of their own (`~/shots%20`, `~/100%done`), so any
+/// `%` that isn't a time placeholder followed by a non-letter is kept as
+/// written, and so are the per-capture placeholders like `%mode`.
+pub fn expand_directory(dir: &Path, now: DateTime<Local>) -> PathBuf {
+    let text = dir.to_string_lossy();
+    if !text.contains('%') {
+        return dir.to_path_buf();
+    }
+    let fields = Fields::new(now);
+    let mut out = String::new();
+    let mut rest: &str = &text;
+    while let Some(start) = rest.find('%') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(literal) = after.strip_prefix('%') {
+            out.push('%');
+            rest = literal;
+            continue;
+        }
+        let placeholder = TIME_PLACEHOLDERS.iter().find(|name| {
+            after
+                .strip_prefix(*name)
+                .is_some_and(|next| !next.starts_with(|c: char| c.is_alphanumeric()))
+        });
+        match placeholder {
+            Some(name) => {
+                out.push_str(&value(name, &fields));
+                rest = &after[name.len()..];
+            }
+            None => {
+                out.push('%');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    PathBuf::from(out)
+}
+
+fn value(name: &str, fields: &Fields) -> String {
+    let known = |value: &Option<String>| {
+        value
+            .as_deref()
+            .map(sanitize)
+            .filter(|value| !value.is_empty())
+            .unwrap_or_else(|| UNKNOWN.to_string())
+    };
+    match name {
+        "Y" => fields.now.format("%Y").to_string(),
+        "m" => fields.now.format("%m").to_string(),
+        "d" => fields.now.format("%d").to_string(),
+        "H" => fields.now.format("%H").to_string(),
+        "M" => fields.now.format("%M").to_string(),
+        "S" => fields.now.format("%S").to_string(),
+        "ms" => format!("{:03}", fields.now.timestamp_subsec_millis()),
+        "mode" => known(&fields.mode),
+        "output" => known(&fields.output),
+        "title" => known(&fields.title)
+            .chars()
+            .take(MAX_TITLE_CHARS)
+            .collect::<String>()
+            .trim_end()
+            .to_string(),
+        "class" => known(&fields.class),
+        "counter" => fields.counter.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Make a window title or class safe as part of a filename: path separators
+/// and control characters become `_`.
+pub(crate) fn sanitize(value: &str) -> String {
+    value
+        .trim()
+        .chars()
+        .map(|c| {
+            if c == '/' || c == '\\' || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Where to save when `path` may already exist: `path` itself, the first free
+/// `stem-N.ext` next to it, or an error, as `policy` says.
+pub fn resolve_conflict(path: &Path, policy: ConflictPolicy) -> Result<PathBuf> {
+    if !path.exists() {
+        return Ok(path.to_path_buf());
+    }
+    match policy {
+        ConflictPolicy::Overwrite => Ok(path.to_path_buf()),
+        ConflictPolicy::Fail => bail!(
+            "'{}' already exists (paths.on_conflict = \"fail\")",
+            path.display()
+        ),
+        ConflictPolicy::Increment => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let extension = path
+                .extension()
+                .map(|ext| format!(".{}", ext.to_string_lossy()))
+                .unwrap_or_default();
+            (1u32..)
+                .map(|n| path.with_file_name(format!("{}-{}{}", stem, n, extension)))
+                .find(|candidate| !candidate.exists())
+                .ok_or_else(|| anyhow::anyhow!("No free name next to '{}'", path.display()))
+        }
+    }
+}
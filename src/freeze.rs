@@ -1,10 +1,32 @@
 use anyhow::{Context, Result};
 
+use crate::backend::CapturedImage;
+use crate::config::FreezeHintPosition;
+use crate::geometry::Geometry;
+
+/// An output as it was frozen, before the hint and outlines were drawn on it.
+pub struct FrozenFrame {
+    pub output: String,
+    /// Logical bounds of the output.
+    pub bounds: Geometry,
+    pub image: CapturedImage,
+}
+
+/// Usage hint drawn onto the frozen screen.
+pub struct FreezeHint {
+    pub text: String,
+    pub position: FreezeHintPosition,
+    /// Logical geometry of the output to draw on; the first frozen output when unknown.
+    pub output: Option<Geometry>,
+}
+
 #[cfg(all(target_os = "linux", feature = "freeze"))]
 mod imp {
     use super::*;
+    use crate::outputs::{OutputEntry, Outputs, delegate_outputs};
     use grim_rs::Grim;
     use std::{
+        io::{BufWriter, ErrorKind, Write},
         os::fd::{AsRawFd, BorrowedFd},
         sync::mpsc,
         thread,
@@ -15,8 +37,6 @@ mod imp {
         protocol::{
             wl_buffer::WlBuffer,
             wl_compositor::WlCompositor,
-            wl_output::Mode as WlOutputMode,
-            wl_output::WlOutput,
             wl_region::WlRegion,
             wl_registry::WlRegistry,
             wl_shm::{self, WlShm},
@@ -24,8 +44,8 @@ mod imp {
             wl_surface::WlSurface,
         },
     };
-    use wayland_protocols::xdg::xdg_output::zv1::client::{
-        zxdg_output_manager_v1::ZxdgOutputManagerV1, zxdg_output_v1::ZxdgOutputV1,
+    use wayland_protocols::wp::viewporter::client::{
+        wp_viewport::WpViewport, wp_viewporter::WpViewporter,
     };
     use wayland_protocols_wlr::layer_shell::v1::client::{
         zwlr_layer_shell_v1::{Layer, ZwlrLayerShellV1},
@@ -35,9 +55,15 @@ mod imp {
     pub struct FreezeGuard {
         stop_tx: mpsc::Sender<()>,
         join: Option<thread::JoinHandle<Result<()>>>,
+        frames: Vec<FrozenFrame>,
     }
 
     impl FreezeGuard {
+        /// The frames shown by the overlay; empty when freeze was disabled.
+        pub fn take_frames(&mut self) -> Vec<FrozenFrame> {
+            std::mem::take(&mut self.frames)
+        }
+
         pub fn stop(mut self) -> Result<()> {
             let _ = self.stop_tx.send(());
             if let Some(join) = self.join.take() {
@@ -70,31 +96,41 @@ mod imp {
         height: u32,
     }
 
-    pub fn start_freeze(selected_output: Option<&str>, debug: bool) -> Result<FreezeGuard> {
+    pub fn start_freeze(
+        selected_output: Option<&str>,
+        hint: Option<FreezeHint>,
+        debug_boxes: Vec<Geometry>,
+        debug: bool,
+    ) -> Result<FreezeGuard> {
         let (stop_tx, stop_rx) = mpsc::channel();
         let (ready_tx, ready_rx) = mpsc::channel();
 
         let selected_output = selected_output.map(str::to_string);
         let mut join = Some(thread::spawn(move || {
-            run_freeze(selected_output, stop_rx, ready_tx, debug)
+            run_freeze(selected_output, hint, debug_boxes, stop_rx, ready_tx, debug)
         }));
         const FREEZE_READY_TIMEOUT: Duration = Duration::from_secs(5);
 
         match ready_rx.recv_timeout(FREEZE_READY_TIMEOUT) {
-            Ok(Ok(())) => {
+            Ok(Ok(frames)) => {
                 if debug {
                     eprintln!("Freeze overlay initialized");
                 }
-                Ok(FreezeGuard { stop_tx, join })
+                Ok(FreezeGuard {
+                    stop_tx,
+                    join,
+                    frames,
+                })
             }
             Ok(Err(err)) => {
-                eprintln!("Freeze disabled: {}", err);
+                eprintln!("Freeze disabled: {:#}", err);
                 if let Some(join) = join.take() {
                     let _ = join.join();
                 }
                 Ok(FreezeGuard {
                     stop_tx,
                     join: None,
+                    frames: Vec::new(),
                 })
             }
             Err(mpsc::RecvTimeoutError::Timeout) => {
@@ -125,46 +161,38 @@ mod imp {
         }
     }
 
-    #[derive(Debug)]
-    struct OutputKey(usize);
-
     #[derive(Debug)]
     struct SurfaceKey(usize);
 
-    struct OutputEntry {
-        output: WlOutput,
-        name: Option<String>,
-        xdg_output: Option<ZxdgOutputV1>,
-        pos_x: Option<i32>,
-        pos_y: Option<i32>,
-        mode_width: Option<i32>,
-        mode_height: Option<i32>,
-        scale: i32,
-        logical_x: Option<i32>,
-        logical_y: Option<i32>,
-        logical_width: Option<i32>,
-        logical_height: Option<i32>,
-    }
-
     struct SurfaceEntry {
         surface: WlSurface,
         layer_surface: ZwlrLayerSurfaceV1,
         buffer: WlBuffer,
         _input_region: WlRegion,
         _tmp: tempfile::NamedTempFile,
-        _mmap: memmap2::MmapMut,
         configured: bool,
+        /// Maps the native-resolution buffer onto the logical surface size, so
+        /// fractional scales need no buffer-scale guess.
+        viewport: Option<WpViewport>,
     }
 
     struct State {
         compositor: Option<WlCompositor>,
         shm: Option<WlShm>,
         layer_shell: Option<ZwlrLayerShellV1>,
-        xdg_output_manager: Option<ZxdgOutputManagerV1>,
-        outputs: Vec<OutputEntry>,
+        viewporter: Option<WpViewporter>,
+        outputs: Outputs,
         surfaces: Vec<SurfaceEntry>,
     }
 
+    impl AsMut<Outputs> for State {
+        fn as_mut(&mut self) -> &mut Outputs {
+            &mut self.outputs
+        }
+    }
+
+    delegate_outputs!(State);
+
     impl Dispatch<WlRegistry, ()> for State {
         fn event(
             state: &mut Self,
@@ -180,6 +208,9 @@ mod imp {
                 version,
             } = event
             {
+                if state.outputs.bind(registry, name, &interface, version, qh) {
+                    return;
+                }
                 match interface.as_str() {
                     "wl_compositor" => {
                         state.compositor = Some(registry.bind(name, version.min(5), qh, ()));
@@ -190,32 +221,8 @@ mod imp {
                     "zwlr_layer_shell_v1" => {
                         state.layer_shell = Some(registry.bind(name, version.min(4), qh, ()));
                     }
-                    "wl_output" => {
-                        let idx = state.outputs.len();
-                        let output = registry.bind::<WlOutput, _, _>(
-                            name,
-                            version.min(4),
-                            qh,
-                            OutputKey(idx),
-                        );
-                        state.outputs.push(OutputEntry {
-                            output,
-                            name: None,
-                            xdg_output: None,
-                            pos_x: None,
-                            pos_y: None,
-                            mode_width: None,
-                            mode_height: None,
-                            scale: 1,
-                            logical_x: None,
-                            logical_y: None,
-                            logical_width: None,
-                            logical_height: None,
-                        });
-                    }
-                    "zxdg_output_manager_v1" => {
-                        state.xdg_output_manager =
-                            Some(registry.bind(name, version.min(3), qh, ()));
+                    "wp_viewporter" => {
+                        state.viewporter = Some(registry.bind(name, version.min(1), qh, ()));
                     }
                     _ => {}
                 }
@@ -223,80 +230,6 @@ mod imp {
         }
     }
 
-    impl Dispatch<WlOutput, OutputKey> for State {
-        fn event(
-            state: &mut Self,
-            _: &WlOutput,
-            event: wayland_client::protocol::wl_output::Event,
-            data: &OutputKey,
-            _: &Connection,
-            _: &QueueHandle<Self>,
-        ) {
-            let Some(entry) = state.outputs.get_mut(data.0) else {
-                return;
-            };
-            match event {
-                wayland_client::protocol::wl_output::Event::Geometry { x, y, .. } => {
-                    entry.pos_x = Some(x);
-                    entry.pos_y = Some(y);
-                }
-                wayland_client::protocol::wl_output::Event::Mode {
-                    flags,
-                    width,
-                    height,
-                    ..
-                } => {
-                    let is_current = match flags {
-                        wayland_client::WEnum::Value(f) => f.contains(WlOutputMode::Current),
-                        wayland_client::WEnum::Unknown(_) => false,
-                    };
-                    if is_current {
-                        entry.mode_width = Some(width);
-                        entry.mode_height = Some(height);
-                    }
-                }
-                wayland_client::protocol::wl_output::Event::Scale { factor } => {
-                    entry.scale = factor.max(1);
-                }
-                wayland_client::protocol::wl_output::Event::Name { name } => {
-                    entry.name = Some(name);
-                }
-                _ => {}
-            }
-        }
-    }
-
-    impl Dispatch<ZxdgOutputV1, OutputKey> for State {
-        fn event(
-            state: &mut Self,
-            _: &ZxdgOutputV1,
-            event: wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1::Event,
-            data: &OutputKey,
-            _: &Connection,
-            _: &QueueHandle<Self>,
-        ) {
-            let Some(entry) = state.outputs.get_mut(data.0) else {
-                return;
-            };
-            match event {
-                wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1::Event::LogicalPosition { x, y } => {
-                    entry.logical_x = Some(x);
-                    entry.logical_y = Some(y);
-                }
-                wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1::Event::LogicalSize { width, height } => {
-                    entry.logical_width = Some(width);
-                    entry.logical_height = Some(height);
-                }
-                wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1::Event::Name {
-                    name,
-                } => {
-                    entry.name = Some(name);
-                }
-                _ => {}
-            }
-        }
-    }
-
     impl Dispatch<ZwlrLayerSurfaceV1, SurfaceKey> for State {
         fn event(
             state: &mut Self,
@@ -310,9 +243,16 @@ mod imp {
                 match event {
                     wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1::Event::Configure {
                         serial,
-                        ..
+                        width,
+                        height,
                     } => {
                         surface.ack_configure(serial);
+                        if let Some(viewport) = &entry.viewport
+                            && width > 0
+                            && height > 0
+                        {
+                            viewport.set_destination(width as i32, height as i32);
+                        }
                         entry.configured = true;
                     }
                     wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1::Event::Closed => {
@@ -408,11 +348,23 @@ mod imp {
         }
     }
 
-    impl Dispatch<ZxdgOutputManagerV1, ()> for State {
+    impl Dispatch<WpViewporter, ()> for State {
         fn event(
             _: &mut Self,
-            _: &ZxdgOutputManagerV1,
-            _: wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_manager_v1::Event,
+            _: &WpViewporter,
+            _: wayland_protocols::wp::viewporter::client::wp_viewporter::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<WpViewport, ()> for State {
+        fn event(
+            _: &mut Self,
+            _: &WpViewport,
+            _: wayland_protocols::wp::viewporter::client::wp_viewport::Event,
             _: &(),
             _: &Connection,
             _: &QueueHandle<Self>,
@@ -422,8 +374,10 @@ mod imp {
 
     fn run_freeze(
         selected_output: Option<String>,
+        hint: Option<FreezeHint>,
+        debug_boxes: Vec<Geometry>,
         stop_rx: mpsc::Receiver<()>,
-        ready_tx: mpsc::Sender<Result<()>>,
+        ready_tx: mpsc::Sender<Result<Vec<FrozenFrame>>>,
         debug: bool,
     ) -> Result<()> {
         if debug {
@@ -439,8 +393,8 @@ mod imp {
             compositor: None,
             shm: None,
             layer_shell: None,
-            xdg_output_manager: None,
-            outputs: Vec::new(),
+            viewporter: None,
+            outputs: Outputs::default(),
             surfaces: Vec::new(),
         };
 
@@ -451,11 +405,7 @@ mod imp {
             eprintln!("Freeze: Wayland globals initialized");
         }
 
-        if let Some(manager) = &state.xdg_output_manager {
-            for (idx, entry) in state.outputs.iter_mut().enumerate() {
-                let xdg_output = manager.get_xdg_output(&entry.output, &qh, OutputKey(idx));
-                entry.xdg_output = Some(xdg_output);
-            }
+        if state.outputs.request_logical(&qh) {
             event_queue
                 .roundtrip(&mut state)
                 .context("Failed to receive output names")?;
@@ -481,7 +431,7 @@ mod imp {
                     "Freeze is disabled: compositor does not support wlr-layer-shell. \
 Check the support for this protocol on Hyprland/Sway/River/Wayfire."
                 );
-                let _ = ready_tx.send(Ok(()));
+                let _ = ready_tx.send(Ok(Vec::new()));
                 return Ok(());
             }
         };
@@ -503,7 +453,7 @@ Check the support for this protocol on Hyprland/Sway/River/Wayfire."
                     "Freeze is disabled: compositor does not support wlr-screencopy. \
         Check the support for this protocol on Hyprland/Sway/River/Wayfire."
                 );
-                let _ = ready_tx.send(Ok(()));
+                let _ = ready_tx.send(Ok(Vec::new()));
                 return Ok(());
             }
             Err(err) => {
@@ -513,7 +463,7 @@ Check the support for this protocol on Hyprland/Sway/River/Wayfire."
         };
 
         if stop_rx.try_recv().is_ok() {
-            let _ = ready_tx.send(Ok(()));
+            let _ = ready_tx.send(Ok(Vec::new()));
             return Ok(());
         }
 
@@ -536,7 +486,7 @@ Check the support for this protocol on Hyprland/Sway/River/Wayfire."
             });
         }
 
-        let mapping = match_outputs(&state.outputs, &metas, selected_output.as_deref())?;
+        let mapping = match_outputs(&state.outputs.entries, &metas, selected_output.as_deref())?;
         if mapping.iter().all(|m| m.is_none()) {
             let _ = ready_tx.send(Err(anyhow::anyhow!(
                 "No matching outputs found for freeze overlay"
@@ -546,39 +496,92 @@ Check the support for this protocol on Hyprland/Sway/River/Wayfire."
         if debug {
             eprintln!("Freeze: output mapping prepared");
         }
+        // grim-rs hands 10-bit buffers back unconverted.
+        let deep_color: Vec<_> = crate::shm::probe_outputs()
+            .map(|outputs| outputs.into_iter().filter(|o| o.is_deep_color()).collect())
+            .unwrap_or_default();
 
+        let mut hint_drawn = false;
+        let mut frames = Vec::new();
         for (idx, meta_index) in mapping.into_iter().enumerate() {
             if stop_rx.try_recv().is_ok() {
-                let _ = ready_tx.send(Ok(()));
+                let _ = ready_tx.send(Ok(Vec::new()));
                 return Ok(());
             }
             let Some(meta_index) = meta_index else {
                 continue;
             };
-            let output = &state.outputs[idx];
+            let output = &state.outputs.entries[idx];
             let meta = &metas[meta_index];
 
-            let capture = grim
-                .capture_output(&meta.name)
-                .with_context(|| format!("Failed to capture output '{}'", meta.name))?;
+            let frozen = match deep_color.iter().find(|o| o.name == meta.name) {
+                Some(output) => crate::backend::capture_outputs(&mut grim, &[output], false)
+                    .and_then(|mut captures| captures.pop().context("No capture returned")),
+                None => grim
+                    .capture_output(&meta.name)
+                    .map(|capture| CapturedImage {
+                        width: capture.width(),
+                        height: capture.height(),
+                        data: capture.into_data(),
+                    })
+                    .map_err(anyhow::Error::from),
+            }
+            .with_context(|| format!("Failed to capture output '{}'", meta.name))?;
+            let (width, height) = (frozen.width, frozen.height);
 
             if debug {
-                eprintln!(
-                    "Freeze capture: {} ({}x{})",
-                    meta.name,
-                    capture.width(),
-                    capture.height()
-                );
+                eprintln!("Freeze capture: {} ({}x{})", meta.name, width, height);
             }
 
-            let width = capture.width();
-            let height = capture.height();
-            let capture = CaptureImage {
-                data: capture.into_data(),
+            let mut capture = CaptureImage {
+                data: frozen.data.clone(),
                 width,
                 height,
             };
 
+            // Outlines share the overlay's logical space with the selector boxes,
+            // so a misaligned freeze shows up as boxes off their windows.
+            let output_geom = Geometry {
+                x: meta.geom.0,
+                y: meta.geom.1,
+                width: meta.geom.2.max(1),
+                height: meta.geom.3.max(1),
+            };
+            let outline_width =
+                (width as f64 / output_geom.width as f64).round().max(1.0) as u32 * 2;
+            for rect in &debug_boxes {
+                if let Some(rect) =
+                    crate::overlay_text::logical_to_buffer(*rect, output_geom, width, height)
+                {
+                    crate::overlay_text::draw_outline(
+                        &mut capture.data,
+                        width,
+                        height,
+                        rect,
+                        outline_width,
+                    );
+                }
+            }
+
+            if let Some(hint) = hint.as_ref()
+                && !hint_drawn
+                && hint.output.is_none_or(|target| {
+                    geometry_close(meta.geom, (target.x, target.y, target.width, target.height))
+                })
+            {
+                // Two buffer pixels per font pixel at scale 1, more on HiDPI outputs.
+                let buffer_scale = (width as f64 / meta.geom.2.max(1) as f64).round().max(1.0);
+                crate::overlay_text::draw_hint(
+                    &mut capture.data,
+                    width,
+                    height,
+                    &hint.text,
+                    hint.position,
+                    buffer_scale as u32 * 2,
+                );
+                hint_drawn = true;
+            }
+
             let surface_idx = state.surfaces.len();
             let surface = compositor.create_surface(&qh, ());
             let layer_surface = layer_shell.get_layer_surface(
@@ -594,16 +597,31 @@ Check the support for this protocol on Hyprland/Sway/River/Wayfire."
             layer_surface.set_keyboard_interactivity(KeyboardInteractivity::None);
             layer_surface.set_exclusive_zone(-1);
 
-            if let Some((logical_w, logical_h)) = output_logical_size(output)
+            if let Some((logical_w, logical_h)) = output.logical_size()
                 && logical_w > 0
                 && logical_h > 0
             {
                 layer_surface.set_size(logical_w as u32, logical_h as u32);
             }
 
-            let buffer_scale = output_buffer_scale(output);
-            if buffer_scale > 1 {
-                surface.set_buffer_scale(buffer_scale);
+            // With a viewport the buffer keeps scale 1 and is mapped onto the
+            // configured logical size; without one, only integer scales line up.
+            let viewport = state
+                .viewporter
+                .as_ref()
+                .map(|viewporter| viewporter.get_viewport(&surface, &qh, ()));
+            if let Some(viewport) = &viewport
+                && let Some((logical_w, logical_h)) = output.logical_size()
+                && logical_w > 0
+                && logical_h > 0
+            {
+                viewport.set_destination(logical_w, logical_h);
+            }
+            if viewport.is_none() {
+                let buffer_scale = output_buffer_scale(output);
+                if buffer_scale > 1 {
+                    surface.set_buffer_scale(buffer_scale);
+                }
             }
 
             let input_region = compositor.create_region(&qh, ());
@@ -611,12 +629,18 @@ Check the support for this protocol on Hyprland/Sway/River/Wayfire."
 
             surface.commit();
 
-            let (buffer, tmp, mmap) = create_buffer(&shm, &qh, &capture).with_context(|| {
-                format!(
-                    "Failed to create buffer for output '{}'",
-                    output.name.as_deref().unwrap_or(&meta.name)
-                )
-            })?;
+            // Without a buffer there is nothing to show; disable freeze instead
+            // of failing the capture.
+            let (buffer, tmp) = match create_buffer(&shm, &qh, &capture) {
+                Ok(created) => created,
+                Err(err) => {
+                    let _ = ready_tx.send(Err(err.context(format!(
+                        "Failed to create buffer for output '{}'",
+                        output.name.as_deref().unwrap_or(&meta.name)
+                    ))));
+                    return Ok(());
+                }
+            };
 
             state.surfaces.push(SurfaceEntry {
                 surface,
@@ -624,8 +648,13 @@ Check the support for this protocol on Hyprland/Sway/River/Wayfire."
                 buffer,
                 _input_region: input_region,
                 _tmp: tmp,
-                _mmap: mmap,
                 configured: false,
+                viewport,
+            });
+            frames.push(FrozenFrame {
+                output: meta.name.clone(),
+                bounds: output_geom,
+                image: frozen,
             });
         }
 
@@ -643,6 +672,9 @@ Check the support for this protocol on Hyprland/Sway/River/Wayfire."
             .roundtrip(&mut state)
             .context("Failed to configure freeze surfaces")?;
 
+        if debug && state.viewporter.is_none() {
+            eprintln!("Freeze: wp_viewporter unavailable; using buffer scale");
+        }
         for entry in &state.surfaces {
             entry.surface.attach(Some(&entry.buffer), 0, 0);
             entry.surface.commit();
@@ -652,26 +684,146 @@ Check the support for this protocol on Hyprland/Sway/River/Wayfire."
             eprintln!("Freeze: overlay committed");
         }
 
-        let _ = ready_tx.send(Ok(()));
+        let _ = ready_tx.send(Ok(frames));
 
-        loop {
+        // A failed roundtrip means the connection is gone (e.g. compositor restart);
+        // stop here and report it through FreezeGuard::stop instead of spinning.
+        let result = loop {
             if stop_rx.try_recv().is_ok() {
-                break;
+                break Ok(());
             }
-            event_queue.roundtrip(&mut state).ok();
-        }
+            if let Err(err) = event_queue.roundtrip(&mut state) {
+                break Err(
+                    anyhow::Error::new(err).context("Freeze overlay lost the Wayland connection")
+                );
+            }
+        };
 
         if debug {
-            eprintln!("Freeze overlay stopped");
+            match &result {
+                Ok(()) => eprintln!("Freeze overlay stopped"),
+                Err(err) => eprintln!("Freeze overlay ended early: {:#}", err),
+            }
         }
 
         for entry in state.surfaces {
+            if let Some(viewport) = entry.viewport {
+                viewport.destroy();
+            }
             entry.layer_surface.destroy();
             entry.surface.destroy();
             entry.buffer.destroy();
         }
         drop(registry);
 
+        result
+    }
+
+    /// How long `flash` keeps the outputs white.
+    const FLASH_DURATION: Duration = Duration::from_millis(120);
+    /// Opacity of the flash, 0-255.
+    const FLASH_ALPHA: u8 = 170;
+
+    /// Briefly cover the outputs showing `area` with translucent white, the
+    /// way a camera flash confirms a shot. Uses the freeze overlay's
+    /// layer-shell surfaces, so the same compositors support it.
+    pub fn flash(area: Geometry, debug: bool) -> Result<()> {
+        let conn = Connection::connect_to_env().context("Failed to connect to Wayland")?;
+        let mut event_queue = conn.new_event_queue();
+        let qh = event_queue.handle();
+        let registry = conn.display().get_registry(&qh, ());
+        let mut state = State {
+            compositor: None,
+            shm: None,
+            layer_shell: None,
+            viewporter: None,
+            outputs: Outputs::default(),
+            surfaces: Vec::new(),
+        };
+        event_queue
+            .roundtrip(&mut state)
+            .context("Failed to initialize Wayland globals")?;
+        if state.outputs.request_logical(&qh) {
+            event_queue
+                .roundtrip(&mut state)
+                .context("Failed to receive output geometry")?;
+        }
+        let compositor = state
+            .compositor
+            .clone()
+            .context("wl_compositor not available")?;
+        let shm = state.shm.clone().context("wl_shm not available")?;
+        let layer_shell = state
+            .layer_shell
+            .clone()
+            .context("The compositor does not support wlr-layer-shell")?;
+
+        for idx in 0..state.outputs.entries.len() {
+            let output = &state.outputs.entries[idx];
+            let Some((x, y, width, height)) = output_geometry(output) else {
+                continue;
+            };
+            let Ok(bounds) = Geometry::new(x, y, width, height) else {
+                continue;
+            };
+            if bounds.intersect(area).is_none() {
+                continue;
+            }
+            // Premultiplied, so white at FLASH_ALPHA is FLASH_ALPHA in every channel.
+            let white = CaptureImage {
+                data: vec![FLASH_ALPHA; width as usize * height as usize * 4],
+                width: width as u32,
+                height: height as u32,
+            };
+            let surface_idx = state.surfaces.len();
+            let surface = compositor.create_surface(&qh, ());
+            let layer_surface = layer_shell.get_layer_surface(
+                &surface,
+                Some(&output.output),
+                Layer::Overlay,
+                "hyprshot-flash".to_string(),
+                &qh,
+                SurfaceKey(surface_idx),
+            );
+            layer_surface.set_anchor(Anchor::Top | Anchor::Bottom | Anchor::Left | Anchor::Right);
+            layer_surface.set_keyboard_interactivity(KeyboardInteractivity::None);
+            layer_surface.set_exclusive_zone(-1);
+            layer_surface.set_size(width as u32, height as u32);
+            let input_region = compositor.create_region(&qh, ());
+            surface.set_input_region(Some(&input_region));
+            surface.commit();
+            let (buffer, tmp) = create_buffer(&shm, &qh, &white)?;
+            state.surfaces.push(SurfaceEntry {
+                surface,
+                layer_surface,
+                buffer,
+                _input_region: input_region,
+                _tmp: tmp,
+                configured: false,
+                viewport: None,
+            });
+        }
+        if debug {
+            eprintln!("Flashing {} output(s)", state.surfaces.len());
+        }
+
+        event_queue
+            .roundtrip(&mut state)
+            .context("Failed to configure flash surfaces")?;
+        for entry in state.surfaces.iter().filter(|entry| entry.configured) {
+            entry.surface.attach(Some(&entry.buffer), 0, 0);
+            entry.surface.commit();
+        }
+        conn.flush().ok();
+        thread::sleep(FLASH_DURATION);
+
+        for entry in state.surfaces.drain(..) {
+            entry.layer_surface.destroy();
+            entry.surface.destroy();
+            entry.buffer.destroy();
+        }
+        event_queue.roundtrip(&mut state).ok();
+        drop(registry);
         Ok(())
     }
 
@@ -679,64 +831,59 @@ Check the support for this protocol on Hyprland/Sway/River/Wayfire."
         shm: &WlShm,
         qh: &QueueHandle<State>,
         capture: &CaptureImage,
-    ) -> Result<(WlBuffer, tempfile::NamedTempFile, memmap2::MmapMut)> {
-        let width = capture.width as i32;
-        let height = capture.height as i32;
-        let stride = width * 4;
-        let size = (stride * height) as usize;
-
-        let mut tmp_file = tempfile::NamedTempFile::new()
-            .context("Failed to create temporary file for shm buffer")?;
-        tmp_file
-            .as_file_mut()
-            .set_len(size as u64)
-            .context("Failed to resize shm buffer file")?;
-
-        let mut mmap = unsafe {
-            memmap2::MmapMut::map_mut(&tmp_file).context("Failed to memory-map shm buffer")?
-        };
-
-        let src = &capture.data;
-        let dst = &mut mmap[..];
-        for (i, px) in src.chunks_exact(4).enumerate() {
-            let offset = i * 4;
-            dst[offset] = px[2];
-            dst[offset + 1] = px[1];
-            dst[offset + 2] = px[0];
-            dst[offset + 3] = px[3];
+    ) -> Result<(WlBuffer, tempfile::NamedTempFile)> {
+        let width = i32::try_from(capture.width).context("Frozen image is too wide")?;
+        let height = i32::try_from(capture.height).context("Frozen image is too tall")?;
+        let stride = width
+            .checked_mul(4)
+            .context("Frozen image is too wide for a shm buffer")?;
+        let size = stride
+            .checked_mul(height)
+            .context("Frozen image is too large for a shm buffer")?;
+
+        // Pixels are written through the file rather than a mapping of a sparse
+        // file: on a full tmpfs that fails with ENOSPC here instead of SIGBUS later.
+        let dir = std::env::temp_dir();
+        let mut tmp_file = tempfile::NamedTempFile::new().with_context(|| {
+            format!(
+                "Failed to create temporary file for shm buffer in '{}' (set TMPDIR to change it)",
+                dir.display()
+            )
+        })?;
+        let mut writer = BufWriter::new(tmp_file.as_file_mut());
+        let written = capture
+            .data
+            .chunks_exact(4)
+            .try_for_each(|px| writer.write_all(&[px[2], px[1], px[0], px[3]]))
+            .and_then(|()| writer.flush());
+        drop(writer);
+        if let Err(err) = written {
+            if err.kind() == ErrorKind::StorageFull {
+                return Err(anyhow::anyhow!(
+                    "Not enough space in '{}' for the {} MiB freeze buffer; set TMPDIR to a \
+directory with more free space",
+                    dir.display(),
+                    size as u64 / (1024 * 1024)
+                ));
+            }
+            return Err(anyhow::Error::new(err).context("Failed to write shm buffer file"));
         }
 
         let pool = shm.create_pool(
             unsafe { BorrowedFd::borrow_raw(tmp_file.as_file().as_raw_fd()) },
-            size as i32,
+            size,
             qh,
             (),
         );
         let buffer = pool.create_buffer(0, width, height, stride, wl_shm::Format::Argb8888, qh, ());
         pool.destroy();
 
-        Ok((buffer, tmp_file, mmap))
-    }
-
-    fn output_logical_size(output: &OutputEntry) -> Option<(i32, i32)> {
-        if let (Some(width), Some(height)) = (output.logical_width, output.logical_height) {
-            return Some((width, height));
-        }
-
-        let mode_width = output.mode_width?;
-        let mode_height = output.mode_height?;
-        let scale = output.scale.max(1);
-        Some((
-            ((mode_width as f64) / (scale as f64)).round() as i32,
-            ((mode_height as f64) / (scale as f64)).round() as i32,
-        ))
+        Ok((buffer, tmp_file))
     }
 
     fn output_geometry(output: &OutputEntry) -> Option<(i32, i32, i32, i32)> {
-        let x = output.logical_x.or(output.pos_x)?;
-        let y = output.logical_y.or(output.pos_y)?;
-        let (width, height) = output_logical_size(output)?;
-        Some((x, y, width, height))
+        let geometry = output.geometry()?;
+        Some((geometry.x, geometry.y, geometry.width, geometry.height))
     }
 
     fn geometry_close(a: (i32, i32, i32, i32), b: (i32, i32, i32, i32)) -> bool {
@@ -860,7 +1007,7 @@ Check the support for this protocol on Hyprland/Sway/River/Wayfire."
 #[cfg(all(target_os = "linux", feature = "freeze"))]
 pub use imp::FreezeGuard;
 #[cfg(all(target_os = "linux", feature = "freeze"))]
-pub use imp::start_freeze;
+pub use imp::{flash, start_freeze};
 
 #[cfg(not(all(target_os = "linux", feature = "freeze")))]
 mod imp_stub {
@@ -869,17 +1016,30 @@ mod imp_stub {
     pub struct FreezeGuard;
 
     impl FreezeGuard {
+        pub fn take_frames(&mut self) -> Vec<FrozenFrame> {
+            Vec::new()
+        }
+
         pub fn stop(self) -> Result<()> {
             Ok(())
         }
     }
 
-    pub fn start_freeze(_selected_output: Option<&str>, _debug: bool) -> Result<FreezeGuard> {
+    pub fn start_freeze(
+        _selected_output: Option<&str>,
+        _hint: Option<FreezeHint>,
+        _debug_boxes: Vec<Geometry>,
+        _debug: bool,
+    ) -> Result<FreezeGuard> {
         Ok(FreezeGuard)
     }
+
+    pub fn flash(_area: Geometry, _debug: bool) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[cfg(not(all(target_os = "linux", feature = "freeze")))]
 pub use imp_stub::FreezeGuard;
 #[cfg(not(all(target_os = "linux", feature = "freeze")))]
-pub use imp_stub::start_freeze;
+pub use imp_stub::{flash, start_freeze};
@@ -35,12 +35,105 @@ impl Geometry {
         Self::new(rect.x, rect.y, rect.width, rect.height)
     }
 
+    /// Adjust to the given aspect ratio, keeping the center fixed.
+    /// `Expand` grows the short side, `Crop` shrinks the long side.
+    pub fn fit_aspect(self, aspect: AspectRatio, mode: AspectMode) -> Result<Self> {
+        let (w, h) = (self.width as i64, self.height as i64);
+        let (rw, rh) = (aspect.width as i64, aspect.height as i64);
+        // Compare w/h with rw/rh without floating point.
+        let too_wide = w * rh > h * rw;
+        let (new_w, new_h) = match (mode, too_wide) {
+            (AspectMode::Expand, true) | (AspectMode::Crop, false) => {
+                (w, ((w * rh) as f64 / rw as f64).round() as i64)
+            }
+            (AspectMode::Expand, false) | (AspectMode::Crop, true) => {
+                (((h * rw) as f64 / rh as f64).round() as i64, h)
+            }
+        };
+        let new_w = i32::try_from(new_w.max(1)).context("Aspect-adjusted width is too large")?;
+        let new_h = i32::try_from(new_h.max(1)).context("Aspect-adjusted height is too large")?;
+        let x = self.x + (self.width - new_w) / 2;
+        let y = self.y + (self.height - new_h) / 2;
+        Self::new(x, y, new_w, new_h)
+    }
+
+    /// Grown by `by` on each side, keeping the center fixed.
+    pub fn grow(self, by: u32) -> Result<Self> {
+        let by = i32::try_from(by).context("Padding is too large")?;
+        Self::new(
+            self.x.saturating_sub(by),
+            self.y.saturating_sub(by),
+            self.width.saturating_add(by.saturating_mul(2)),
+            self.height.saturating_add(by.saturating_mul(2)),
+        )
+    }
+
+    /// Smallest geometry containing both.
+    pub fn union(self, other: Self) -> Self {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Self {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+
+    /// Overlapping area of two geometries, or `None` if they don't overlap.
+    pub fn intersect(self, other: Self) -> Option<Self> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        Self::new(x, y, right - x, bottom - y).ok()
+    }
+
     #[cfg(feature = "grim")]
     pub fn to_grim_box(self) -> grim_rs::Box {
         grim_rs::Box::new(self.x, self.y, self.width, self.height)
     }
 }
 
+/// Target aspect ratio, parsed from `W:H` (e.g. `16:9`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AspectRatio {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for AspectRatio {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (w, h) = s
+            .trim()
+            .split_once(':')
+            .context("Invalid aspect ratio: expected 'W:H' (e.g. 16:9)")?;
+        let width: u32 = w.parse().context("Invalid aspect ratio width")?;
+        let height: u32 = h.parse().context("Invalid aspect ratio height")?;
+        if width == 0 || height == 0 {
+            return Err(anyhow::anyhow!(
+                "Invalid aspect ratio: '{}' must be positive",
+                s
+            ));
+        }
+        Ok(Self { width, height })
+    }
+}
+
+/// How a selection is adjusted to match an aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum AspectMode {
+    /// Grow the selection to include more of the screen.
+    #[default]
+    Expand,
+    /// Shrink the selection to fit inside it.
+    Crop,
+}
+
 impl FromStr for Geometry {
     type Err = anyhow::Error;
 
@@ -0,0 +1,69 @@
+//! `[hooks]`: shell commands run around a capture.
+//!
+//! Each entry is a line for `sh -c`, so pipelines and quoting work as in a
+//! terminal. Details of the capture are passed as `HYPRSHOT_*` variables.
+
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::geometry::Geometry;
+
+/// Variables for `post_capture` hooks: those of `-- command` plus the saved
+/// file, the mode (e.g. `region`, `active-window`) and the `--upload` URL.
+pub(crate) fn post_capture_env(
+    path: &Path,
+    geometry: &Geometry,
+    mode: Option<&str>,
+    output_name: Option<&str>,
+    url: Option<&str>,
+) -> Vec<(&'static str, String)> {
+    let mut env = crate::save::command_env(geometry, output_name);
+    env.push(("HYPRSHOT_PATH", path.to_string_lossy().into_owned()));
+    if let Some(mode) = mode {
+        env.push(("HYPRSHOT_MODE", mode.to_string()));
+    }
+    if let Some(url) = url {
+        env.push(("HYPRSHOT_URL", url.to_string()));
+    }
+    env
+}
+
+/// Run every `pre_capture` hook in order with `HYPRSHOT_MODE` set. The first
+/// failing hook aborts the capture.
+pub(crate) fn run_pre_capture(commands: &[String], mode: &str, debug: bool) -> Result<()> {
+    let env = [("HYPRSHOT_MODE", mode.to_string())];
+    for command in commands.iter().filter(|command| !command.trim().is_empty()) {
+        run_hook(command, &env, debug).context("Capture aborted by a pre_capture hook")?;
+    }
+    Ok(())
+}
+
+/// Hook output goes to stderr: stdout may carry `--raw`, `--print-geometry` or
+/// `--json` output that a pipeline reads.
+fn run_hook(command: &str, env: &[(&'static str, String)], debug: bool) -> Result<()> {
+    if debug {
+        eprintln!("Running hook: {}", command);
+    }
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .stdout(Stdio::from(std::io::stderr()))
+        .status()
+        .with_context(|| format!("Failed to run hook '{}'", command))?;
+    if !status.success() {
+        bail!("Hook '{}' failed ({})", command, status);
+    }
+    Ok(())
+}
+
+/// Run every `post_capture` hook in order. A failing hook is reported and the
+/// next one still runs; the screenshot is already saved.
+pub(crate) fn run_post_capture(commands: &[String], env: &[(&'static str, String)], debug: bool) {
+    for command in commands.iter().filter(|command| !command.trim().is_empty()) {
+        if let Err(err) = run_hook(command, env, debug) {
+            eprintln!("Warning: {:#}", err);
+        }
+    }
+}
@@ -1,10 +1,25 @@
 use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
 
 use crate::config;
+use crate::config_cmds::prompt_general_settings;
+
+/// Print a warning for every hotkey that is bound more than once
+fn warn_hotkey_conflicts(config: &config::Config, with_clipboard: bool) {
+    for (hotkey, names) in config.hotkey_conflicts(with_clipboard) {
+        eprintln!(
+            "Warning: hotkey \"{}\" is used by: {}",
+            hotkey,
+            names.join(", ")
+        );
+    }
+}
 
 /// Generate Hyprland keybindings
 pub fn handle_generate_hyprland_config(with_clipboard: bool) -> Result<()> {
     let config = config::Config::load()?;
+    warn_hotkey_conflicts(&config, with_clipboard);
 
     let binds = if with_clipboard {
         config.generate_hyprland_binds_with_clipboard()
@@ -46,18 +61,17 @@ pub fn handle_install_binds(with_clipboard: bool) -> Result<()> {
         );
     }
 
+    warn_hotkey_conflicts(&config, with_clipboard);
+
     println!("Installing hyprshot-rs keybindings to Hyprland config...\n");
 
-    let installed_path = config
+    let (installed_path, backup_path) = config
         .install_hyprland_binds(with_clipboard)
         .context("Failed to install keybindings")?;
 
     println!("Keybindings installed successfully!");
     println!("Config file: {}", installed_path.display());
-    println!(
-        "Backup created: {}",
-        installed_path.with_extension("conf.backup").display()
-    );
+    println!("Backup created: {}", backup_path.display());
 
     if with_clipboard {
         println!("\nInstalled bindings (with clipboard variants):");
@@ -84,18 +98,115 @@ pub fn handle_install_binds(with_clipboard: bool) -> Result<()> {
     Ok(())
 }
 
-/// Interactive hotkeys setup wizard
-pub fn handle_setup_hotkeys() -> Result<()> {
-    use dialoguer::{Confirm, Input, theme::ColorfulTheme};
+/// Find the most recent `hyprland.conf.backup*` next to `hyprland_conf`.
+/// Timestamped backups are ordered by their epoch suffix; a legacy
+/// `hyprland.conf.backup` falls back to its modification time.
+pub(crate) fn latest_hyprland_backup(hyprland_conf: &Path) -> Result<Option<PathBuf>> {
+    let dir = hyprland_conf
+        .parent()
+        .context("Hyprland config has no parent directory")?;
+    let file_name = hyprland_conf
+        .file_name()
+        .and_then(|n| n.to_str())
+        .context("Invalid Hyprland config file name")?;
+    let prefix = format!("{}.backup", file_name);
 
-    println!("This wizard will help you configure hotkeys for hyprshot-rs.");
-    println!("Format: \"MODIFIER, KEY\" (e.g., \"SUPER, Print\", \"ALT SHIFT, S\")");
-    println!();
+    let mut latest: Option<(i64, PathBuf)> = None;
+    for entry in fs::read_dir(dir).context(format!("Failed to read {}", dir.display()))? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        let Some(suffix) = name.strip_prefix(&prefix) else {
+            continue;
+        };
+        let epoch = if suffix.is_empty() {
+            entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0)
+        } else {
+            match suffix.strip_prefix('.').and_then(|s| s.parse::<i64>().ok()) {
+                Some(epoch) => epoch,
+                None => continue,
+            }
+        };
+        if latest.as_ref().is_none_or(|(best, _)| epoch > *best) {
+            latest = Some((epoch, entry.path()));
+        }
+    }
+
+    Ok(latest.map(|(_, path)| path))
+}
+
+/// Restore hyprland.conf from the most recent backup
+pub fn handle_restore_binds(yes: bool) -> Result<()> {
+    use dialoguer::{Confirm, theme::ColorfulTheme};
+
+    let hyprland_conf = config::Config::hyprland_config_path()?;
+    let backup = latest_hyprland_backup(&hyprland_conf)?.with_context(|| {
+        format!(
+            "No hyprland.conf backup found next to {}",
+            hyprland_conf.display()
+        )
+    })?;
+
+    println!("Latest backup: {}", backup.display());
+
+    if !yes
+        && !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Restore it over {}?", hyprland_conf.display()))
+            .default(false)
+            .interact()?
+    {
+        println!("Restore cancelled.");
+        return Ok(());
+    }
+
+    if hyprland_conf.exists() {
+        let mut name = hyprland_conf
+            .file_name()
+            .map(|n| n.to_os_string())
+            .unwrap_or_else(|| "hyprland.conf".into());
+        name.push(format!(".pre-restore.{}", chrono::Local::now().timestamp()));
+        let safety_copy = hyprland_conf.with_file_name(name);
+        fs::copy(&hyprland_conf, &safety_copy)
+            .context("Failed to save a copy of the current hyprland.conf")?;
+        println!("Current config saved to: {}", safety_copy.display());
+    }
+
+    fs::copy(&backup, &hyprland_conf).context("Failed to restore hyprland.conf")?;
+
+    println!("Restored: {}", hyprland_conf.display());
+    println!("\nTo apply the changes:");
+    println!("hyprctl reload");
+
+    Ok(())
+}
+
+/// Interactive setup. With `general`, also asks for the save directory,
+/// image format, notifications and freeze before the hotkeys (`--setup`).
+pub fn handle_setup_hotkeys(general: bool) -> Result<()> {
+    use dialoguer::{Confirm, Input, theme::ColorfulTheme};
 
     let mut config = config::Config::load().unwrap_or_else(|_| config::Config::default());
 
     let theme = ColorfulTheme::default();
 
+    if general {
+        println!("This wizard will help you configure hyprshot-rs.");
+        println!();
+        prompt_general_settings(&mut config, &theme)?;
+    }
+
+    println!("This wizard will help you configure hotkeys for hyprshot-rs.");
+    println!("Format: \"MODIFIER, KEY\" (e.g., \"SUPER, Print\", \"ALT SHIFT, S\")");
+    println!();
+
     println!("Window Screenshot");
     println!("Capture a selected window");
     let window_hotkey: String = Input::with_theme(&theme)
@@ -161,10 +272,20 @@ pub fn handle_setup_hotkeys() -> Result<()> {
     println!();
 
     println!("Configuration Summary:");
+    if general {
+        println!("Screenshots directory:{}", config.paths.screenshots_dir);
+        println!("Notifications:{}", config.capture.notification);
+        println!(
+            "Notification timeout:{} ms",
+            config.capture.notification_timeout
+        );
+        println!("Freeze on region:{}", config.advanced.freeze_on_region);
+    }
     println!("Window Screenshot:{}", config.hotkeys.window);
     println!("Region Screenshot:{}", config.hotkeys.region);
     println!("Output Screenshot:{}", config.hotkeys.output);
     println!("Active Output Screenshot:{}", config.hotkeys.active_output);
+    warn_hotkey_conflicts(&config, false);
 
     if Confirm::with_theme(&theme)
         .with_prompt("Save this configuration?")
@@ -213,7 +334,11 @@ pub fn handle_setup_hotkeys() -> Result<()> {
         println!("• View config:     hyprshot-rs --show-config");
         println!("• Generate binds:  hyprshot-rs --generate-hyprland-config");
         println!("• Install binds:   hyprshot-rs --install-binds");
-        println!("• Run setup again: hyprshot-rs --setup-hotkeys");
+        if general {
+            println!("• Run setup again: hyprshot-rs --setup");
+        } else {
+            println!("• Run setup again: hyprshot-rs --setup-hotkeys");
+        }
     } else {
         println!("\nConfiguration not saved.");
     }
@@ -0,0 +1,137 @@
+//! Client for Hyprland's request socket, used instead of running `hyprctl`
+//! for every query.
+//!
+//! A request is written as plain text (`j/` asks for JSON) and the reply is
+//! everything Hyprland writes before closing the connection.
+
+use anyhow::Result;
+use serde_json::Value;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const SOCKET_NAME: &str = ".socket.sock";
+const IPC_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug)]
+pub(crate) enum HyprlandIpcError {
+    /// `HYPRLAND_INSTANCE_SIGNATURE` is unset.
+    NoSignature,
+    Connect {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Io(io::Error),
+    /// Hyprland answered with text instead of JSON, e.g. `unknown request`.
+    Reply {
+        command: String,
+        reply: String,
+    },
+}
+
+impl fmt::Display for HyprlandIpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoSignature => write!(
+                f,
+                "Hyprland IPC socket not found: HYPRLAND_INSTANCE_SIGNATURE is unset"
+            ),
+            Self::Connect { path, source } => write!(
+                f,
+                "Failed to connect to Hyprland socket '{}': {}",
+                path.display(),
+                source
+            ),
+            Self::Io(err) => write!(f, "Hyprland IPC failed: {}", err),
+            Self::Reply { command, reply } => {
+                write!(f, "Hyprland {} reply is not JSON: {}", command, reply)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HyprlandIpcError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Connect { source, .. } | Self::Io(source) => Some(source),
+            Self::NoSignature | Self::Reply { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for HyprlandIpcError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Where Hyprland may have put its socket: `$XDG_RUNTIME_DIR/hypr/<sig>`
+/// since 0.40, `/tmp/hypr/<sig>` before.
+pub(crate) fn socket_candidates(runtime_dir: Option<&OsStr>, signature: &OsStr) -> Vec<PathBuf> {
+    let mut candidates = Vec::with_capacity(2);
+    if let Some(dir) = runtime_dir.filter(|dir| !dir.is_empty()) {
+        candidates.push(
+            Path::new(dir)
+                .join("hypr")
+                .join(signature)
+                .join(SOCKET_NAME),
+        );
+    }
+    candidates.push(Path::new("/tmp/hypr").join(signature).join(SOCKET_NAME));
+    candidates
+}
+
+/// The request socket of the running instance.
+pub(crate) fn socket_path() -> Result<PathBuf, HyprlandIpcError> {
+    let signature = std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE")
+        .filter(|sig| !sig.is_empty())
+        .ok_or(HyprlandIpcError::NoSignature)?;
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR");
+    let mut candidates = socket_candidates(runtime_dir.as_deref(), &signature);
+    let found = candidates
+        .iter()
+        .position(|path| path.exists())
+        .unwrap_or(0);
+    Ok(candidates.swap_remove(found))
+}
+
+fn connect() -> Result<UnixStream, HyprlandIpcError> {
+    let path = socket_path()?;
+    UnixStream::connect(&path).map_err(|source| HyprlandIpcError::Connect { path, source })
+}
+
+fn request(message: &str) -> Result<Vec<u8>, HyprlandIpcError> {
+    let mut stream = connect()?;
+    stream.set_read_timeout(Some(IPC_TIMEOUT))?;
+    stream.set_write_timeout(Some(IPC_TIMEOUT))?;
+    stream.write_all(message.as_bytes())?;
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply)?;
+    Ok(reply)
+}
+
+/// Send `command` (e.g. `clients`) and return the parsed JSON reply.
+pub(crate) fn query(command: &str) -> Result<Value> {
+    let reply = request(&format!("j/{}", command))?;
+    Ok(parse_reply(command, &reply)?)
+}
+
+/// Set a config option for the running session, like `hyprctl keyword`.
+pub(crate) fn keyword(name: &str, value: &str) -> Result<()> {
+    let reply = request(&format!("keyword {} {}", name, value))?;
+    let reply = String::from_utf8_lossy(&reply);
+    if reply.trim() != "ok" {
+        anyhow::bail!("Hyprland refused '{} = {}': {}", name, value, reply.trim());
+    }
+    Ok(())
+}
+
+pub(crate) fn parse_reply(command: &str, reply: &[u8]) -> Result<Value, HyprlandIpcError> {
+    serde_json::from_slice(reply).map_err(|_| HyprlandIpcError::Reply {
+        command: command.to_string(),
+        reply: String::from_utf8_lossy(reply).trim().to_string(),
+    })
+}
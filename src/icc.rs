@@ -0,0 +1,119 @@
+//! A minimal ICC v2 sRGB display profile, for formats that have no sRGB flag
+//! of their own (JPEG). PNG uses its `sRGB` chunk instead.
+
+/// D50, the profile connection space illuminant.
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+/// sRGB primaries adapted to D50 (Bradford), as in the usual sRGB profiles.
+const RED: [f64; 3] = [0.4360747, 0.2225045, 0.0139322];
+const GREEN: [f64; 3] = [0.3850649, 0.7168786, 0.0971045];
+const BLUE: [f64; 3] = [0.1430804, 0.0606169, 0.7139080];
+/// Entries in the sampled sRGB tone curve.
+const CURVE_POINTS: usize = 1024;
+const DESCRIPTION: &str = "sRGB";
+const COPYRIGHT: &str = "No copyright, use freely";
+
+fn s15_fixed16(value: f64) -> [u8; 4] {
+    ((value * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn xyz(value: [f64; 3]) -> Vec<u8> {
+    let mut tag = b"XYZ \0\0\0\0".to_vec();
+    for component in value {
+        tag.extend_from_slice(&s15_fixed16(component));
+    }
+    tag
+}
+
+/// The sRGB transfer function, sampled.
+fn curve() -> Vec<u8> {
+    let mut tag = b"curv\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(CURVE_POINTS as u32).to_be_bytes());
+    for i in 0..CURVE_POINTS {
+        let encoded = i as f64 / (CURVE_POINTS - 1) as f64;
+        let linear = if encoded <= 0.04045 {
+            encoded / 12.92
+        } else {
+            ((encoded + 0.055) / 1.055).powf(2.4)
+        };
+        tag.extend_from_slice(&((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+    tag
+}
+
+fn description(text: &str) -> Vec<u8> {
+    let mut tag = b"desc\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    // No Unicode or ScriptCode description.
+    tag.extend_from_slice(&[0; 4 + 4 + 2 + 1 + 67]);
+    tag
+}
+
+fn text(text: &str) -> Vec<u8> {
+    let mut tag = b"text\0\0\0\0".to_vec();
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    tag
+}
+
+/// The profile's bytes, ready for a JPEG APP2 `ICC_PROFILE` segment.
+pub(crate) fn srgb_profile() -> Vec<u8> {
+    let trc = curve();
+    let tags: [(&[u8; 4], Vec<u8>); 9] = [
+        (b"desc", description(DESCRIPTION)),
+        (b"cprt", text(COPYRIGHT)),
+        (b"wtpt", xyz(D50)),
+        (b"rXYZ", xyz(RED)),
+        (b"gXYZ", xyz(GREEN)),
+        (b"bXYZ", xyz(BLUE)),
+        (b"rTRC", trc.clone()),
+        (b"gTRC", trc.clone()),
+        (b"bTRC", trc),
+    ];
+
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    let data_start = 128 + 4 + 12 * tags.len();
+    let mut offsets = Vec::with_capacity(tags.len());
+    for (index, (signature, tag)) in tags.iter().enumerate() {
+        // The three channels share one curve.
+        let offset = match tags[..index].iter().position(|(_, earlier)| earlier == tag) {
+            Some(earlier) => offsets[earlier],
+            None => {
+                let offset = data_start + data.len();
+                data.extend_from_slice(tag);
+                // Tags start on 4-byte boundaries.
+                data.resize(data.len().next_multiple_of(4), 0);
+                offset
+            }
+        };
+        offsets.push(offset);
+        table.extend_from_slice(*signature);
+        table.extend_from_slice(&(offset as u32).to_be_bytes());
+        table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+    }
+
+    let size = data_start + data.len();
+    let mut profile = Vec::with_capacity(size);
+    profile.extend_from_slice(&(size as u32).to_be_bytes());
+    profile.extend_from_slice(&[0; 4]);
+    // Version 2.1.
+    profile.extend_from_slice(&[2, 0x10, 0, 0]);
+    profile.extend_from_slice(b"mntrRGB XYZ ");
+    // Creation date: none given.
+    profile.extend_from_slice(&[0; 12]);
+    profile.extend_from_slice(b"acsp");
+    // Platform, flags, manufacturer, model, attributes.
+    profile.extend_from_slice(&[0; 4 + 4 + 4 + 4 + 8]);
+    // Perceptual rendering intent.
+    profile.extend_from_slice(&0u32.to_be_bytes());
+    for component in D50 {
+        profile.extend_from_slice(&s15_fixed16(component));
+    }
+    // Creator, profile ID and the reserved bytes up to the tag table.
+    profile.resize(128, 0);
+    profile.extend_from_slice(&table);
+    profile.extend_from_slice(&data);
+    profile
+}
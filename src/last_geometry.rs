@@ -0,0 +1,53 @@
+//! `--last`: the geometry of the previous capture in each mode, kept in
+//! `$XDG_STATE_HOME/hyprshot-rs/last.toml` as `region = "x,y WxH"` lines.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::geometry::Geometry;
+
+pub(crate) fn state_path() -> Option<PathBuf> {
+    dirs::state_dir().map(|dir| dir.join("hyprshot-rs").join("last.toml"))
+}
+
+fn read(path: &Path) -> Result<BTreeMap<String, String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => toml::from_str(&content)
+            .with_context(|| format!("Failed to parse '{}'", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(err) => Err(err).with_context(|| format!("Failed to read '{}'", path.display())),
+    }
+}
+
+/// The geometry last captured with `mode` (a [`crate::app::mode_label`]).
+pub(crate) fn load(path: &Path, mode: &str) -> Result<Geometry> {
+    let entries = read(path)?;
+    let geometry = entries.get(mode).with_context(|| {
+        format!(
+            "No previous {} capture to repeat; capture once without --last first",
+            mode
+        )
+    })?;
+    geometry.parse().with_context(|| {
+        format!(
+            "Invalid {} geometry '{}' in '{}'",
+            mode,
+            geometry,
+            path.display()
+        )
+    })
+}
+
+/// Remember `geometry` for `mode`, keeping the other modes' entries.
+pub(crate) fn store(path: &Path, mode: &str, geometry: &Geometry) -> Result<()> {
+    // A damaged file is replaced rather than blocking every later capture.
+    let mut entries = read(path).unwrap_or_default();
+    entries.insert(mode.to_string(), geometry.to_string());
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    }
+    let content = toml::to_string(&entries).context("Failed to serialize last geometries")?;
+    fs::write(path, content).with_context(|| format!("Failed to write '{}'", path.display()))
+}
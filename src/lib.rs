@@ -0,0 +1,109 @@
+//! hyprshot-rs as a library: capture a region or an output of the running
+//! Wayland compositor without spawning the CLI.
+//!
+//! ```no_run
+//! use hyprshot::{Geometry, capture_region};
+//!
+//! let shot = capture_region(Geometry::new(0, 0, 800, 600)?)?;
+//! println!("{}x{} RGBA", shot.width, shot.height);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+mod app;
+mod backend;
+mod beautify;
+mod capture;
+mod cli;
+mod clipboard;
+mod completions;
+mod compositor;
+mod config;
+mod config_cmds;
+#[cfg(feature = "dmabuf")]
+mod dmabuf;
+mod doctor;
+mod editor;
+mod exit;
+#[cfg(feature = "ext-capture")]
+mod ext_capture;
+mod filename;
+mod freeze;
+mod geometry;
+mod hooks;
+mod hyprland_cmds;
+mod hyprland_ipc;
+mod icc;
+mod last_geometry;
+mod list_cmds;
+mod metadata;
+mod notify;
+mod obscure;
+#[cfg(feature = "freeze")]
+mod outputs;
+#[cfg(feature = "freeze")]
+mod overlay_text;
+mod paste_cmds;
+mod png_encoder;
+mod record;
+mod resize;
+mod save;
+mod selector;
+#[cfg(feature = "freeze")]
+mod shm;
+mod sway_ipc;
+mod term_preview;
+#[cfg(feature = "freeze")]
+mod toplevel_export;
+mod upload;
+mod utils;
+mod wallpaper;
+
+/// Layout of a capture written by [`capture_region_into`].
+pub use backend::BufferInfo;
+/// Raw pixels of a capture: 8-bit RGBA, top row first, no row padding.
+pub use backend::CapturedImage as CaptureResult;
+/// The CLI's `config.toml`; `Config::load()` reads it from the usual place.
+pub use config::Config;
+pub use geometry::Geometry;
+
+/// Run the `hyprshot-rs` command line on the process arguments, as the binary
+/// does, and return its exit code.
+pub fn run_cli() -> std::process::ExitCode {
+    use clap::Parser;
+
+    match app::run(cli::Args::parse()) {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            std::process::ExitCode::from(exit::exit_code(&err))
+        }
+    }
+}
+
+/// Capture a region in logical (compositor) coordinates. The region may span
+/// several outputs; areas no output covers are transparent.
+#[cfg(feature = "grim")]
+pub fn capture_region(geometry: Geometry) -> anyhow::Result<CaptureResult> {
+    backend::create_backend(false, false)?.capture_region(&geometry)
+}
+
+/// Capture a region like [`capture_region`], but into `buffer` instead of a
+/// new allocation, e.g. a frame buffer an embedder reuses between captures.
+///
+/// Pixels are 8-bit RGBA, not premultiplied, top row first. Rows have no
+/// padding: the returned `stride` is always `width * 4`, and only the first
+/// `stride * height` bytes of `buffer` are written. A buffer shorter than that
+/// is an error and is left untouched.
+#[cfg(feature = "grim")]
+pub fn capture_region_into(geometry: Geometry, buffer: &mut [u8]) -> anyhow::Result<BufferInfo> {
+    backend::create_backend(false, false)?.capture_region_into(&geometry, buffer)
+}
+
+/// Capture the output named `name` (e.g. `DP-1`) at its native resolution.
+#[cfg(feature = "grim")]
+pub fn capture_output(name: &str) -> anyhow::Result<CaptureResult> {
+    backend::create_backend(false, false)?.capture_output(name)
+}
+
+#[cfg(test)]
+mod tests;
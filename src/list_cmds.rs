@@ -0,0 +1,131 @@
+use anyhow::Result;
+use serde_json::json;
+
+use crate::capture::{self, OutputInfo};
+use crate::compositor::{self, Window};
+
+/// `hyprshot-rs list outputs`: the names `-m OUTPUT` accepts, on any compositor.
+pub fn handle_list_outputs(json: bool, debug: bool) -> Result<()> {
+    let outputs = capture::wayland_output_info(debug)?;
+    if json {
+        println!("{}", outputs_json(&outputs));
+    } else {
+        print!("{}", outputs_table(&outputs));
+    }
+    Ok(())
+}
+
+/// `hyprshot-rs list windows`: exactly what `-m window` offers the selector.
+pub fn handle_list_windows(json: bool, client_area: bool, debug: bool) -> Result<()> {
+    let windows = compositor::detect(debug).list_windows(client_area, debug)?;
+    if json {
+        println!("{}", windows_json(&windows));
+    } else {
+        print!("{}", windows_table(&windows));
+    }
+    Ok(())
+}
+
+pub(crate) fn outputs_json(outputs: &[OutputInfo]) -> serde_json::Value {
+    outputs
+        .iter()
+        .map(|output| {
+            json!({
+                "name": output.name,
+                "description": output.description,
+                "x": output.geometry.x,
+                "y": output.geometry.y,
+                "width": output.geometry.width,
+                "height": output.geometry.height,
+                "scale": output.scale,
+                "transform": output.transform,
+            })
+        })
+        .collect()
+}
+
+/// One line per output, columns padded to the widest entry.
+pub(crate) fn outputs_table(outputs: &[OutputInfo]) -> String {
+    let rows: Vec<[String; 5]> = outputs
+        .iter()
+        .map(|output| {
+            [
+                output.name.clone(),
+                output.geometry.to_string(),
+                format!("scale {}", output.scale),
+                output.transform.to_string(),
+                output.description.clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+    table(&rows)
+}
+
+pub(crate) fn windows_json(windows: &[Window]) -> serde_json::Value {
+    windows
+        .iter()
+        .map(|window| {
+            json!({
+                "title": window.title,
+                "class": window.class,
+                "address": window.address,
+                "id": window.id,
+                "pid": window.pid,
+                "workspace": window.workspace,
+                "x": window.geometry.x,
+                "y": window.geometry.y,
+                "width": window.geometry.width,
+                "height": window.geometry.height,
+            })
+        })
+        .collect()
+}
+
+/// Geometry, class, Hyprland address or Sway id, workspace and title; the
+/// title goes last as it may contain anything.
+pub(crate) fn windows_table(windows: &[Window]) -> String {
+    let rows: Vec<[String; 5]> = windows
+        .iter()
+        .map(|window| {
+            let id = match (&window.address, window.id) {
+                (Some(address), _) => address.clone(),
+                (None, Some(id)) => id.to_string(),
+                (None, None) => "-".to_string(),
+            };
+            [
+                window.geometry.to_string(),
+                match window.class.as_str() {
+                    "" => "-".to_string(),
+                    class => class.to_string(),
+                },
+                id,
+                window.workspace.clone().unwrap_or_else(|| "-".to_string()),
+                window.title.replace('\n', " "),
+            ]
+        })
+        .collect();
+    table(&rows)
+}
+
+fn table<const N: usize>(rows: &[[String; N]]) -> String {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut text = String::new();
+    for row in rows {
+        let mut line = String::new();
+        for (index, (cell, width)) in row.iter().zip(widths).enumerate() {
+            if index + 1 == N {
+                line.push_str(cell);
+            } else {
+                line.push_str(&format!("{:<width$}  ", cell, width = width));
+            }
+        }
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    text
+}
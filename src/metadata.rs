@@ -0,0 +1,146 @@
+//! `--metadata`: capture details written into saved files, as PNG text
+//! chunks or a JPEG EXIF block, so screenshots can be searched by window or
+//! date later. Nothing is embedded unless asked for.
+
+use chrono::{DateTime, Local};
+
+use crate::backend::{insert_png_chunks, png_chunk};
+use crate::config::MetadataLevel;
+use crate::geometry::Geometry;
+
+const SOFTWARE: &str = concat!("hyprshot-rs ", env!("CARGO_PKG_VERSION"));
+
+/// EXIF tags written to IFD0, in ascending order as TIFF requires.
+const IMAGE_DESCRIPTION: u16 = 0x010e;
+const SOFTWARE_TAG: u16 = 0x0131;
+const DATE_TIME: u16 = 0x0132;
+
+/// What is known about a capture besides its pixels. The output and geometry
+/// are passed when encoding, so each file of `--split` gets its own.
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    pub level: MetadataLevel,
+    pub time: DateTime<Local>,
+    pub title: Option<String>,
+    pub class: Option<String>,
+    pub workspace: Option<String>,
+}
+
+impl Metadata {
+    /// `None` for `MetadataLevel::None`, so callers can skip embedding.
+    pub fn new(level: MetadataLevel, time: DateTime<Local>) -> Option<Self> {
+        (level != MetadataLevel::None).then_some(Self {
+            level,
+            time,
+            title: None,
+            class: None,
+            workspace: None,
+        })
+    }
+
+    /// Keyword/value pairs, the `basic` ones first.
+    pub(crate) fn entries(
+        &self,
+        geometry: &Geometry,
+        output_name: Option<&str>,
+    ) -> Vec<(&'static str, String)> {
+        let mut entries = vec![
+            ("Software", SOFTWARE.to_string()),
+            ("Creation Time", self.time.to_rfc3339()),
+        ];
+        if self.level == MetadataLevel::Full {
+            let details = [
+                ("Window Title", self.title.clone()),
+                ("Window Class", self.class.clone()),
+                ("Output", output_name.map(str::to_string)),
+                ("Geometry", Some(geometry.to_string())),
+                ("Workspace", self.workspace.clone()),
+            ];
+            entries.extend(
+                details
+                    .into_iter()
+                    .filter_map(|(key, value)| Some((key, value?)))
+                    .filter(|(_, value)| !value.is_empty()),
+            );
+        }
+        entries
+    }
+
+    /// `png` with a text chunk per entry after IHDR: `tEXt` for plain ASCII,
+    /// `iTXt` (UTF-8) for anything else, such as most window titles.
+    pub(crate) fn embed_png(
+        &self,
+        png: Vec<u8>,
+        geometry: &Geometry,
+        output_name: Option<&str>,
+    ) -> Vec<u8> {
+        let mut chunks = Vec::new();
+        for (key, value) in self.entries(geometry, output_name) {
+            let mut body = key.as_bytes().to_vec();
+            body.push(0);
+            if value
+                .bytes()
+                .all(|b| b == b'\n' || (b' '..=b'~').contains(&b))
+            {
+                body.extend_from_slice(value.as_bytes());
+                chunks.extend(png_chunk(b"tEXt", &body));
+            } else {
+                // Uncompressed, no language tag or translated keyword.
+                body.extend_from_slice(&[0, 0, 0, 0]);
+                body.extend_from_slice(value.as_bytes());
+                chunks.extend(png_chunk(b"iTXt", &body));
+            }
+        }
+        insert_png_chunks(png, &chunks)
+    }
+
+    /// APP1 segment body: a little-endian TIFF header and one IFD with the
+    /// time, the tool and, for `full`, the details joined into
+    /// ImageDescription.
+    pub(crate) fn exif(&self, geometry: &Geometry, output_name: Option<&str>) -> Vec<u8> {
+        let details: Vec<String> = self
+            .entries(geometry, output_name)
+            .into_iter()
+            .skip(2)
+            .map(|(key, value)| format!("{}: {}", key, value))
+            .collect();
+        let mut tags = Vec::new();
+        if !details.is_empty() {
+            tags.push((IMAGE_DESCRIPTION, details.join("; ")));
+        }
+        tags.push((SOFTWARE_TAG, SOFTWARE.to_string()));
+        tags.push((DATE_TIME, self.time.format("%Y:%m:%d %H:%M:%S").to_string()));
+
+        const HEADER: usize = 8;
+        let ifd_len = 2 + tags.len() * 12 + 4;
+        let mut ifd = (tags.len() as u16).to_le_bytes().to_vec();
+        let mut data = Vec::new();
+        for (tag, value) in &tags {
+            let mut value = value.as_bytes().to_vec();
+            value.push(0);
+            ifd.extend_from_slice(&tag.to_le_bytes());
+            // Type 2: ASCII, NUL-terminated.
+            ifd.extend_from_slice(&2u16.to_le_bytes());
+            ifd.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            if value.len() <= 4 {
+                value.resize(4, 0);
+                ifd.extend_from_slice(&value);
+            } else {
+                let offset = HEADER + ifd_len + data.len();
+                ifd.extend_from_slice(&(offset as u32).to_le_bytes());
+                data.extend_from_slice(&value);
+                // Offsets must be even.
+                if data.len() % 2 == 1 {
+                    data.push(0);
+                }
+            }
+        }
+        ifd.extend_from_slice(&0u32.to_le_bytes());
+
+        let mut exif = b"Exif\0\0II*\0".to_vec();
+        exif.extend_from_slice(&(HEADER as u32).to_le_bytes());
+        exif.extend(ifd);
+        exif.extend(data);
+        exif
+    }
+}
@@ -0,0 +1,18 @@
+//! Desktop notification helpers.
+
+use std::sync::OnceLock;
+
+/// Check once per run whether a notification daemon answers on D-Bus.
+/// Without one, every `Notification::show()` would fail with the same error.
+pub fn daemon_available(debug: bool) -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| match notify_rust::get_server_information() {
+        Ok(_) => true,
+        Err(err) => {
+            if debug {
+                eprintln!("No notification daemon, notifications disabled: {}", err);
+            }
+            false
+        }
+    })
+}
@@ -0,0 +1,135 @@
+//! `--obscure`: pixelate or blur boxes dragged after the selection, for
+//! hiding emails, tokens and usernames before a screenshot is shared.
+
+use crate::backend::CapturedImage;
+use crate::config::ObscureStyle;
+use crate::geometry::Geometry;
+
+/// Mosaic cell size, in logical pixels.
+const PIXELATE_CELL: f64 = 12.0;
+/// Box-blur radius per pass, in logical pixels; three passes approximate a
+/// gaussian with sigma of about 10.
+const BLUR_RADIUS: f64 = 6.0;
+const BLUR_PASSES: usize = 3;
+
+/// Obscure `boxes` (logical, like `area`) in `image`, a capture of `area`.
+pub(crate) fn apply(
+    image: &mut CapturedImage,
+    area: &Geometry,
+    boxes: &[Geometry],
+    style: ObscureStyle,
+) {
+    let scale_x = f64::from(image.width) / f64::from(area.width);
+    let scale_y = f64::from(image.height) / f64::from(area.height);
+    let scale = scale_x.max(scale_y);
+    let size = (image.width, image.height);
+    for rect in boxes
+        .iter()
+        .filter_map(|b| pixel_rect(size, area, b, (scale_x, scale_y)))
+    {
+        match style {
+            ObscureStyle::Pixelate => {
+                let cell = ((PIXELATE_CELL * scale).round() as u32).max(2);
+                pixelate(image, rect, cell);
+            }
+            ObscureStyle::Blur => {
+                let radius = ((BLUR_RADIUS * scale).round() as u32).max(1);
+                for _ in 0..BLUR_PASSES {
+                    blur(image, rect, radius);
+                }
+            }
+        }
+    }
+}
+
+/// `(x, y, width, height)` of `b` in image pixels, clipped to the image.
+fn pixel_rect(
+    (image_width, image_height): (u32, u32),
+    area: &Geometry,
+    b: &Geometry,
+    (scale_x, scale_y): (f64, f64),
+) -> Option<(u32, u32, u32, u32)> {
+    let b = b.intersect(*area)?;
+    let x0 = (f64::from(b.x - area.x) * scale_x).floor() as u32;
+    let y0 = (f64::from(b.y - area.y) * scale_y).floor() as u32;
+    let x1 = ((f64::from(b.x - area.x + b.width) * scale_x).ceil() as u32).min(image_width);
+    let y1 = ((f64::from(b.y - area.y + b.height) * scale_y).ceil() as u32).min(image_height);
+    (x1 > x0 && y1 > y0).then_some((x0, y0, x1 - x0, y1 - y0))
+}
+
+/// Fill each `cell`-sized block of `rect` with its average color.
+fn pixelate(image: &mut CapturedImage, (x, y, width, height): (u32, u32, u32, u32), cell: u32) {
+    let stride = image.width as usize * 4;
+    for block_y in (y..y + height).step_by(cell as usize) {
+        for block_x in (x..x + width).step_by(cell as usize) {
+            let rows = block_y as usize..(block_y + cell).min(y + height) as usize;
+            let cols = block_x as usize..(block_x + cell).min(x + width) as usize;
+            let mut sum = [0u64; 4];
+            for row in rows.clone() {
+                for col in cols.clone() {
+                    let i = row * stride + col * 4;
+                    for (total, &value) in sum.iter_mut().zip(&image.data[i..i + 4]) {
+                        *total += u64::from(value);
+                    }
+                }
+            }
+            let count = (rows.len() * cols.len()) as u64;
+            let average = sum.map(|total| (total / count) as u8);
+            for row in rows.clone() {
+                for col in cols.clone() {
+                    let i = row * stride + col * 4;
+                    image.data[i..i + 4].copy_from_slice(&average);
+                }
+            }
+        }
+    }
+}
+
+/// One horizontal and one vertical box-blur pass over `rect`. Samples are
+/// taken only from inside `rect`, so nothing outside it bleeds in or changes.
+fn blur(image: &mut CapturedImage, (x, y, width, height): (u32, u32, u32, u32), radius: u32) {
+    let stride = image.width as usize * 4;
+    let (x, y, width, height) = (x as usize, y as usize, width as usize, height as usize);
+    let radius = radius as usize;
+    let mut line = Vec::new();
+    for row in y..y + height {
+        line.clear();
+        line.extend((x..x + width).map(|col| pixel(&image.data, row * stride + col * 4)));
+        for (offset, value) in box_filter(&line, radius).into_iter().enumerate() {
+            let i = row * stride + (x + offset) * 4;
+            image.data[i..i + 4].copy_from_slice(&value.map(|v| v as u8));
+        }
+    }
+    for col in x..x + width {
+        line.clear();
+        line.extend((y..y + height).map(|row| pixel(&image.data, row * stride + col * 4)));
+        for (offset, value) in box_filter(&line, radius).into_iter().enumerate() {
+            let i = (y + offset) * stride + col * 4;
+            image.data[i..i + 4].copy_from_slice(&value.map(|v| v as u8));
+        }
+    }
+}
+
+fn pixel(data: &[u8], i: usize) -> [f64; 4] {
+    [data[i], data[i + 1], data[i + 2], data[i + 3]].map(f64::from)
+}
+
+/// Moving average over `radius` neighbours on each side, clamped at the ends,
+/// per channel. Also softens the shadow mask in `beautify`.
+pub(crate) fn box_filter<const N: usize>(line: &[[f64; N]], radius: usize) -> Vec<[f64; N]> {
+    let len = line.len();
+    let mut prefix = vec![[0.0; N]; len + 1];
+    for (i, value) in line.iter().enumerate() {
+        for channel in 0..N {
+            prefix[i + 1][channel] = prefix[i][channel] + value[channel];
+        }
+    }
+    (0..len)
+        .map(|i| {
+            let start = i.saturating_sub(radius);
+            let end = (i + radius + 1).min(len);
+            let count = (end - start) as f64;
+            std::array::from_fn(|channel| (prefix[end][channel] - prefix[start][channel]) / count)
+        })
+        .collect()
+}
@@ -0,0 +1,325 @@
+//! `wl_output` and xdg-output enumeration shared by every Wayland client here.
+//!
+//! A client state embeds [`Outputs`], hands it the `wl_output` and
+//! `zxdg_output_manager_v1` globals from its registry handler and delegates the
+//! output events to it with `delegate_outputs!`.
+
+use anyhow::{Context, Result};
+use wayland_client::{
+    Connection, Dispatch, QueueHandle, WEnum,
+    protocol::{
+        wl_output::{self, Mode as WlOutputMode, Transform as WlTransform, WlOutput},
+        wl_registry::{self, WlRegistry},
+    },
+};
+use wayland_protocols::xdg::xdg_output::zv1::client::{
+    zxdg_output_manager_v1::{self, ZxdgOutputManagerV1},
+    zxdg_output_v1::{self, ZxdgOutputV1},
+};
+
+use crate::capture::{OutputInfo, is_rotated, mode_logical_size, output_scale, transform_name};
+use crate::geometry::Geometry;
+
+/// Index of an output in [`Outputs::entries`], the user data of its proxies.
+#[derive(Debug)]
+pub(crate) struct OutputKey(pub usize);
+
+pub(crate) struct OutputEntry {
+    pub output: WlOutput,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub transform: WlTransform,
+    pub xdg_output: Option<ZxdgOutputV1>,
+    pub pos_x: Option<i32>,
+    pub pos_y: Option<i32>,
+    pub mode_width: Option<i32>,
+    pub mode_height: Option<i32>,
+    pub scale: i32,
+    pub logical_x: Option<i32>,
+    pub logical_y: Option<i32>,
+    pub logical_width: Option<i32>,
+    pub logical_height: Option<i32>,
+}
+
+impl OutputEntry {
+    /// Logical size from xdg-output, or from the current mode and integer scale
+    /// on compositors without it.
+    pub fn logical_size(&self) -> Option<(i32, i32)> {
+        if let (Some(width), Some(height)) = (self.logical_width, self.logical_height) {
+            return Some((width, height));
+        }
+        Some(mode_logical_size(
+            (self.mode_width?, self.mode_height?),
+            self.scale,
+            is_rotated(self.transform),
+        ))
+    }
+
+    pub fn geometry(&self) -> Option<Geometry> {
+        let x = self.logical_x.or(self.pos_x)?;
+        let y = self.logical_y.or(self.pos_y)?;
+        let (width, height) = self.logical_size()?;
+        Geometry::new(x, y, width, height).ok()
+    }
+
+    /// The output as `list outputs` shows it; `None` until it has a name.
+    pub fn info(&self) -> Option<OutputInfo> {
+        let geometry = self.geometry()?;
+        let scale = match (self.mode_width, self.mode_height) {
+            (Some(width), Some(height)) => output_scale(
+                (width, height),
+                (geometry.width, geometry.height),
+                is_rotated(self.transform),
+            ),
+            _ => self.scale as f64,
+        };
+        Some(OutputInfo {
+            name: self.name.clone()?,
+            description: self.description.clone(),
+            geometry,
+            scale,
+            transform: transform_name(self.transform),
+        })
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Outputs {
+    pub entries: Vec<OutputEntry>,
+    manager: Option<ZxdgOutputManagerV1>,
+}
+
+impl Outputs {
+    /// Bind `interface` if it is one of ours; `false` leaves it to the caller.
+    pub fn bind<D>(
+        &mut self,
+        registry: &WlRegistry,
+        name: u32,
+        interface: &str,
+        version: u32,
+        qh: &QueueHandle<D>,
+    ) -> bool
+    where
+        D: Dispatch<WlOutput, OutputKey> + Dispatch<ZxdgOutputManagerV1, ()> + 'static,
+    {
+        match interface {
+            "wl_output" => {
+                let key = OutputKey(self.entries.len());
+                let output = registry.bind::<WlOutput, _, _>(name, version.min(4), qh, key);
+                self.entries.push(OutputEntry {
+                    output,
+                    name: None,
+                    description: None,
+                    transform: WlTransform::Normal,
+                    xdg_output: None,
+                    pos_x: None,
+                    pos_y: None,
+                    mode_width: None,
+                    mode_height: None,
+                    scale: 1,
+                    logical_x: None,
+                    logical_y: None,
+                    logical_width: None,
+                    logical_height: None,
+                });
+                true
+            }
+            "zxdg_output_manager_v1" => {
+                self.manager = Some(registry.bind(name, version.min(3), qh, ()));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Ask for the xdg-output of every bound output, after the roundtrip that
+    /// delivered the globals. `false` when there is no manager, so the
+    /// logical geometry roundtrip can be skipped.
+    pub fn request_logical<D>(&mut self, qh: &QueueHandle<D>) -> bool
+    where
+        D: Dispatch<ZxdgOutputV1, OutputKey> + 'static,
+    {
+        let Some(manager) = &self.manager else {
+            return false;
+        };
+        for (idx, entry) in self.entries.iter_mut().enumerate() {
+            entry.xdg_output = Some(manager.get_xdg_output(&entry.output, qh, OutputKey(idx)));
+        }
+        true
+    }
+}
+
+impl<D> Dispatch<WlOutput, OutputKey, D> for Outputs
+where
+    D: Dispatch<WlOutput, OutputKey> + AsMut<Outputs>,
+{
+    fn event(
+        state: &mut D,
+        _: &WlOutput,
+        event: wl_output::Event,
+        data: &OutputKey,
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+        let Some(entry) = state.as_mut().entries.get_mut(data.0) else {
+            return;
+        };
+        match event {
+            wl_output::Event::Geometry {
+                x, y, transform, ..
+            } => {
+                entry.pos_x = Some(x);
+                entry.pos_y = Some(y);
+                if let WEnum::Value(transform) = transform {
+                    entry.transform = transform;
+                }
+            }
+            wl_output::Event::Mode {
+                flags,
+                width,
+                height,
+                ..
+            } => {
+                let is_current = match flags {
+                    WEnum::Value(f) => f.contains(WlOutputMode::Current),
+                    WEnum::Unknown(_) => false,
+                };
+                if is_current {
+                    entry.mode_width = Some(width);
+                    entry.mode_height = Some(height);
+                }
+            }
+            wl_output::Event::Scale { factor } => {
+                entry.scale = factor.max(1);
+            }
+            wl_output::Event::Name { name } => {
+                entry.name = Some(name);
+            }
+            wl_output::Event::Description { description } => {
+                entry.description = Some(description);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ZxdgOutputV1, OutputKey, D> for Outputs
+where
+    D: Dispatch<ZxdgOutputV1, OutputKey> + AsMut<Outputs>,
+{
+    fn event(
+        state: &mut D,
+        _: &ZxdgOutputV1,
+        event: zxdg_output_v1::Event,
+        data: &OutputKey,
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+        let Some(entry) = state.as_mut().entries.get_mut(data.0) else {
+            return;
+        };
+        match event {
+            zxdg_output_v1::Event::LogicalPosition { x, y } => {
+                entry.logical_x = Some(x);
+                entry.logical_y = Some(y);
+            }
+            zxdg_output_v1::Event::LogicalSize { width, height } => {
+                entry.logical_width = Some(width);
+                entry.logical_height = Some(height);
+            }
+            zxdg_output_v1::Event::Name { name } => {
+                entry.name = Some(name);
+            }
+            // wl_output v4 descriptions win; xdg-output's is deprecated.
+            zxdg_output_v1::Event::Description { description } => {
+                entry.description.get_or_insert(description);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ZxdgOutputManagerV1, (), D> for Outputs
+where
+    D: Dispatch<ZxdgOutputManagerV1, ()>,
+{
+    fn event(
+        _: &mut D,
+        _: &ZxdgOutputManagerV1,
+        _: zxdg_output_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+    }
+}
+
+/// Route a client state's output events to its [`Outputs`].
+macro_rules! delegate_outputs {
+    ($state:ty) => {
+        wayland_client::delegate_dispatch!($state: [
+            wayland_client::protocol::wl_output::WlOutput: $crate::outputs::OutputKey
+        ] => $crate::outputs::Outputs);
+        wayland_client::delegate_dispatch!($state: [
+            wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1::ZxdgOutputV1:
+                $crate::outputs::OutputKey
+        ] => $crate::outputs::Outputs);
+        wayland_client::delegate_dispatch!($state: [
+            wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_manager_v1::ZxdgOutputManagerV1: ()
+        ] => $crate::outputs::Outputs);
+    };
+}
+pub(crate) use delegate_outputs;
+
+/// Outputs on a connection of their own, with their logical geometry.
+pub(crate) fn enumerate() -> Result<Vec<OutputEntry>> {
+    let conn = Connection::connect_to_env().context("Failed to connect to Wayland")?;
+    let mut event_queue = conn.new_event_queue();
+    let qh = event_queue.handle();
+
+    let _registry = conn.display().get_registry(&qh, ());
+
+    #[derive(Default)]
+    struct State {
+        outputs: Outputs,
+    }
+
+    impl AsMut<Outputs> for State {
+        fn as_mut(&mut self) -> &mut Outputs {
+            &mut self.outputs
+        }
+    }
+
+    impl Dispatch<WlRegistry, ()> for State {
+        fn event(
+            state: &mut Self,
+            registry: &WlRegistry,
+            event: wl_registry::Event,
+            _: &(),
+            _: &Connection,
+            qh: &QueueHandle<Self>,
+        ) {
+            if let wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } = event
+            {
+                state.outputs.bind(registry, name, &interface, version, qh);
+            }
+        }
+    }
+
+    delegate_outputs!(State);
+
+    let mut state = State::default();
+    event_queue
+        .roundtrip(&mut state)
+        .context("Failed to initialize Wayland outputs")?;
+    if state.outputs.request_logical(&qh) {
+        event_queue
+            .roundtrip(&mut state)
+            .context("Failed to receive Wayland output geometry")?;
+    }
+    Ok(state.outputs.entries)
+}
@@ -0,0 +1,222 @@
+//! Minimal drawing for the freeze overlay: the usage hint (embedded 5x7 bitmap
+//! font) and debug outlines of selection candidates.
+
+use crate::config::FreezeHintPosition;
+use crate::geometry::Geometry;
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+/// Glyph cell width including one column of spacing.
+const CELL_WIDTH: u32 = GLYPH_WIDTH + 1;
+const PADDING: u32 = 4;
+const MARGIN: u32 = 16;
+
+/// Printable ASCII (0x20..=0x7E), one byte per column, least significant bit on top.
+const FONT_5X7: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // '!'
+    [0x00, 0x07, 0x00, 0x07, 0x00], // '"'
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // '#'
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // '$'
+    [0x23, 0x13, 0x08, 0x64, 0x62], // '%'
+    [0x36, 0x49, 0x55, 0x22, 0x50], // '&'
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '\''
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // '('
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // ')'
+    [0x14, 0x08, 0x3E, 0x08, 0x14], // '*'
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // '+'
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ','
+    [0x08, 0x08, 0x08, 0x08, 0x08], // '-'
+    [0x00, 0x60, 0x60, 0x00, 0x00], // '.'
+    [0x20, 0x10, 0x08, 0x04, 0x02], // '/'
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // '0'
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // '1'
+    [0x42, 0x61, 0x51, 0x49, 0x46], // '2'
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // '3'
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // '4'
+    [0x27, 0x45, 0x45, 0x45, 0x39], // '5'
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // '6'
+    [0x01, 0x71, 0x09, 0x05, 0x03], // '7'
+    [0x36, 0x49, 0x49, 0x49, 0x36], // '8'
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // '9'
+    [0x00, 0x36, 0x36, 0x00, 0x00], // ':'
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ';'
+    [0x08, 0x14, 0x22, 0x41, 0x00], // '<'
+    [0x14, 0x14, 0x14, 0x14, 0x14], // '='
+    [0x00, 0x41, 0x22, 0x14, 0x08], // '>'
+    [0x02, 0x01, 0x51, 0x09, 0x06], // '?'
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // '@'
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // 'A'
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // 'B'
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // 'C'
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // 'D'
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // 'E'
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // 'F'
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // 'G'
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // 'H'
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // 'I'
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // 'J'
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // 'K'
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // 'L'
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // 'M'
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // 'N'
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // 'O'
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // 'P'
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // 'Q'
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // 'R'
+    [0x46, 0x49, 0x49, 0x49, 0x31], // 'S'
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // 'T'
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // 'U'
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // 'V'
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // 'W'
+    [0x63, 0x14, 0x08, 0x14, 0x63], // 'X'
+    [0x07, 0x08, 0x70, 0x08, 0x07], // 'Y'
+    [0x61, 0x51, 0x49, 0x45, 0x43], // 'Z'
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // '['
+    [0x02, 0x04, 0x08, 0x10, 0x20], // '\\'
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ']'
+    [0x04, 0x02, 0x01, 0x02, 0x04], // '^'
+    [0x40, 0x40, 0x40, 0x40, 0x40], // '_'
+    [0x00, 0x01, 0x02, 0x04, 0x00], // '`'
+    [0x20, 0x54, 0x54, 0x54, 0x78], // 'a'
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // 'b'
+    [0x38, 0x44, 0x44, 0x44, 0x20], // 'c'
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // 'd'
+    [0x38, 0x54, 0x54, 0x54, 0x18], // 'e'
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // 'f'
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // 'g'
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // 'h'
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // 'i'
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // 'j'
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // 'k'
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // 'l'
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // 'm'
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // 'n'
+    [0x38, 0x44, 0x44, 0x44, 0x38], // 'o'
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // 'p'
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // 'q'
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // 'r'
+    [0x48, 0x54, 0x54, 0x54, 0x20], // 's'
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // 't'
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // 'u'
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // 'v'
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // 'w'
+    [0x44, 0x28, 0x10, 0x28, 0x44], // 'x'
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // 'y'
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // 'z'
+    [0x00, 0x08, 0x36, 0x41, 0x00], // '{'
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // '|'
+    [0x00, 0x41, 0x36, 0x08, 0x00], // '}'
+    [0x10, 0x08, 0x08, 0x10, 0x08], // '~'
+];
+
+fn glyph(c: char) -> &'static [u8; 5] {
+    let index = match c {
+        ' '..='~' => c as usize - ' ' as usize,
+        _ => '?' as usize - ' ' as usize,
+    };
+    &FONT_5X7[index]
+}
+
+/// Draw `text` on a dimmed box into an RGBA image. `scale` is the size of one
+/// font pixel in image pixels. Text that doesn't fit is clipped.
+pub fn draw_hint(
+    data: &mut [u8],
+    width: u32,
+    height: u32,
+    text: &str,
+    position: FreezeHintPosition,
+    scale: u32,
+) {
+    let scale = scale.max(1);
+    let chars = text.chars().count() as u32;
+    if chars == 0 || data.len() < (width as usize * height as usize * 4) {
+        return;
+    }
+    let box_w = ((chars * CELL_WIDTH - 1 + PADDING * 2) * scale).min(width);
+    let box_h = ((GLYPH_HEIGHT + PADDING * 2) * scale).min(height);
+    let box_x = (width - box_w) / 2;
+    let box_y = match position {
+        FreezeHintPosition::Top => (MARGIN * scale).min(height - box_h),
+        FreezeHintPosition::Center => (height - box_h) / 2,
+        FreezeHintPosition::Bottom => height - box_h - (MARGIN * scale).min(height - box_h),
+    };
+
+    // Dim the background so the text stays readable on any wallpaper.
+    for y in box_y..box_y + box_h {
+        for x in box_x..box_x + box_w {
+            let offset = (y as usize * width as usize + x as usize) * 4;
+            for channel in &mut data[offset..offset + 3] {
+                *channel = (*channel as u16 * 2 / 5) as u8;
+            }
+        }
+    }
+
+    let text_x = box_x + PADDING * scale;
+    let text_y = box_y + PADDING * scale;
+    for (i, c) in text.chars().enumerate() {
+        for (col, bits) in glyph(c).iter().enumerate() {
+            for row in 0..GLYPH_HEIGHT {
+                if bits & (1 << row) == 0 {
+                    continue;
+                }
+                let px = text_x + (i as u32 * CELL_WIDTH + col as u32) * scale;
+                let py = text_y + row * scale;
+                for y in py..(py + scale).min(height) {
+                    for x in px..(px + scale).min(width) {
+                        let offset = (y as usize * width as usize + x as usize) * 4;
+                        data[offset..offset + 3].copy_from_slice(&[0xff, 0xff, 0xff]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Map a logical rectangle onto the buffer of an output with logical geometry
+/// `output`, clipped to the buffer. `None` if it doesn't touch the output.
+pub fn logical_to_buffer(
+    rect: Geometry,
+    output: Geometry,
+    width: u32,
+    height: u32,
+) -> Option<(u32, u32, u32, u32)> {
+    let visible = rect.intersect(output)?;
+    let scale_x = width as f64 / output.width as f64;
+    let scale_y = height as f64 / output.height as f64;
+    let x0 = ((visible.x - output.x) as f64 * scale_x).round() as u32;
+    let y0 = ((visible.y - output.y) as f64 * scale_y).round() as u32;
+    let x1 = (((visible.x + visible.width - output.x) as f64 * scale_x).round() as u32).min(width);
+    let y1 =
+        (((visible.y + visible.height - output.y) as f64 * scale_y).round() as u32).min(height);
+    (x1 > x0 && y1 > y0).then_some((x0, y0, x1 - x0, y1 - y0))
+}
+
+/// Draw a magenta outline of `thickness` pixels just inside `rect` (buffer pixels).
+pub fn draw_outline(
+    data: &mut [u8],
+    width: u32,
+    height: u32,
+    rect: (u32, u32, u32, u32),
+    thickness: u32,
+) {
+    let (x, y, w, h) = rect;
+    if data.len() < (width as usize * height as usize * 4) || x >= width || y >= height {
+        return;
+    }
+    let right = (x + w).min(width);
+    let bottom = (y + h).min(height);
+    let thickness = thickness.max(1);
+    for py in y..bottom {
+        for px in x..right {
+            let edge = px < x + thickness
+                || py < y + thickness
+                || px + thickness >= right
+                || py + thickness >= bottom;
+            if edge {
+                let offset = (py as usize * width as usize + px as usize) * 4;
+                data[offset..offset + 3].copy_from_slice(&[0xff, 0x00, 0xff]);
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! `hyprshot-rs paste [PATH]`: save the image on the clipboard through the
+//! same pipeline as a capture, the reverse of `--clipboard-only`.
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::path::Path;
+
+use crate::backend::{CaptureBackend, CapturedImage};
+use crate::cli::{
+    Args, output_filename, resolve_filename_template, resolve_format, resolve_notif_timeout,
+};
+use crate::clipboard;
+use crate::config::{self, ImageFormat, PngCompression};
+use crate::editor;
+use crate::filename;
+use crate::geometry::Geometry;
+use crate::metadata::Metadata;
+use crate::png_encoder;
+use crate::save;
+
+/// Hands out the pasted image as if it had just been captured.
+struct PastedBackend {
+    image: CapturedImage,
+}
+
+impl CaptureBackend for PastedBackend {
+    fn capture_region(&mut self, _geometry: &Geometry) -> Result<CapturedImage> {
+        Ok(self.image.clone())
+    }
+
+    fn capture_output(&mut self, _name: &str) -> Result<CapturedImage> {
+        Ok(self.image.clone())
+    }
+
+    fn to_png(&self, image: &CapturedImage, compression: PngCompression) -> Result<Vec<u8>> {
+        png_encoder::encode(image, compression.level())
+    }
+}
+
+/// Save the clipboard image to `path` (a file, or a directory that gets a
+/// name from the filename template), or like a capture without one.
+pub fn handle_paste(args: &Args, config: &config::Config, path: Option<&Path>) -> Result<()> {
+    let debug = args.debug;
+    let png = clipboard::paste("image/png", debug)?
+        .context("The clipboard holds no image (image/png)")?;
+    let image = editor::decode_png(&png).context("The clipboard image isn't a valid PNG")?;
+    let geometry = Geometry::new(
+        0,
+        0,
+        i32::try_from(image.width).context("The clipboard image is too wide")?,
+        i32::try_from(image.height).context("The clipboard image is too tall")?,
+    )?;
+    if debug {
+        eprintln!("Pasted a {}x{} image", image.width, image.height);
+    }
+
+    let now = Local::now();
+    let (save_fullpath, format) = match path {
+        Some(file) if !file.is_dir() => {
+            let format =
+                ImageFormat::from_extension(file).unwrap_or_else(|| resolve_format(args, config));
+            (file.to_path_buf(), format)
+        }
+        _ => {
+            let dir = match path {
+                Some(dir) => dir.to_path_buf(),
+                None => filename::expand_directory(
+                    &config::get_screenshots_dir(args.output_folder.clone(), config, debug)?,
+                    now,
+                ),
+            };
+            let dir =
+                config::ensure_directory(&dir.to_string_lossy(), config.paths.create_missing_dirs)?;
+            let format = resolve_format(args, config);
+            let template = resolve_filename_template(args, config);
+            filename::validate(template)?;
+            let mut fields = filename::Fields::new(now);
+            fields.mode = Some("paste".to_string());
+            let name = output_filename(args, format, template, &dir, &mut fields);
+            (dir.join(name), format)
+        }
+    };
+    let save_fullpath = filename::resolve_conflict(&save_fullpath, config.paths.on_conflict)?;
+    if debug {
+        eprintln!("Saving in: {}", save_fullpath.display());
+    }
+
+    let options = save::SaveOptions {
+        // It came from there.
+        clipboard: false,
+        png_compression: args
+            .png_compression
+            .unwrap_or(config.capture.png_compression),
+        optimize: args.optimize,
+        format,
+        quality: args.quality.unwrap_or(config.capture.quality),
+        flatten_background: config::parse_hex_color(&config.capture.flatten_background)
+            .context("Invalid capture.flatten_background")?,
+        resize: args.resize,
+        metadata: Metadata::new(args.metadata.unwrap_or(config.capture.metadata), now),
+        silent: args.silent || !config.capture.notification,
+        notif_timeout: resolve_notif_timeout(args, config),
+        also_save: args.also_save.clone(),
+        create_missing_dirs: config.paths.create_missing_dirs,
+        keep_recent: config.paths.keep_recent,
+        post_capture: config.hooks.post_capture.clone(),
+        debug,
+        ..Default::default()
+    };
+    let mut backend = PastedBackend { image };
+    save::save_geometry(&mut backend, &geometry, None, &save_fullpath, &options)?;
+    Ok(())
+}
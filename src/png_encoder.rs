@@ -0,0 +1,320 @@
+//! PNG encoding with real zlib levels 0-9 and per-row adaptive filters, and
+//! the `--optimize` pass: the smallest lossless color type the pixels allow
+//! (palette, grayscale, no alpha), tried with every filter strategy at level 9.
+
+use anyhow::{Context, Result, bail};
+use flate2::{Compression, write::ZlibEncoder};
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::backend::{CapturedImage, png_chunk};
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Size of each IDAT chunk.
+const IDAT_SIZE: usize = 1 << 20;
+
+/// PNG color types.
+const GRAY: u8 = 0;
+const RGB: u8 = 2;
+const INDEXED: u8 = 3;
+const GRAY_ALPHA: u8 = 4;
+const RGBA: u8 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Filter {
+    /// One PNG filter type (0-4) for every row.
+    Fixed(u8),
+    /// Per row, the filter whose output has the smallest sum of absolute
+    /// values (the libpng heuristic).
+    MinSum,
+}
+
+impl Filter {
+    const ALL: [Self; 6] = [
+        Self::Fixed(0),
+        Self::Fixed(1),
+        Self::Fixed(2),
+        Self::Fixed(3),
+        Self::Fixed(4),
+        Self::MinSum,
+    ];
+}
+
+/// Pixels in a PNG color type, rows packed but unfiltered.
+struct Raster {
+    width: u32,
+    height: u32,
+    color: u8,
+    depth: u8,
+    data: Vec<u8>,
+    palette: Vec<u8>,
+    /// Alpha of the first palette entries; the rest are opaque.
+    trns: Vec<u8>,
+}
+
+impl Raster {
+    fn rgba(image: &CapturedImage) -> Self {
+        Self {
+            width: image.width,
+            height: image.height,
+            color: RGBA,
+            depth: 8,
+            data: image.data.clone(),
+            palette: Vec::new(),
+            trns: Vec::new(),
+        }
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        let channels = match self.color {
+            RGB => 3,
+            GRAY_ALPHA => 2,
+            RGBA => 4,
+            _ => 1,
+        };
+        channels * usize::from(self.depth)
+    }
+
+    fn stride(&self) -> usize {
+        (self.width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+}
+
+/// Encode 8-bit RGBA at zlib `level` (0 stores the pixels uncompressed).
+pub(crate) fn encode(image: &CapturedImage, level: u8) -> Result<Vec<u8>> {
+    let filter = if level == 0 {
+        Filter::Fixed(0)
+    } else {
+        Filter::MinSum
+    };
+    write_png(&Raster::rgba(image), level.min(9), filter)
+}
+
+/// Encode `image` as small as this encoder can: reduce the color type, then
+/// keep the smallest of all filter strategies at level 9, tried in parallel.
+/// The plain RGBA encoding competes too, so small images whose palette costs
+/// more than it saves never grow.
+pub(crate) fn optimize(image: &CapturedImage) -> Result<Vec<u8>> {
+    let reduced = reduce(image);
+    let rgba = Raster::rgba(image);
+    let mut jobs: Vec<(&Raster, Filter)> = Filter::ALL
+        .iter()
+        .map(|&filter| (&reduced, filter))
+        .collect();
+    if reduced.color != RGBA {
+        jobs.push((&rgba, Filter::MinSum));
+    }
+    let encoded: Vec<Result<Vec<u8>>> = std::thread::scope(|scope| {
+        let workers: Vec<_> = jobs
+            .into_iter()
+            .map(|(raster, filter)| scope.spawn(move || write_png(raster, 9, filter)))
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("PNG optimization thread panicked")))
+            })
+            .collect()
+    });
+    let mut smallest: Option<Vec<u8>> = None;
+    for png in encoded {
+        let png = png?;
+        if smallest.as_ref().is_none_or(|best| png.len() < best.len()) {
+            smallest = Some(png);
+        }
+    }
+    smallest.context("No PNG filter strategy produced an image")
+}
+
+/// The smallest color type that holds every pixel of `image` exactly.
+fn reduce(image: &CapturedImage) -> Raster {
+    let pixels = || image.data.chunks_exact(4);
+    let opaque = pixels().all(|px| px[3] == 255);
+    let gray = pixels().all(|px| px[0] == px[1] && px[1] == px[2]);
+
+    let mut colors: HashMap<[u8; 4], usize> = HashMap::new();
+    for px in pixels() {
+        let next = colors.len();
+        colors.entry([px[0], px[1], px[2], px[3]]).or_insert(next);
+        if colors.len() > 256 {
+            break;
+        }
+    }
+    // A palette beats 8-bit gray only once it can pack several pixels a byte.
+    if colors.len() <= 256 && !(gray && opaque && colors.len() > 16) {
+        return indexed(image, colors);
+    }
+
+    let (color, channels): (u8, &[usize]) = match (gray, opaque) {
+        (true, true) => (GRAY, &[0]),
+        (true, false) => (GRAY_ALPHA, &[0, 3]),
+        (false, true) => (RGB, &[0, 1, 2]),
+        (false, false) => return Raster::rgba(image),
+    };
+    Raster {
+        width: image.width,
+        height: image.height,
+        color,
+        depth: 8,
+        data: pixels()
+            .flat_map(|px| channels.iter().map(move |&c| px[c]))
+            .collect(),
+        palette: Vec::new(),
+        trns: Vec::new(),
+    }
+}
+
+/// Palette image at the smallest bit depth for `colors` (color to first
+/// appearance), with translucent entries first so tRNS stays short.
+fn indexed(image: &CapturedImage, colors: HashMap<[u8; 4], usize>) -> Raster {
+    let mut entries: Vec<([u8; 4], usize)> = colors.into_iter().collect();
+    entries.sort_by_key(|&(color, seen)| (color[3] == 255, seen));
+    let index: HashMap<[u8; 4], u8> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, &(color, _))| (color, i as u8))
+        .collect();
+    let depth: u8 = match entries.len() {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8,
+    };
+
+    let per_byte = 8 / usize::from(depth);
+    let width = image.width as usize;
+    let stride = width.div_ceil(per_byte);
+    let mut data = vec![0u8; stride * image.height as usize];
+    for (i, px) in image.data.chunks_exact(4).enumerate() {
+        let (y, x) = (i / width, i % width);
+        let shift = 8 - usize::from(depth) * (x % per_byte + 1);
+        data[y * stride + x / per_byte] |= index[&[px[0], px[1], px[2], px[3]]] << shift;
+    }
+
+    Raster {
+        width: image.width,
+        height: image.height,
+        color: INDEXED,
+        depth,
+        data,
+        palette: entries
+            .iter()
+            .flat_map(|(color, _)| color[..3].to_vec())
+            .collect(),
+        trns: entries
+            .iter()
+            .map(|(color, _)| color[3])
+            .take_while(|&alpha| alpha < 255)
+            .collect(),
+    }
+}
+
+fn write_png(raster: &Raster, level: u8, filter: Filter) -> Result<Vec<u8>> {
+    if raster.width == 0 || raster.height == 0 {
+        bail!("Can't encode an empty image as PNG");
+    }
+    let mut zlib = ZlibEncoder::new(Vec::new(), Compression::new(u32::from(level)));
+    zlib.write_all(&filtered(raster, filter))
+        .context("Failed to compress PNG data")?;
+    let idat = zlib.finish().context("Failed to compress PNG data")?;
+
+    let mut ihdr = raster.width.to_be_bytes().to_vec();
+    ihdr.extend_from_slice(&raster.height.to_be_bytes());
+    // Deflate, adaptive filtering, no interlacing.
+    ihdr.extend_from_slice(&[raster.depth, raster.color, 0, 0, 0]);
+
+    let mut png = SIGNATURE.to_vec();
+    png.extend(png_chunk(b"IHDR", &ihdr));
+    // sRGB, perceptual, so viewers on wide-gamut displays don't stretch the
+    // colors to their gamut.
+    png.extend(png_chunk(b"sRGB", &[0]));
+    if raster.color == INDEXED {
+        png.extend(png_chunk(b"PLTE", &raster.palette));
+        if !raster.trns.is_empty() {
+            png.extend(png_chunk(b"tRNS", &raster.trns));
+        }
+    }
+    for part in idat.chunks(IDAT_SIZE) {
+        png.extend(png_chunk(b"IDAT", part));
+    }
+    png.extend(png_chunk(b"IEND", &[]));
+    Ok(png)
+}
+
+/// Every row prefixed with its filter type and filtered against the row above.
+fn filtered(raster: &Raster, filter: Filter) -> Vec<u8> {
+    let stride = raster.stride();
+    // Filters compare whole pixels, or bytes below 8 bits per pixel.
+    let bpp = (raster.bits_per_pixel() / 8).max(1);
+    let zeros = vec![0u8; stride];
+    let mut out = Vec::with_capacity((stride + 1) * raster.height as usize);
+    let mut row_out = vec![0u8; stride];
+    let mut best = vec![0u8; stride];
+    for (y, row) in raster.data.chunks_exact(stride).enumerate() {
+        let prev = if y == 0 {
+            &zeros[..]
+        } else {
+            &raster.data[(y - 1) * stride..y * stride]
+        };
+        match filter {
+            Filter::Fixed(kind) => {
+                apply(kind, row, prev, bpp, &mut row_out);
+                out.push(kind);
+                out.extend_from_slice(&row_out);
+            }
+            Filter::MinSum => {
+                let mut best_kind = 0;
+                let mut best_sum = u64::MAX;
+                for kind in 0..5 {
+                    apply(kind, row, prev, bpp, &mut row_out);
+                    let sum: u64 = row_out
+                        .iter()
+                        .map(|&b| u64::from((b as i8).unsigned_abs()))
+                        .sum();
+                    if sum < best_sum {
+                        best_sum = sum;
+                        best_kind = kind;
+                        best.copy_from_slice(&row_out);
+                    }
+                }
+                out.push(best_kind);
+                out.extend_from_slice(&best);
+            }
+        }
+    }
+    out
+}
+
+fn apply(kind: u8, row: &[u8], prev: &[u8], bpp: usize, out: &mut [u8]) {
+    for i in 0..row.len() {
+        let left = if i >= bpp { row[i - bpp] } else { 0 };
+        let up = prev[i];
+        let up_left = if i >= bpp { prev[i - bpp] } else { 0 };
+        let predicted = match kind {
+            1 => left,
+            2 => up,
+            3 => ((u16::from(left) + u16::from(up)) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => 0,
+        };
+        out[i] = row[i].wrapping_sub(predicted);
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let (pa, pb, pc) = (
+        (p - i16::from(a)).abs(),
+        (p - i16::from(b)).abs(),
+        (p - i16::from(c)).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
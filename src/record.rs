@@ -0,0 +1,392 @@
+//! `--record`: capture the selected geometry repeatedly and pipe the frames
+//! into `ffmpeg`, which encodes them to mp4/webm/mkv or an animated GIF/WebP.
+//! With `--still` the frames are saved as numbered PNGs instead, one every
+//! `--every` interval, for timelapses.
+//!
+//! The recording stops on SIGINT/SIGTERM or when `hyprshot-rs --record` is run
+//! again, which finds the running recorder through a pid file.
+
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use crate::backend::CaptureBackend;
+use crate::config::{PngCompression, RecordFormat};
+use crate::geometry::Geometry;
+
+/// Frames per second handed to the encoder unless `record.fps` says otherwise.
+/// Captures slower than this repeat the previous frame, so the video keeps
+/// real-time speed.
+pub const FPS: u32 = 30;
+/// Default for GIF/WebP, where every frame adds to a file meant to be shared.
+pub const ANIMATED_FPS: u32 = 15;
+/// Time between `--still` frames unless `--every` says otherwise.
+pub const STILL_INTERVAL: Duration = Duration::from_secs(30);
+/// How often a `--still` recording checks for a stop request while waiting.
+const STOP_POLL: Duration = Duration::from_millis(200);
+
+pub struct RecordOptions {
+    pub fps: u32,
+    /// Stop on its own after this long.
+    pub max_duration: Option<Duration>,
+}
+
+pub(crate) fn default_fps(format: RecordFormat) -> u32 {
+    if format.is_animated_image() {
+        ANIMATED_FPS
+    } else {
+        FPS
+    }
+}
+
+const PID_FILE: &str = "hyprshot-rs-record.pid";
+
+static STOP: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_stop(_signal: libc::c_int) {
+    STOP.store(true, Ordering::SeqCst);
+}
+
+/// `path` with the extension of `format`, e.g. the default `.png` filename
+/// becomes `.mp4`.
+pub(crate) fn video_path(path: &Path, format: RecordFormat) -> PathBuf {
+    if RecordFormat::from_extension(path) == Some(format) {
+        path.to_path_buf()
+    } else {
+        path.with_extension(format.extension())
+    }
+}
+
+/// `ffmpeg` arguments reading raw RGBA frames of `width`x`height` from stdin.
+pub(crate) fn encoder_args(path: &Path, width: u32, height: u32, fps: u32) -> Vec<String> {
+    let mut args: Vec<String> = [
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-y",
+        "-f",
+        "rawvideo",
+        "-pix_fmt",
+        "rgba",
+        "-video_size",
+    ]
+    .map(String::from)
+    .into();
+    args.push(format!("{}x{}", width, height));
+    args.push("-framerate".into());
+    args.push(fps.to_string());
+    args.extend(["-i", "-"].map(String::from));
+    match RecordFormat::from_extension(path) {
+        // One palette for the whole clip, built from the frames themselves.
+        Some(RecordFormat::Gif) => {
+            args.extend(
+                [
+                    "-vf",
+                    "split[a][b];[a]palettegen=stats_mode=diff[p];[b][p]paletteuse=dither=bayer",
+                    "-loop",
+                    "0",
+                ]
+                .map(String::from),
+            );
+            args.push(path.to_string_lossy().into_owned());
+            return args;
+        }
+        Some(RecordFormat::Webp) => {
+            args.extend(
+                [
+                    "-c:v",
+                    "libwebp",
+                    "-lossless",
+                    "0",
+                    "-quality",
+                    "75",
+                    "-loop",
+                    "0",
+                ]
+                .map(String::from),
+            );
+            args.push(path.to_string_lossy().into_owned());
+            return args;
+        }
+        _ => {}
+    }
+    // yuv420p needs even dimensions.
+    args.extend(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"].map(String::from));
+    let codec: &[&str] = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("webm") => &["-c:v", "libvpx-vp9", "-b:v", "0", "-crf", "32"],
+        _ => &["-c:v", "libx264", "-preset", "veryfast", "-crf", "23"],
+    };
+    args.extend(codec.iter().map(|s| s.to_string()));
+    args.extend(["-pix_fmt", "yuv420p"].map(String::from));
+    args.push(path.to_string_lossy().into_owned());
+    args
+}
+
+/// Number of frames that should have been written `elapsed` after the start.
+pub(crate) fn frames_due(elapsed: Duration, fps: u32) -> u64 {
+    (elapsed.as_secs_f64() * fps as f64) as u64 + 1
+}
+
+fn pid_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(PID_FILE)
+}
+
+/// Whether `pid` is a running hyprshot-rs, so a stale pid file reused by an
+/// unrelated process is never signalled.
+fn is_recorder(pid: libc::pid_t) -> bool {
+    let Ok(comm) = fs::read_to_string(format!("/proc/{}/comm", pid)) else {
+        return false;
+    };
+    let own = std::env::current_exe()
+        .ok()
+        .and_then(|exe| {
+            exe.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "hyprshot-rs".to_string());
+    // The kernel truncates comm to 15 bytes.
+    let own: String = own.chars().take(15).collect();
+    comm.trim_end() == own
+}
+
+/// Stop a recording started by another hyprshot-rs process. Returns whether
+/// one was running.
+pub fn stop_running(debug: bool) -> Result<bool> {
+    let path = pid_path();
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Ok(false);
+    };
+    let pid = content.trim().parse::<libc::pid_t>().ok();
+    match pid {
+        Some(pid) if pid != std::process::id() as libc::pid_t && is_recorder(pid) => {
+            if unsafe { libc::kill(pid, libc::SIGINT) } != 0 {
+                return Err(std::io::Error::last_os_error())
+                    .with_context(|| format!("Failed to stop recording process {}", pid));
+            }
+            if debug {
+                eprintln!("Sent SIGINT to recording process {}", pid);
+            }
+            Ok(true)
+        }
+        _ => {
+            if debug {
+                eprintln!("Removing stale recording pid file {}", path.display());
+            }
+            let _ = fs::remove_file(&path);
+            Ok(false)
+        }
+    }
+}
+
+/// Removes the pid file when the recording ends, however it ends.
+struct PidFile(PathBuf);
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Announce this process as the running recorder and stop on SIGINT/SIGTERM.
+fn start_session() -> Result<PidFile> {
+    let pid_path = pid_path();
+    fs::write(&pid_path, std::process::id().to_string()).with_context(|| {
+        format!(
+            "Failed to write recording pid file '{}'",
+            pid_path.display()
+        )
+    })?;
+
+    let handler = request_stop as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+    Ok(PidFile(pid_path))
+}
+
+/// Name of the `index`th `--still` frame (from 1), sorted and globbable as
+/// `frame-%05d.png` by ffmpeg.
+pub(crate) fn still_frame_name(index: u64) -> String {
+    format!("frame-{:05}.png", index)
+}
+
+/// Save `geometry` into `dir` as numbered PNGs, one every `every`, until
+/// stopped or `max_duration` has passed. A failed capture skips its frame,
+/// so an output going to sleep doesn't end a long timelapse. Returns the
+/// number of frames saved.
+pub fn record_stills(
+    backend: &mut dyn CaptureBackend,
+    geometry: &Geometry,
+    dir: &Path,
+    every: Duration,
+    max_duration: Option<Duration>,
+    compression: PngCompression,
+    debug: bool,
+) -> Result<u64> {
+    if every.is_zero() {
+        bail!("--every must be longer than zero");
+    }
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create frame directory '{}'", dir.display()))?;
+    let _pid_file = start_session()?;
+    eprintln!(
+        "Saving a frame every {:.1}s to {} - run `hyprshot-rs --record` again or press Ctrl+C to stop",
+        every.as_secs_f64(),
+        dir.display()
+    );
+
+    let start = Instant::now();
+    let mut saved: u64 = 0;
+    for tick in 0u32.. {
+        let due = every.checked_mul(tick).unwrap_or(Duration::MAX);
+        if max_duration.is_some_and(|max| due > max) {
+            break;
+        }
+        while let Some(wait) = (start + due).checked_duration_since(Instant::now()) {
+            if STOP.load(Ordering::SeqCst) {
+                break;
+            }
+            sleep(wait.min(STOP_POLL));
+        }
+        if STOP.load(Ordering::SeqCst) {
+            break;
+        }
+        let png = backend
+            .capture_region(geometry)
+            .and_then(|frame| backend.to_png(&frame, compression));
+        match png {
+            Ok(png) => {
+                let path = dir.join(still_frame_name(saved + 1));
+                fs::write(&path, png)
+                    .with_context(|| format!("Failed to save frame '{}'", path.display()))?;
+                saved += 1;
+                if debug {
+                    eprintln!("Saved {}", path.display());
+                }
+            }
+            Err(err) => eprintln!("Warning: skipped a frame: {:#}", err),
+        }
+    }
+    if debug {
+        eprintln!(
+            "Saved {} frames in {:.1}s",
+            saved,
+            start.elapsed().as_secs_f64()
+        );
+    }
+    Ok(saved)
+}
+
+/// Record `geometry` into `path` until stopped or `options.max_duration` has
+/// passed.
+pub fn record(
+    backend: &mut dyn CaptureBackend,
+    geometry: &Geometry,
+    path: &Path,
+    options: &RecordOptions,
+    debug: bool,
+) -> Result<()> {
+    let fps = options.fps;
+    let _pid_file = start_session()?;
+
+    let first = backend.capture_region(geometry)?;
+    let (width, height) = (first.width, first.height);
+    let args = encoder_args(path, width, height, fps);
+    if debug {
+        eprintln!(
+            "Recording {} as {}x{}: ffmpeg {:?}",
+            geometry, width, height, args
+        );
+    }
+    // Own process group: Ctrl+C in a terminal reaches only hyprshot-rs, which
+    // then closes stdin so ffmpeg finishes the file.
+    let mut encoder = Command::new("ffmpeg")
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .process_group(0)
+        .spawn()
+        .map_err(|err| match err.kind() {
+            ErrorKind::NotFound => anyhow::anyhow!("--record needs ffmpeg in PATH"),
+            _ => anyhow::Error::new(err).context("Failed to start ffmpeg"),
+        })?;
+    let mut stdin = encoder.stdin.take().context("ffmpeg stdin is not piped")?;
+    match options.max_duration {
+        Some(max) => eprintln!(
+            "Recording to {} for up to {:.1}s - run `hyprshot-rs --record` again or press Ctrl+C to stop early",
+            path.display(),
+            max.as_secs_f64()
+        ),
+        None => eprintln!(
+            "Recording to {} - run `hyprshot-rs --record` again or press Ctrl+C to stop",
+            path.display()
+        ),
+    }
+
+    let start = Instant::now();
+    let mut written: u64 = 0;
+    let mut frame = first;
+    let result = loop {
+        let elapsed = start.elapsed();
+        let due = match options.max_duration {
+            // The last frame due before the limit, so the clip is no longer than asked.
+            Some(max) if elapsed >= max => frames_due(max, fps).saturating_sub(1).max(1),
+            _ => frames_due(elapsed, fps),
+        };
+        let mut write_error = None;
+        while written < due {
+            if let Err(err) = stdin.write_all(&frame.data) {
+                write_error = Some(err);
+                break;
+            }
+            written += 1;
+        }
+        if let Some(err) = write_error {
+            break Err(anyhow::Error::new(err).context("ffmpeg stopped accepting frames"));
+        }
+        if STOP.load(Ordering::SeqCst) || options.max_duration.is_some_and(|max| elapsed >= max) {
+            break Ok(());
+        }
+        let next_frame = start + Duration::from_secs_f64(written as f64 / fps as f64);
+        if let Some(wait) = next_frame.checked_duration_since(Instant::now()) {
+            sleep(wait);
+        }
+        frame = match backend.capture_region(geometry) {
+            Ok(next) if (next.width, next.height) == (width, height) => next,
+            Ok(next) => {
+                break Err(anyhow::anyhow!(
+                    "Capture size changed from {}x{} to {}x{} while recording",
+                    width,
+                    height,
+                    next.width,
+                    next.height
+                ));
+            }
+            Err(err) => break Err(err),
+        };
+    };
+
+    drop(stdin);
+    let status = encoder.wait().context("Failed to wait for ffmpeg")?;
+    if debug {
+        eprintln!(
+            "Recorded {} frames in {:.1}s",
+            written,
+            start.elapsed().as_secs_f64()
+        );
+    }
+    // A failing encoder also breaks the pipe; its exit status is the real error.
+    if !status.success() {
+        bail!("ffmpeg failed ({})", status);
+    }
+    result
+}
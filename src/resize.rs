@@ -0,0 +1,192 @@
+//! The one resampler: `--resize` scales the finished capture to a percentage
+//! or into a box, so screenshots from 4K displays are chat-sized without an
+//! imagemagick step; `capture.force_scale` and the terminal preview use the
+//! same Lanczos filter.
+
+use anyhow::{Context, Result, bail};
+use std::str::FromStr;
+
+use crate::backend::CapturedImage;
+
+/// Lanczos lobes on each side of a sample.
+const LOBES: f64 = 3.0;
+const MAX_PERCENT: u32 = 800;
+/// Largest side of a `WxH` box.
+const MAX_SIDE: u32 = 32768;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resize {
+    /// Both sides scaled by this many percent.
+    Percent(u32),
+    /// Scaled to fit inside `width`x`height`, keeping the aspect ratio.
+    Fit(u32, u32),
+}
+
+impl FromStr for Resize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(percent) = s.strip_suffix('%') {
+            let percent: u32 = percent
+                .trim()
+                .parse()
+                .context("Invalid resize percentage")?;
+            if percent == 0 || percent > MAX_PERCENT {
+                bail!(
+                    "Invalid resize '{}': the percentage must be 1-{}",
+                    s,
+                    MAX_PERCENT
+                );
+            }
+            return Ok(Self::Percent(percent));
+        }
+        let (w, h) = s.split_once(['x', 'X']).context(
+            "Invalid resize: expected 'WxH' (e.g. 1920x1080) or a percentage (e.g. 50%)",
+        )?;
+        let width: u32 = w.trim().parse().context("Invalid resize width")?;
+        let height: u32 = h.trim().parse().context("Invalid resize height")?;
+        if width == 0 || height == 0 || width > MAX_SIDE || height > MAX_SIDE {
+            bail!(
+                "Invalid resize '{}': sides must be 1-{} pixels",
+                s,
+                MAX_SIDE
+            );
+        }
+        Ok(Self::Fit(width, height))
+    }
+}
+
+impl Resize {
+    /// Size of a `width`x`height` image after resizing (at least 1x1).
+    pub(crate) fn target(self, width: u32, height: u32) -> (u32, u32) {
+        let factor = match self {
+            Self::Percent(percent) => f64::from(percent) / 100.0,
+            Self::Fit(box_w, box_h) => {
+                (f64::from(box_w) / f64::from(width)).min(f64::from(box_h) / f64::from(height))
+            }
+        };
+        let side = |pixels: u32| ((f64::from(pixels) * factor).round() as u32).max(1);
+        (side(width), side(height))
+    }
+}
+
+pub(crate) fn apply(image: CapturedImage, resize: Resize) -> CapturedImage {
+    let (width, height) = resize.target(image.width, image.height);
+    resample(image, width, height)
+}
+
+/// Resize RGBA pixels to `width`x`height`.
+pub(crate) fn resample(image: CapturedImage, width: u32, height: u32) -> CapturedImage {
+    lanczos(&image, width, height).unwrap_or(image)
+}
+
+/// Resample RGBA pixels with a separable Lanczos filter, widened when
+/// shrinking so every source pixel contributes. Colors are premultiplied by
+/// alpha so transparent areas don't bleed into their neighbours. `None` when
+/// the size doesn't change.
+fn lanczos(image: &CapturedImage, width: u32, height: u32) -> Option<CapturedImage> {
+    if (image.width, image.height) == (width, height) || image.width == 0 || image.height == 0 {
+        return None;
+    }
+    let (src_w, src_h) = (image.width as usize, image.height as usize);
+    let (dst_w, dst_h) = (width as usize, height as usize);
+    let premultiplied: Vec<[f32; 4]> = image
+        .data
+        .chunks_exact(4)
+        .map(|px| {
+            let alpha = f32::from(px[3]) / 255.0;
+            [
+                f32::from(px[0]) * alpha,
+                f32::from(px[1]) * alpha,
+                f32::from(px[2]) * alpha,
+                alpha,
+            ]
+        })
+        .collect();
+
+    let columns = taps(src_w, dst_w);
+    let mut wide = vec![[0.0f32; 4]; dst_w * src_h];
+    for y in 0..src_h {
+        let row = &premultiplied[y * src_w..(y + 1) * src_w];
+        for (x, (start, weights)) in columns.iter().enumerate() {
+            wide[y * dst_w + x] = weighted(weights, |i| row[start + i]);
+        }
+    }
+    let rows = taps(src_h, dst_h);
+    let mut data = Vec::with_capacity(dst_w * dst_h * 4);
+    for (start, weights) in &rows {
+        for x in 0..dst_w {
+            let [r, g, b, a] = weighted(weights, |i| wide[(start + i) * dst_w + x]);
+            let alpha = a.clamp(0.0, 1.0);
+            let channel = |c: f32| {
+                if alpha > 0.0 {
+                    (c / alpha).clamp(0.0, 255.0).round() as u8
+                } else {
+                    0
+                }
+            };
+            data.extend_from_slice(&[
+                channel(r),
+                channel(g),
+                channel(b),
+                (alpha * 255.0).round() as u8,
+            ]);
+        }
+    }
+
+    Some(CapturedImage {
+        data,
+        width,
+        height,
+    })
+}
+
+fn weighted(weights: &[f32], sample: impl Fn(usize) -> [f32; 4]) -> [f32; 4] {
+    let mut sum = [0.0f32; 4];
+    for (i, weight) in weights.iter().enumerate() {
+        let px = sample(i);
+        for (total, value) in sum.iter_mut().zip(px) {
+            *total += value * weight;
+        }
+    }
+    sum
+}
+
+/// For each of `dst` pixels, the first of `src` pixels it reads and the
+/// normalized weights of that pixel and the ones after it.
+fn taps(src: usize, dst: usize) -> Vec<(usize, Vec<f32>)> {
+    let ratio = src as f64 / dst as f64;
+    let spread = ratio.max(1.0);
+    let support = LOBES * spread;
+    (0..dst)
+        .map(|d| {
+            let center = (d as f64 + 0.5) * ratio;
+            let start = ((center - support).floor().max(0.0) as usize).min(src - 1);
+            let end = ((center + support).ceil() as usize).clamp(start + 1, src);
+            let weights: Vec<f64> = (start..end)
+                .map(|s| kernel((s as f64 + 0.5 - center) / spread))
+                .collect();
+            let total: f64 = weights.iter().sum();
+            if total.abs() < f64::EPSILON {
+                // Too few taps to reach a lobe: take the nearest pixel.
+                let nearest = (center.floor() as usize).clamp(start, end - 1);
+                let mut weights = vec![0.0; end - start];
+                weights[nearest - start] = 1.0;
+                return (start, weights);
+            }
+            (start, weights.iter().map(|w| (w / total) as f32).collect())
+        })
+        .collect()
+}
+
+fn kernel(x: f64) -> f64 {
+    if x == 0.0 {
+        return 1.0;
+    }
+    if x.abs() >= LOBES {
+        return 0.0;
+    }
+    let px = std::f64::consts::PI * x;
+    LOBES * px.sin() * (px / LOBES).sin() / (px * px)
+}
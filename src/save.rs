@@ -1,131 +1,839 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use notify_rust::Notification;
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
 use std::fs::{create_dir_all, write};
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::time::Duration;
 
+use crate::backend::{CaptureBackend, CapturedImage};
+use crate::beautify;
+use crate::cli::Mode;
+use crate::clipboard;
+use crate::config::{self, ImageFormat, ObscureStyle, PngCompression};
+use crate::editor;
+use crate::exit::Stage;
+use crate::freeze;
 use crate::geometry::Geometry;
-use crate::utils::wait_with_timeout;
+use crate::hooks;
+use crate::icc;
+use crate::metadata::Metadata;
+use crate::notify;
+use crate::obscure;
+use crate::png_encoder;
+use crate::resize::{self, Resize};
+use crate::term_preview;
+use crate::upload;
+use crate::utils::{self, wait_for_exit};
+use crate::wallpaper;
 
 #[cfg(feature = "grim")]
 pub(crate) fn to_grim_box(geometry: &Geometry) -> grim_rs::Box {
     (*geometry).to_grim_box()
 }
 
-#[cfg(feature = "grim")]
-#[allow(clippy::too_many_arguments)]
-pub fn save_geometry_with_grim(
+/// Environment passed to commands launched after capture.
+/// `HYPRSHOT_OUTPUT` is only set when the output name is known.
+pub(crate) fn command_env(
     geometry: &Geometry,
-    save_fullpath: &PathBuf,
-    clipboard_only: bool,
-    raw: bool,
-    command: Option<Vec<String>>,
-    silent: bool,
-    notif_timeout: u32,
-    debug: bool,
+    output_name: Option<&str>,
+) -> Vec<(&'static str, String)> {
+    let mut env = vec![
+        ("HYPRSHOT_GEOMETRY", geometry.to_string()),
+        ("HYPRSHOT_X", geometry.x.to_string()),
+        ("HYPRSHOT_Y", geometry.y.to_string()),
+        ("HYPRSHOT_WIDTH", geometry.width.to_string()),
+        ("HYPRSHOT_HEIGHT", geometry.height.to_string()),
+    ];
+    if let Some(name) = output_name {
+        env.push(("HYPRSHOT_OUTPUT", name.to_string()));
+    }
+    env
+}
+
+/// Build a `file://` URI for the saved screenshot, percent-encoding
+/// everything except unreserved characters and `/`.
+pub(crate) fn file_uri(path: &Path) -> Result<String> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let absolute = std::path::absolute(path).context(format!(
+        "Failed to resolve absolute path: {}",
+        path.display()
+    ))?;
+    let mut uri = String::from("file://");
+    for &byte in absolute.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    Ok(uri)
+}
+
+/// What was captured, so the notification can say so.
+#[derive(Debug, Clone, Default)]
+pub struct CaptureContext {
+    /// `None` when unknown (e.g. library callers); the summary stays generic.
+    pub mode: Option<Mode>,
+    /// `-m active` was given.
+    pub current: bool,
+}
+
+impl CaptureContext {
+    /// Notification summary such as "Window captured" or "Monitor DP-1 captured".
+    pub(crate) fn summary(&self, output_name: Option<&str>) -> String {
+        let subject = match (&self.mode, self.current) {
+            (Some(Mode::Output), _) => match output_name {
+                Some(name) => return format!("Monitor {} captured", name),
+                None => "Monitor",
+            },
+            (Some(Mode::Window), true) => "Active window",
+            (Some(Mode::Window), false) => "Window",
+            (Some(Mode::WindowPair), _) => "Windows",
+            (Some(Mode::Region), _) => "Region",
+            (Some(Mode::Layer), _) => "Layer",
+            (Some(Mode::All), _) => "All outputs",
+            _ => return "Screenshot saved".to_string(),
+        };
+        format!("{} captured", subject)
+    }
+}
+
+/// What `save_geometry` did with the capture, for `--json`.
+#[derive(Debug, Default)]
+pub struct SaveOutcome {
+    /// The saved file; `None` with `--clipboard-only` or when skipped.
+    pub path: Option<PathBuf>,
+    /// The clipboard received the capture (or its URI or upload link).
+    pub copied: bool,
+    /// Link returned by `--upload`.
+    pub url: Option<String>,
+    /// `--skip-unchanged` found the same pixels as last time.
+    pub skipped: bool,
+}
+
+/// The `--json` result line: what a waybar module or wrapper script needs to
+/// find the capture without guessing the filename.
+pub(crate) fn json_report(
+    outcome: &SaveOutcome,
+    geometry: &Geometry,
+    mode: &str,
+    output_name: Option<&str>,
+    format: &str,
+    duration: Duration,
+) -> serde_json::Value {
+    serde_json::json!({
+        "path": outcome.path.as_ref().map(|path| path.display().to_string()),
+        "mode": mode,
+        "geometry": {
+            "x": geometry.x,
+            "y": geometry.y,
+            "width": geometry.width,
+            "height": geometry.height,
+        },
+        "output": output_name,
+        "format": format,
+        "duration_ms": duration.as_millis() as u64,
+        "clipboard": outcome.copied,
+        "url": outcome.url,
+        "skipped": outcome.skipped,
+    })
+}
+
+/// What `--raw-format` writes to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum RawFormat {
+    Png,
+    Jpeg,
+    /// Binary PPM (P6), transparency blended like JPEG.
+    Ppm,
+    /// Bare pixels, 4 bytes each in B, G, R, A order, rows top to bottom.
+    Bgra,
+}
+
+impl RawFormat {
+    /// The encoded format to write, or `None` for bare pixel formats.
+    pub fn image_format(self) -> Option<ImageFormat> {
+        match self {
+            Self::Png => Some(ImageFormat::Png),
+            Self::Jpeg => Some(ImageFormat::Jpeg),
+            Self::Ppm | Self::Bgra => None,
+        }
+    }
+}
+
+/// How `--raw` output is written to stdout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum StdoutEncoding {
+    #[default]
+    Binary,
+    /// One line of base64, for channels that only carry text.
+    Base64,
+}
+
+/// Options controlling what happens to a capture after it is taken.
+#[derive(Debug, Default)]
+pub struct SaveOptions {
+    pub clipboard_only: bool,
+    /// Copy saved captures to the clipboard (`capture.clipboard_on_capture`).
+    pub clipboard: bool,
+    /// Flash the captured outputs right after the screencopy.
+    pub flash: bool,
+    pub clipboard_uri: bool,
+    pub png_compression: PngCompression,
+    /// Run saved PNGs through the lossless optimization pass.
+    pub optimize: bool,
+    pub format: ImageFormat,
+    /// JPEG/WebP quality, 1-100.
+    pub quality: u8,
+    /// RGB that transparent pixels are blended onto for JPEG.
+    pub flatten_background: [u8; 3],
+    /// Capture the named output at native resolution instead of the logical region.
+    pub output_physical_pixels: bool,
+    /// Resample the capture to this many pixels per logical pixel.
+    pub force_scale: Option<f64>,
+    pub raw: bool,
+    /// `--raw-format`; PNG and JPEG are already in `format`.
+    pub raw_format: Option<RawFormat>,
+    /// Prefix bare pixels with a `WIDTH HEIGHT STRIDE` line.
+    pub raw_header: bool,
+    pub stdout_encoding: StdoutEncoding,
+    /// Show the capture inline when stdout is a kitty or sixel terminal.
+    pub term_preview: bool,
+    pub command: Option<Vec<String>>,
+    /// How long to wait for `command` before returning (0 waits until it exits).
+    pub command_timeout_ms: u64,
+    pub silent: bool,
+    pub notif_timeout: u32,
+    pub skip_unchanged: bool,
+    /// Extra directories that receive a copy of the saved file.
+    pub also_save: Vec<PathBuf>,
+    pub create_missing_dirs: bool,
+    /// Size of the recent-screenshots ring (0 disables it).
+    pub keep_recent: usize,
+    /// Set the saved file as wallpaper afterwards.
+    pub set_wallpaper: bool,
+    /// Name and endpoint to upload the saved file to; its URL replaces the
+    /// image on the clipboard.
+    pub upload: Option<(String, config::UploadTarget)>,
+    /// Boxes (logical, like the capture) to pixelate or blur.
+    pub obscure: Option<(ObscureStyle, Vec<Geometry>)>,
+    /// Frame added after obscuring and editing.
+    pub beautify: Option<beautify::Style>,
+    /// Scaling applied last, to the framed image.
+    pub resize: Option<Resize>,
+    /// Details embedded in PNG and JPEG files (`--metadata basic|full`).
+    pub metadata: Option<Metadata>,
+    /// Annotator the capture is opened in before it is saved.
+    pub editor: Option<String>,
+    /// `hooks.post_capture` commands, run once the file is saved.
+    pub post_capture: Vec<String>,
+    pub context: CaptureContext,
+    pub debug: bool,
+}
+
+/// The capture as PNG for the clipboard and the recent ring, which every
+/// application reads. It is the saved file when that is a PNG already, and is
+/// otherwise encoded on first use, so a JPEG or WebP save that needs neither
+/// doesn't pay for it.
+struct PngCopy<'a> {
+    backend: &'a dyn CaptureBackend,
+    capture: &'a CapturedImage,
+    compression: PngCompression,
+    saved: Option<&'a [u8]>,
+    encoded: OnceCell<Vec<u8>>,
+}
+
+impl PngCopy<'_> {
+    fn bytes(&self) -> Result<&[u8]> {
+        if let Some(saved) = self.saved {
+            return Ok(saved);
+        }
+        if let Some(encoded) = self.encoded.get() {
+            return Ok(encoded);
+        }
+        let encoded = self.backend.to_png(self.capture, self.compression)?;
+        Ok(self.encoded.get_or_init(|| encoded))
+    }
+}
+
+/// Put a saved capture on the clipboard; `false` (with a warning) if that fails.
+/// The URI and the upload link replace the image, so a paste gives what was
+/// asked for even in apps that would rather take the picture.
+fn copy_saved(
+    path: &Path,
+    png: &PngCopy,
+    encoded: &[u8],
+    outcome: &SaveOutcome,
+    options: &SaveOptions,
+) -> Result<bool> {
+    let uri_list;
+    let path_text;
+    let offers = if let Some(url) = &outcome.url {
+        clipboard::text_offers(url)
+    } else if options.clipboard_uri {
+        uri_list = format!("{}\r\n", file_uri(path)?);
+        path_text = path.display().to_string();
+        let mut offers = vec![("text/uri-list", uri_list.as_bytes())];
+        offers.extend(clipboard::text_offers(&path_text));
+        offers
+    } else {
+        let mut offers = vec![("image/png", png.bytes().context(Stage::Save)?)];
+        if options.format != ImageFormat::Png {
+            offers.push((options.format.mime_type(), encoded));
+        }
+        offers
+    };
+    match clipboard::copy(&offers, options.debug) {
+        Ok(()) => Ok(true),
+        Err(err) => {
+            eprintln!("Warning: failed to copy screenshot to clipboard: {:#}", err);
+            Ok(false)
+        }
+    }
+}
+
+/// Upper bound for `capture.force_scale`; beyond it images get absurdly large.
+const MAX_FORCE_SCALE: f64 = 8.0;
+
+pub(crate) fn validate_scale(scale: f64) -> Result<()> {
+    if !(scale.is_finite() && scale > 0.0 && scale <= MAX_FORCE_SCALE) {
+        bail!(
+            "Invalid capture.force_scale {}: expected a number above 0 and at most {}",
+            scale,
+            MAX_FORCE_SCALE
+        );
+    }
+    Ok(())
+}
+
+/// Pixel size of `geometry` at `scale` pixels per logical pixel (at least 1x1).
+pub(crate) fn scaled_size(geometry: &Geometry, scale: f64) -> Result<(u32, u32)> {
+    validate_scale(scale)?;
+    let size = |logical: i32| ((logical as f64 * scale).round() as u32).max(1);
+    Ok((size(geometry.width), size(geometry.height)))
+}
+
+/// Blend RGBA pixels onto `background` and drop alpha, for formats without
+/// an alpha channel. Gaps between outputs and translucent windows get a
+/// defined color instead of whatever the encoder makes of them.
+pub(crate) fn flatten(image: &CapturedImage, background: [u8; 3]) -> Vec<u8> {
+    let mut rgb = Vec::with_capacity(image.data.len() / 4 * 3);
+    for pixel in image.data.chunks_exact(4) {
+        let alpha = pixel[3] as u32;
+        for (channel, bg) in pixel[..3].iter().zip(background) {
+            let blended = (*channel as u32 * alpha + bg as u32 * (255 - alpha) + 127) / 255;
+            rgb.push(blended as u8);
+        }
+    }
+    rgb
+}
+
+/// Encode `image` in the chosen format, with `options.metadata` describing the
+/// capture of `geometry` on `output_name`.
+pub(crate) fn encode(
+    backend: &dyn CaptureBackend,
+    image: &CapturedImage,
+    options: &SaveOptions,
+    geometry: &Geometry,
+    output_name: Option<&str>,
+) -> Result<Vec<u8>> {
+    let metadata = options.metadata.as_ref();
+    match options.format {
+        ImageFormat::Png => {
+            let png = if options.optimize {
+                png_encoder::optimize(image).context("Failed to optimize PNG")?
+            } else {
+                backend.to_png(image, options.png_compression)?
+            };
+            Ok(match metadata {
+                Some(metadata) => metadata.embed_png(png, geometry, output_name),
+                None => png,
+            })
+        }
+        ImageFormat::Jpeg => encode_jpeg(
+            image,
+            options.quality,
+            options.flatten_background,
+            metadata.map(|metadata| metadata.exif(geometry, output_name)),
+        ),
+        ImageFormat::Webp => encode_webp(image, options.quality),
+    }
+}
+
+/// What `--raw` writes: bare pixels for `--raw-format ppm|bgra` (after the
+/// `--raw-header` line), else `image` encoded like a saved file.
+pub(crate) fn raw_output(
+    backend: &dyn CaptureBackend,
+    image: &CapturedImage,
+    options: &SaveOptions,
+    geometry: &Geometry,
+    output_name: Option<&str>,
+) -> Result<Vec<u8>> {
+    let width = image.width as usize;
+    let (stride, pixels) = match options.raw_format {
+        Some(RawFormat::Bgra) => (
+            width * 4,
+            image
+                .data
+                .chunks_exact(4)
+                .flat_map(|px| [px[2], px[1], px[0], px[3]])
+                .collect::<Vec<u8>>(),
+        ),
+        Some(RawFormat::Ppm) => {
+            let mut ppm = format!("P6\n{} {}\n255\n", image.width, image.height).into_bytes();
+            ppm.extend(flatten(image, options.flatten_background));
+            (width * 3, ppm)
+        }
+        _ => return encode(backend, image, options, geometry, output_name),
+    };
+    if !options.raw_header {
+        return Ok(pixels);
+    }
+    let mut out = format!("{} {} {}\n", image.width, image.height, stride).into_bytes();
+    out.extend(pixels);
+    Ok(out)
+}
+
+fn encode_jpeg(
+    image: &CapturedImage,
+    quality: u8,
+    background: [u8; 3],
+    exif: Option<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    let (Ok(width), Ok(height)) = (u16::try_from(image.width), u16::try_from(image.height)) else {
+        bail!(
+            "{}x{} is too large for JPEG (at most 65535 pixels per side)",
+            image.width,
+            image.height
+        );
+    };
+    let mut bytes = Vec::new();
+    let mut encoder = jpeg_encoder::Encoder::new(&mut bytes, quality);
+    // Tagged like our PNGs, so wide-gamut displays don't stretch the colors.
+    encoder
+        .add_icc_profile(&icc::srgb_profile())
+        .context("Failed to add the sRGB profile")?;
+    if let Some(exif) = exif {
+        encoder
+            .add_app_segment(1, exif)
+            .context("Failed to add EXIF metadata")?;
+    }
+    encoder
+        .encode(
+            &flatten(image, background),
+            width,
+            height,
+            jpeg_encoder::ColorType::Rgb,
+        )
+        .context("Failed to encode JPEG")?;
+    Ok(bytes)
+}
+
+#[cfg(feature = "webp")]
+fn encode_webp(image: &CapturedImage, quality: u8) -> Result<Vec<u8>> {
+    /// libwebp's limit per side.
+    const MAX_SIDE: u32 = 16383;
+    if image.width > MAX_SIDE || image.height > MAX_SIDE {
+        bail!(
+            "{}x{} is too large for WebP (at most {} pixels per side)",
+            image.width,
+            image.height,
+            MAX_SIDE
+        );
+    }
+    let encoded = webp::Encoder::from_rgba(&image.data, image.width, image.height)
+        .encode_simple(false, quality as f32)
+        .map_err(|err| anyhow::anyhow!("Failed to encode WebP: {:?}", err))?;
+    Ok(encoded.to_vec())
+}
+
+#[cfg(not(feature = "webp"))]
+fn encode_webp(_image: &CapturedImage, _quality: u8) -> Result<Vec<u8>> {
+    bail!("WebP output needs the 'webp' feature")
+}
+
+/// Run the post-capture command. With a timeout, a command still running when
+/// it expires (e.g. an editor) keeps running; hyprshot-rs just stops waiting.
+pub(crate) fn run_command(
+    cmd: &[String],
+    save_fullpath: &Path,
+    geometry: &Geometry,
+    output_name: Option<&str>,
+    options: &SaveOptions,
 ) -> Result<()> {
+    let mut child = Command::new(&cmd[0])
+        .args(&cmd[1..])
+        .arg(save_fullpath)
+        .envs(command_env(geometry, output_name))
+        .spawn()
+        .context(format!("Failed to run command '{}'", cmd[0]))?;
+    let status = if options.command_timeout_ms == 0 {
+        Some(
+            child
+                .wait()
+                .context(format!("Failed to wait for command '{}'", cmd[0]))?,
+        )
+    } else {
+        wait_for_exit(
+            &mut child,
+            Duration::from_millis(options.command_timeout_ms),
+        )?
+    };
+    match status {
+        Some(status) if !status.success() => Err(anyhow::anyhow!("Command '{}' failed", cmd[0])),
+        Some(_) => Ok(()),
+        None => {
+            if options.debug {
+                eprintln!(
+                    "Command '{}' still running after {} ms; not waiting for it",
+                    cmd[0], options.command_timeout_ms
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+/// 64-bit FNV-1a over the capture size and pixels; stable across builds,
+/// unlike `DefaultHasher`, so it can be persisted between runs.
+pub(crate) fn capture_fingerprint(capture: &CapturedImage) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = OFFSET;
+    let dims = [capture.width.to_le_bytes(), capture.height.to_le_bytes()];
+    for &byte in dims.iter().flatten().chain(capture.data.iter()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(PRIME);
+    }
+    hash
+}
+
+/// `--skip-unchanged` fingerprints, one per mode and geometry, as
+/// `"region 10,20 300x200" = "<fingerprint>"` lines.
+fn last_capture_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("hyprshot-rs").join("last_capture.toml"))
+}
+
+pub(crate) fn recent_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("hyprshot-rs").join("recent"))
+}
+
+/// Shift `recent-0.png` .. `recent-(keep-2).png` up by one, dropping the
+/// oldest, and store the new screenshot as `recent-0.png`.
+pub(crate) fn rotate_recent(dir: &Path, png_bytes: &[u8], keep: usize) -> Result<PathBuf> {
+    create_dir_all(dir).with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    let slot = |i: usize| dir.join(format!("recent-{}.png", i));
+    for i in (1..keep).rev() {
+        let from = slot(i - 1);
+        if from.exists() {
+            std::fs::rename(&from, slot(i))
+                .with_context(|| format!("Failed to rotate '{}'", from.display()))?;
+        }
+    }
+    // Drop leftovers from a previously larger ring.
+    let mut i = keep;
+    while slot(i).exists() {
+        let _ = std::fs::remove_file(slot(i));
+        i += 1;
+    }
+    let newest = slot(0);
+    write(&newest, png_bytes).with_context(|| format!("Failed to write '{}'", newest.display()))?;
+    Ok(newest)
+}
+
+/// Print the path of the newest screenshot kept by `paths.keep_recent`.
+pub fn handle_show_recent() -> Result<()> {
+    let newest = recent_dir()
+        .map(|dir| dir.join("recent-0.png"))
+        .filter(|path| path.exists())
+        .context("No recent screenshots (set paths.keep_recent to enable)")?;
+    println!("{}", newest.display());
+    Ok(())
+}
+
+/// Compare the capture with the previous one taken as `key` (mode and
+/// geometry) and remember it for next time, keeping the other keys' entries.
+/// Returns true if it is identical to the last recorded capture.
+pub(crate) fn is_unchanged_since_last(
+    path: &Path,
+    key: &str,
+    capture: &CapturedImage,
+    debug: bool,
+) -> bool {
+    // A missing or damaged file just means nothing to compare with.
+    let mut entries: BTreeMap<String, String> = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_default();
+    let fingerprint = format!("{:016x}", capture_fingerprint(capture));
+    if entries.get(key) == Some(&fingerprint) {
+        return true;
+    }
+
+    entries.insert(key.to_string(), fingerprint);
+    let stored = path
+        .parent()
+        .map(create_dir_all)
+        .transpose()
+        .map_err(anyhow::Error::from)
+        .and_then(|_| Ok(toml::to_string(&entries)?))
+        .and_then(|content| Ok(write(path, content)?));
+    if let Err(err) = stored
+        && debug
+    {
+        eprintln!("Failed to record capture fingerprint: {}", err);
+    }
+    false
+}
+
+/// Write the encoded screenshot into each `--also-save` directory under the same
+/// filename. Failures are reported per directory and don't stop the others.
+fn save_copies(encoded: &[u8], save_fullpath: &Path, options: &SaveOptions) {
+    let Some(filename) = save_fullpath.file_name() else {
+        return;
+    };
+    for dir in &options.also_save {
+        let result = config::ensure_directory(&dir.to_string_lossy(), options.create_missing_dirs)
+            .and_then(|dir| {
+                let target = dir.join(filename);
+                if target == save_fullpath {
+                    return Ok(target);
+                }
+                write(&target, encoded)
+                    .with_context(|| format!("Failed to write '{}'", target.display()))?;
+                Ok(target)
+            });
+        match result {
+            Ok(target) => {
+                if options.debug {
+                    eprintln!("Also saved to: {}", target.display());
+                }
+            }
+            Err(err) => eprintln!(
+                "Warning: failed to save copy in '{}': {:#}",
+                dir.display(),
+                err
+            ),
+        }
+    }
+}
+
+pub fn save_geometry(
+    backend: &mut dyn CaptureBackend,
+    geometry: &Geometry,
+    output_name: Option<&str>,
+    save_fullpath: &PathBuf,
+    options: &SaveOptions,
+) -> Result<SaveOutcome> {
     use std::io::Write;
 
-    if debug {
-        eprintln!("Saving geometry with grim-rs library: {}", geometry);
+    if options.debug {
+        eprintln!("Saving geometry: {}", geometry);
     }
 
-    let region = to_grim_box(geometry);
+    let capture = match output_name {
+        Some(name) if options.output_physical_pixels => backend.capture_output(name),
+        _ => backend.capture_region(geometry),
+    }
+    .context(Stage::Capture)?;
+    // After the screencopy, so the flash never ends up in the image.
+    if options.flash
+        && !options.raw
+        && let Err(err) = freeze::flash(*geometry, options.debug)
+    {
+        eprintln!("Warning: failed to flash the screen: {:#}", err);
+    }
+    let capture = match options.force_scale {
+        Some(scale) => {
+            let (width, height) = scaled_size(geometry, scale)?;
+            if options.debug && (width, height) != (capture.width, capture.height) {
+                eprintln!(
+                    "Resampling {}x{} to {}x{} (force_scale {})",
+                    capture.width, capture.height, width, height, scale
+                );
+            }
+            resize::resample(capture, width, height)
+        }
+        None => capture,
+    };
+
+    let unchanged = || {
+        let Some(path) = last_capture_path() else {
+            return false;
+        };
+        let mode = options.context.mode.as_ref().map_or("capture", |mode| {
+            crate::app::mode_label(mode, options.context.current)
+        });
+        let key = format!("{} {}", mode, geometry);
+        is_unchanged_since_last(&path, &key, &capture, options.debug)
+    };
+    if options.skip_unchanged && !options.raw && unchanged() {
+        eprintln!("Screenshot unchanged since the last capture of this area; skipped");
+        return Ok(SaveOutcome {
+            skipped: true,
+            ..SaveOutcome::default()
+        });
+    }
 
-    let mut grim = grim_rs::Grim::new().context("Failed to initialize grim-rs")?;
+    let mut capture = capture;
+    if let Some((style, boxes)) = &options.obscure {
+        obscure::apply(&mut capture, geometry, boxes, *style);
+    }
 
-    let capture_result = grim
-        .capture_region(region)
-        .context("Failed to capture screenshot region")?;
+    let capture = match &options.editor {
+        Some(command) => editor::edit(backend, capture, command, options.debug)?,
+        None => capture,
+    };
+    let capture = match &options.beautify {
+        Some(style) => {
+            let scale = f64::from(capture.width) / f64::from(geometry.width);
+            beautify::apply(&capture, style, scale)
+        }
+        None => capture,
+    };
+    let capture = match options.resize {
+        Some(resize) => {
+            let resized = resize::apply(capture, resize);
+            if options.debug {
+                eprintln!("Resized to {}x{}", resized.width, resized.height);
+            }
+            resized
+        }
+        None => capture,
+    };
 
-    let png_bytes = grim
-        .to_png(
-            capture_result.data(),
-            capture_result.width(),
-            capture_result.height(),
-        )
-        .context("Failed to encode screenshot as PNG")?;
+    if options.raw {
+        raw_output(backend, &capture, options, geometry, output_name)
+            .map(|bytes| match options.stdout_encoding {
+                StdoutEncoding::Binary => bytes,
+                StdoutEncoding::Base64 => format!("{}\n", utils::base64(&bytes)).into_bytes(),
+            })
+            .and_then(|bytes| Ok(std::io::stdout().write_all(&bytes)?))
+            .context(Stage::Save)?;
+        return Ok(SaveOutcome::default());
+    }
 
-    if raw {
-        std::io::stdout().write_all(&png_bytes)?;
-        return Ok(());
+    let encoded = if options.clipboard_only {
+        backend.to_png(&capture, options.png_compression)
+    } else {
+        encode(backend, &capture, options, geometry, output_name)
     }
+    .context(Stage::Save)?;
+    let png = PngCopy {
+        backend: &*backend,
+        capture: &capture,
+        compression: options.png_compression,
+        saved: (options.clipboard_only || options.format == ImageFormat::Png)
+            .then_some(encoded.as_slice()),
+        encoded: OnceCell::new(),
+    };
 
-    if !clipboard_only {
+    let mut outcome = SaveOutcome::default();
+    if !options.clipboard_only {
         create_dir_all(save_fullpath.parent().unwrap())
-            .context("Failed to create screenshot directory")?;
-
-        write(save_fullpath, &png_bytes).context(format!(
-            "Failed to save screenshot to '{}'",
-            save_fullpath.display()
-        ))?;
-
-        let wl_copy_result = (|| -> Result<()> {
-            let mut wl_copy = Command::new("wl-copy")
-                .arg("--type")
-                .arg("image/png")
-                .stdin(Stdio::piped())
-                .spawn()
-                .context("Failed to start wl-copy")?;
-            wl_copy
-                .stdin
-                .as_mut()
-                .unwrap()
-                .write_all(&png_bytes)
-                .context("Failed to write to wl-copy stdin")?;
-            // Best-effort in normal mode: don't block on wl-copy completion.
-            std::mem::drop(wl_copy);
-            Ok(())
-        })();
-        if let Err(err) = wl_copy_result {
-            eprintln!("Warning: failed to copy screenshot to clipboard: {}", err);
-        }
-
-        if let Some(cmd) = command {
-            let cmd_status = Command::new(&cmd[0])
-                .args(&cmd[1..])
-                .arg(save_fullpath)
-                .status()
-                .context(format!("Failed to run command '{}'", cmd[0]))?;
-            if !cmd_status.success() {
-                return Err(anyhow::anyhow!("Command '{}' failed", cmd[0]));
+            .context("Failed to create screenshot directory")
+            .context(Stage::Save)?;
+
+        write(save_fullpath, &encoded)
+            .context(format!(
+                "Failed to save screenshot to '{}'",
+                save_fullpath.display()
+            ))
+            .context(Stage::Save)?;
+        outcome.path = Some(save_fullpath.clone());
+        save_copies(&encoded, save_fullpath, options);
+        if options.keep_recent > 0
+            && let Some(dir) = recent_dir()
+        {
+            match png
+                .bytes()
+                .and_then(|png| rotate_recent(&dir, png, options.keep_recent))
+            {
+                Ok(path) if options.debug => eprintln!("Recent screenshot: {}", path.display()),
+                Ok(_) => {}
+                Err(err) => eprintln!("Warning: failed to update recent screenshots: {:#}", err),
             }
         }
+
+        if options.set_wallpaper
+            && let Err(err) = wallpaper::set_wallpaper(save_fullpath, options.debug)
+        {
+            eprintln!("Warning: failed to set wallpaper: {:#}", err);
+        }
+
+        // A failed upload leaves the saved file and the usual clipboard content.
+        outcome.url = options.upload.as_ref().and_then(|(name, target)| {
+            upload::upload(name, target, save_fullpath, options.debug)
+                .map_err(|err| eprintln!("Warning: {:#}", err))
+                .ok()
+        });
+
+        if options.clipboard {
+            outcome.copied = copy_saved(save_fullpath, &png, &encoded, &outcome, options)?;
+        }
+
+        if !options.post_capture.is_empty() {
+            let mode = options
+                .context
+                .mode
+                .as_ref()
+                .map(|mode| crate::app::mode_label(mode, options.context.current));
+            let env = hooks::post_capture_env(
+                save_fullpath,
+                geometry,
+                mode,
+                output_name,
+                outcome.url.as_deref(),
+            );
+            hooks::run_post_capture(&options.post_capture, &env, options.debug);
+        }
+
+        if let Some(cmd) = &options.command {
+            run_command(cmd, save_fullpath, geometry, output_name, options)?;
+        }
     } else {
-        let mut wl_copy = Command::new("wl-copy")
-            .arg("--type")
-            .arg("image/png")
-            .stdin(Stdio::piped())
-            .spawn()
-            .context("Failed to start wl-copy")?;
-        wl_copy
-            .stdin
-            .as_mut()
-            .unwrap()
-            .write_all(&png_bytes)
-            .context("Failed to write to wl-copy stdin")?;
-        let wl_copy_status = wait_with_timeout(&mut wl_copy, Duration::from_secs(3))
-            .context("Failed to wait for wl-copy")?;
-        if !wl_copy_status.success() {
-            return Err(anyhow::anyhow!("wl-copy failed to copy screenshot"));
-        }
-    }
-
-    if !silent {
-        let message = if clipboard_only {
+        clipboard::copy(&[("image/png", png.bytes()?)], options.debug)
+            .context("Failed to copy screenshot to clipboard")
+            .context(Stage::Save)?;
+        outcome.copied = true;
+    }
+
+    if options.term_preview
+        && let Err(err) = term_preview::show(&capture, options.debug)
+    {
+        eprintln!("Warning: {:#}", err);
+    }
+
+    if !options.silent && notify::daemon_available(options.debug) {
+        let message = if options.clipboard_only {
             "Image copied to the clipboard".to_string()
-        } else {
+        } else if let Some(url) = &outcome.url {
+            format!(
+                "Uploaded to <a href=\"{url}\">{url}</a>{}. Saved in <i>{}</i>.",
+                if outcome.copied {
+                    " (link copied to the clipboard)"
+                } else {
+                    ""
+                },
+                save_fullpath.display()
+            )
+        } else if outcome.copied {
             format!(
                 "Image saved in <i>{}</i> and copied to the clipboard.",
                 save_fullpath.display()
             )
+        } else {
+            format!("Image saved in <i>{}</i>.", save_fullpath.display())
         };
         if let Err(err) = Notification::new()
-            .summary("Screenshot saved")
+            .summary(&options.context.summary(output_name))
             .body(&message)
             .icon(save_fullpath.to_str().unwrap_or("screenshot"))
-            .timeout(notif_timeout as i32)
+            .timeout(options.notif_timeout as i32)
             .appname("Hyprshot-rs")
             .show()
         {
@@ -133,31 +841,5 @@ pub fn save_geometry_with_grim(
         }
     }
 
-    Ok(())
-}
-
-#[allow(clippy::too_many_arguments)]
-pub fn save_geometry(
-    geometry: &Geometry,
-    save_fullpath: &PathBuf,
-    clipboard_only: bool,
-    raw: bool,
-    command: Option<Vec<String>>,
-    silent: bool,
-    notif_timeout: u32,
-    debug: bool,
-) -> Result<()> {
-    #[cfg(feature = "grim")]
-    return save_geometry_with_grim(
-        geometry,
-        save_fullpath,
-        clipboard_only,
-        raw,
-        command,
-        silent,
-        notif_timeout,
-        debug,
-    );
-    #[cfg(not(feature = "grim"))]
-    compile_error!("Feature 'grim' must be enabled to save screenshots");
+    Ok(outcome)
 }
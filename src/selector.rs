@@ -1,5 +1,9 @@
 use anyhow::{Context, Result};
+use std::cell::Cell;
 use std::fmt;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::geometry::Geometry;
 
@@ -23,6 +27,8 @@ impl SelectionTarget {
 #[derive(Debug)]
 pub(crate) enum SelectorError {
     Cancelled(SelectionTarget),
+    /// `--selection-timeout` ran out after the given time.
+    TimedOut(Duration),
     Failed {
         target: SelectionTarget,
         message: String,
@@ -33,6 +39,9 @@ impl fmt::Display for SelectorError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Cancelled(target) => write!(f, "slurp failed to select {}", target.as_str()),
+            Self::TimedOut(after) => {
+                write!(f, "nothing was selected within {}s", after.as_secs_f64())
+            }
             Self::Failed { target, message } => {
                 write!(f, "slurp failed to select {}: {}", target.as_str(), message)
             }
@@ -58,14 +67,80 @@ fn selection_failed(target: SelectionTarget, message: impl Into<String>) -> anyh
     })
 }
 
-pub fn select_output(debug: bool) -> Result<Geometry> {
-    let selection = slurp_rs::select_output(slurp_rs::SelectOptions::default())
-        .map_err(|err| map_api_error(err, SelectionTarget::Output))?;
+thread_local! {
+    /// Deadline and length of the live [`SelectionTimeout`] on this thread.
+    static DEADLINE: Cell<Option<(Instant, Duration)>> = const { Cell::new(None) };
+}
+
+/// `--selection-timeout`: until dropped, a selection still open `after` the
+/// start fails with [`SelectorError::TimedOut`] ([`crate::exit::TIMED_OUT`]). The
+/// error unwinds like any other, so rounding, the freeze and temp files are
+/// cleaned up on the way out.
+#[must_use]
+pub struct SelectionTimeout {
+    _private: (),
+}
+
+impl SelectionTimeout {
+    pub fn start(after: Duration) -> Self {
+        DEADLINE.set(Some((Instant::now() + after, after)));
+        Self { _private: () }
+    }
+}
+
+impl Drop for SelectionTimeout {
+    fn drop(&mut self) {
+        DEADLINE.set(None);
+    }
+}
+
+/// Wait for `rx` until the [`SelectionTimeout`] deadline, if there is one.
+fn recv_until_deadline<T>(rx: &mpsc::Receiver<T>) -> Result<Option<T>, SelectorError> {
+    match DEADLINE.get() {
+        Some((deadline, after)) => {
+            match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(value) => Ok(Some(value)),
+                Err(mpsc::RecvTimeoutError::Timeout) => Err(SelectorError::TimedOut(after)),
+                Err(mpsc::RecvTimeoutError::Disconnected) => Ok(None),
+            }
+        }
+        None => Ok(rx.recv().ok()),
+    }
+}
+
+/// Run a slurp-rs selection within the [`SelectionTimeout`]. slurp-rs can't be
+/// interrupted, so with a deadline it runs on its own thread, which is left
+/// behind on timeout; its overlay goes when the process exits.
+fn slurp<T: Send + 'static>(
+    target: SelectionTarget,
+    select: impl FnOnce() -> Result<T, slurp_rs::SlurpError> + Send + 'static,
+) -> Result<T> {
+    if DEADLINE.get().is_none() {
+        return select().map_err(|err| map_api_error(err, target));
+    }
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(select());
+    });
+    match recv_until_deadline(&rx)? {
+        Some(result) => result.map_err(|err| map_api_error(err, target)),
+        None => Err(selection_failed(
+            target,
+            "slurp-rs stopped without a result",
+        )),
+    }
+}
+
+/// Returns the picked output's logical geometry and, when slurp reports it, its name.
+pub fn select_output(debug: bool) -> Result<(Geometry, Option<String>)> {
+    let selection = slurp(SelectionTarget::Output, || {
+        slurp_rs::select_output(slurp_rs::SelectOptions::default())
+    })?;
     let geometry = rect_to_geometry(&selection.rect)?;
     if debug {
         eprintln!("Output geometry: {}", geometry);
     }
-    Ok(geometry)
+    Ok((geometry, selection.output_name))
 }
 
 pub fn select_region(debug: bool) -> Result<Geometry> {
@@ -73,8 +148,7 @@ pub fn select_region(debug: bool) -> Result<Geometry> {
         display_dimensions: true,
         ..slurp_rs::SelectOptions::default()
     };
-    let selection = slurp_rs::select_region(options)
-        .map_err(|err| map_api_error(err, SelectionTarget::Region))?;
+    let selection = slurp(SelectionTarget::Region, || slurp_rs::select_region(options))?;
     let geometry = rect_to_geometry(&selection.rect)?;
     if debug {
         eprintln!("Region geometry: {}", geometry);
@@ -82,10 +156,28 @@ pub fn select_region(debug: bool) -> Result<Geometry> {
     Ok(geometry)
 }
 
+/// Boxes dragged one after another until the selection is cancelled
+/// (Escape), clipped to `within`; boxes entirely outside it are dropped.
+pub fn select_boxes_within(within: &Geometry, debug: bool) -> Result<Vec<Geometry>> {
+    let mut boxes = Vec::new();
+    loop {
+        match select_region(debug) {
+            Ok(selected) => match selected.intersect(*within) {
+                Some(clipped) => boxes.push(clipped),
+                None if debug => eprintln!("Ignoring {} outside the capture", selected),
+                None => {}
+            },
+            Err(err) if is_cancelled(&err, SelectionTarget::Region) => return Ok(boxes),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
 pub fn select_from_boxes(boxes: &str, debug: bool) -> Result<Geometry> {
     let choices = parse_choice_boxes(boxes)?;
-    let selection = slurp_rs::select_from_boxes(choices, slurp_rs::SelectOptions::default())
-        .map_err(|err| map_api_error(err, SelectionTarget::Window))?;
+    let selection = slurp(SelectionTarget::Window, || {
+        slurp_rs::select_from_boxes(choices, slurp_rs::SelectOptions::default())
+    })?;
     let geometry = rect_to_geometry(&selection.rect)?;
     if debug {
         eprintln!("Window geometry: {}", geometry);
@@ -93,6 +185,65 @@ pub fn select_from_boxes(boxes: &str, debug: bool) -> Result<Geometry> {
     Ok(geometry)
 }
 
+/// Let the user pick one of `choices` with a dmenu-style program (e.g.
+/// `fuzzel --dmenu`): choices go to its stdin one per line and the chosen line
+/// is read back from stdout. `menu_command` is split on whitespace.
+pub fn select_from_menu(menu_command: &str, choices: &[String], debug: bool) -> Result<String> {
+    use std::io::{Read, Write};
+    use std::process::{Command, Stdio};
+
+    let mut argv = menu_command.split_whitespace();
+    let program = argv.next().context("Menu command is empty")?;
+    let mut child = Command::new(program)
+        .args(argv)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start menu command '{}'", program))?;
+    // A menu that exits without reading (e.g. closed at once) is handled below
+    // as a cancellation, not as a write error.
+    if let Some(mut stdin) = child.stdin.take()
+        && let Err(err) = stdin.write_all(choices.join("\n").as_bytes())
+        && err.kind() != std::io::ErrorKind::BrokenPipe
+    {
+        return Err(anyhow::Error::new(err).context("Failed to write choices to the menu"));
+    }
+    // Read on a thread, so a menu left open past the timeout can be killed.
+    let mut stdout = child.stdout.take().context("Menu stdout is not piped")?;
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut out = Vec::new();
+        let _ = tx.send(stdout.read_to_end(&mut out).map(|_| out));
+    });
+    let read = match recv_until_deadline(&rx) {
+        Ok(read) => read,
+        Err(err) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(err.into());
+        }
+    };
+    let out = read
+        .context("Menu reader stopped")?
+        .context("Failed to read the menu selection")?;
+    let status = child.wait().context("Failed to read the menu selection")?;
+
+    let choice = String::from_utf8_lossy(&out).trim().to_string();
+    if debug {
+        eprintln!("Menu selection: {:?} ({})", choice, status);
+    }
+    if !status.success() || choice.is_empty() {
+        return Err(cancelled_error(SelectionTarget::Output));
+    }
+    if !choices.contains(&choice) {
+        return Err(selection_failed(
+            SelectionTarget::Output,
+            format!("menu returned an unknown output '{}'", choice),
+        ));
+    }
+    Ok(choice)
+}
+
 fn rect_to_geometry(rect: &slurp_rs::Rect) -> Result<Geometry> {
     Geometry::from_slurp_rect(rect)
 }
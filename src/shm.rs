@@ -0,0 +1,262 @@
+//! shm buffer formats: converting them to RGBA, undoing output transforms,
+//! and finding out which format each output's screencopy frames use.
+//!
+//! grim-rs converts only 8-bit buffers and only turns rotated outputs upright
+//! on its region path; captures through `Grim::capture_outputs` (the one that
+//! can include the cursor) need both done here.
+
+use anyhow::{Context, Result};
+use wayland_client::{
+    Connection, Dispatch, QueueHandle, WEnum,
+    protocol::{
+        wl_output::Transform,
+        wl_registry::{self, WlRegistry},
+        wl_shm,
+    },
+};
+use wayland_protocols_wlr::screencopy::v1::client::{
+    zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+    zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+};
+
+use crate::backend::CapturedImage;
+use crate::geometry::Geometry;
+use crate::outputs::{Outputs, delegate_outputs};
+
+/// Turn a buffer of an output with `transform` the way the output shows it.
+/// The compositor renders into the buffer with the transform applied (a
+/// quarter turn counter-clockwise for `90`), so this undoes it.
+pub(crate) fn untransform(image: CapturedImage, transform: Transform) -> CapturedImage {
+    if transform == Transform::Normal {
+        return image;
+    }
+    let (w, h) = (image.width, image.height);
+    let (width, height) = if crate::capture::is_rotated(transform) {
+        (h, w)
+    } else {
+        (w, h)
+    };
+    // Buffer pixel shown at (x, y).
+    let source = |x: u32, y: u32| match transform {
+        Transform::_90 => (y, width - 1 - x),
+        Transform::_180 => (width - 1 - x, height - 1 - y),
+        Transform::_270 => (height - 1 - y, x),
+        Transform::Flipped => (width - 1 - x, y),
+        Transform::Flipped90 => (y, x),
+        Transform::Flipped180 => (x, height - 1 - y),
+        Transform::Flipped270 => (height - 1 - y, width - 1 - x),
+        _ => (x, y),
+    };
+    let mut data = Vec::with_capacity(image.data.len());
+    for y in 0..height {
+        for x in 0..width {
+            let (sx, sy) = source(x, y);
+            let i = (sy as usize * w as usize + sx as usize) * 4;
+            data.extend_from_slice(&image.data[i..i + 4]);
+        }
+    }
+    CapturedImage {
+        data,
+        width,
+        height,
+    }
+}
+
+/// RGB formats [`to_rgba`] can convert, 8-bit ones first since they need no
+/// rounding.
+pub(crate) const SUPPORTED_FORMATS: [wl_shm::Format; 8] = [
+    wl_shm::Format::Xrgb8888,
+    wl_shm::Format::Argb8888,
+    wl_shm::Format::Xbgr8888,
+    wl_shm::Format::Abgr8888,
+    wl_shm::Format::Xrgb2101010,
+    wl_shm::Format::Argb2101010,
+    wl_shm::Format::Xbgr2101010,
+    wl_shm::Format::Abgr2101010,
+];
+
+pub(crate) fn is_deep_color(format: wl_shm::Format) -> bool {
+    matches!(
+        format,
+        wl_shm::Format::Xrgb2101010
+            | wl_shm::Format::Argb2101010
+            | wl_shm::Format::Xbgr2101010
+            | wl_shm::Format::Abgr2101010
+    )
+}
+
+/// Convert a little-endian shm buffer into RGBA bytes. 10-bit channels are
+/// rounded to 8 bits; like the 8-bit formats they are already sRGB-encoded.
+pub(crate) fn to_rgba(buffer: &[u8], format: wl_shm::Format) -> Vec<u8> {
+    let mut out = Vec::with_capacity(buffer.len());
+    for px in buffer.chunks_exact(4) {
+        let word = u32::from_le_bytes([px[0], px[1], px[2], px[3]]);
+        let ten = |shift: u32| ((((word >> shift) & 0x3ff) * 255 + 511) / 1023) as u8;
+        let two = ((word >> 30) * 85) as u8;
+        let (r, g, b, a) = match format {
+            wl_shm::Format::Argb8888 => (px[2], px[1], px[0], px[3]),
+            wl_shm::Format::Xrgb8888 => (px[2], px[1], px[0], 0xff),
+            wl_shm::Format::Abgr8888 => (px[0], px[1], px[2], px[3]),
+            wl_shm::Format::Argb2101010 => (ten(20), ten(10), ten(0), two),
+            wl_shm::Format::Xrgb2101010 => (ten(20), ten(10), ten(0), 0xff),
+            wl_shm::Format::Abgr2101010 => (ten(0), ten(10), ten(20), two),
+            wl_shm::Format::Xbgr2101010 => (ten(0), ten(10), ten(20), 0xff),
+            _ => (px[0], px[1], px[2], 0xff),
+        };
+        out.extend_from_slice(&[r, g, b, a]);
+    }
+    out
+}
+
+/// A named output and the shm format of its screencopy frames.
+pub(crate) struct ScreencopyOutput {
+    pub name: String,
+    /// Logical geometry.
+    pub bounds: Geometry,
+    pub transform: Transform,
+    /// The last shm format offered, which is the one grim-rs allocates;
+    /// `None` when the compositor offered none it names.
+    pub format: Option<wl_shm::Format>,
+}
+
+impl ScreencopyOutput {
+    /// Whether grim-rs hands this output's pixels back unconverted.
+    pub fn is_deep_color(&self) -> bool {
+        self.format.is_some_and(is_deep_color)
+    }
+}
+
+/// Every named output with its screencopy format, found by asking for a
+/// frame of each and dropping it once its buffer types are known.
+pub(crate) fn probe_outputs() -> Result<Vec<ScreencopyOutput>> {
+    let conn = Connection::connect_to_env().context("Failed to connect to Wayland")?;
+    let mut queue = conn.new_event_queue();
+    let qh = queue.handle();
+    let _registry = conn.display().get_registry(&qh, ());
+
+    #[derive(Default)]
+    struct Probe {
+        format: Option<wl_shm::Format>,
+        done: bool,
+    }
+
+    #[derive(Default)]
+    struct State {
+        outputs: Outputs,
+        manager: Option<(ZwlrScreencopyManagerV1, u32)>,
+        probe: Probe,
+    }
+
+    impl AsMut<Outputs> for State {
+        fn as_mut(&mut self) -> &mut Outputs {
+            &mut self.outputs
+        }
+    }
+
+    impl Dispatch<WlRegistry, ()> for State {
+        fn event(
+            state: &mut Self,
+            registry: &WlRegistry,
+            event: wl_registry::Event,
+            _: &(),
+            _: &Connection,
+            qh: &QueueHandle<Self>,
+        ) {
+            if let wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } = event
+            {
+                if interface == "zwlr_screencopy_manager_v1" {
+                    let version = version.min(3);
+                    state.manager = Some((registry.bind(name, version, qh, ()), version));
+                } else {
+                    state.outputs.bind(registry, name, &interface, version, qh);
+                }
+            }
+        }
+    }
+
+    impl Dispatch<ZwlrScreencopyFrameV1, ()> for State {
+        fn event(
+            state: &mut Self,
+            _: &ZwlrScreencopyFrameV1,
+            event: zwlr_screencopy_frame_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            let version = state.manager.as_ref().map_or(1, |(_, version)| *version);
+            match event {
+                zwlr_screencopy_frame_v1::Event::Buffer { format, .. } => {
+                    if let WEnum::Value(format) = format {
+                        state.probe.format = Some(format);
+                    }
+                    // Before version 3 this is the only buffer event.
+                    if version < 3 {
+                        state.probe.done = true;
+                    }
+                }
+                zwlr_screencopy_frame_v1::Event::BufferDone
+                | zwlr_screencopy_frame_v1::Event::Failed => {
+                    state.probe.done = true;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    impl Dispatch<ZwlrScreencopyManagerV1, ()> for State {
+        fn event(
+            _: &mut Self,
+            _: &ZwlrScreencopyManagerV1,
+            _: zwlr_screencopy_manager_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    delegate_outputs!(State);
+
+    let mut state = State::default();
+    queue
+        .roundtrip(&mut state)
+        .context("Failed to initialize Wayland globals")?;
+    if state.outputs.request_logical(&qh) {
+        queue
+            .roundtrip(&mut state)
+            .context("Failed to receive output geometry")?;
+    }
+    let manager = state
+        .manager
+        .as_ref()
+        .map(|(manager, _)| manager.clone())
+        .context("wlr-screencopy is not offered by the compositor")?;
+
+    let mut outputs = Vec::new();
+    for idx in 0..state.outputs.entries.len() {
+        let entry = &state.outputs.entries[idx];
+        let (Some(name), Some(bounds)) = (entry.name.clone(), entry.geometry()) else {
+            continue;
+        };
+        let transform = entry.transform;
+        state.probe = Probe::default();
+        let frame = manager.capture_output(0, &entry.output, &qh, ());
+        while !state.probe.done {
+            queue
+                .blocking_dispatch(&mut state)
+                .context("Failed to receive screencopy buffer constraints")?;
+        }
+        frame.destroy();
+        outputs.push(ScreencopyOutput {
+            name,
+            bounds,
+            transform,
+            format: state.probe.format,
+        });
+    }
+    Ok(outputs)
+}
@@ -0,0 +1,165 @@
+//! Minimal client for the i3/Sway IPC protocol, used instead of running
+//! `swaymsg` for every query.
+//!
+//! A message is the magic `i3-ipc`, the payload length and the message type
+//! (both native-endian `u32`), followed by the payload. Replies use the same
+//! header and echo the message type.
+
+use anyhow::Result;
+use serde_json::Value;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const MAGIC: &[u8; 6] = b"i3-ipc";
+const HEADER_LEN: usize = MAGIC.len() + 8;
+const IPC_TIMEOUT: Duration = Duration::from_secs(3);
+/// Large trees are a few hundred KiB; anything near this is a broken reply.
+const MAX_REPLY_LEN: u32 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MessageType {
+    Workspaces = 1,
+    Outputs = 3,
+    Tree = 4,
+    Version = 7,
+}
+
+impl MessageType {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Workspaces => "get_workspaces",
+            Self::Outputs => "get_outputs",
+            Self::Tree => "get_tree",
+            Self::Version => "get_version",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum SwayIpcError {
+    /// Neither `SWAYSOCK` nor `I3SOCK` is set.
+    NoSocket,
+    Connect {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Io(io::Error),
+    Protocol(String),
+}
+
+impl fmt::Display for SwayIpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoSocket => write!(
+                f,
+                "Sway IPC socket not found: SWAYSOCK and I3SOCK are unset"
+            ),
+            Self::Connect { path, source } => write!(
+                f,
+                "Failed to connect to Sway IPC socket '{}': {}",
+                path.display(),
+                source
+            ),
+            Self::Io(err) => write!(f, "Sway IPC failed: {}", err),
+            Self::Protocol(message) => write!(f, "Invalid Sway IPC reply: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SwayIpcError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Connect { source, .. } | Self::Io(source) => Some(source),
+            Self::NoSocket | Self::Protocol(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for SwayIpcError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+fn socket_path() -> Result<PathBuf, SwayIpcError> {
+    ["SWAYSOCK", "I3SOCK"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .find(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .ok_or(SwayIpcError::NoSocket)
+}
+
+/// Send `kind` with an empty payload and return the parsed JSON reply.
+pub(crate) fn query(kind: MessageType) -> Result<Value> {
+    let path = socket_path()?;
+    let mut stream = UnixStream::connect(&path).map_err(|source| SwayIpcError::Connect {
+        path: path.clone(),
+        source,
+    })?;
+    stream
+        .set_read_timeout(Some(IPC_TIMEOUT))
+        .map_err(SwayIpcError::Io)?;
+    stream
+        .set_write_timeout(Some(IPC_TIMEOUT))
+        .map_err(SwayIpcError::Io)?;
+    stream
+        .write_all(&encode_message(kind, b""))
+        .map_err(SwayIpcError::Io)?;
+    let reply = read_reply(&mut stream, kind)?;
+    serde_json::from_slice(&reply).map_err(|err| {
+        anyhow::Error::new(SwayIpcError::Protocol(format!(
+            "{} reply is not JSON: {}",
+            kind.as_str(),
+            err
+        )))
+    })
+}
+
+pub(crate) fn encode_message(kind: MessageType, payload: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(HEADER_LEN + payload.len());
+    message.extend_from_slice(MAGIC);
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&(kind as u32).to_ne_bytes());
+    message.extend_from_slice(payload);
+    message
+}
+
+/// Read one reply and check that it answers `kind`.
+pub(crate) fn read_reply(
+    reader: &mut impl Read,
+    kind: MessageType,
+) -> Result<Vec<u8>, SwayIpcError> {
+    let mut header = [0u8; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(SwayIpcError::Protocol("bad magic".to_string()));
+    }
+    let field = |offset: usize| {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&header[offset..offset + 4]);
+        u32::from_ne_bytes(bytes)
+    };
+    let len = field(MAGIC.len());
+    let reply_kind = field(MAGIC.len() + 4);
+    if reply_kind != kind as u32 {
+        return Err(SwayIpcError::Protocol(format!(
+            "expected a {} reply, got message type {}",
+            kind.as_str(),
+            reply_kind
+        )));
+    }
+    if len > MAX_REPLY_LEN {
+        return Err(SwayIpcError::Protocol(format!(
+            "{} reply of {} bytes is too large",
+            kind.as_str(),
+            len
+        )));
+    }
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload)?;
+    Ok(payload)
+}
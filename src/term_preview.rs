@@ -0,0 +1,177 @@
+//! `--term-preview`: show the capture inline in the terminal with the kitty
+//! graphics protocol or sixel, so users on ssh can see what they captured
+//! without opening a viewer.
+
+use anyhow::{Context, Result};
+use std::io::{IsTerminal, Write};
+
+use crate::backend::CapturedImage;
+use crate::png_encoder;
+use crate::resize::{self, Resize};
+use crate::utils::base64;
+
+/// Box the preview is shrunk to fit; a terminal shows no more than that and
+/// the image may go over a slow ssh link.
+const MAX_WIDTH: u32 = 800;
+const MAX_HEIGHT: u32 = 600;
+/// Largest payload of one kitty graphics escape.
+const KITTY_CHUNK: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Protocol {
+    Kitty,
+    Sixel,
+}
+
+/// Protocol of a terminal from `TERM`, `TERM_PROGRAM` and whether
+/// `KITTY_WINDOW_ID` is set. Only `TERM` survives ssh, so it is checked first.
+pub(crate) fn protocol_for(term: &str, term_program: &str, kitty_window: bool) -> Option<Protocol> {
+    if term == "xterm-kitty" || term == "xterm-ghostty" || kitty_window {
+        return Some(Protocol::Kitty);
+    }
+    if term.starts_with("foot") || term.contains("mlterm") || term.contains("sixel") {
+        return Some(Protocol::Sixel);
+    }
+    match term_program {
+        "WezTerm" | "ghostty" => Some(Protocol::Kitty),
+        _ => None,
+    }
+}
+
+/// The protocol the terminal on stdout understands; `None` when stdout is
+/// piped or the terminal isn't known to show images.
+fn detect() -> Option<Protocol> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    protocol_for(
+        &std::env::var("TERM").unwrap_or_default(),
+        &std::env::var("TERM_PROGRAM").unwrap_or_default(),
+        std::env::var_os("KITTY_WINDOW_ID").is_some(),
+    )
+}
+
+/// Print `image` to stdout if the terminal can show it.
+pub(crate) fn show(image: &CapturedImage, debug: bool) -> Result<()> {
+    let Some(protocol) = detect() else {
+        if debug {
+            eprintln!("stdout isn't a terminal with kitty graphics or sixel; no preview");
+        }
+        return Ok(());
+    };
+    let shrunk;
+    let preview = if image.width > MAX_WIDTH || image.height > MAX_HEIGHT {
+        shrunk = resize::apply(image.clone(), Resize::Fit(MAX_WIDTH, MAX_HEIGHT));
+        &shrunk
+    } else {
+        image
+    };
+    let escapes = match protocol {
+        Protocol::Kitty => kitty(&png_encoder::encode(preview, 1)?),
+        Protocol::Sixel => sixel(preview),
+    };
+    let mut stdout = std::io::stdout().lock();
+    stdout
+        .write_all(&escapes)
+        .and_then(|_| stdout.flush())
+        .context("Failed to print the preview")
+}
+
+/// Kitty graphics escapes transmitting and placing `png`, split into
+/// chunks, then a newline so the prompt starts below the image.
+pub(crate) fn kitty(png: &[u8]) -> Vec<u8> {
+    let encoded = base64(png);
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut out = Vec::with_capacity(encoded.len() + chunks.len() * 16);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        let control = if i == 0 {
+            format!("a=T,f=100,m={}", more)
+        } else {
+            format!("m={}", more)
+        };
+        out.extend_from_slice(format!("\x1b_G{};", control).as_bytes());
+        out.extend_from_slice(chunk);
+        out.extend_from_slice(b"\x1b\\");
+    }
+    out.push(b'\n');
+    out
+}
+
+/// Sixel image of `image` in a 6x6x6 color cube. Mostly transparent pixels
+/// are left unpainted, showing the terminal background.
+pub(crate) fn sixel(image: &CapturedImage) -> Vec<u8> {
+    let (width, height) = (image.width as usize, image.height as usize);
+    let level = |c: u8| (usize::from(c) * 5 + 127) / 255;
+    let colors: Vec<Option<u8>> = image
+        .data
+        .chunks_exact(4)
+        .map(|px| {
+            (px[3] >= 128).then(|| (level(px[0]) * 36 + level(px[1]) * 6 + level(px[2])) as u8)
+        })
+        .collect();
+
+    // P2 = 1: pixels without a color keep the background.
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", width, height).into_bytes();
+    for i in 0..216 {
+        let percent = |step: usize| step * 20;
+        out.extend_from_slice(
+            format!(
+                "#{};2;{};{};{}",
+                i,
+                percent(i / 36),
+                percent(i / 6 % 6),
+                percent(i % 6)
+            )
+            .as_bytes(),
+        );
+    }
+    for top in (0..height).step_by(6) {
+        let rows = top..(top + 6).min(height);
+        let mut used = [false; 216];
+        for y in rows.clone() {
+            for color in colors[y * width..(y + 1) * width].iter().flatten() {
+                used[usize::from(*color)] = true;
+            }
+        }
+        for color in (0..216u8).filter(|&color| used[usize::from(color)]) {
+            out.extend_from_slice(format!("#{}", color).as_bytes());
+            let mut run: Option<(u8, usize)> = None;
+            for x in 0..width {
+                let bits = rows.clone().fold(0u8, |bits, y| {
+                    if colors[y * width + x] == Some(color) {
+                        bits | 1 << (y - top)
+                    } else {
+                        bits
+                    }
+                });
+                let sixel = b'?' + bits;
+                run = match run {
+                    Some((c, n)) if c == sixel => Some((c, n + 1)),
+                    Some((c, n)) => {
+                        push_run(&mut out, c, n);
+                        Some((sixel, 1))
+                    }
+                    None => Some((sixel, 1)),
+                };
+            }
+            if let Some((c, n)) = run {
+                push_run(&mut out, c, n);
+            }
+            // Back to the start of the band for the next color.
+            out.push(b'$');
+        }
+        out.push(b'-');
+    }
+    out.extend_from_slice(b"\x1b\\");
+    out
+}
+
+fn push_run(out: &mut Vec<u8>, sixel: u8, count: usize) {
+    if count > 3 {
+        out.extend_from_slice(format!("!{}", count).as_bytes());
+        out.push(sixel);
+    } else {
+        out.extend(std::iter::repeat_n(sixel, count));
+    }
+}
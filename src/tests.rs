@@ -1,4 +1,4 @@
-use crate::{Args, Mode, default_filename, resolve_delay, resolve_notif_timeout};
+use crate::cli::{Args, Mode, parse_delay, resolve_delay, resolve_freeze, resolve_notif_timeout};
 use chrono::TimeZone;
 use clap::Parser;
 use std::str::FromStr;
@@ -34,6 +34,168 @@ fn delay_uses_milliseconds_from_config() {
     assert_eq!(resolve_delay(&args, &config), Duration::from_millis(250));
 }
 
+#[test]
+fn delay_cli_overrides_config() {
+    let mut config = crate::config::Config::default();
+    config.advanced.delay_ms = 250;
+
+    let args = Args::parse_from(["hyprshot-rs", "-m", "region", "-D", "500ms"]);
+    assert_eq!(resolve_delay(&args, &config), Duration::from_millis(500));
+}
+
+#[test]
+fn freeze_flags_override_config() {
+    let mut config = crate::config::Config::default();
+    config.advanced.freeze_on_region = true;
+
+    let args = Args::parse_from(["hyprshot-rs", "-m", "region"]);
+    assert!(resolve_freeze(&args, &config));
+    let args = Args::parse_from(["hyprshot-rs", "-m", "region", "--no-freeze"]);
+    assert!(!resolve_freeze(&args, &config));
+
+    config.advanced.freeze_on_region = false;
+    let args = Args::parse_from(["hyprshot-rs", "-m", "region"]);
+    assert!(!resolve_freeze(&args, &config));
+    let args = Args::parse_from(["hyprshot-rs", "-m", "region", "--freeze"]);
+    assert!(resolve_freeze(&args, &config));
+
+    assert!(Args::try_parse_from(["hyprshot-rs", "--freeze", "--no-freeze"]).is_err());
+}
+
+#[test]
+fn record_picks_container_and_encoder() {
+    use crate::config::RecordFormat;
+    use crate::record::{default_fps, encoder_args, frames_due, video_path};
+    use std::path::Path;
+
+    assert_eq!(
+        video_path(Path::new("/tmp/shot_hyprshot.png"), RecordFormat::Mp4),
+        Path::new("/tmp/shot_hyprshot.mp4")
+    );
+    assert_eq!(
+        video_path(Path::new("/tmp/clip"), RecordFormat::Mp4),
+        Path::new("/tmp/clip.mp4")
+    );
+    assert_eq!(
+        video_path(Path::new("/tmp/clip.webm"), RecordFormat::Webm),
+        Path::new("/tmp/clip.webm")
+    );
+    assert_eq!(
+        video_path(Path::new("/tmp/shot.webp"), RecordFormat::Gif),
+        Path::new("/tmp/shot.gif")
+    );
+
+    let mp4 = encoder_args(Path::new("/tmp/clip.mp4"), 801, 600, 30);
+    let value_after = |args: &[String], flag: &str| {
+        args.iter()
+            .position(|a| a == flag)
+            .and_then(|i| args.get(i + 1).cloned())
+    };
+    assert_eq!(value_after(&mp4, "-video_size").as_deref(), Some("801x600"));
+    assert_eq!(value_after(&mp4, "-framerate").as_deref(), Some("30"));
+    assert_eq!(value_after(&mp4, "-c:v").as_deref(), Some("libx264"));
+    assert_eq!(mp4.last().map(String::as_str), Some("/tmp/clip.mp4"));
+    let webm = encoder_args(Path::new("/tmp/clip.webm"), 800, 600, 30);
+    assert_eq!(value_after(&webm, "-c:v").as_deref(), Some("libvpx-vp9"));
+    let gif = encoder_args(Path::new("/tmp/clip.gif"), 801, 600, 15);
+    assert!(value_after(&gif, "-vf").is_some_and(|vf| vf.contains("paletteuse")));
+    assert_eq!(value_after(&gif, "-loop").as_deref(), Some("0"));
+    assert!(!gif.iter().any(|a| a == "yuv420p"));
+    let webp = encoder_args(Path::new("/tmp/clip.webp"), 800, 600, 15);
+    assert_eq!(value_after(&webp, "-c:v").as_deref(), Some("libwebp"));
+    assert_eq!(webp.last().map(String::as_str), Some("/tmp/clip.webp"));
+    assert_eq!(default_fps(RecordFormat::Mp4), 30);
+    assert_eq!(default_fps(RecordFormat::Gif), 15);
+
+    assert_eq!(frames_due(Duration::ZERO, 30), 1);
+    assert_eq!(frames_due(Duration::from_millis(1000), 30), 31);
+    assert_eq!(frames_due(Duration::from_millis(1010), 30), 31);
+}
+
+#[test]
+fn record_format_precedence() {
+    use crate::cli::resolve_record_format;
+    use crate::config::RecordFormat;
+
+    let mut config = crate::config::Config::default();
+    let args = Args::parse_from(["hyprshot-rs", "-m", "region", "--record"]);
+    assert_eq!(resolve_record_format(&args, &config), RecordFormat::Mp4);
+    config.record.format = RecordFormat::Webm;
+    assert_eq!(resolve_record_format(&args, &config), RecordFormat::Webm);
+    let args = Args::parse_from(["hyprshot-rs", "-m", "region", "--record", "-f", "bug.GIF"]);
+    assert_eq!(resolve_record_format(&args, &config), RecordFormat::Gif);
+    let args = Args::parse_from([
+        "hyprshot-rs",
+        "-m",
+        "region",
+        "--record",
+        "-f",
+        "bug.gif",
+        "--record-format",
+        "webp",
+        "--fps",
+        "10",
+        "--max-duration",
+        "5s",
+    ]);
+    assert_eq!(resolve_record_format(&args, &config), RecordFormat::Webp);
+    assert_eq!(args.fps, Some(10));
+    assert_eq!(args.max_duration, Some(Duration::from_secs(5)));
+
+    assert!(Args::try_parse_from(["hyprshot-rs", "--fps", "10"]).is_err());
+    assert!(Args::try_parse_from(["hyprshot-rs", "--record", "--fps", "0"]).is_err());
+}
+
+#[test]
+fn record_conflicts_with_non_file_outputs() {
+    for flag in ["--raw", "--clipboard-only", "--print-geometry"] {
+        assert!(
+            Args::try_parse_from(["hyprshot-rs", "-m", "region", "--record", flag]).is_err(),
+            "--record {flag} should be rejected"
+        );
+    }
+    assert!(Args::try_parse_from(["hyprshot-rs", "--record"]).is_ok());
+}
+
+#[test]
+fn parse_delay_supports_units() {
+    assert_eq!(parse_delay("3"), Ok(Duration::from_secs(3)));
+    assert_eq!(parse_delay("2s"), Ok(Duration::from_secs(2)));
+    assert_eq!(parse_delay("500ms"), Ok(Duration::from_millis(500)));
+    assert_eq!(parse_delay("1m"), Ok(Duration::from_secs(60)));
+    assert_eq!(parse_delay("2h"), Ok(Duration::from_secs(7200)));
+    assert_eq!(parse_delay("0"), Ok(Duration::from_secs(0)));
+}
+
+#[test]
+fn parse_delay_accepts_decimals() {
+    assert_eq!(parse_delay("0.5"), Ok(Duration::from_millis(500)));
+    assert_eq!(parse_delay(".25"), Ok(Duration::from_millis(250)));
+    assert_eq!(parse_delay("1.5s"), Ok(Duration::from_millis(1500)));
+    assert_eq!(parse_delay("0.25m"), Ok(Duration::from_secs(15)));
+    assert_eq!(parse_delay("2.5ms"), Ok(Duration::from_micros(2500)));
+    assert!(parse_delay("1.").is_err());
+    assert!(parse_delay(".").is_err());
+    assert!(parse_delay("1.2.3").is_err());
+
+    let args = Args::parse_from(["hyprshot-rs", "-m", "region", "-D", "0.5"]);
+    let config = crate::config::Config::default();
+    assert_eq!(
+        crate::cli::resolve_delay(&args, &config),
+        Duration::from_millis(500)
+    );
+}
+
+#[test]
+fn parse_delay_rejects_invalid_input() {
+    assert!(parse_delay("").is_err());
+    assert!(parse_delay("ms").is_err());
+    assert!(parse_delay("-1").is_err());
+    assert!(parse_delay("5d").is_err());
+    assert!(parse_delay("5 s").is_err());
+    assert!(parse_delay("99999999999999999999").is_err());
+}
+
 #[test]
 fn filename_includes_milliseconds() {
     let now = match chrono::Local
@@ -43,8 +205,178 @@ fn filename_includes_milliseconds() {
         Some(v) => v,
         None => panic!("Failed to construct timestamp for test"),
     };
-    let name = default_filename(now);
-    assert!(name.ends_with("-123_hyprshot.png"));
+    let name = crate::filename::expand(
+        crate::filename::DEFAULT_TEMPLATE,
+        &crate::filename::Fields::new(now),
+    );
+    assert!(name.ends_with("-123_hyprshot"));
+}
+
+#[test]
+fn filename_template_expands_placeholders() {
+    use crate::filename::{DEFAULT_TEMPLATE, Fields, expand, uses, validate};
+
+    let now = match chrono::Local.with_ymd_and_hms(2024, 5, 6, 7, 8, 9) {
+        chrono::LocalResult::Single(v) => v,
+        _ => panic!("invalid test time"),
+    };
+    let mut fields = Fields::new(now);
+    assert_eq!(
+        expand(DEFAULT_TEMPLATE, &fields),
+        "2024-05-06-070809-000_hyprshot"
+    );
+    assert_eq!(
+        expand("%mode-%output-%title", &fields),
+        "unknown-unknown-unknown"
+    );
+
+    fields.mode = Some("active-window".to_string());
+    fields.output = Some("DP-1".to_string());
+    fields.title = Some(" ~/src: vim\tmain.rs ".to_string());
+    fields.class = Some("kitty".to_string());
+    fields.counter = 3;
+    assert_eq!(
+        expand(
+            "%Y%m%d_%H%M%S_%mode_%ms_%output_%class_%counter_100%%",
+            &fields
+        ),
+        "20240506_070809_active-window_000_DP-1_kitty_3_100%"
+    );
+    assert_eq!(expand("%title", &fields), "~_src: vim_main.rs");
+    fields.title = Some("x".repeat(100));
+    assert_eq!(expand("%title", &fields).len(), 64);
+
+    assert!(uses("shot-%counter", "counter"));
+    assert!(!uses("shot-%%counter", "counter"));
+    assert!(!uses("%mode", "m"));
+
+    if let Err(err) = validate("%Y-%q") {
+        assert!(err.to_string().contains("'%q'"), "{err}");
+    } else {
+        panic!("expected unknown placeholder error");
+    }
+    assert!(validate("shots/%Y").is_err());
+    assert!(validate("").is_err());
+    assert!(validate("100%%_%counter").is_ok());
+
+    // %counter skips names that are taken.
+    let dir = test_output_dir("free-name");
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        panic!("Failed to create {}: {}", dir.display(), err);
+    }
+    for taken in ["shot-1.png", "shot-2.png"] {
+        if let Err(err) = std::fs::write(dir.join(taken), b"") {
+            panic!("Failed to write {}: {}", taken, err);
+        }
+    }
+    let mut fields = Fields::new(now);
+    assert_eq!(
+        crate::filename::free_name(&dir, "shot-%counter", "png", &mut fields),
+        "shot-3.png"
+    );
+    assert_eq!(
+        crate::filename::free_name(&dir, "shot-%counter", "jpg", &mut Fields::new(now)),
+        "shot-1.jpg"
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let outputs = vec![
+        ("DP-1".to_string(), geometry_or_panic(0, 0, 1920, 1080)),
+        (
+            "HDMI-A-1".to_string(),
+            geometry_or_panic(1920, 0, 1920, 1080),
+        ),
+    ];
+    assert_eq!(
+        crate::capture::output_with_most_overlap(&geometry_or_panic(1800, 10, 400, 300), &outputs),
+        Some("HDMI-A-1")
+    );
+    assert_eq!(
+        crate::capture::output_with_most_overlap(&geometry_or_panic(5000, 0, 10, 10), &outputs),
+        None
+    );
+}
+
+#[test]
+fn existing_files_are_kept_by_the_conflict_policy() {
+    use crate::config::ConflictPolicy;
+    use crate::filename::resolve_conflict;
+
+    let dir = test_output_dir("on-conflict");
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        panic!("Failed to create {}: {}", dir.display(), err);
+    }
+    let path = dir.join("shot.png");
+    match resolve_conflict(&path, ConflictPolicy::Fail) {
+        Ok(resolved) => assert_eq!(resolved, path),
+        Err(err) => panic!("a free name should be kept: {}", err),
+    }
+
+    for name in ["shot.png", "shot-1.png"] {
+        if let Err(err) = std::fs::write(dir.join(name), b"") {
+            panic!("Failed to write {}: {}", name, err);
+        }
+    }
+    match resolve_conflict(&path, ConflictPolicy::Increment) {
+        Ok(resolved) => assert_eq!(resolved, dir.join("shot-2.png")),
+        Err(err) => panic!("increment failed: {}", err),
+    }
+    match resolve_conflict(&path, ConflictPolicy::Overwrite) {
+        Ok(resolved) => assert_eq!(resolved, path),
+        Err(err) => panic!("overwrite failed: {}", err),
+    }
+    if resolve_conflict(&path, ConflictPolicy::Fail).is_ok() {
+        panic!("expected an existing file to be rejected");
+    }
+    if let Err(err) = std::fs::write(dir.join("notes"), b"") {
+        panic!("Failed to write notes: {}", err);
+    }
+    match resolve_conflict(&dir.join("notes"), ConflictPolicy::Increment) {
+        Ok(resolved) => assert_eq!(resolved, dir.join("notes-1")),
+        Err(err) => panic!("increment failed: {}", err),
+    }
+    assert_eq!(
+        crate::config::Config::default().paths.on_conflict,
+        ConflictPolicy::Increment
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn constrain_to_output_clips_to_the_output_showing_most() {
+    use crate::capture::constrain_to_output;
+
+    let outputs = vec![
+        ("DP-1".to_string(), geometry_or_panic(0, 0, 1920, 1080)),
+        (
+            "HDMI-A-1".to_string(),
+            geometry_or_panic(1920, 0, 2560, 1440),
+        ),
+    ];
+    assert_eq!(
+        constrain_to_output(geometry_or_panic(1800, 100, 400, 300), &outputs),
+        geometry_or_panic(1920, 100, 280, 300)
+    );
+    assert_eq!(
+        constrain_to_output(geometry_or_panic(1000, 900, 1000, 400), &outputs),
+        geometry_or_panic(1000, 900, 920, 180)
+    );
+    // Inside one output, or on none, the selection is kept as is.
+    assert_eq!(
+        constrain_to_output(geometry_or_panic(10, 10, 50, 50), &outputs),
+        geometry_or_panic(10, 10, 50, 50)
+    );
+    assert_eq!(
+        constrain_to_output(geometry_or_panic(-500, -500, 50, 50), &outputs),
+        geometry_or_panic(-500, -500, 50, 50)
+    );
+}
+
+fn geometry_or_panic(x: i32, y: i32, width: i32, height: i32) -> crate::geometry::Geometry {
+    match crate::geometry::Geometry::new(x, y, width, height) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to construct geometry: {}", err),
+    }
 }
 
 #[test]
@@ -63,8 +395,13 @@ fn filenames_differ_for_distinct_timestamps() {
         Some(v) => v,
         None => panic!("Failed to construct second timestamp for test"),
     };
-    let a = default_filename(first);
-    let b = default_filename(second);
+    let name = |now| {
+        crate::filename::expand(
+            crate::filename::DEFAULT_TEMPLATE,
+            &crate::filename::Fields::new(now),
+        )
+    };
+    let (a, b) = (name(first), name(second));
     assert_ne!(a, b);
 }
 
@@ -84,6 +421,238 @@ fn geometry_parses_and_validates() {
     assert!(crate::geometry::Geometry::from_str("10,20 300x0").is_err());
 }
 
+#[test]
+fn aspect_ratio_parses_and_validates() {
+    use crate::geometry::AspectRatio;
+
+    let aspect = match AspectRatio::from_str("16:9") {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to parse aspect ratio: {}", err),
+    };
+    assert_eq!(aspect.width, 16);
+    assert_eq!(aspect.height, 9);
+
+    assert!(AspectRatio::from_str("16x9").is_err());
+    assert!(AspectRatio::from_str("0:9").is_err());
+    assert!(AspectRatio::from_str("16:").is_err());
+    assert!(AspectRatio::from_str("-16:9").is_err());
+}
+
+#[test]
+fn geometry_fit_aspect_expands_and_crops_around_center() {
+    use crate::geometry::{AspectMode, AspectRatio, Geometry};
+
+    let square = match Geometry::new(100, 100, 90, 90) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to construct geometry: {}", err),
+    };
+    let wide = AspectRatio {
+        width: 16,
+        height: 9,
+    };
+
+    let expanded = match square.fit_aspect(wide, AspectMode::Expand) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to expand geometry: {}", err),
+    };
+    assert_eq!((expanded.width, expanded.height), (160, 90));
+    assert_eq!((expanded.x, expanded.y), (65, 100));
+
+    let cropped = match square.fit_aspect(wide, AspectMode::Crop) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to crop geometry: {}", err),
+    };
+    assert_eq!((cropped.width, cropped.height), (90, 51));
+    assert_eq!((cropped.x, cropped.y), (100, 119));
+
+    let tall = AspectRatio {
+        width: 1,
+        height: 2,
+    };
+    let expanded = match square.fit_aspect(tall, AspectMode::Expand) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to expand geometry: {}", err),
+    };
+    assert_eq!((expanded.width, expanded.height), (90, 180));
+    assert_eq!((expanded.x, expanded.y), (100, 55));
+}
+
+#[test]
+fn geometry_intersect_clips_to_output() {
+    use crate::geometry::Geometry;
+
+    let output = Geometry {
+        x: 1920,
+        y: 0,
+        width: 1920,
+        height: 1080,
+    };
+    let spanning = Geometry {
+        x: 1800,
+        y: 100,
+        width: 300,
+        height: 200,
+    };
+    let clipped = match spanning.intersect(output) {
+        Some(v) => v,
+        None => panic!("Expected overlap"),
+    };
+    assert_eq!((clipped.x, clipped.y), (1920, 100));
+    assert_eq!((clipped.width, clipped.height), (180, 200));
+
+    let inside = Geometry {
+        x: 2000,
+        y: 10,
+        width: 50,
+        height: 50,
+    };
+    assert_eq!(inside.intersect(output), Some(inside));
+
+    // Outside or merely touching the edge is no overlap.
+    let outside = Geometry {
+        x: 0,
+        y: 0,
+        width: 100,
+        height: 100,
+    };
+    assert_eq!(outside.intersect(output), None);
+    let touching = Geometry {
+        x: 1820,
+        y: 0,
+        width: 100,
+        height: 100,
+    };
+    assert_eq!(touching.intersect(output), None);
+}
+
+#[test]
+fn geometry_union_covers_both() {
+    use crate::geometry::Geometry;
+
+    let left = Geometry {
+        x: 10,
+        y: 50,
+        width: 100,
+        height: 40,
+    };
+    let right = Geometry {
+        x: 300,
+        y: 20,
+        width: 50,
+        height: 50,
+    };
+    let union = left.union(right);
+    assert_eq!((union.x, union.y), (10, 20));
+    assert_eq!((union.width, union.height), (340, 70));
+    assert_eq!(left.union(left), left);
+}
+
+#[test]
+fn padding_grows_the_selection_within_its_monitor() {
+    use crate::geometry::Geometry;
+    use crate::utils::crop_to_monitors;
+
+    let window = geometry_or_panic(10, 100, 200, 100);
+    match window.grow(16) {
+        Ok(padded) => {
+            assert_eq!((padded.x, padded.y), (-6, 84));
+            assert_eq!((padded.width, padded.height), (232, 132));
+            let monitor = geometry_or_panic(0, 0, 1920, 1080);
+            assert_eq!(
+                crop_to_monitors(&padded, &[monitor]),
+                Some(Geometry {
+                    x: 0,
+                    y: 84,
+                    width: 226,
+                    height: 132,
+                })
+            );
+        }
+        Err(err) => panic!("padding failed: {err}"),
+    }
+    assert!(matches!(window.grow(0), Ok(same) if same == window));
+    assert!(window.grow(u32::MAX).is_err());
+}
+
+#[test]
+fn crop_to_monitors_prefers_largest_overlap() {
+    use crate::geometry::Geometry;
+    use crate::utils::crop_to_monitors;
+
+    let geo = |x, y, width, height| Geometry {
+        x,
+        y,
+        width,
+        height,
+    };
+    let monitors = [geo(0, 0, 1920, 1080), geo(1920, 0, 2560, 1440)];
+
+    // Top-left above the screen, still hanging off the left monitor's top edge.
+    assert_eq!(
+        crop_to_monitors(&geo(100, -200, 400, 300), &monitors),
+        Some(geo(100, 0, 400, 100))
+    );
+    // Straddles both monitors but mostly on the right one.
+    assert_eq!(
+        crop_to_monitors(&geo(1820, 100, 500, 200), &monitors),
+        Some(geo(1920, 100, 400, 200))
+    );
+    // Top-left left of every monitor, spanning into the first.
+    assert_eq!(
+        crop_to_monitors(&geo(-300, 500, 400, 100), &monitors),
+        Some(geo(0, 500, 100, 100))
+    );
+    assert_eq!(
+        crop_to_monitors(&geo(-500, -500, 100, 100), &monitors),
+        None
+    );
+}
+
+#[test]
+fn parse_window_pair_mode() {
+    let args = Args::parse_from(["hyprshot-rs", "-m", "window-pair"]);
+    assert!(matches!(args.mode.first(), Some(Mode::WindowPair)));
+}
+
+#[test]
+fn parse_aspect_args() {
+    let args = Args::parse_from([
+        "hyprshot-rs",
+        "-m",
+        "region",
+        "--aspect",
+        "4:3",
+        "--aspect-mode",
+        "crop",
+    ]);
+    assert_eq!(
+        args.aspect,
+        Some(crate::geometry::AspectRatio {
+            width: 4,
+            height: 3
+        })
+    );
+    assert_eq!(args.aspect_mode, crate::geometry::AspectMode::Crop);
+    assert!(Args::try_parse_from(["hyprshot-rs", "--aspect", "wide"]).is_err());
+}
+
+#[test]
+fn parse_explicit_geometry() {
+    let args = Args::parse_from(["hyprshot-rs", "--geometry", "-1920,40 800x600"]);
+    assert_eq!(args.geometry, Some(geometry_or_panic(-1920, 40, 800, 600)));
+    assert!(args.mode.is_empty());
+    assert!(Args::try_parse_from(["hyprshot-rs", "--geometry", "10,10"]).is_err());
+    assert!(Args::try_parse_from(["hyprshot-rs", "--geometry", "0,0 0x10"]).is_err());
+    assert!(Args::try_parse_from(["hyprshot-rs", "--geometry", "0,0 10x10", "--last"]).is_err());
+}
+
+#[test]
+fn parse_print_geometry_without_mode() {
+    let args = Args::parse_from(["hyprshot-rs", "--print-geometry"]);
+    assert!(args.print_geometry);
+    assert!(args.mode.is_empty());
+}
+
 #[test]
 fn geometry_slurp_rect_roundtrip_preserves_values() {
     let rect = slurp_rs::Rect {
@@ -117,376 +686,3844 @@ fn geometry_to_grim_box_preserves_values() {
 }
 
 #[test]
-fn freeze_module_does_not_depend_on_selector() {
-    let freeze_src = include_str!("freeze.rs");
-    assert!(!freeze_src.contains("crate::selector"));
-    assert!(!freeze_src.contains("selector::"));
+fn command_env_exposes_geometry() {
+    let geometry = match crate::geometry::Geometry::new(10, 20, 300, 400) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to construct geometry: {}", err),
+    };
+    let env = crate::save::command_env(&geometry, Some("DP-1"));
+    let get = |key: &str| env.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str());
+    assert_eq!(get("HYPRSHOT_GEOMETRY"), Some("10,20 300x400"));
+    assert_eq!(get("HYPRSHOT_X"), Some("10"));
+    assert_eq!(get("HYPRSHOT_Y"), Some("20"));
+    assert_eq!(get("HYPRSHOT_WIDTH"), Some("300"));
+    assert_eq!(get("HYPRSHOT_HEIGHT"), Some("400"));
+    assert_eq!(get("HYPRSHOT_OUTPUT"), Some("DP-1"));
+
+    let env = crate::save::command_env(&geometry, None);
+    assert!(env.iter().all(|(k, _)| *k != "HYPRSHOT_OUTPUT"));
 }
 
-#[test]
-fn region_cancel_detection_is_typed() {
-    let typed_region_cancel: anyhow::Error =
-        crate::selector::SelectorError::Cancelled(crate::selector::SelectionTarget::Region).into();
-    assert!(crate::capture::is_region_selection_cancelled(
-        &typed_region_cancel
-    ));
+/// Synthetic backend: fills captures with a solid color and "encodes" by
+/// prefixing the raw pixels with a marker, so tests can check what was written.
+struct MockBackend {
+    captured: Vec<crate::geometry::Geometry>,
+    /// How many times `to_png` ran.
+    png_encodes: std::cell::Cell<usize>,
+}
 
-    let typed_output_cancel: anyhow::Error =
-        crate::selector::SelectorError::Cancelled(crate::selector::SelectionTarget::Output).into();
-    assert!(!crate::capture::is_region_selection_cancelled(
-        &typed_output_cancel
-    ));
+impl MockBackend {
+    fn new() -> Self {
+        Self {
+            captured: Vec::new(),
+            png_encodes: std::cell::Cell::new(0),
+        }
+    }
+}
 
-    let legacy_string_error = anyhow::anyhow!("slurp failed to select region");
-    assert!(!crate::capture::is_region_selection_cancelled(
-        &legacy_string_error
-    ));
+impl crate::backend::CaptureBackend for MockBackend {
+    fn capture_region(
+        &mut self,
+        geometry: &crate::geometry::Geometry,
+    ) -> anyhow::Result<crate::backend::CapturedImage> {
+        self.captured.push(*geometry);
+        let (width, height) = (geometry.width as u32, geometry.height as u32);
+        Ok(crate::backend::CapturedImage {
+            data: [0x11, 0x22, 0x33, 0xff].repeat((width * height) as usize),
+            width,
+            height,
+        })
+    }
+
+    fn capture_output(&mut self, _name: &str) -> anyhow::Result<crate::backend::CapturedImage> {
+        Ok(crate::backend::CapturedImage {
+            data: vec![0; 4],
+            width: 1,
+            height: 1,
+        })
+    }
+
+    fn to_png(
+        &self,
+        image: &crate::backend::CapturedImage,
+        _compression: crate::config::PngCompression,
+    ) -> anyhow::Result<Vec<u8>> {
+        self.png_encodes.set(self.png_encodes.get() + 1);
+        let mut out = b"MOCKPNG".to_vec();
+        out.extend_from_slice(&image.width.to_le_bytes());
+        out.extend_from_slice(&image.height.to_le_bytes());
+        out.extend_from_slice(&image.data);
+        Ok(out)
+    }
 }
 
 #[test]
-fn selector_parse_choice_boxes_parses_labels_and_blank_lines() {
-    let input = "\n10,20 300x400 Terminal App\n1,2 3x4\n";
-    let parsed = match crate::selector::parse_choice_boxes(input) {
+fn capture_region_into_fills_buffer_and_rejects_short_ones() {
+    use crate::backend::CaptureBackend;
+
+    let geometry = match crate::geometry::Geometry::new(0, 0, 2, 2) {
         Ok(v) => v,
-        Err(err) => panic!("Expected parsed boxes, got error: {err}"),
+        Err(err) => panic!("Failed to construct geometry: {}", err),
     };
+    let mut backend = MockBackend::new();
 
-    assert_eq!(parsed.len(), 2);
-    assert_eq!(
-        parsed[0].rect,
-        slurp_rs::Rect {
-            x: 10,
-            y: 20,
-            width: 300,
-            height: 400
+    let mut buffer = vec![0u8; 20];
+    let info = match backend.capture_region_into(&geometry, &mut buffer) {
+        Ok(v) => v,
+        Err(err) => panic!("capture_region_into failed: {}", err),
+    };
+    assert_eq!((info.width, info.height, info.stride), (2, 2, 8));
+    assert_eq!(&buffer[..16], [0x11, 0x22, 0x33, 0xff].repeat(4).as_slice());
+    assert_eq!(&buffer[16..], &[0, 0, 0, 0]);
+
+    let mut short = vec![0u8; 15];
+    if backend.capture_region_into(&geometry, &mut short).is_ok() {
+        panic!("expected a short buffer to be rejected");
+    }
+}
+
+#[test]
+fn save_geometry_encodes_png_only_when_something_needs_it() {
+    let geometry = geometry_or_panic(0, 0, 2, 2);
+    let dir = test_output_dir("lazy-png");
+    let save = |format, name: &str| {
+        let mut backend = MockBackend::new();
+        let options = crate::save::SaveOptions {
+            format,
+            quality: 90,
+            silent: true,
+            ..Default::default()
+        };
+        if let Err(err) =
+            crate::save::save_geometry(&mut backend, &geometry, None, &dir.join(name), &options)
+        {
+            panic!("save_geometry failed: {:#}", err);
         }
-    );
-    assert_eq!(parsed[0].label.as_deref(), Some("Terminal App"));
-
-    assert_eq!(
-        parsed[1].rect,
-        slurp_rs::Rect {
-            x: 1,
-            y: 2,
-            width: 3,
-            height: 4
-        }
-    );
-    assert_eq!(parsed[1].label, None);
-}
+        backend.png_encodes.get()
+    };
+    assert_eq!(save(crate::config::ImageFormat::Jpeg, "shot.jpg"), 0);
+    assert_eq!(save(crate::config::ImageFormat::Png, "shot.png"), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
 
 #[test]
-fn selector_parse_choice_boxes_rejects_invalid_input() {
-    let err = match crate::selector::parse_choice_boxes("10,20\n") {
-        Ok(_) => panic!("Expected parse error for invalid input"),
-        Err(err) => err,
+fn save_geometry_captures_whole_output_when_physical_pixels_requested() {
+    let geometry = match crate::geometry::Geometry::new(0, 0, 4, 2) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to construct geometry: {}", err),
     };
-    assert!(err.to_string().contains("missing dimensions"));
-}
-
-#[test]
-fn selector_map_api_error_maps_cancel_to_typed_cancel() {
-    let err = crate::selector::map_api_error(
-        slurp_rs::SlurpError::Cancelled,
-        crate::selector::SelectionTarget::Region,
-    );
-    assert!(crate::selector::is_cancelled(
-        &err,
-        crate::selector::SelectionTarget::Region
-    ));
-    assert!(!crate::selector::is_cancelled(
-        &err,
-        crate::selector::SelectionTarget::Output
-    ));
-    assert_eq!(err.to_string(), "slurp failed to select region");
-}
-
-#[test]
-fn selector_map_api_error_maps_non_cancel_to_failed() {
-    let err = crate::selector::map_api_error(
-        slurp_rs::SlurpError::InvalidInput("bad".to_string()),
-        crate::selector::SelectionTarget::Window,
-    );
-    assert!(!crate::selector::is_cancelled(
-        &err,
-        crate::selector::SelectionTarget::Window
-    ));
+    let dir = test_output_dir("physical");
+    let path = dir.join("shot.png");
+    let mut backend = MockBackend::new();
 
-    let typed = match err.downcast_ref::<crate::selector::SelectorError>() {
-        Some(v) => v,
-        None => panic!("Expected SelectorError"),
+    let options = crate::save::SaveOptions {
+        output_physical_pixels: true,
+        silent: true,
+        ..Default::default()
     };
-    match typed {
-        crate::selector::SelectorError::Cancelled(_) => panic!("Expected failed error"),
-        crate::selector::SelectorError::Failed { target, message } => {
-            assert_eq!(*target, crate::selector::SelectionTarget::Window);
-            assert!(message.contains("invalid input: bad"));
-        }
+    if let Err(err) =
+        crate::save::save_geometry(&mut backend, &geometry, Some("DP-1"), &path, &options)
+    {
+        panic!("save_geometry failed: {}", err);
     }
-}
 
-#[test]
-fn parse_active_output_mode_combo() {
-    let args = Args::parse_from(["hyprshot-rs", "-m", "output", "-m", "active"]);
-    assert!(matches!(args.mode.first(), Some(Mode::Output)));
-    assert!(matches!(args.mode.get(1), Some(Mode::Active)));
+    // MockBackend::capture_output returns a 1x1 image; no region was captured.
+    assert!(backend.captured.is_empty());
+    let written = match std::fs::read(&path) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to read saved screenshot: {}", err),
+    };
+    assert_eq!(&written[7..15], &[1, 0, 0, 0, 1, 0, 0, 0]);
+    let _ = std::fs::remove_dir_all(&dir);
 }
 
 #[test]
-fn test_default_config() {
-    let config = crate::config::Config::default();
-    assert_eq!(config.paths.screenshots_dir, "~/Pictures");
-    assert_eq!(config.hotkeys.window, "SUPER, Print");
-    assert!(config.capture.notification);
-    assert_eq!(config.capture.notification_timeout, 3000);
-    assert!(config.advanced.freeze_on_region);
-    assert_eq!(config.advanced.delay_ms, 0);
-}
+fn raw_formats_write_pixels_with_optional_header() {
+    use crate::save::RawFormat;
 
-#[test]
-fn test_config_serialization() {
-    let config = crate::config::Config::default();
-    let toml_str = match toml::to_string(&config) {
-        Ok(v) => v,
-        Err(err) => panic!("Failed to serialize config: {}", err),
+    let geometry = geometry_or_panic(0, 0, 2, 1);
+    let image = crate::backend::CapturedImage {
+        data: vec![10, 20, 30, 255, 40, 50, 60, 0],
+        width: 2,
+        height: 1,
     };
-    assert!(toml_str.contains("[paths]"));
-    assert!(toml_str.contains("[hotkeys]"));
-    assert!(toml_str.contains("[capture]"));
-    assert!(toml_str.contains("[advanced]"));
+    let backend = MockBackend::new();
+    let raw = |raw_format, raw_header| {
+        let options = crate::save::SaveOptions {
+            raw: true,
+            raw_format: Some(raw_format),
+            raw_header,
+            flatten_background: [255, 255, 255],
+            ..Default::default()
+        };
+        match crate::save::raw_output(&backend, &image, &options, &geometry, None) {
+            Ok(bytes) => bytes,
+            Err(err) => panic!("raw output failed: {}", err),
+        }
+    };
+
+    assert_eq!(
+        raw(RawFormat::Bgra, false),
+        vec![30, 20, 10, 255, 60, 50, 40, 0]
+    );
+    assert_eq!(
+        raw(RawFormat::Bgra, true),
+        [b"2 1 8\n".as_slice(), &[30, 20, 10, 255, 60, 50, 40, 0]].concat()
+    );
+    assert_eq!(
+        raw(RawFormat::Ppm, true),
+        [
+            b"2 1 6\nP6\n2 1\n255\n".as_slice(),
+            &[10, 20, 30, 255, 255, 255]
+        ]
+        .concat()
+    );
+    assert!(raw(RawFormat::Png, false).starts_with(b"MOCKPNG"));
+
+    let args = Args::parse_from(["hyprshot-rs", "-r", "--raw-format", "jpeg"]);
+    assert_eq!(
+        crate::cli::resolve_format(&args, &crate::config::Config::default()),
+        crate::config::ImageFormat::Jpeg
+    );
+    assert!(Args::try_parse_from(["hyprshot-rs", "--raw-format", "bgra"]).is_err());
+    assert!(Args::try_parse_from(["hyprshot-rs", "-r", "--raw-header"]).is_err());
 }
 
 #[test]
-fn test_config_deserialization() {
-    let toml_str = r#"
-        [paths]
-        screenshots_dir = "~/Documents"
+fn base64_and_terminal_previews_encode_images() {
+    use crate::term_preview::{Protocol, kitty, protocol_for, sixel};
+    use crate::utils::base64;
 
-        [hotkeys]
-        window = "ALT, W"
-        region = "ALT, R"
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
 
-        [capture]
-        notification = false
+    assert_eq!(
+        protocol_for("xterm-kitty", "", false),
+        Some(Protocol::Kitty)
+    );
+    assert_eq!(
+        protocol_for("xterm-256color", "WezTerm", false),
+        Some(Protocol::Kitty)
+    );
+    assert_eq!(
+        protocol_for("xterm-256color", "", true),
+        Some(Protocol::Kitty)
+    );
+    assert_eq!(protocol_for("foot-extra", "", false), Some(Protocol::Sixel));
+    assert_eq!(protocol_for("xterm-256color", "", false), None);
 
-        [advanced]
-        delay_ms = 500
-    "#;
+    // One byte over 3 KiB is 4 KiB and a group of base64: a full chunk and a
+    // short one.
+    let escapes = kitty(&[0u8; 3073]);
+    let text = String::from_utf8_lossy(&escapes);
+    assert!(text.starts_with("\x1b_Ga=T,f=100,m=1;AAAA"));
+    assert!(text.contains("\x1b\\\x1b_Gm=0;AA==\x1b\\"));
+    assert_eq!(text.matches("\x1b_G").count(), 2);
+    assert!(text.ends_with("\x1b\\\n"));
 
-    let config: crate::config::Config = match toml::from_str(toml_str) {
-        Ok(v) => v,
-        Err(err) => panic!("Failed to deserialize config: {}", err),
+    // A red pixel over a transparent one, then a blue one: red and blue are
+    // each painted with one sixel, the transparent pixel is left out.
+    let image = crate::backend::CapturedImage {
+        data: vec![255, 0, 0, 255, 0, 0, 255, 255, 0, 0, 0, 0, 0, 0, 0, 0],
+        width: 2,
+        height: 2,
     };
-    assert_eq!(config.paths.screenshots_dir, "~/Documents");
-    assert_eq!(config.hotkeys.window, "ALT, W");
-    assert!(!config.capture.notification);
-    assert_eq!(config.advanced.delay_ms, 500);
-}
-
-#[test]
-fn test_partial_config() {
-    let toml_str = r#"
-        [paths]
-        screenshots_dir = "~/Custom"
-    "#;
+    let text = String::from_utf8_lossy(&sixel(&image)).into_owned();
+    assert!(text.starts_with("\x1bP0;1;0q\"1;1;2;2"));
+    assert!(text.contains("#5;2;0;0;100"));
+    assert!(text.ends_with("#5?@$#180@?$-\x1b\\"));
 
-    let config: crate::config::Config = match toml::from_str(toml_str) {
-        Ok(v) => v,
-        Err(err) => panic!("Failed to deserialize partial config: {}", err),
-    };
-    assert_eq!(config.paths.screenshots_dir, "~/Custom");
-    assert_eq!(config.hotkeys.window, "SUPER, Print");
-    assert!(config.capture.notification);
+    let args = Args::parse_from(["hyprshot-rs", "-r", "--stdout-encoding", "base64"]);
+    assert_eq!(
+        args.stdout_encoding,
+        Some(crate::save::StdoutEncoding::Base64)
+    );
+    assert!(Args::try_parse_from(["hyprshot-rs", "-r", "--term-preview"]).is_err());
 }
 
 #[test]
-fn test_expand_path_tilde() {
-    let result = match crate::config::expand_path("~/Pictures") {
+fn save_geometry_resamples_to_force_scale() {
+    let geometry = match crate::geometry::Geometry::new(0, 0, 4, 2) {
         Ok(v) => v,
-        Err(err) => panic!("Failed to expand path: {}", err),
+        Err(err) => panic!("Failed to construct geometry: {}", err),
     };
-    let home = match dirs::home_dir() {
-        Some(v) => v,
-        None => panic!("Failed to resolve home directory"),
+    let dir = test_output_dir("force-scale");
+    let path = dir.join("shot.png");
+    let mut backend = MockBackend::new();
+
+    let options = crate::save::SaveOptions {
+        force_scale: Some(1.5),
+        silent: true,
+        ..Default::default()
     };
-    assert_eq!(result, home.join("Pictures"));
+    if let Err(err) = crate::save::save_geometry(&mut backend, &geometry, None, &path, &options) {
+        panic!("save_geometry failed: {}", err);
+    }
 
-    let result = match crate::config::expand_path("~") {
+    let written = match std::fs::read(&path) {
         Ok(v) => v,
-        Err(err) => panic!("Failed to expand home path: {}", err),
+        Err(err) => panic!("Failed to read saved screenshot: {}", err),
     };
-    assert_eq!(result, home);
+    // 4x2 logical pixels at 1.5 pixels each; the uniform mock color survives.
+    assert_eq!(&written[7..15], &[6, 0, 0, 0, 3, 0, 0, 0]);
+    assert_eq!(
+        &written[15..],
+        [0x11, 0x22, 0x33, 0xff].repeat(18).as_slice()
+    );
+    let _ = std::fs::remove_dir_all(&dir);
 }
 
 #[test]
-fn test_expand_path_env_vars() {
-    unsafe {
-        env::set_var("TEST_VAR", "/test/path");
-    }
+fn save_geometry_writes_jpeg_and_webp() {
+    use crate::config::ImageFormat;
 
-    let result = match crate::config::expand_path("$TEST_VAR/screenshots") {
+    let geometry = match crate::geometry::Geometry::new(0, 0, 4, 2) {
         Ok(v) => v,
-        Err(err) => panic!("Failed to expand env path: {}", err),
+        Err(err) => panic!("Failed to construct geometry: {}", err),
     };
-    assert_eq!(result, PathBuf::from("/test/path/screenshots"));
+    let dir = test_output_dir("formats");
+    let mut backend = MockBackend::new();
 
-    unsafe {
-        env::remove_var("TEST_VAR");
+    let mut formats = vec![(ImageFormat::Jpeg, "shot.jpg")];
+    if cfg!(feature = "webp") {
+        formats.push((ImageFormat::Webp, "shot.webp"));
+    }
+    for (format, name) in formats {
+        let path = dir.join(name);
+        let options = crate::save::SaveOptions {
+            format,
+            quality: 80,
+            flatten_background: [255, 255, 255],
+            silent: true,
+            ..Default::default()
+        };
+        if let Err(err) = crate::save::save_geometry(&mut backend, &geometry, None, &path, &options)
+        {
+            panic!("save_geometry failed for {:?}: {}", format, err);
+        }
+        let written = match std::fs::read(&path) {
+            Ok(v) => v,
+            Err(err) => panic!("Failed to read {}: {}", path.display(), err),
+        };
+        match format {
+            ImageFormat::Jpeg => assert_eq!(&written[..3], &[0xff, 0xd8, 0xff]),
+            _ => assert!(
+                written.starts_with(b"RIFF") && &written[8..12] == b"WEBP",
+                "not a WebP file"
+            ),
+        }
     }
+    let _ = std::fs::remove_dir_all(&dir);
 }
 
 #[test]
-fn test_expand_path_xdg_pictures() {
-    let result = match crate::config::expand_path("$XDG_PICTURES_DIR/screenshots") {
+fn composite_region_pastes_outputs_at_the_highest_scale() {
+    use crate::backend::CapturedImage;
+    use crate::geometry::Geometry;
+
+    let geometry = |x, y, w, h| match Geometry::new(x, y, w, h) {
         Ok(v) => v,
-        Err(err) => panic!("Failed to expand XDG pictures path: {}", err),
+        Err(err) => panic!("Failed to construct geometry: {}", err),
     };
-    if let Some(pictures_dir) = dirs::picture_dir() {
-        assert_eq!(result, pictures_dir.join("screenshots"));
-    } else {
-        let home = match dirs::home_dir() {
-            Some(v) => v,
-            None => panic!("Failed to resolve home directory"),
-        };
-        assert_eq!(result, home.join("Pictures/screenshots"));
-    }
+    let solid = |width: u32, height: u32, px: [u8; 4]| CapturedImage {
+        data: px.repeat((width * height) as usize),
+        width,
+        height,
+    };
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    // A 1x output left of a 2x output; the region hangs one unit below both.
+    let region = geometry(1, 0, 2, 3);
+    let captures = [
+        (geometry(0, 0, 2, 2), geometry(1, 0, 1, 2), solid(2, 2, RED)),
+        (
+            geometry(2, 0, 2, 2),
+            geometry(2, 0, 1, 2),
+            solid(4, 4, BLUE),
+        ),
+    ];
+    let image = crate::backend::composite_region(&region, &captures);
+    assert_eq!((image.width, image.height), (4, 6));
+
+    let pixel = |x: usize, y: usize| {
+        let at = (y * image.width as usize + x) * 4;
+        [
+            image.data[at],
+            image.data[at + 1],
+            image.data[at + 2],
+            image.data[at + 3],
+        ]
+    };
+    assert_eq!(pixel(0, 0), RED);
+    assert_eq!(pixel(1, 3), RED);
+    assert_eq!(pixel(2, 0), BLUE);
+    assert_eq!(pixel(3, 3), BLUE);
+    assert_eq!(pixel(0, 5), [0, 0, 0, 0]);
+    assert_eq!(pixel(3, 4), [0, 0, 0, 0]);
 }
 
 #[test]
-fn test_expand_path_empty() {
-    let result = match crate::config::expand_path("") {
+fn frozen_backend_crops_frozen_frames_and_falls_back_to_live() {
+    use crate::backend::{CaptureBackend, CapturedImage, FrozenBackend};
+    use crate::freeze::FrozenFrame;
+    use crate::geometry::Geometry;
+
+    let geometry = |x, y, w, h| match Geometry::new(x, y, w, h) {
         Ok(v) => v,
-        Err(err) => panic!("Failed to expand empty path: {}", err),
+        Err(err) => panic!("Failed to construct geometry: {}", err),
     };
-    assert_eq!(result, PathBuf::from("."));
-}
+    const FROZEN: [u8; 4] = [9, 8, 7, 255];
+    let frames = vec![FrozenFrame {
+        output: "DP-1".to_string(),
+        bounds: geometry(0, 0, 4, 4),
+        image: CapturedImage {
+            data: FROZEN.repeat(16),
+            width: 4,
+            height: 4,
+        },
+    }];
+    let mut backend = FrozenBackend::new(frames, Box::new(MockBackend::new()), false);
 
-#[test]
-fn test_expand_path_no_expansion() {
-    let result = match crate::config::expand_path("/absolute/path") {
+    let inside = match backend.capture_region(&geometry(1, 1, 2, 2)) {
         Ok(v) => v,
-        Err(err) => panic!("Failed to expand absolute path: {}", err),
+        Err(err) => panic!("Frozen capture failed: {:#}", err),
     };
-    assert_eq!(result, PathBuf::from("/absolute/path"));
+    assert_eq!((inside.width, inside.height), (2, 2));
+    assert_eq!(inside.data, FROZEN.repeat(4));
 
-    let result = match crate::config::expand_path("relative/path") {
+    // Half of this region was never frozen, so it is captured live.
+    let outside = match backend.capture_region(&geometry(2, 0, 4, 2)) {
         Ok(v) => v,
-        Err(err) => panic!("Failed to expand relative path: {}", err),
+        Err(err) => panic!("Live capture failed: {:#}", err),
     };
-    assert_eq!(result, PathBuf::from("relative/path"));
-}
+    assert_eq!(&outside.data[..4], &[0x11, 0x22, 0x33, 0xff]);
 
-#[test]
-fn test_expand_path_undefined_var() {
-    let result = match crate::config::expand_path("$UNDEFINED_VAR_12345/test") {
+    let output = match backend.capture_output("DP-1") {
         Ok(v) => v,
-        Err(err) => panic!("Failed to expand undefined var path: {}", err),
+        Err(err) => panic!("Frozen output capture failed: {:#}", err),
     };
-    assert_eq!(result, PathBuf::from("$UNDEFINED_VAR_12345/test"));
+    assert_eq!((output.width, output.height), (4, 4));
+    let other = match backend.capture_output("HDMI-A-1") {
+        Ok(v) => v,
+        Err(err) => panic!("Live output capture failed: {:#}", err),
+    };
+    assert_eq!((other.width, other.height), (1, 1));
 }
 
 #[test]
-fn test_get_screenshots_dir_priority_cli() {
-    let config = crate::config::Config::default();
-    let cli_path = Some(PathBuf::from("/cli/path"));
-
-    unsafe {
-        env::set_var("HYPRSHOT_DIR", "/env/path");
-    }
+fn flatten_blends_alpha_onto_background() {
+    use crate::backend::CapturedImage;
 
-    let result = match crate::config::get_screenshots_dir(cli_path, &config, false) {
-        Ok(v) => v,
-        Err(err) => panic!("Failed to resolve screenshots dir (cli): {}", err),
+    let image = CapturedImage {
+        data: vec![
+            255, 0, 0, 255, // opaque red
+            255, 0, 0, 128, // half red
+            9, 9, 9, 0, // transparent gap
+        ],
+        width: 3,
+        height: 1,
     };
-    assert_eq!(result, PathBuf::from("/cli/path"));
+    assert_eq!(
+        crate::save::flatten(&image, [255, 255, 255]),
+        vec![255, 0, 0, 255, 127, 127, 255, 255, 255]
+    );
+    assert_eq!(crate::save::flatten(&image, [0, 0, 0])[6..], [0, 0, 0]);
 
-    unsafe {
-        env::remove_var("HYPRSHOT_DIR");
-    }
+    assert_eq!(
+        crate::config::parse_hex_color("#1e1e2e").ok(),
+        Some([0x1e, 0x1e, 0x2e])
+    );
+    assert_eq!(
+        crate::config::parse_hex_color("FFFFFF").ok(),
+        Some([255, 255, 255])
+    );
+    assert!(crate::config::parse_hex_color("#fff").is_err());
+    assert!(crate::config::parse_hex_color("#gggggg").is_err());
 }
 
 #[test]
-fn test_get_screenshots_dir_priority_env() {
-    let config = crate::config::Config::default();
+fn format_precedence_and_filename() {
+    use crate::cli::{output_filename, resolve_format};
+    use crate::config::{Config, ImageFormat};
 
-    unsafe {
-        env::set_var("HYPRSHOT_DIR", "/env/path");
-    }
+    let now = match chrono::Local.with_ymd_and_hms(2024, 5, 6, 7, 8, 9) {
+        chrono::LocalResult::Single(v) => v,
+        _ => panic!("invalid test time"),
+    };
+    let mut config = Config::default();
+    config.capture.default_format = ImageFormat::Webp;
 
-    let result = match crate::config::get_screenshots_dir(None, &config, false) {
-        Ok(v) => v,
-        Err(err) => panic!("Failed to resolve screenshots dir (env): {}", err),
+    let resolve = |argv: &[&str], config: &Config| {
+        let args = Args::parse_from(argv);
+        let format = resolve_format(&args, config);
+        let mut fields = crate::filename::Fields::new(now);
+        let template = crate::cli::resolve_filename_template(&args, config);
+        let dir = std::path::Path::new("/nonexistent");
+        (
+            format,
+            output_filename(&args, format, template, dir, &mut fields),
+        )
     };
-    assert_eq!(result, PathBuf::from("/env/path"));
 
-    unsafe {
-        env::remove_var("HYPRSHOT_DIR");
-    }
+    // Config alone, then --format over config.
+    let (format, name) = resolve(&["hyprshot-rs"], &config);
+    assert_eq!(format, ImageFormat::Webp);
+    assert!(name.ends_with("_hyprshot.webp"), "{name}");
+    let (format, _) = resolve(&["hyprshot-rs", "--format", "jpg"], &config);
+    assert_eq!(format, ImageFormat::Jpeg);
+
+    // The -f extension wins over --format and is kept; unknown ones don't count.
+    let (format, name) = resolve(&["hyprshot-rs", "--format", "png", "-f", "a.JPEG"], &config);
+    assert_eq!((format, name.as_str()), (ImageFormat::Jpeg, "a.JPEG"));
+    let (format, name) = resolve(&["hyprshot-rs", "--format", "png", "-f", "notes"], &config);
+    assert_eq!((format, name.as_str()), (ImageFormat::Png, "notes"));
+
+    // --force-format beats everything and rewrites the extension.
+    let (format, name) = resolve(
+        &[
+            "hyprshot-rs",
+            "--force-format",
+            "png",
+            "-f",
+            "a.jpg",
+            "--format",
+            "webp",
+        ],
+        &config,
+    );
+    assert_eq!((format, name.as_str()), (ImageFormat::Png, "a.png"));
+
+    // Templates get the extension appended, even when they contain dots.
+    let (_, name) = resolve(
+        &[
+            "hyprshot-rs",
+            "--filename-template",
+            "%Y.%m.%d",
+            "--format",
+            "jpeg",
+        ],
+        &config,
+    );
+    assert_eq!(name, "2024.05.06.jpg");
+
+    assert!(Args::try_parse_from(["hyprshot-rs", "--quality", "0"]).is_err());
+    assert!(Args::try_parse_from(["hyprshot-rs", "--format", "gif"]).is_err());
 }
 
 #[test]
-fn test_get_screenshots_dir_priority_config() {
-    let mut config = crate::config::Config::default();
-    config.paths.screenshots_dir = "/config/path".to_string();
+fn resample_averages_and_ignores_transparent_pixels() {
+    use crate::backend::CapturedImage;
+    use crate::resize::resample;
+    use crate::save::{scaled_size, validate_scale};
 
-    let result = match crate::config::get_screenshots_dir(None, &config, false) {
-        Ok(v) => v,
-        Err(err) => panic!("Failed to resolve screenshots dir (config): {}", err),
+    // 2x2 -> 1x1: opaque red, opaque blue, two transparent gap pixels.
+    let image = CapturedImage {
+        data: vec![
+            200, 0, 0, 255, 0, 0, 100, 255, //
+            0, 0, 0, 0, 0, 0, 0, 0,
+        ],
+        width: 2,
+        height: 2,
     };
-    assert_eq!(result, PathBuf::from("/config/path"));
+    let down = resample(image, 1, 1);
+    assert_eq!((down.width, down.height), (1, 1));
+    assert_eq!(down.data, vec![100, 0, 50, 128]);
+
+    // 1x1 -> 2x2 repeats the pixel.
+    let up = resample(
+        CapturedImage {
+            data: vec![1, 2, 3, 255],
+            width: 1,
+            height: 1,
+        },
+        2,
+        2,
+    );
+    assert_eq!(up.data, [1, 2, 3, 255].repeat(4));
+
+    let geometry = match crate::geometry::Geometry::new(0, 0, 1920, 1080) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to construct geometry: {}", err),
+    };
+    match scaled_size(&geometry, 0.5) {
+        Ok(size) => assert_eq!(size, (960, 540)),
+        Err(err) => panic!("scaled_size failed: {}", err),
+    }
+    for bad in [0.0, -1.0, f64::NAN, f64::INFINITY, 9.0] {
+        assert!(validate_scale(bad).is_err(), "{bad} should be rejected");
+    }
 }
 
 #[test]
-fn test_get_screenshots_dir_with_tilde() {
-    let mut config = crate::config::Config::default();
-    config.paths.screenshots_dir = "~/Screenshots".to_string();
+fn resize_parses_sizes_and_keeps_flat_colors() {
+    use crate::backend::CapturedImage;
+    use crate::cli::Args;
+    use crate::resize::{self, Resize};
+    use clap::Parser;
 
-    let result = match crate::config::get_screenshots_dir(None, &config, false) {
+    let parse = |s: &str| s.parse::<Resize>().ok();
+    assert_eq!(parse("50%"), Some(Resize::Percent(50)));
+    assert_eq!(parse("1920x1080"), Some(Resize::Fit(1920, 1080)));
+    for bad in ["0%", "900%", "x1080", "1920x0", "1920", "half"] {
+        assert_eq!(parse(bad), None, "{bad} should be rejected");
+    }
+
+    // 4K fits a 1920x1920 box at half size; percentages apply to both sides.
+    assert_eq!(Resize::Fit(1920, 1920).target(3840, 2160), (1920, 1080));
+    assert_eq!(Resize::Percent(50).target(3841, 2161), (1921, 1081));
+    assert_eq!(Resize::Percent(1).target(10, 10), (1, 1));
+
+    // A flat color stays flat; Lanczos ringing needs an edge.
+    let flat = CapturedImage {
+        data: [40, 120, 200, 255].repeat(6 * 4),
+        width: 6,
+        height: 4,
+    };
+    let half = resize::apply(flat, Resize::Percent(50));
+    assert_eq!((half.width, half.height), (3, 2));
+    assert_eq!(half.data, [40, 120, 200, 255].repeat(6));
+
+    // Black and white columns average to gray when halved, away from the
+    // edges where the filter is cut off.
+    let stripes = CapturedImage {
+        data: (0..64 * 4)
+            .flat_map(|i| if i % 2 == 0 { [0, 0, 0, 255] } else { [255; 4] })
+            .collect(),
+        width: 64,
+        height: 4,
+    };
+    let gray = resize::apply(stripes, Resize::Fit(32, 32));
+    assert_eq!((gray.width, gray.height), (32, 2));
+    for (i, px) in gray.data.chunks_exact(4).enumerate() {
+        assert_eq!(px[3], 255);
+        if (3..29).contains(&(i % 32)) {
+            assert!((126..=129).contains(&px[0]), "{px:?} should be mid gray");
+        }
+    }
+
+    assert!(Args::try_parse_from(["hyprshot-rs", "-m", "region", "--resize", "50%"]).is_ok());
+    assert!(
+        Args::try_parse_from(["hyprshot-rs", "-m", "region", "--record", "--resize", "50%"])
+            .is_err()
+    );
+}
+
+#[test]
+fn skip_unchanged_compares_per_mode_and_geometry() {
+    use crate::save::is_unchanged_since_last;
+
+    let dir = test_output_dir("skip-unchanged");
+    let path = dir.join("last_capture.toml");
+    let image = |data: Vec<u8>| crate::backend::CapturedImage {
+        data,
+        width: 1,
+        height: 1,
+    };
+    let panel = image(vec![1, 2, 3, 255]);
+    let desktop = image(vec![9, 9, 9, 255]);
+
+    assert!(!is_unchanged_since_last(
+        &path,
+        "region 0,0 1x1",
+        &panel,
+        false
+    ));
+    assert!(is_unchanged_since_last(
+        &path,
+        "region 0,0 1x1",
+        &panel,
+        false
+    ));
+    // Another area in between no longer resets the first one.
+    assert!(!is_unchanged_since_last(
+        &path,
+        "output 0,0 1920x1080",
+        &desktop,
+        false
+    ));
+    assert!(is_unchanged_since_last(
+        &path,
+        "region 0,0 1x1",
+        &panel,
+        false
+    ));
+    assert!(is_unchanged_since_last(
+        &path,
+        "output 0,0 1920x1080",
+        &desktop,
+        false
+    ));
+    // The same pixels in another mode are compared on their own.
+    assert!(!is_unchanged_since_last(
+        &path,
+        "window 0,0 1x1",
+        &panel,
+        false
+    ));
+    assert!(!is_unchanged_since_last(
+        &path,
+        "region 0,0 1x1",
+        &desktop,
+        false
+    ));
+
+    // A fingerprint file from before this format is simply replaced.
+    if let Err(err) = std::fs::write(&path, "0123456789abcdef") {
+        panic!("Failed to write {}: {}", path.display(), err);
+    }
+    assert!(!is_unchanged_since_last(
+        &path,
+        "region 0,0 1x1",
+        &panel,
+        false
+    ));
+    assert!(is_unchanged_since_last(
+        &path,
+        "region 0,0 1x1",
+        &panel,
+        false
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn capture_fingerprint_detects_changes() {
+    let image = |data: Vec<u8>, width: u32, height: u32| crate::backend::CapturedImage {
+        data,
+        width,
+        height,
+    };
+    let a = image(vec![1, 2, 3, 255, 4, 5, 6, 255], 2, 1);
+    let same = image(vec![1, 2, 3, 255, 4, 5, 6, 255], 2, 1);
+    let changed = image(vec![1, 2, 3, 255, 4, 5, 7, 255], 2, 1);
+    let reshaped = image(vec![1, 2, 3, 255, 4, 5, 6, 255], 1, 2);
+
+    let fp = crate::save::capture_fingerprint;
+    assert_eq!(fp(&a), fp(&same));
+    assert_ne!(fp(&a), fp(&changed));
+    assert_ne!(fp(&a), fp(&reshaped));
+}
+
+fn test_output_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("hyprshot-rs-test-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn last_geometry_is_remembered_per_mode() {
+    use crate::last_geometry::{load, store};
+
+    let dir = test_output_dir("last-geometry");
+    let path = dir.join("state").join("last.toml");
+    if load(&path, "region").is_ok() {
+        panic!("expected an error before anything was stored");
+    }
+
+    let region = geometry_or_panic(-1920, 40, 800, 600);
+    let window = geometry_or_panic(10, 20, 300, 200);
+    for (mode, geometry) in [("region", &region), ("window", &window)] {
+        if let Err(err) = store(&path, mode, geometry) {
+            panic!("Failed to store {} geometry: {:#}", mode, err);
+        }
+    }
+    match load(&path, "region") {
+        Ok(geometry) => assert_eq!(geometry, region),
+        Err(err) => panic!("Failed to load region geometry: {:#}", err),
+    }
+    match load(&path, "window") {
+        Ok(geometry) => assert_eq!(geometry, window),
+        Err(err) => panic!("Failed to load window geometry: {:#}", err),
+    }
+
+    let moved = geometry_or_panic(0, 0, 100, 100);
+    if let Err(err) = store(&path, "region", &moved) {
+        panic!("Failed to overwrite region geometry: {:#}", err);
+    }
+    match load(&path, "region") {
+        Ok(geometry) => assert_eq!(geometry, moved),
+        Err(err) => panic!("Failed to reload region geometry: {:#}", err),
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn upload_targets_resolve_and_extract_urls() {
+    use crate::upload::{curl_args, curl_headers, extract_url, resolve_target};
+    use std::path::Path;
+
+    let config: crate::config::Config = match toml::from_str(
+        r#"
+        [upload.targets.0x0]
+        url = "https://0x0.st"
+        form_field = "file"
+
+        [upload.targets.imgur]
+        url = "https://api.imgur.com/3/image"
+        form_field = "image"
+        headers = { Authorization = "Client-ID abc" }
+        json_path = "data.link"
+
+        [upload.targets.paste]
+        url = "https://paste.example/upload"
+        method = "put"
+        url_regex = 'href="([^"]+)"'
+        "#,
+    ) {
         Ok(v) => v,
-        Err(err) => panic!("Failed to resolve screenshots dir (tilde): {}", err),
+        Err(err) => panic!("Failed to parse upload config: {}", err),
     };
-    let home = match dirs::home_dir() {
-        Some(v) => v,
-        None => panic!("Failed to resolve home directory"),
+
+    assert!(resolve_target(&config.upload, "").is_err());
+    let (name, imgur) = match resolve_target(&config.upload, "imgur") {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to resolve imgur: {:#}", err),
     };
-    assert_eq!(result, home.join("Screenshots"));
+    assert_eq!(name, "imgur");
+    assert!(resolve_target(&config.upload, "nope").is_err());
+    let mut with_default = config.upload.clone();
+    with_default.default = Some("0x0".to_string());
+    match resolve_target(&with_default, "") {
+        Ok((name, _)) => assert_eq!(name, "0x0"),
+        Err(err) => panic!("Failed to resolve the default target: {:#}", err),
+    }
+    assert!(resolve_target(&crate::config::UploadConfig::default(), "").is_err());
+
+    let args = curl_args(imgur, Path::new("/tmp/shot.png"));
+    let value_after = |flag: &str| {
+        args.iter()
+            .position(|a| a == flag)
+            .and_then(|i| args.get(i + 1).cloned())
+    };
+    assert_eq!(value_after("--request").as_deref(), Some("POST"));
+    // Headers go through stdin, never argv, so API keys stay out of `ps`.
+    assert_eq!(value_after("--header").as_deref(), Some("@-"));
+    assert!(!args.iter().any(|a| a.contains("Client-ID")));
+    match curl_headers(imgur) {
+        Ok(headers) => assert_eq!(headers, "Authorization: Client-ID abc\n"),
+        Err(err) => panic!("Failed to build headers: {:#}", err),
+    }
+    let mut smuggled = imgur.clone();
+    smuggled
+        .headers
+        .insert("X-Note".to_string(), "a\r\nX-Evil: 1".to_string());
+    assert!(curl_headers(&smuggled).is_err());
+    assert_eq!(
+        value_after("--form").as_deref(),
+        Some("image=@\"/tmp/shot.png\"")
+    );
+    assert_eq!(
+        args.last().map(String::as_str),
+        Some("https://api.imgur.com/3/image")
+    );
+    let paste = &config.upload.targets["paste"];
+    let args = curl_args(paste, Path::new("/tmp/shot.png"));
+    assert!(args.iter().any(|a| a == "PUT"));
+    assert!(args.iter().any(|a| a == "@/tmp/shot.png"));
+    assert!(!args.iter().any(|a| a == "--header"));
+
+    let reply = r#"{"data":{"link":"https://i.imgur.com/x.png"},"success":true}"#;
+    match extract_url(imgur, reply) {
+        Ok(url) => assert_eq!(url, "https://i.imgur.com/x.png"),
+        Err(err) => panic!("Failed to extract JSON URL: {:#}", err),
+    }
+    assert!(extract_url(imgur, r#"{"data":{}}"#).is_err());
+    match extract_url(paste, r#"<a href="https://paste.example/p/1">ok</a>"#) {
+        Ok(url) => assert_eq!(url, "https://paste.example/p/1"),
+        Err(err) => panic!("Failed to extract regex URL: {:#}", err),
+    }
+    let bare = &config.upload.targets["0x0"];
+    match extract_url(bare, "https://0x0.st/abc.png\n") {
+        Ok(url) => assert_eq!(url, "https://0x0.st/abc.png"),
+        Err(err) => panic!("Failed to extract bare URL: {:#}", err),
+    }
+    assert!(extract_url(bare, "rate limited").is_err());
 }
 
 #[test]
-fn test_generate_hyprland_binds() {
-    let config = crate::config::Config::default();
-    let binds = config.generate_hyprland_binds();
+fn post_capture_hooks_see_the_capture_and_keep_going() {
+    use crate::hooks::{post_capture_env, run_post_capture};
 
-    assert!(binds.contains("# hyprshot-rs keybindings"));
-    assert!(binds.contains("# Generated by: hyprshot-rs --generate-hyprland-config"));
+    let dir = test_output_dir("post-capture");
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        panic!("Failed to create {}: {}", dir.display(), err);
+    }
+    let shot = dir.join("shot.png");
+    let log = dir.join("hooks.log");
+    let env = post_capture_env(
+        &shot,
+        &geometry_or_panic(10, 20, 300, 400),
+        Some("region"),
+        None,
+        None,
+    );
+    let get = |key: &str| env.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str());
+    assert_eq!(get("HYPRSHOT_MODE"), Some("region"));
+    assert_eq!(get("HYPRSHOT_GEOMETRY"), Some("10,20 300x400"));
+    assert!(get("HYPRSHOT_OUTPUT").is_none());
+    assert!(get("HYPRSHOT_URL").is_none());
 
-    assert!(binds.contains("bind = SUPER, Print, exec, hyprshot-rs -m window"));
-    assert!(binds.contains("bind = SUPER SHIFT, Print, exec, hyprshot-rs -m region"));
-    assert!(binds.contains("bind = SUPER CTRL, Print, exec, hyprshot-rs -m output"));
-    assert!(binds.contains("bind = , Print, exec, hyprshot-rs -m active -m output"));
+    let commands = vec![
+        format!(
+            "echo \"$HYPRSHOT_MODE $HYPRSHOT_PATH\" >> '{}'",
+            log.display()
+        ),
+        "exit 3".to_string(),
+        format!("echo second >> '{}'", log.display()),
+    ];
+    run_post_capture(&commands, &env, false);
+    match std::fs::read_to_string(&log) {
+        Ok(content) => assert_eq!(content, format!("region {}\nsecond\n", shot.display())),
+        Err(err) => panic!("Hooks didn't write {}: {}", log.display(), err),
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
 
-    assert!(!binds.contains("--clipboard-only"));
+#[test]
+fn pre_capture_hooks_abort_on_failure() {
+    use crate::hooks::run_pre_capture;
+
+    let dir = test_output_dir("pre-capture");
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        panic!("Failed to create {}: {}", dir.display(), err);
+    }
+    let log = dir.join("hooks.log");
+    let append = |text: &str| format!("echo {} >> '{}'", text, log.display());
+
+    let ok = vec![append("$HYPRSHOT_MODE"), String::new()];
+    if let Err(err) = run_pre_capture(&ok, "window", false) {
+        panic!("Passing hooks failed: {:#}", err);
+    }
+    let failing = vec![append("first"), "false".to_string(), append("never")];
+    match run_pre_capture(&failing, "region", false) {
+        Ok(()) => panic!("expected the failing hook to abort"),
+        Err(err) => assert!(format!("{:#}", err).contains("aborted"), "{err:#}"),
+    }
+    match std::fs::read_to_string(&log) {
+        Ok(content) => assert_eq!(content, "window\nfirst\n"),
+        Err(err) => panic!("Hooks didn't write {}: {}", log.display(), err),
+    }
+    let _ = std::fs::remove_dir_all(&dir);
 }
 
+/// Writes `--raw` bytes between printing hooks when re-run by
+/// `hook_output_leaves_raw_stdout_intact`, which checks the process's stdout.
 #[test]
-fn test_generate_hyprland_binds_with_clipboard() {
-    let config = crate::config::Config::default();
-    let binds = config.generate_hyprland_binds_with_clipboard();
+fn raw_stdout_with_printing_hooks() {
+    use std::io::Write;
 
-    assert!(binds.contains("bind = SUPER, Print, exec, hyprshot-rs -m window"));
-    assert!(binds.contains("bind = SUPER SHIFT, Print, exec, hyprshot-rs -m region"));
+    if env::var_os("HYPRSHOT_TEST_HOOK_STDOUT").is_none() {
+        return;
+    }
+    if let Err(err) =
+        crate::hooks::run_pre_capture(&["echo pre-noise".to_string()], "region", false)
+    {
+        panic!("pre_capture hook failed: {:#}", err);
+    }
+    let image = crate::backend::CapturedImage {
+        data: vec![10, 20, 30, 255],
+        width: 1,
+        height: 1,
+    };
+    let options = crate::save::SaveOptions {
+        raw: true,
+        raw_format: Some(crate::save::RawFormat::Bgra),
+        ..Default::default()
+    };
+    let geometry = geometry_or_panic(0, 0, 1, 1);
+    let bytes =
+        match crate::save::raw_output(&MockBackend::new(), &image, &options, &geometry, None) {
+            Ok(bytes) => bytes,
+            Err(err) => panic!("raw output failed: {}", err),
+        };
+    let mut stdout = std::io::stdout();
+    if let Err(err) = stdout
+        .write_all(&[b"<raw>".as_slice(), &bytes, b"</raw>"].concat())
+        .and_then(|()| stdout.flush())
+    {
+        panic!("Failed to write raw bytes: {}", err);
+    }
+    // post_capture runs before `--json` prints its document.
+    let env = crate::hooks::post_capture_env(
+        std::path::Path::new("shot.png"),
+        &geometry,
+        None,
+        None,
+        None,
+    );
+    crate::hooks::run_post_capture(&["echo post-noise".to_string()], &env, false);
+}
 
-    assert!(binds.contains("# Screenshot to clipboard (no file saved)"));
+#[test]
+fn hook_output_leaves_raw_stdout_intact() {
+    let exe = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(err) => panic!("No test binary: {}", err),
+    };
+    let output = match std::process::Command::new(exe)
+        .args([
+            "--exact",
+            "tests::raw_stdout_with_printing_hooks",
+            "--nocapture",
+        ])
+        .env("HYPRSHOT_TEST_HOOK_STDOUT", "1")
+        .output()
+    {
+        Ok(output) => output,
+        Err(err) => panic!("Failed to re-run the test binary: {}", err),
+    };
+    assert!(output.status.success(), "{:?}", output);
+    let expected = b"<raw>\x1e\x14\x0a\xff</raw>";
     assert!(
-        binds.contains("bind = SUPER ALT, Print, exec, hyprshot-rs -m window --clipboard-only")
+        output.stdout.windows(expected.len()).any(|w| w == expected),
+        "{:?}",
+        output
     );
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("noise"));
+    let stderr = String::from_utf8_lossy(&output.stderr);
     assert!(
-        binds.contains(
-            "bind = SUPER SHIFT ALT, Print, exec, hyprshot-rs -m region --clipboard-only"
-        )
+        stderr.contains("pre-noise") && stderr.contains("post-noise"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn flat_corners_then_sigterm() {
+    if env::var_os("HYPRSHOT_TEST_FLAT_CORNERS").is_none() {
+        return;
+    }
+    let _flat_corners = match crate::compositor::FlatCorners::apply(false) {
+        Ok(guard) => guard,
+        Err(err) => panic!("FlatCorners::apply failed: {:#}", err),
+    };
+    unsafe {
+        libc::raise(libc::SIGTERM);
+    }
+    panic!("SIGTERM didn't end the process");
+}
+
+#[test]
+fn no_rounding_is_restored_when_killed() {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixListener;
+    use std::os::unix::process::ExitStatusExt;
+
+    let runtime = test_output_dir("flat-corners");
+    let socket_dir = runtime.join("hypr").join("test");
+    if let Err(err) = std::fs::create_dir_all(&socket_dir) {
+        panic!("Failed to create {}: {}", socket_dir.display(), err);
+    }
+    let listener = match UnixListener::bind(socket_dir.join(".socket.sock")) {
+        Ok(listener) => listener,
+        Err(err) => panic!("Failed to bind the fake Hyprland socket: {}", err),
+    };
+    // Answers like Hyprland until the child's third request, the restore.
+    let server = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for stream in listener.incoming().take(3) {
+            let Ok(mut stream) = stream else { break };
+            let mut buf = [0u8; 256];
+            let len = stream.read(&mut buf).unwrap_or(0);
+            let request = String::from_utf8_lossy(&buf[..len]).into_owned();
+            let reply: &[u8] = if request.starts_with("j/getoption") {
+                br#"{"option": "decoration:rounding", "int": 8, "set": true}"#
+            } else {
+                b"ok"
+            };
+            let _ = stream.write_all(reply);
+            requests.push(request);
+        }
+        requests
+    });
+
+    let exe = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(err) => panic!("Failed to locate the test binary: {}", err),
+    };
+    let output = match std::process::Command::new(exe)
+        .args(["--exact", "tests::flat_corners_then_sigterm", "--nocapture"])
+        .env("HYPRSHOT_TEST_FLAT_CORNERS", "1")
+        .env("HYPRLAND_INSTANCE_SIGNATURE", "test")
+        .env("XDG_RUNTIME_DIR", &runtime)
+        .output()
+    {
+        Ok(output) => output,
+        Err(err) => panic!("Failed to re-run the test binary: {}", err),
+    };
+    assert_eq!(output.status.signal(), Some(libc::SIGTERM), "{:?}", output);
+    let requests = match server.join() {
+        Ok(requests) => requests,
+        Err(_) => panic!("Fake Hyprland socket thread panicked"),
+    };
+    assert_eq!(
+        requests,
+        [
+            "j/getoption decoration:rounding",
+            "keyword decoration:rounding 0",
+            "keyword decoration:rounding 8"
+        ]
+    );
+    let _ = std::fs::remove_dir_all(&runtime);
+}
+
+#[test]
+fn clipboard_text_is_offered_under_every_text_type() {
+    use crate::clipboard::text_offers;
+
+    let offers = text_offers("https://example.com/a.png");
+    let types: Vec<&str> = offers.iter().map(|(mime, _)| *mime).collect();
+    assert_eq!(
+        types,
+        [
+            "text/plain;charset=utf-8",
+            "text/plain",
+            "UTF8_STRING",
+            "STRING",
+            "TEXT"
+        ]
     );
     assert!(
-        binds
-            .contains("bind = SUPER CTRL ALT, Print, exec, hyprshot-rs -m output --clipboard-only")
+        offers
+            .iter()
+            .all(|(_, payload)| *payload == b"https://example.com/a.png")
+    );
+    assert_eq!(crate::config::ImageFormat::Jpeg.mime_type(), "image/jpeg");
+}
+
+#[test]
+fn json_report_describes_the_saved_capture() {
+    use crate::save::{SaveOutcome, json_report};
+
+    let outcome = SaveOutcome {
+        path: Some(PathBuf::from("/tmp/shot.png")),
+        copied: true,
+        url: None,
+        skipped: false,
+    };
+    let geometry = geometry_or_panic(-10, 20, 300, 200);
+    let report = json_report(
+        &outcome,
+        &geometry,
+        "region",
+        Some("DP-1"),
+        "png",
+        Duration::from_millis(1250),
+    );
+    assert_eq!(report["path"], "/tmp/shot.png");
+    assert_eq!(report["mode"], "region");
+    assert_eq!(report["geometry"]["x"], -10);
+    assert_eq!(report["geometry"]["width"], 300);
+    assert_eq!(report["output"], "DP-1");
+    assert_eq!(report["format"], "png");
+    assert_eq!(report["duration_ms"], 1250);
+    assert_eq!(report["clipboard"], true);
+    assert!(report["url"].is_null());
+
+    let skipped = json_report(
+        &SaveOutcome {
+            skipped: true,
+            ..SaveOutcome::default()
+        },
+        &geometry,
+        "window",
+        None,
+        "png",
+        Duration::ZERO,
+    );
+    assert!(skipped["path"].is_null());
+    assert_eq!(skipped["skipped"], true);
+
+    if Args::try_parse_from(["hyprshot-rs", "-m", "region", "--json", "--raw"]).is_ok() {
+        panic!("--json must conflict with --raw");
+    }
+}
+
+#[test]
+fn list_outputs_reports_scale_and_aligns_columns() {
+    use crate::capture::{OutputInfo, output_scale};
+    use crate::cli::{Action, ListTarget};
+    use crate::list_cmds::{outputs_json, outputs_table};
+
+    assert_eq!(output_scale((2560, 1440), (2560, 1440), false), 1.0);
+    assert_eq!(output_scale((3840, 2160), (2560, 1440), false), 1.5);
+    assert_eq!(output_scale((1080, 1920), (1920, 1080), true), 1.0);
+    assert_eq!(output_scale((2880, 1800), (2304, 1440), false), 1.25);
+
+    let outputs = vec![
+        OutputInfo {
+            name: "DP-1".to_string(),
+            description: Some("Dell U2720Q".to_string()),
+            geometry: geometry_or_panic(0, 0, 2560, 1440),
+            scale: 1.5,
+            transform: "normal",
+        },
+        OutputInfo {
+            name: "HDMI-A-1".to_string(),
+            description: None,
+            geometry: geometry_or_panic(2560, 0, 1080, 1920),
+            scale: 1.0,
+            transform: "90",
+        },
+    ];
+    assert_eq!(
+        outputs_table(&outputs),
+        "DP-1      0,0 2560x1440     scale 1.5  normal  Dell U2720Q\n\
+         HDMI-A-1  2560,0 1080x1920  scale 1    90\n"
+    );
+    let json = outputs_json(&outputs);
+    assert_eq!(json[0]["name"], "DP-1");
+    assert_eq!(json[0]["scale"], 1.5);
+    assert_eq!(json[1]["x"], 2560);
+    assert_eq!(json[1]["transform"], "90");
+    assert!(json[1]["description"].is_null());
+
+    match Args::try_parse_from(["hyprshot-rs", "list", "outputs", "--json"]) {
+        Ok(args) => assert!(matches!(
+            args.action,
+            Some(Action::List {
+                target: ListTarget::Outputs { json: true }
+            })
+        )),
+        Err(err) => panic!("list outputs should parse: {}", err),
+    }
+    match Args::try_parse_from(["hyprshot-rs", "-m", "window", "--", "mirage"]) {
+        Ok(args) => {
+            assert!(args.action.is_none());
+            assert_eq!(args.command, ["mirage"]);
+        }
+        Err(err) => panic!("a post-capture command should still parse: {}", err),
+    }
+}
+
+#[test]
+fn list_windows_shows_workspace_and_ids() {
+    use crate::list_cmds::{windows_json, windows_table};
+
+    let tree = serde_json::json!({
+        "type": "root",
+        "nodes": [{
+            "type": "workspace",
+            "name": "2: web",
+            "nodes": [
+                {"type": "con", "id": 17, "app_id": "firefox", "name": "Mozilla Firefox", "pid": 7,
+                 "rect": {"x": 0, "y": 0, "width": 960, "height": 1080}, "nodes": []}
+            ],
+            "floating_nodes": [
+                {"type": "floating_con", "id": 23, "name": "xterm",
+                 "window_properties": {"class": "XTerm"},
+                 "rect": {"x": 100, "y": 100, "width": 400, "height": 300}, "nodes": []}
+            ]
+        }]
+    });
+    let workspaces = serde_json::json!([{"name": "2: web", "visible": true}]);
+    let windows = crate::compositor::sway_windows(&workspaces, &tree, false);
+    assert_eq!(
+        windows_table(&windows),
+        "0,0 960x1080     firefox  17  2: web  Mozilla Firefox\n\
+         100,100 400x300  XTerm    23  2: web  xterm\n"
+    );
+
+    let monitors = serde_json::json!([{"name": "DP-1", "activeWorkspace": {"id": 1}}]);
+    let clients = serde_json::json!([
+        {"address": "0x55f0a1b2c3d0", "pid": 42, "class": "", "title": "two\nlines",
+         "at": [0, 0], "size": [100, 100], "workspace": {"id": 1, "name": "1"}}
+    ]);
+    let windows = crate::compositor::hyprland_windows(&monitors, &clients);
+    assert_eq!(
+        windows_table(&windows),
+        "0,0 100x100  -  0x55f0a1b2c3d0  1  two lines\n"
+    );
+    let json = windows_json(&windows);
+    assert_eq!(json[0]["address"], "0x55f0a1b2c3d0");
+    assert!(json[0]["id"].is_null());
+    assert_eq!(json[0]["workspace"], "1");
+    assert_eq!(json[0]["pid"], 42);
+    assert_eq!(json[0]["width"], 100);
+}
+
+#[test]
+fn completion_scripts_cover_flags_subcommands_modes_and_outputs() {
+    use crate::cli::Shell;
+    use crate::completions::generate;
+
+    let lister = "hyprshot-rs list outputs 2>/dev/null | cut -d ' ' -f 1";
+
+    let bash = generate(Shell::Bash);
+    assert!(bash.contains("complete -F _hyprshot__rs_outputs -o nosort"));
+    assert!(bash.contains("--only-output|--exclude-output)"));
+    assert!(bash.contains(&format!("COMPREPLY+=($(compgen -W \"$({lister})\"")));
+    assert!(
+        bash.contains(
+            "COMPREPLY=($(compgen -W \"output window window-pair region layer all active\""
+        )
+    );
+    assert!(bash.contains("COMPREPLY=($(compgen -W \"png jpeg webp\""));
+    match std::process::Command::new("bash")
+        .args(["-n", "-c", &bash])
+        .status()
+    {
+        Ok(status) => assert!(status.success(), "bash rejected the script"),
+        Err(err) => eprintln!("bash not available, skipping syntax check: {}", err),
+    }
+
+    let zsh = generate(Shell::Zsh);
+    assert!(zsh.starts_with("#compdef hyprshot-rs\n"));
+    assert!(zsh.contains(":MODE:_hyprshot-rs_modes' \\"));
+    assert!(zsh.contains("'modes:mode:(output window window-pair region layer all active)'"));
+    assert!(zsh.contains(":NAME:_hyprshot-rs_outputs' \\"));
+    assert!(zsh.contains(lister));
+    assert!(zsh.trim_end().ends_with("fi"));
+    assert!(zsh.contains(":shell -- bash, zsh, fish or nushell:(bash zsh fish nushell)"));
+
+    let fish = generate(Shell::Fish);
+    assert!(fish.contains("-s m -l mode"));
+    for flag in ["-s m -l mode", "-l only-output", "-l exclude-output"] {
+        assert!(
+            fish.contains(&format!("{flag} -x -a \"({lister})\"")),
+            "{flag}"
+        );
+    }
+    assert!(fish.contains(
+        "-s o -l output-folder -d 'Directory to save screenshot' -r -f -a \"(__fish_complete_directories)\""
+    ));
+
+    let nushell = generate(Shell::Nushell);
+    assert!(nushell.contains("export extern \"hyprshot-rs list windows\" ["));
+    assert!(nushell.contains("--mode(-m): string@\"nu-complete hyprshot-rs mode\""));
+    assert!(nushell.contains("...command: string"));
+    assert!(nushell.contains("--only-output: string@\"nu-complete hyprshot-rs outputs\""));
+    assert!(nushell.contains("\"active\" ] | append (nu-complete hyprshot-rs outputs)"));
+
+    // Output names parse as -m values too.
+    let args = Args::parse_from(["hyprshot-rs", "-m", "DP-1"]);
+    assert!(matches!(args.mode.as_slice(), [Mode::OutputName(name)] if name == "DP-1"));
+}
+
+#[test]
+fn doctor_fails_on_a_config_that_does_not_parse() {
+    use crate::doctor::{Status, config_file};
+
+    let dir = test_output_dir("doctor_config");
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        panic!("creating {} failed: {err}", dir.display());
+    }
+    let path = dir.join("config.toml");
+    if let Err(err) = std::fs::write(&path, "[paths\nscreenshots_dir = 1\n") {
+        panic!("writing the broken config failed: {err}");
+    }
+    let loaded: anyhow::Result<crate::config::Config> = match std::fs::read_to_string(&path) {
+        Ok(text) => toml::from_str(&text).map_err(anyhow::Error::from),
+        Err(err) => panic!("reading the broken config failed: {err}"),
+    };
+    let check = config_file(&path, &loaded);
+    assert_eq!(check.status, Status::Fail);
+    assert!(check.detail.starts_with(&path.display().to_string()));
+    assert!(check.detail.contains("defaults"));
+    match &check.fix {
+        Some(fix) => assert!(fix.contains(&path.display().to_string())),
+        None => panic!("a broken config needs a fix hint"),
+    }
+
+    let fine = config_file(&path, &Ok(crate::config::Config::default()));
+    assert_eq!(fine.status, Status::Ok);
+    let missing = config_file(
+        &dir.join("absent.toml"),
+        &Ok(crate::config::Config::default()),
+    );
+    assert_eq!(missing.status, Status::Ok);
+    assert!(missing.detail.contains("using defaults"));
+}
+
+#[test]
+fn doctor_flags_missing_protocols_with_a_fix() {
+    use crate::doctor::{Status, protocol_checks, report};
+
+    let globals = [
+        "wl_compositor",
+        "zwlr_screencopy_manager_v1",
+        "zxdg_output_manager_v1",
+    ]
+    .map(String::from);
+    let checks = protocol_checks(&globals);
+    let statuses: Vec<_> = checks
+        .iter()
+        .map(|check| (check.name, check.status))
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            ("screencopy", Status::Ok),
+            ("layer-shell", Status::Fail),
+            ("xdg-output", Status::Ok),
+        ]
+    );
+    assert_eq!(
+        report(&checks),
+        "[ok]   screencopy   wlr-screencopy\n\
+         [FAIL] layer-shell  not offered; selection and --freeze can't draw their overlay\n\
+         \x20                   -> use a compositor with wlr-layer-shell (Hyprland, Sway, ...)\n\
+         [ok]   xdg-output   zxdg_output_manager_v1\n"
     );
+
+    let none = protocol_checks(&[]);
+    assert_eq!(none[0].status, Status::Fail);
+    assert_eq!(none[2].status, Status::Warn);
+    assert!(none.iter().all(|check| check.fix.is_some()));
+
+    match Args::try_parse_from(["hyprshot-rs", "doctor"]) {
+        Ok(args) => assert!(matches!(args.action, Some(crate::cli::Action::Doctor))),
+        Err(err) => panic!("doctor should parse: {}", err),
+    }
+}
+
+#[test]
+fn notification_summary_reflects_capture_mode() {
+    use crate::save::CaptureContext;
+
+    let context = |mode: Option<Mode>, current: bool| CaptureContext { mode, current };
+    assert_eq!(
+        context(Some(Mode::Output), false).summary(Some("DP-1")),
+        "Monitor DP-1 captured"
+    );
+    assert_eq!(
+        context(Some(Mode::Output), true).summary(None),
+        "Monitor captured"
+    );
+    assert_eq!(
+        context(Some(Mode::Window), true).summary(None),
+        "Active window captured"
+    );
+    assert_eq!(
+        context(Some(Mode::Region), false).summary(Some("DP-1")),
+        "Region captured"
+    );
+    assert_eq!(
+        context(Some(Mode::All), false).summary(None),
+        "All outputs captured"
+    );
+    assert_eq!(context(None, false).summary(None), "Screenshot saved");
+}
+
+#[test]
+fn wallpaper_command_per_tool() {
+    use crate::wallpaper::{WallpaperTool, wallpaper_command};
+
+    let path = std::path::Path::new("/tmp/shot.png");
+    assert_eq!(
+        wallpaper_command(WallpaperTool::Swww, path),
+        ["swww", "img", "/tmp/shot.png"]
+    );
+    assert_eq!(
+        wallpaper_command(WallpaperTool::Hyprpaper, path),
+        ["hyprctl", "hyprpaper", "reload", ",/tmp/shot.png"]
+    );
+    assert_eq!(
+        wallpaper_command(WallpaperTool::Swaybg, path),
+        ["swaybg", "-i", "/tmp/shot.png", "-m", "fill"]
+    );
+}
+
+#[test]
+fn run_command_stops_waiting_after_timeout() {
+    let geometry = match crate::geometry::Geometry::new(0, 0, 1, 1) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to construct geometry: {}", err),
+    };
+    let path = std::path::Path::new("/dev/null");
+    let sh = |script: &str| vec!["sh".to_string(), "-c".to_string(), script.to_string()];
+    let options = crate::save::SaveOptions {
+        command_timeout_ms: 100,
+        ..Default::default()
+    };
+
+    let start = std::time::Instant::now();
+    if let Err(err) = crate::save::run_command(&sh("sleep 5"), path, &geometry, None, &options) {
+        panic!("run_command failed: {}", err);
+    }
+    assert!(start.elapsed() < std::time::Duration::from_secs(3));
+
+    let options = crate::save::SaveOptions {
+        command_timeout_ms: 5000,
+        ..Default::default()
+    };
+    if crate::save::run_command(&sh("exit 3"), path, &geometry, None, &options).is_ok() {
+        panic!("expected a failing command to be reported");
+    }
+}
+
+#[test]
+fn save_geometry_writes_encoded_capture_to_disk() {
+    let geometry = match crate::geometry::Geometry::new(5, 6, 2, 3) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to construct geometry: {}", err),
+    };
+    let dir = test_output_dir("save");
+    let path = dir.join("shot.png");
+    let mut backend = MockBackend::new();
+
+    let options = crate::save::SaveOptions {
+        silent: true,
+        ..Default::default()
+    };
+    if let Err(err) = crate::save::save_geometry(&mut backend, &geometry, None, &path, &options) {
+        panic!("save_geometry failed: {}", err);
+    }
+
+    assert_eq!(backend.captured, vec![geometry]);
+    let written = match std::fs::read(&path) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to read saved screenshot: {}", err),
+    };
+    assert!(written.starts_with(b"MOCKPNG"));
+    assert_eq!(written.len(), 7 + 8 + 2 * 3 * 4);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn edit_with_saves_what_the_editor_leaves_behind() {
+    use crate::editor::{decode_png, editor_command};
+
+    assert_eq!(editor_command("gimp"), "gimp \"$HYPRSHOT_PATH\"");
+    let satty = "satty -f \"$HYPRSHOT_PATH\" -o \"$HYPRSHOT_PATH\"";
+    assert_eq!(editor_command(satty), satty);
+
+    // The "edit": a 1x2 RGB image replacing the 2x2 capture.
+    let dir = test_output_dir("edit-with");
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        panic!("Failed to create {}: {}", dir.display(), err);
+    }
+    let mut edited = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut edited, 1, 2);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let written = encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&[1, 2, 3, 4, 5, 6]));
+        if let Err(err) = written {
+            panic!("Failed to encode PNG: {}", err);
+        }
+    }
+    match decode_png(&edited) {
+        Ok(image) => assert_eq!(image.data, [1, 2, 3, 255, 4, 5, 6, 255]),
+        Err(err) => panic!("decode_png failed: {}", err),
+    }
+    let fixture = dir.join("edited.png");
+    if let Err(err) = std::fs::write(&fixture, &edited) {
+        panic!("Failed to write {}: {}", fixture.display(), err);
+    }
+
+    let geometry = geometry_or_panic(0, 0, 2, 2);
+    let path = dir.join("shot.png");
+    let options = crate::save::SaveOptions {
+        editor: Some(format!("cp '{}'", fixture.display())),
+        silent: true,
+        ..Default::default()
+    };
+    if let Err(err) =
+        crate::save::save_geometry(&mut MockBackend::new(), &geometry, None, &path, &options)
+    {
+        panic!("save_geometry failed: {}", err);
+    }
+    match std::fs::read(&path) {
+        Ok(saved) => {
+            let mut expected = b"MOCKPNG".to_vec();
+            expected.extend_from_slice(&1u32.to_le_bytes());
+            expected.extend_from_slice(&2u32.to_le_bytes());
+            expected.extend_from_slice(&[1, 2, 3, 255, 4, 5, 6, 255]);
+            assert_eq!(saved, expected);
+        }
+        Err(err) => panic!("Failed to read {}: {}", path.display(), err),
+    }
+
+    let aborted = dir.join("aborted.png");
+    let options = crate::save::SaveOptions {
+        editor: Some("false".to_string()),
+        silent: true,
+        ..Default::default()
+    };
+    if crate::save::save_geometry(&mut MockBackend::new(), &geometry, None, &aborted, &options)
+        .is_ok()
+    {
+        panic!("expected a failing editor to abort the save");
+    }
+    assert!(!aborted.exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn obscure_hides_only_the_dragged_boxes() {
+    use crate::backend::CapturedImage;
+    use crate::config::ObscureStyle;
+
+    // 8x4 pixels of a 4x2 logical area at scale 2; each pixel's red is its column.
+    let area = geometry_or_panic(100, 50, 4, 2);
+    let image = || CapturedImage {
+        data: (0..32u8).flat_map(|i| [(i % 8) * 10, 0, 0, 255]).collect(),
+        width: 8,
+        height: 4,
+    };
+    let red = |image: &CapturedImage, x: usize, y: usize| image.data[(y * 8 + x) * 4];
+
+    // The right half, reaching past the area: everything there becomes one color.
+    let mut pixelated = image();
+    crate::obscure::apply(
+        &mut pixelated,
+        &area,
+        &[geometry_or_panic(102, 40, 10, 20)],
+        ObscureStyle::Pixelate,
+    );
+    for y in 0..4 {
+        for x in 0..4 {
+            assert_eq!(red(&pixelated, x, y), red(&image(), x, y));
+        }
+        for x in 4..8 {
+            assert_eq!(red(&pixelated, x, y), 55);
+        }
+    }
+
+    let mut blurred = image();
+    crate::obscure::apply(
+        &mut blurred,
+        &area,
+        &[geometry_or_panic(102, 50, 2, 2)],
+        ObscureStyle::Blur,
+    );
+    assert_eq!(red(&blurred, 3, 0), 30);
+    assert!(red(&blurred, 4, 0) > 40 && red(&blurred, 7, 3) < 70);
+    assert_eq!(blurred.data[3], 255);
+
+    let mut untouched = image();
+    crate::obscure::apply(
+        &mut untouched,
+        &area,
+        &[geometry_or_panic(0, 0, 10, 10)],
+        ObscureStyle::Blur,
+    );
+    assert_eq!(untouched.data, image().data);
+
+    match Args::try_parse_from(["hyprshot-rs", "-m", "region", "--obscure"]) {
+        Ok(args) => assert_eq!(args.obscure, Some(ObscureStyle::Pixelate)),
+        Err(err) => panic!("--obscure should parse: {}", err),
+    }
+    match Args::try_parse_from(["hyprshot-rs", "-m", "region", "--obscure", "blur"]) {
+        Ok(args) => assert_eq!(args.obscure, Some(ObscureStyle::Blur)),
+        Err(err) => panic!("--obscure blur should parse: {}", err),
+    }
+}
+
+#[test]
+fn beautify_frames_the_capture() {
+    use crate::backend::CapturedImage;
+    use crate::beautify::{Background, Style, apply, parse_background};
+
+    match Style::from_config(&crate::config::BeautifyConfig::default()) {
+        Ok(style) => assert_eq!(
+            style.background,
+            Background::Gradient([0x8e, 0xc5, 0xfc], [0xe0, 0xc3, 0xfc])
+        ),
+        Err(err) => panic!("default [beautify] should be valid: {}", err),
+    }
+    if parse_background("#123456:blue").is_ok() {
+        panic!("expected an invalid gradient to be rejected");
+    }
+
+    // A 4x4 red capture at scale 2 with 1 logical pixel of white padding.
+    let image = CapturedImage {
+        data: [255, 0, 0, 255].repeat(16),
+        width: 4,
+        height: 4,
+    };
+    let at = |image: &CapturedImage, x: u32, y: u32| {
+        let i = (y * image.width + x) as usize * 4;
+        [
+            image.data[i],
+            image.data[i + 1],
+            image.data[i + 2],
+            image.data[i + 3],
+        ]
+    };
+    let mut style = Style {
+        padding: 1,
+        corner_radius: 0,
+        shadow_blur: 0,
+        background: Background::Solid([255, 255, 255]),
+    };
+    let framed = apply(&image, &style, 2.0);
+    assert_eq!((framed.width, framed.height), (8, 8));
+    assert_eq!(at(&framed, 0, 0), [255, 255, 255, 255]);
+    assert_eq!(at(&framed, 2, 2), [255, 0, 0, 255]);
+    assert_eq!(at(&framed, 5, 5), [255, 0, 0, 255]);
+    assert_eq!(at(&framed, 6, 5), [255, 255, 255, 255]);
+
+    // Rounded corners let the background through; the middle stays red.
+    style.corner_radius = 1;
+    let rounded = apply(&image, &style, 2.0);
+    assert!(at(&rounded, 2, 2)[1] > 0);
+    assert_eq!(at(&rounded, 3, 3), [255, 0, 0, 255]);
+
+    // The shadow darkens the padding below the capture more than above it.
+    style.corner_radius = 0;
+    style.shadow_blur = 2;
+    let shadowed = apply(&image, &style, 2.0);
+    assert!(at(&shadowed, 4, 7)[1] < at(&shadowed, 4, 0)[1]);
+    assert!(at(&shadowed, 4, 7)[1] < 255);
+}
+
+#[test]
+fn save_geometry_runs_command_with_saved_path() {
+    let geometry = match crate::geometry::Geometry::new(0, 0, 1, 1) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to construct geometry: {}", err),
+    };
+    let dir = test_output_dir("command");
+    let path = dir.join("shot.png");
+    let mut backend = MockBackend::new();
+
+    let options = crate::save::SaveOptions {
+        command: Some(vec!["test".to_string(), "-f".to_string()]),
+        silent: true,
+        ..Default::default()
+    };
+    if let Err(err) = crate::save::save_geometry(&mut backend, &geometry, None, &path, &options) {
+        panic!("save_geometry failed: {}", err);
+    }
+
+    let options = crate::save::SaveOptions {
+        command: Some(vec!["false".to_string()]),
+        silent: true,
+        ..Default::default()
+    };
+    assert!(crate::save::save_geometry(&mut backend, &geometry, None, &path, &options).is_err());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn save_geometry_writes_also_save_copies_despite_failures() {
+    let geometry = match crate::geometry::Geometry::new(0, 0, 2, 2) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to construct geometry: {}", err),
+    };
+    let dir = test_output_dir("also-save");
+    let path = dir.join("primary").join("shot.png");
+    let blocker = dir.join("not-a-dir");
+    let copy_dir = dir.join("copies");
+    if let Err(err) = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&blocker, b"x")) {
+        panic!("Failed to prepare test directory: {}", err);
+    }
+    let mut backend = MockBackend::new();
+
+    let options = crate::save::SaveOptions {
+        silent: true,
+        also_save: vec![blocker.clone(), copy_dir.clone()],
+        create_missing_dirs: true,
+        ..Default::default()
+    };
+    if let Err(err) = crate::save::save_geometry(&mut backend, &geometry, None, &path, &options) {
+        panic!("save_geometry failed: {}", err);
+    }
+
+    assert_eq!(backend.captured.len(), 1);
+    let primary = std::fs::read(&path).ok();
+    let copy = std::fs::read(copy_dir.join("shot.png")).ok();
+    assert!(primary.is_some());
+    assert_eq!(primary, copy);
+    assert!(blocker.is_file());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn rotate_recent_keeps_last_n_newest_first() {
+    let dir = test_output_dir("recent");
+    for shot in [b"one", b"two", b"thr", b"fou"] {
+        if let Err(err) = crate::save::rotate_recent(&dir, shot, 3) {
+            panic!("rotate_recent failed: {}", err);
+        }
+    }
+    let read = |i: usize| std::fs::read(dir.join(format!("recent-{}.png", i))).ok();
+    assert_eq!(read(0), Some(b"fou".to_vec()));
+    assert_eq!(read(1), Some(b"thr".to_vec()));
+    assert_eq!(read(2), Some(b"two".to_vec()));
+    assert_eq!(read(3), None);
+
+    // Shrinking the ring drops the extra slots.
+    if let Err(err) = crate::save::rotate_recent(&dir, b"fiv", 1) {
+        panic!("rotate_recent failed: {}", err);
+    }
+    assert_eq!(read(0), Some(b"fiv".to_vec()));
+    assert_eq!(read(1), None);
+    assert_eq!(read(2), None);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn file_uri_percent_encodes_path() {
+    let uri = match crate::save::file_uri(std::path::Path::new("/tmp/my shots/a#1.png")) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to build file URI: {}", err),
+    };
+    assert_eq!(uri, "file:///tmp/my%20shots/a%231.png");
+}
+
+#[test]
+fn file_uri_resolves_relative_paths() {
+    let uri = match crate::save::file_uri(std::path::Path::new("shot.png")) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to build file URI: {}", err),
+    };
+    assert!(uri.starts_with("file:///"));
+    assert!(uri.ends_with("/shot.png"));
+}
+
+#[test]
+fn freeze_module_does_not_depend_on_selector() {
+    let freeze_src = include_str!("freeze.rs");
+    assert!(!freeze_src.contains("crate::selector"));
+    assert!(!freeze_src.contains("selector::"));
+}
+
+#[test]
+fn region_cancel_detection_is_typed() {
+    let typed_region_cancel: anyhow::Error =
+        crate::selector::SelectorError::Cancelled(crate::selector::SelectionTarget::Region).into();
+    assert!(crate::capture::is_region_selection_cancelled(
+        &typed_region_cancel
+    ));
+
+    let typed_output_cancel: anyhow::Error =
+        crate::selector::SelectorError::Cancelled(crate::selector::SelectionTarget::Output).into();
+    assert!(!crate::capture::is_region_selection_cancelled(
+        &typed_output_cancel
+    ));
+
+    let legacy_string_error = anyhow::anyhow!("slurp failed to select region");
+    assert!(!crate::capture::is_region_selection_cancelled(
+        &legacy_string_error
+    ));
+}
+
+#[test]
+fn selector_parse_choice_boxes_parses_labels_and_blank_lines() {
+    let input = "\n10,20 300x400 Terminal App\n1,2 3x4\n";
+    let parsed = match crate::selector::parse_choice_boxes(input) {
+        Ok(v) => v,
+        Err(err) => panic!("Expected parsed boxes, got error: {err}"),
+    };
+
+    assert_eq!(parsed.len(), 2);
+    assert_eq!(
+        parsed[0].rect,
+        slurp_rs::Rect {
+            x: 10,
+            y: 20,
+            width: 300,
+            height: 400
+        }
+    );
+    assert_eq!(parsed[0].label.as_deref(), Some("Terminal App"));
+
+    assert_eq!(
+        parsed[1].rect,
+        slurp_rs::Rect {
+            x: 1,
+            y: 2,
+            width: 3,
+            height: 4
+        }
+    );
+    assert_eq!(parsed[1].label, None);
+}
+
+#[test]
+fn selector_parse_choice_boxes_rejects_invalid_input() {
+    let err = match crate::selector::parse_choice_boxes("10,20\n") {
+        Ok(_) => panic!("Expected parse error for invalid input"),
+        Err(err) => err,
+    };
+    assert!(err.to_string().contains("missing dimensions"));
+}
+
+#[test]
+fn select_from_menu_returns_the_chosen_line() {
+    let choices = vec!["DP-1".to_string(), "HDMI-A-1".to_string()];
+    match crate::selector::select_from_menu("tail -n 1", &choices, false) {
+        Ok(choice) => assert_eq!(choice, "HDMI-A-1"),
+        Err(err) => panic!("select_from_menu failed: {}", err),
+    }
+
+    // Closing the menu without a choice counts as a cancelled selection.
+    let err = match crate::selector::select_from_menu("true", &choices, false) {
+        Ok(choice) => panic!("expected cancellation, got {}", choice),
+        Err(err) => err,
+    };
+    assert!(crate::selector::is_cancelled(
+        &err,
+        crate::selector::SelectionTarget::Output
+    ));
+}
+
+#[test]
+fn selection_timeout_kills_the_menu_and_returns_an_error() {
+    let choices = vec!["DP-1".to_string()];
+    let started = std::time::Instant::now();
+    let timeout = crate::selector::SelectionTimeout::start(Duration::from_millis(200));
+    let err = match crate::selector::select_from_menu("sleep 30", &choices, false) {
+        Ok(choice) => panic!("expected a timeout, got {}", choice),
+        Err(err) => err,
+    };
+    drop(timeout);
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(crate::exit::exit_code(&err), crate::exit::TIMED_OUT);
+    assert!(format!("{:#}", err).contains("nothing was selected within 0.2s"));
+
+    // Dropped, the timeout no longer applies.
+    match crate::selector::select_from_menu("tail -n 1", &choices, false) {
+        Ok(choice) => assert_eq!(choice, "DP-1"),
+        Err(err) => panic!("select_from_menu failed: {:#}", err),
+    }
+}
+
+#[test]
+fn selector_map_api_error_maps_cancel_to_typed_cancel() {
+    let err = crate::selector::map_api_error(
+        slurp_rs::SlurpError::Cancelled,
+        crate::selector::SelectionTarget::Region,
+    );
+    assert!(crate::selector::is_cancelled(
+        &err,
+        crate::selector::SelectionTarget::Region
+    ));
+    assert!(!crate::selector::is_cancelled(
+        &err,
+        crate::selector::SelectionTarget::Output
+    ));
+    assert_eq!(err.to_string(), "slurp failed to select region");
+}
+
+#[test]
+fn selector_map_api_error_maps_non_cancel_to_failed() {
+    let err = crate::selector::map_api_error(
+        slurp_rs::SlurpError::InvalidInput("bad".to_string()),
+        crate::selector::SelectionTarget::Window,
+    );
+    assert!(!crate::selector::is_cancelled(
+        &err,
+        crate::selector::SelectionTarget::Window
+    ));
+
+    let typed = match err.downcast_ref::<crate::selector::SelectorError>() {
+        Some(v) => v,
+        None => panic!("Expected SelectorError"),
+    };
+    match typed {
+        crate::selector::SelectorError::Failed { target, message } => {
+            assert_eq!(*target, crate::selector::SelectionTarget::Window);
+            assert!(message.contains("invalid input: bad"));
+        }
+        other => panic!("Expected failed error, got {}", other),
+    }
+}
+
+#[test]
+fn all_mode_covers_every_output() {
+    use crate::capture::outputs_bounding_box;
+    use crate::geometry::Geometry;
+
+    let args = Args::parse_from(["hyprshot-rs", "-m", "all"]);
+    assert!(matches!(args.mode.first(), Some(Mode::All)));
+
+    let geo = |x, y, width, height| Geometry {
+        x,
+        y,
+        width,
+        height,
+    };
+    // Laptop left of and below the main monitor, with a gap between them.
+    let outputs = [geo(0, 0, 2560, 1440), geo(-1920, 1500, 1920, 1200)];
+    assert_eq!(
+        outputs_bounding_box(&outputs),
+        Some(geo(-1920, 0, 4480, 2700))
+    );
+    assert_eq!(outputs_bounding_box(&outputs[..1]), Some(outputs[0]));
+    assert_eq!(outputs_bounding_box(&[]), None);
+}
+
+#[test]
+fn all_mode_output_filters_keep_and_mask_outputs() {
+    use crate::backend::{CaptureBackend, MaskedBackend};
+    use crate::capture::filter_outputs;
+
+    let args = Args::parse_from([
+        "hyprshot-rs",
+        "-m",
+        "all",
+        "--exclude-output",
+        "HDMI-A-1",
+        "--exclude-output",
+        "DP-2",
+    ]);
+    assert_eq!(args.exclude_output, ["HDMI-A-1", "DP-2"]);
+    assert!(
+        Args::try_parse_from([
+            "hyprshot-rs",
+            "-m",
+            "all",
+            "--only-output",
+            "DP-1",
+            "--exclude-output",
+            "DP-2",
+        ])
+        .is_err()
+    );
+
+    let outputs = vec![
+        ("DP-1".to_string(), geometry_or_panic(0, 0, 4, 2)),
+        ("HDMI-A-1".to_string(), geometry_or_panic(4, 0, 2, 2)),
+        ("DP-2".to_string(), geometry_or_panic(6, 0, 4, 2)),
+    ];
+    let names = |kept: anyhow::Result<Vec<(String, crate::geometry::Geometry)>>| match kept {
+        Ok(kept) => kept.into_iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        Err(err) => panic!("filter failed: {:#}", err),
+    };
+    let strings = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        names(filter_outputs(
+            outputs.clone(),
+            &[],
+            &strings(&["HDMI-A-1"])
+        )),
+        ["DP-1", "DP-2"]
+    );
+    assert_eq!(
+        names(filter_outputs(outputs.clone(), &strings(&["DP-2"]), &[])),
+        ["DP-2"]
+    );
+    match filter_outputs(outputs.clone(), &[], &strings(&["DP-3"])) {
+        Ok(_) => panic!("an unknown output should be an error"),
+        Err(err) => assert!(err.to_string().contains("DP-3"), "{}", err),
+    }
+    if filter_outputs(outputs, &[], &strings(&["DP-1", "HDMI-A-1", "DP-2"])).is_ok() {
+        panic!("excluding every output should be an error");
+    }
+
+    // The excluded middle output stays transparent inside the bounding box.
+    let kept = vec![geometry_or_panic(0, 0, 4, 2), geometry_or_panic(6, 0, 4, 2)];
+    let mut backend = MaskedBackend::new(kept, Box::new(MockBackend::new()));
+    let image = match backend.capture_region(&geometry_or_panic(0, 0, 10, 2)) {
+        Ok(image) => image,
+        Err(err) => panic!("masked capture failed: {:#}", err),
+    };
+    assert_eq!((image.width, image.height), (10, 2));
+    let alpha = |x: usize| image.data[x * 4 + 3];
+    assert_eq!([alpha(3), alpha(4), alpha(5), alpha(6)], [0xff, 0, 0, 0xff]);
+}
+
+#[test]
+fn parse_active_output_mode_combo() {
+    let args = Args::parse_from(["hyprshot-rs", "-m", "output", "-m", "active"]);
+    assert!(matches!(args.mode.first(), Some(Mode::Output)));
+    assert!(matches!(args.mode.get(1), Some(Mode::Active)));
+}
+
+#[test]
+fn test_default_config() {
+    let config = crate::config::Config::default();
+    assert_eq!(config.paths.screenshots_dir, "~/Pictures");
+    assert_eq!(config.hotkeys.window, "SUPER, Print");
+    assert!(config.capture.notification);
+    assert_eq!(config.capture.notification_timeout, 3000);
+    assert!(config.advanced.freeze_on_region);
+    assert_eq!(config.advanced.delay_ms, 0);
+    assert_eq!(config.capture.png_compression.level(), 6);
+    assert!(!config.capture.output_physical_pixels);
+    assert!(!config.capture.include_cursor);
+    assert_eq!(config.capture.force_scale, None);
+    assert_eq!(
+        config.capture.default_format,
+        crate::config::ImageFormat::Png
+    );
+    assert_eq!(config.capture.quality, 90);
+    assert_eq!(config.capture.flatten_background, "#ffffff");
+}
+
+#[test]
+fn test_config_serialization() {
+    let config = crate::config::Config::default();
+    let toml_str = match toml::to_string(&config) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to serialize config: {}", err),
+    };
+    assert!(toml_str.contains("[paths]"));
+    assert!(toml_str.contains("[hotkeys]"));
+    assert!(toml_str.contains("[capture]"));
+    assert!(toml_str.contains("[advanced]"));
+}
+
+#[test]
+fn test_config_deserialization() {
+    let toml_str = r#"
+        [paths]
+        screenshots_dir = "~/Documents"
+
+        [hotkeys]
+        window = "ALT, W"
+        region = "ALT, R"
+
+        [capture]
+        notification = false
+        png_compression = "best"
+
+        [advanced]
+        delay_ms = 500
+    "#;
+
+    let config: crate::config::Config = match toml::from_str(toml_str) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to deserialize config: {}", err),
+    };
+    assert_eq!(config.paths.screenshots_dir, "~/Documents");
+    assert_eq!(config.hotkeys.window, "ALT, W");
+    assert!(!config.capture.notification);
+    assert_eq!(
+        config.capture.png_compression,
+        crate::config::PngCompression::Best
+    );
+    assert_eq!(config.advanced.delay_ms, 500);
+}
+
+#[test]
+fn apply_profile_fills_gaps_below_cli_flags() {
+    let toml_str = r#"
+        [paths]
+        screenshots_dir = "~/Pictures"
+
+        [profiles.archive]
+        mode = ["output", "active"]
+        screenshots_dir = "~/Archive"
+        png_compression = "best"
+        clipboard_only = true
+        command = ["mirage"]
+
+        [profiles.share]
+        format = "webp"
+        quality = 75
+        upload = "imgur"
+    "#;
+    let base: crate::config::Config = match toml::from_str(toml_str) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to deserialize config: {}", err),
+    };
+
+    let mut config = base.clone();
+    let mut args = Args::parse_from(["hyprshot-rs", "--profile", "archive"]);
+    if let Err(err) = crate::cli::apply_profile(&mut args, &mut config, "archive") {
+        panic!("apply_profile failed: {}", err);
+    }
+    assert!(matches!(args.mode.as_slice(), [Mode::Output, Mode::Active]));
+    assert_eq!(args.command, vec!["mirage".to_string()]);
+    assert!(args.clipboard_only);
+    assert_eq!(config.paths.screenshots_dir, "~/Archive");
+    assert_eq!(
+        config.capture.png_compression,
+        crate::config::PngCompression::Best
+    );
+    // Untouched fields keep the base config.
+    assert!(config.capture.notification);
+
+    let mut config = base.clone();
+    let mut args = Args::parse_from(["hyprshot-rs", "-m", "region", "--", "imv"]);
+    if let Err(err) = crate::cli::apply_profile(&mut args, &mut config, "archive") {
+        panic!("apply_profile failed: {}", err);
+    }
+    assert!(matches!(args.mode.as_slice(), [Mode::Region]));
+    assert_eq!(args.command, vec!["imv".to_string()]);
+
+    let mut config = base.clone();
+    if crate::cli::apply_profile(&mut args, &mut config, "missing").is_ok() {
+        panic!("expected an unknown profile to be rejected");
+    }
+
+    let mut config = base.clone();
+    let mut args = Args::parse_from(["hyprshot-rs", "-m", "region"]);
+    if let Err(err) = crate::cli::apply_profile(&mut args, &mut config, "share") {
+        panic!("apply_profile failed: {}", err);
+    }
+    assert_eq!(
+        crate::cli::resolve_format(&args, &config),
+        crate::config::ImageFormat::Webp
+    );
+    assert_eq!(config.capture.quality, 75);
+    assert_eq!(args.upload.as_deref(), Some("imgur"));
+
+    // Flags still win over the profile.
+    let mut config = base.clone();
+    let mut args = Args::parse_from(["hyprshot-rs", "--format", "jpeg", "--upload", "0x0"]);
+    if let Err(err) = crate::cli::apply_profile(&mut args, &mut config, "share") {
+        panic!("apply_profile failed: {}", err);
+    }
+    assert_eq!(
+        crate::cli::resolve_format(&args, &config),
+        crate::config::ImageFormat::Jpeg
+    );
+    assert_eq!(args.upload.as_deref(), Some("0x0"));
+    let mut config = base;
+    let mut args = Args::parse_from(["hyprshot-rs", "--raw"]);
+    if let Err(err) = crate::cli::apply_profile(&mut args, &mut config, "share") {
+        panic!("apply_profile failed: {}", err);
+    }
+    assert_eq!(args.upload, None);
+
+    let typo = "[profiles.share]\nformats = \"webp\"\n";
+    if toml::from_str::<crate::config::Config>(typo).is_ok() {
+        panic!("expected an unknown profile key to be rejected");
+    }
+}
+
+#[test]
+fn clipboard_on_capture_can_turn_copying_off() {
+    assert!(
+        crate::config::Config::default()
+            .capture
+            .clipboard_on_capture
+    );
+    let toml_str = r#"
+        [capture]
+        clipboard_on_capture = false
+
+        [profiles.share]
+        clipboard_on_capture = true
+    "#;
+    let base: crate::config::Config = match toml::from_str(toml_str) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to deserialize config: {}", err),
+    };
+    assert!(!base.capture.clipboard_on_capture);
+
+    let mut config = base.clone();
+    let mut args = Args::parse_from(["hyprshot-rs", "--profile", "share"]);
+    if let Err(err) = crate::cli::apply_profile(&mut args, &mut config, "share") {
+        panic!("apply_profile failed: {}", err);
+    }
+    assert!(config.capture.clipboard_on_capture);
+
+    let geometry = geometry_or_panic(0, 0, 2, 2);
+    let path = test_output_dir("no-clipboard").join("shot.png");
+    let options = crate::save::SaveOptions {
+        clipboard: base.capture.clipboard_on_capture,
+        silent: true,
+        ..Default::default()
+    };
+    match crate::save::save_geometry(&mut MockBackend::new(), &geometry, None, &path, &options) {
+        Ok(outcome) => {
+            assert!(!outcome.copied);
+            assert_eq!(outcome.path.as_deref(), Some(path.as_path()));
+        }
+        Err(err) => panic!("save_geometry failed: {}", err),
+    }
+}
+
+#[test]
+fn detach_delay_refuses_flags_that_print_to_stdout() {
+    use crate::cli::stdout_flags;
+
+    let flags = |extra: &[&str]| {
+        let mut argv = vec!["hyprshot-rs", "-m", "region", "-D", "3", "--detach-delay"];
+        argv.extend_from_slice(extra);
+        stdout_flags(&Args::parse_from(argv))
+    };
+    assert!(flags(&[]).is_empty());
+    assert_eq!(flags(&["--json"]), ["--json"]);
+    assert_eq!(flags(&["--print-geometry"]), ["--print-geometry"]);
+    assert_eq!(flags(&["--term-preview"]), ["--term-preview"]);
+    assert_eq!(
+        flags(&["--raw", "--stdout-encoding", "base64"]),
+        ["--raw", "--stdout-encoding"]
+    );
+}
+
+#[test]
+fn detached_args_drop_only_the_detach_flag() {
+    use std::ffi::OsString;
+
+    let args = [
+        "-m",
+        "region",
+        "--detach-delay",
+        "-D",
+        "5",
+        "--",
+        "echo",
+        "--detach-delay",
+    ]
+    .map(OsString::from);
+    let expected = ["-m", "region", "-D", "5", "--", "echo", "--detach-delay"].map(OsString::from);
+    assert_eq!(crate::cli::detached_args(args), expected.to_vec());
+}
+
+#[test]
+fn test_partial_config() {
+    let toml_str = r#"
+        [paths]
+        screenshots_dir = "~/Custom"
+    "#;
+
+    let config: crate::config::Config = match toml::from_str(toml_str) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to deserialize partial config: {}", err),
+    };
+    assert_eq!(config.paths.screenshots_dir, "~/Custom");
+    assert_eq!(config.hotkeys.window, "SUPER, Print");
+    assert!(config.capture.notification);
+}
+
+#[test]
+fn test_ensure_directory_respects_create_missing() {
+    let root = test_output_dir("ensure-directory");
+    let dir = root.join("missing");
+    let path = dir.to_string_lossy().to_string();
+
+    let err = match crate::config::ensure_directory(&path, false) {
+        Ok(_) => panic!("Expected missing directory to be rejected"),
+        Err(err) => err,
+    };
+    assert!(err.to_string().contains("does not exist"));
+    assert!(!dir.exists());
+
+    match crate::config::ensure_directory(&path, true) {
+        Ok(created) => assert_eq!(created, dir),
+        Err(err) => panic!("Failed to create directory: {}", err),
+    }
+    assert!(dir.is_dir());
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_expand_path_tilde() {
+    let result = match crate::config::expand_path("~/Pictures") {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to expand path: {}", err),
+    };
+    let home = match dirs::home_dir() {
+        Some(v) => v,
+        None => panic!("Failed to resolve home directory"),
+    };
+    assert_eq!(result, home.join("Pictures"));
+
+    let result = match crate::config::expand_path("~") {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to expand home path: {}", err),
+    };
+    assert_eq!(result, home);
+}
+
+#[test]
+fn test_expand_path_env_vars() {
+    unsafe {
+        env::set_var("TEST_VAR", "/test/path");
+    }
+
+    let result = match crate::config::expand_path("$TEST_VAR/screenshots") {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to expand env path: {}", err),
+    };
+    assert_eq!(result, PathBuf::from("/test/path/screenshots"));
+
+    unsafe {
+        env::remove_var("TEST_VAR");
+    }
+}
+
+#[test]
+fn test_expand_path_xdg_pictures() {
+    let result = match crate::config::expand_path("$XDG_PICTURES_DIR/screenshots") {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to expand XDG pictures path: {}", err),
+    };
+    if let Some(pictures_dir) = dirs::picture_dir() {
+        assert_eq!(result, pictures_dir.join("screenshots"));
+    } else {
+        let home = match dirs::home_dir() {
+            Some(v) => v,
+            None => panic!("Failed to resolve home directory"),
+        };
+        assert_eq!(result, home.join("Pictures/screenshots"));
+    }
+}
+
+#[test]
+fn test_expand_path_empty() {
+    let result = match crate::config::expand_path("") {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to expand empty path: {}", err),
+    };
+    assert_eq!(result, PathBuf::from("."));
+}
+
+#[test]
+fn test_expand_path_no_expansion() {
+    let result = match crate::config::expand_path("/absolute/path") {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to expand absolute path: {}", err),
+    };
+    assert_eq!(result, PathBuf::from("/absolute/path"));
+
+    let result = match crate::config::expand_path("relative/path") {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to expand relative path: {}", err),
+    };
+    assert_eq!(result, PathBuf::from("relative/path"));
+}
+
+#[test]
+fn test_expand_path_undefined_var() {
+    let result = match crate::config::expand_path("$UNDEFINED_VAR_12345/test") {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to expand undefined var path: {}", err),
+    };
+    assert_eq!(result, PathBuf::from("$UNDEFINED_VAR_12345/test"));
+}
+
+#[test]
+fn directory_placeholders_build_nested_directories() {
+    use crate::filename::expand_directory;
+    use chrono::TimeZone;
+
+    let now = match chrono::Local.with_ymd_and_hms(2026, 3, 7, 9, 5, 0).single() {
+        Some(v) => v,
+        None => panic!("Failed to build timestamp"),
+    };
+    let base = test_output_dir("date-tokens");
+    let expanded = expand_directory(&base.join("%Y/%m/%d-%H/100%%"), now);
+    assert_eq!(expanded, base.join("2026/03/07-09/100%"));
+
+    let created = match crate::config::ensure_directory(&expanded.to_string_lossy(), true) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to create dated directory: {}", err),
+    };
+    assert!(created.is_dir());
+
+    let plain = PathBuf::from("/no/tokens/here");
+    assert_eq!(expand_directory(&plain, now), plain);
+    // A % that isn't a time placeholder is part of the directory's name.
+    for literal in [
+        "/shots%20",
+        "/100%done",
+        "/shots/%mode",
+        "/shots/%counter",
+        "/shots/%q",
+        "/50%",
+    ] {
+        assert_eq!(
+            expand_directory(std::path::Path::new(literal), now),
+            PathBuf::from(literal)
+        );
+    }
+    assert_eq!(
+        expand_directory(std::path::Path::new("/%Y%m%d_%H%M%S%ms"), now),
+        PathBuf::from("/20260307_090500000")
+    );
+    let _ = std::fs::remove_dir_all(&base);
+}
+
+#[test]
+fn test_get_screenshots_dir_priority_cli() {
+    let config = crate::config::Config::default();
+    let cli_path = Some(PathBuf::from("/cli/path"));
+
+    unsafe {
+        env::set_var("HYPRSHOT_DIR", "/env/path");
+    }
+
+    let result = match crate::config::get_screenshots_dir(cli_path, &config, false) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to resolve screenshots dir (cli): {}", err),
+    };
+    assert_eq!(result, PathBuf::from("/cli/path"));
+
+    unsafe {
+        env::remove_var("HYPRSHOT_DIR");
+    }
+}
+
+#[test]
+fn test_get_screenshots_dir_rejects_file() {
+    let config = crate::config::Config::default();
+    let dir = test_output_dir("dir-is-file");
+    let file = dir.join("not-a-dir");
+    if let Err(err) = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&file, b"x")) {
+        panic!("Failed to prepare test file: {}", err);
+    }
+
+    let err = match crate::config::get_screenshots_dir(Some(file.clone()), &config, false) {
+        Ok(v) => panic!("Expected a file path to be rejected, got {}", v.display()),
+        Err(err) => err.to_string(),
+    };
+    assert!(err.contains("-o/--output-folder"));
+    assert!(err.contains(&file.display().to_string()));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_get_screenshots_dir_priority_env() {
+    let config = crate::config::Config::default();
+
+    unsafe {
+        env::set_var("HYPRSHOT_DIR", "/env/path");
+    }
+
+    let result = match crate::config::get_screenshots_dir(None, &config, false) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to resolve screenshots dir (env): {}", err),
+    };
+    assert_eq!(result, PathBuf::from("/env/path"));
+
+    unsafe {
+        env::remove_var("HYPRSHOT_DIR");
+    }
+}
+
+#[test]
+fn test_get_screenshots_dir_priority_config() {
+    let mut config = crate::config::Config::default();
+    config.paths.screenshots_dir = "/config/path".to_string();
+
+    let result = match crate::config::get_screenshots_dir(None, &config, false) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to resolve screenshots dir (config): {}", err),
+    };
+    assert_eq!(result, PathBuf::from("/config/path"));
+}
+
+#[test]
+fn test_get_screenshots_dir_with_tilde() {
+    let mut config = crate::config::Config::default();
+    config.paths.screenshots_dir = "~/Screenshots".to_string();
+
+    let result = match crate::config::get_screenshots_dir(None, &config, false) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to resolve screenshots dir (tilde): {}", err),
+    };
+    let home = match dirs::home_dir() {
+        Some(v) => v,
+        None => panic!("Failed to resolve home directory"),
+    };
+    assert_eq!(result, home.join("Screenshots"));
+}
+
+#[test]
+fn test_generate_hyprland_binds() {
+    let config = crate::config::Config::default();
+    let binds = config.generate_hyprland_binds();
+
+    assert!(binds.contains("# hyprshot-rs keybindings"));
+    assert!(binds.contains("# Generated by: hyprshot-rs --generate-hyprland-config"));
+
+    assert!(binds.contains("bind = SUPER, Print, exec, hyprshot-rs -m window"));
+    assert!(binds.contains("bind = SUPER SHIFT, Print, exec, hyprshot-rs -m region"));
+    assert!(binds.contains("bind = SUPER CTRL, Print, exec, hyprshot-rs -m output"));
+    assert!(binds.contains("bind = , Print, exec, hyprshot-rs -m active -m output"));
+
+    assert!(!binds.contains("--clipboard-only"));
+}
+
+#[test]
+fn test_generate_hyprland_binds_with_clipboard() {
+    let config = crate::config::Config::default();
+    let binds = config.generate_hyprland_binds_with_clipboard();
+
+    assert!(binds.contains("bind = SUPER, Print, exec, hyprshot-rs -m window"));
+    assert!(binds.contains("bind = SUPER SHIFT, Print, exec, hyprshot-rs -m region"));
+
+    assert!(binds.contains("# Screenshot to clipboard (no file saved)"));
+    assert!(
+        binds.contains("bind = SUPER ALT, Print, exec, hyprshot-rs -m window --clipboard-only")
+    );
+    assert!(
+        binds.contains(
+            "bind = SUPER SHIFT ALT, Print, exec, hyprshot-rs -m region --clipboard-only"
+        )
+    );
+    assert!(
+        binds
+            .contains("bind = SUPER CTRL ALT, Print, exec, hyprshot-rs -m output --clipboard-only")
+    );
+}
+
+#[test]
+fn hyprland_backup_path_is_timestamped() {
+    let conf = PathBuf::from("/home/user/.config/hypr/hyprland.conf");
+    let first = crate::config::Config::hyprland_backup_path(&conf, 1_700_000_000);
+    let second = crate::config::Config::hyprland_backup_path(&conf, 1_700_000_060);
+    assert_eq!(
+        first,
+        PathBuf::from("/home/user/.config/hypr/hyprland.conf.backup.1700000000")
+    );
+    assert_ne!(first, second);
+}
+
+#[test]
+fn latest_hyprland_backup_prefers_newest_timestamp() {
+    let dir = test_output_dir("backups");
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        panic!("Failed to create test dir: {}", err);
+    }
+    let conf = dir.join("hyprland.conf");
+    for name in [
+        "hyprland.conf",
+        "hyprland.conf.backup.1700000000",
+        "hyprland.conf.backup.1700000100",
+        "hyprland.conf.backup.notanumber",
+        "other.conf.backup.1800000000",
+    ] {
+        if let Err(err) = std::fs::write(dir.join(name), name) {
+            panic!("Failed to write {}: {}", name, err);
+        }
+    }
+
+    let latest = match crate::hyprland_cmds::latest_hyprland_backup(&conf) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to find latest backup: {}", err),
+    };
+    assert_eq!(latest, Some(dir.join("hyprland.conf.backup.1700000100")));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn latest_hyprland_backup_none_without_backups() {
+    let dir = test_output_dir("no-backups");
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        panic!("Failed to create test dir: {}", err);
+    }
+    let conf = dir.join("hyprland.conf");
+
+    let latest = match crate::hyprland_cmds::latest_hyprland_backup(&conf) {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to scan backups: {}", err),
+    };
+    assert_eq!(latest, None);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn hotkey_conflicts_detects_duplicates() {
+    let mut config = crate::config::Config::default();
+    assert!(config.hotkey_conflicts(true).is_empty());
+
+    config.hotkeys.region = "super, print".to_string();
+    let conflicts = config.hotkey_conflicts(false);
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].0, "SUPER, PRINT");
+    assert_eq!(conflicts[0].1, vec!["window", "region"]);
+}
+
+#[test]
+fn hotkey_conflicts_ignore_modifier_order_and_check_clipboard_variants() {
+    let mut config = crate::config::Config::default();
+    config.hotkeys.output = "ALT SUPER, Print".to_string();
+
+    assert!(config.hotkey_conflicts(false).is_empty());
+
+    let conflicts = config.hotkey_conflicts(true);
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].0, "ALT SUPER, PRINT");
+    assert_eq!(
+        conflicts[0].1,
+        vec!["output", "window (clipboard)", "output (clipboard)"]
+    );
+}
+
+#[test]
+fn parse_layer_mode() {
+    let args = Args::parse_from(["hyprshot-rs", "-m", "layer"]);
+    assert!(matches!(args.mode.first(), Some(Mode::Layer)));
+}
+
+#[test]
+fn hyprland_layer_boxes_lists_surfaces() {
+    let layers = serde_json::json!({
+        "DP-1": {
+            "levels": {
+                "0": [],
+                "2": [
+                    {"x": 0, "y": 0, "w": 1920, "h": 30, "namespace": "waybar"},
+                    {"x": 0, "y": 0, "w": 0, "h": 0, "namespace": "empty"}
+                ],
+                "3": [
+                    {"x": 0, "y": 0, "w": 1920, "h": 1080, "namespace": "hyprshot-freeze"}
+                ]
+            }
+        },
+        "HDMI-A-1": {
+            "levels": {
+                "2": [{"x": 1920, "y": 1050, "w": 1280, "h": 30, "namespace": "panel"}]
+            }
+        }
+    });
+    let mut boxes = crate::compositor::hyprland_layer_boxes(&layers);
+    boxes.sort();
+    assert_eq!(boxes, vec!["0,0 1920x30 waybar", "1920,1050 1280x30 panel"]);
+}
+
+#[test]
+fn compositor_detected_from_ipc_socket_variables() {
+    use crate::compositor::{CompositorKind, detect_kind};
+
+    let with = |set: &'static [&'static str]| move |name: &str| set.contains(&name);
+    assert_eq!(
+        detect_kind(with(&["HYPRLAND_INSTANCE_SIGNATURE", "SWAYSOCK"])),
+        CompositorKind::Hyprland
+    );
+    assert_eq!(detect_kind(with(&["SWAYSOCK"])), CompositorKind::Sway);
+    assert_eq!(detect_kind(with(&["I3SOCK"])), CompositorKind::Sway);
+    assert_eq!(
+        detect_kind(with(&["WAYLAND_DISPLAY"])),
+        CompositorKind::Generic
+    );
+}
+
+#[test]
+fn hyprland_ipc_finds_socket_and_rejects_text_replies() {
+    use crate::hyprland_ipc::{parse_reply, socket_candidates};
+    use std::ffi::OsStr;
+    use std::path::PathBuf;
+
+    assert_eq!(
+        socket_candidates(Some(OsStr::new("/run/user/1000")), OsStr::new("abc_123")),
+        vec![
+            PathBuf::from("/run/user/1000/hypr/abc_123/.socket.sock"),
+            PathBuf::from("/tmp/hypr/abc_123/.socket.sock"),
+        ]
+    );
+    assert_eq!(
+        socket_candidates(Some(OsStr::new("")), OsStr::new("abc_123")),
+        vec![PathBuf::from("/tmp/hypr/abc_123/.socket.sock")]
+    );
+
+    match parse_reply("clients", br#"[{"title": "kitty"}]"#) {
+        Ok(value) => assert_eq!(value[0]["title"], "kitty"),
+        Err(err) => panic!("expected JSON reply, got error: {err}"),
+    }
+    match parse_reply("bogus", b"unknown request\n") {
+        Ok(value) => panic!("expected error, got {value}"),
+        Err(err) => assert_eq!(
+            err.to_string(),
+            "Hyprland bogus reply is not JSON: unknown request"
+        ),
+    }
+}
+
+#[test]
+fn sway_ipc_messages_round_trip() {
+    use crate::sway_ipc::{MessageType, SwayIpcError, encode_message, read_reply};
+    use std::io::Cursor;
+
+    let request = encode_message(MessageType::Tree, b"");
+    assert_eq!(&request[..6], b"i3-ipc");
+    assert_eq!(request.len(), 14);
+
+    // A reply has the same header shape as a request.
+    let reply = encode_message(MessageType::Tree, br#"{"type":"root"}"#);
+    match read_reply(&mut Cursor::new(reply), MessageType::Tree) {
+        Ok(payload) => assert_eq!(payload, br#"{"type":"root"}"#),
+        Err(err) => panic!("expected a payload, got {}", err),
+    }
+
+    let wrong_type = encode_message(MessageType::Outputs, b"[]");
+    match read_reply(&mut Cursor::new(wrong_type), MessageType::Tree) {
+        Err(SwayIpcError::Protocol(message)) => assert!(message.contains("get_tree")),
+        other => panic!("expected a protocol error, got {:?}", other),
+    }
+
+    let mut bad_magic = encode_message(MessageType::Tree, b"{}");
+    bad_magic[0] = b'x';
+    assert!(matches!(
+        read_reply(&mut Cursor::new(bad_magic), MessageType::Tree),
+        Err(SwayIpcError::Protocol(_))
+    ));
+
+    let mut truncated = encode_message(MessageType::Tree, b"{}");
+    truncated.pop();
+    assert!(matches!(
+        read_reply(&mut Cursor::new(truncated), MessageType::Tree),
+        Err(SwayIpcError::Io(_))
+    ));
+}
+
+#[test]
+fn window_filter_picks_the_single_match() {
+    use crate::capture::{WindowFilter, match_window};
+    use crate::compositor::Window;
+    use crate::geometry::Geometry;
+
+    let window = |x: i32, title: &str, class: &str| Window {
+        geometry: match Geometry::new(x, 0, 100, 100) {
+            Ok(geometry) => geometry,
+            Err(err) => panic!("invalid geometry: {}", err),
+        },
+        title: title.to_string(),
+        class: class.to_string(),
+        address: None,
+        id: None,
+        pid: None,
+        workspace: None,
+    };
+    let windows = vec![
+        window(0, "Mozilla Firefox", "firefox"),
+        window(100, "~/src - nvim", "foot"),
+        window(200, "htop", "foot"),
+    ];
+    let regex = |pattern: &str| match regex::Regex::new(pattern) {
+        Ok(regex) => Some(regex),
+        Err(err) => panic!("invalid regex: {}", err),
+    };
+
+    let by_title = WindowFilter {
+        title: regex("nvim$"),
+        class: None,
+        ..WindowFilter::default()
+    };
+    match match_window(&windows, &by_title) {
+        Ok(found) => assert_eq!(found.geometry.x, 100),
+        Err(err) => panic!("expected a match, got {:#}", err),
+    }
+
+    let by_class = WindowFilter {
+        title: None,
+        class: Some("FIREFOX".to_string()),
+        ..WindowFilter::default()
+    };
+    match match_window(&windows, &by_class) {
+        Ok(found) => assert_eq!(found.title, "Mozilla Firefox"),
+        Err(err) => panic!("expected a match, got {:#}", err),
+    }
+
+    let both = WindowFilter {
+        title: regex("^htop"),
+        class: Some("foot".to_string()),
+        ..WindowFilter::default()
+    };
+    match match_window(&windows, &both) {
+        Ok(found) => assert_eq!(found.geometry.x, 200),
+        Err(err) => panic!("expected a match, got {:#}", err),
+    }
+
+    let ambiguous = WindowFilter {
+        title: None,
+        class: Some("foot".to_string()),
+        ..WindowFilter::default()
+    };
+    match match_window(&windows, &ambiguous) {
+        Ok(found) => panic!("expected an ambiguity error, got {:?}", found),
+        Err(err) => {
+            let message = format!("{:#}", err);
+            assert!(message.starts_with("2 windows match"), "{message}");
+            assert!(
+                message.contains("nvim") && message.contains("htop"),
+                "{message}"
+            );
+            assert!(!message.contains("Firefox"), "{message}");
+        }
+    }
+
+    let missing = WindowFilter {
+        title: regex("gimp"),
+        class: None,
+        ..WindowFilter::default()
+    };
+    match match_window(&windows, &missing) {
+        Ok(found) => panic!("expected no match, got {:?}", found),
+        Err(err) => assert!(format!("{:#}", err).contains("Mozilla Firefox")),
+    }
+}
+
+#[test]
+fn window_filter_matches_hyprland_address_and_pid() {
+    use crate::capture::{WindowFilter, match_window};
+
+    let monitors = serde_json::json!([{"name": "DP-1", "activeWorkspace": {"id": 1}}]);
+    let clients = serde_json::json!([
+        {"address": "0x55f0a1b2c3d0", "pid": 4242, "class": "foot", "title": "a",
+         "at": [0, 0], "size": [100, 100], "workspace": {"id": 1}},
+        {"address": "0x55f0a1b2c4e0", "pid": 4242, "class": "foot", "title": "b",
+         "at": [100, 0], "size": [100, 100], "workspace": {"id": 1}},
+        {"address": "0x55f0a1b2c5f0", "pid": -1, "class": "xwayland", "title": "c",
+         "at": [200, 0], "size": [100, 100], "workspace": {"id": 1}}
+    ]);
+    let windows = crate::compositor::hyprland_windows(&monitors, &clients);
+    assert_eq!(windows[0].pid, Some(4242));
+    assert_eq!(windows[2].pid, None);
+
+    let by_address = WindowFilter {
+        address: Some("55F0A1B2C4E0".to_string()),
+        ..WindowFilter::default()
+    };
+    match match_window(&windows, &by_address) {
+        Ok(found) => assert_eq!(found.title, "b"),
+        Err(err) => panic!("expected a match, got {:#}", err),
+    }
+
+    // Two windows of one process: the pid alone is ambiguous.
+    let by_pid = WindowFilter {
+        pid: Some(4242),
+        ..WindowFilter::default()
+    };
+    match match_window(&windows, &by_pid) {
+        Ok(found) => panic!("expected an ambiguity error, got {:?}", found),
+        Err(err) => assert!(format!("{:#}", err).contains("0x55f0a1b2c3d0")),
+    }
+}
+
+#[test]
+fn window_filter_flags_parse() {
+    let args = Args::parse_from([
+        "hyprshot-rs",
+        "--window-title",
+        "^Mozilla",
+        "--window-class",
+        "firefox",
+    ]);
+    match &args.window_title {
+        Some(title) => assert!(title.is_match("Mozilla Firefox")),
+        None => panic!("expected --window-title to be parsed"),
+    }
+    assert_eq!(args.window_class.as_deref(), Some("firefox"));
+
+    let args = Args::parse_from(["hyprshot-rs", "--address", "0x55f0a1b2c3d0", "--pid", "42"]);
+    assert_eq!(args.address.as_deref(), Some("0x55f0a1b2c3d0"));
+    assert_eq!(args.pid, Some(42));
+
+    assert!(Args::try_parse_from(["hyprshot-rs", "--window-title", "(unclosed"]).is_err());
+}
+
+/// Window lists as the selector boxes they are offered as.
+fn hyprland_client_boxes(monitors: &serde_json::Value, clients: &serde_json::Value) -> Vec<String> {
+    crate::compositor::hyprland_windows(monitors, clients)
+        .iter()
+        .map(crate::compositor::Window::selector_box)
+        .collect()
+}
+
+fn sway_window_boxes(
+    workspaces: &serde_json::Value,
+    tree: &serde_json::Value,
+    client_area: bool,
+) -> Vec<String> {
+    crate::compositor::sway_windows(workspaces, tree, client_area)
+        .iter()
+        .map(crate::compositor::Window::selector_box)
+        .collect()
+}
+
+#[test]
+fn hyprland_client_boxes_keep_active_workspaces_only() {
+    let monitors = serde_json::json!([
+        {"name": "DP-1", "activeWorkspace": {"id": 2, "name": "2"}},
+        {"name": "HDMI-A-1", "activeWorkspace": {"id": -98, "name": "special:scratch"}}
+    ]);
+    let clients = serde_json::json!([
+        {"at": [10, 20], "size": [800, 600], "workspace": {"id": 2}, "title": "kitty"},
+        {"at": [0, 0], "size": [640, 480], "workspace": {"id": 12}, "title": "firefox"},
+        {"at": [-1280, -40], "size": [1280, 720], "workspace": {"id": -98}, "title": "notes"},
+        {"at": [5, 5], "size": [0, 300], "workspace": {"id": 2}, "title": "hidden"},
+        {"at": [5], "size": [300, 300], "workspace": {"id": 2}, "title": "broken"},
+        {"at": [7, 7], "size": [100, 100], "workspace": {"id": 2}}
+    ]);
+    let boxes = hyprland_client_boxes(&monitors, &clients);
+    assert_eq!(
+        boxes,
+        vec![
+            "10,20 800x600 kitty",
+            "-1280,-40 1280x720 notes",
+            "7,7 100x100 ",
+        ]
+    );
+
+    let not_arrays = hyprland_client_boxes(&serde_json::json!({}), &serde_json::json!(null));
+    assert!(not_arrays.is_empty());
+}
+
+#[test]
+fn hyprland_active_window_geometry_parses_and_rejects() {
+    use crate::compositor::hyprland_active_window_geometry;
+    use crate::geometry::Geometry;
+
+    let active = serde_json::json!({"at": [-1920, 30], "size": [1280, 1050]});
+    match hyprland_active_window_geometry(&active) {
+        Ok(geometry) => assert_eq!(
+            geometry,
+            Geometry {
+                x: -1920,
+                y: 30,
+                width: 1280,
+                height: 1050
+            }
+        ),
+        Err(err) => panic!("expected geometry, got error: {err}"),
+    }
+
+    for bad in [
+        serde_json::json!({}),
+        serde_json::json!({"at": [0, 0], "size": [0, 100]}),
+        serde_json::json!({"at": [0], "size": [100, 100]}),
+        serde_json::json!({"at": ["0", "0"], "size": [100, 100]}),
+    ] {
+        if hyprland_active_window_geometry(&bad).is_ok() {
+            panic!("expected error for {bad}");
+        }
+    }
+}
+
+#[test]
+fn sway_client_area_drops_decorations() {
+    use crate::compositor::sway_focused_window_geometry;
+    use crate::geometry::Geometry;
+
+    // Title bar of 25px and 2px borders; window_rect is relative to rect.
+    let tree = serde_json::json!({
+        "type": "root",
+        "nodes": [{
+            "type": "workspace",
+            "name": "1",
+            "nodes": [
+                {"type": "con", "app_id": "foot", "name": "foot", "pid": 1, "focused": true,
+                 "rect": {"x": -1920, "y": 0, "width": 960, "height": 1080},
+                 "window_rect": {"x": 2, "y": 25, "width": 956, "height": 1053},
+                 "deco_rect": {"x": 0, "y": 0, "width": 960, "height": 25}, "nodes": []},
+                {"type": "con", "app_id": "kitty", "name": "no-window-rect", "pid": 2,
+                 "rect": {"x": -960, "y": 0, "width": 960, "height": 1080}, "nodes": []}
+            ]
+        }]
+    });
+    let workspaces = serde_json::json!([{"name": "1", "visible": true}]);
+
+    assert_eq!(
+        sway_window_boxes(&workspaces, &tree, true),
+        vec!["-1918,25 956x1053 foot", "-960,0 960x1080 no-window-rect"]
+    );
+    assert_eq!(
+        sway_window_boxes(&workspaces, &tree, false)[0],
+        "-1920,0 960x1080 foot"
+    );
+    match sway_focused_window_geometry(&tree, true) {
+        Ok(geometry) => assert_eq!(
+            geometry,
+            Geometry {
+                x: -1918,
+                y: 25,
+                width: 956,
+                height: 1053
+            }
+        ),
+        Err(err) => panic!("expected geometry, got error: {err}"),
+    }
+}
+
+fn sway_tree_fixture() -> serde_json::Value {
+    serde_json::json!({
+        "type": "root",
+        "nodes": [{
+            "type": "output",
+            "name": "DP-1",
+            "nodes": [
+                {
+                    "type": "workspace",
+                    "name": "2",
+                    "nodes": [{
+                        "type": "con",
+                        "nodes": [
+                            {"type": "con", "app_id": "kitty", "name": "term", "pid": 1, "focused": true,
+                             "rect": {"x": 0, "y": 0, "width": 960, "height": 1080}, "nodes": []},
+                            {"type": "con", "app_id": "foot", "name": "foot", "pid": 2, "focused": false,
+                             "rect": {"x": 960, "y": 0, "width": 960, "height": 1080}, "nodes": []}
+                        ]
+                    }],
+                    "floating_nodes": [
+                        {"type": "floating_con", "app_id": "pavucontrol", "name": "Volume", "pid": 3,
+                         "rect": {"x": -200, "y": -100, "width": 400, "height": 300}, "nodes": []}
+                    ]
+                },
+                {
+                    "type": "workspace",
+                    "name": "12",
+                    "nodes": [
+                        {"type": "con", "app_id": "firefox", "name": "web", "pid": 4,
+                         "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080}, "nodes": []}
+                    ]
+                }
+            ]
+        }]
+    })
+}
+
+#[test]
+fn sway_window_boxes_follow_visible_workspaces() {
+    let workspaces = serde_json::json!([
+        {"name": "2", "visible": true},
+        {"name": "12", "visible": false}
+    ]);
+    let boxes = sway_window_boxes(&workspaces, &sway_tree_fixture(), false);
+    assert_eq!(boxes.len(), 3, "{boxes:?}");
+    assert!(boxes.iter().all(|b| !b.contains("firefox")), "{boxes:?}");
+    assert!(
+        boxes.iter().any(|b| b.starts_with("-200,-100 400x300")),
+        "{boxes:?}"
+    );
+
+    let none_visible = serde_json::json!([{"name": "2", "visible": false}]);
+    assert!(sway_window_boxes(&none_visible, &sway_tree_fixture(), false).is_empty());
+}
+
+#[test]
+fn sway_window_boxes_handle_scratchpad_and_background_tabs() {
+    let tree = serde_json::json!({
+        "type": "root",
+        "nodes": [
+            {
+                "type": "output",
+                "name": "__i3",
+                "nodes": [{
+                    "type": "workspace",
+                    "name": "__i3_scratch",
+                    "nodes": [],
+                    "floating_nodes": [
+                        {"type": "floating_con", "app_id": "keepassxc", "name": "hidden", "pid": 5,
+                         "scratchpad_state": "fresh", "visible": false,
+                         "rect": {"x": 0, "y": 0, "width": 600, "height": 400}, "nodes": []}
+                    ]
+                }]
+            },
+            {
+                "type": "output",
+                "name": "DP-1",
+                "nodes": [{
+                    "type": "workspace",
+                    "name": "1",
+                    "nodes": [{
+                        "type": "con",
+                        "layout": "tabbed",
+                        "nodes": [
+                            {"type": "con", "app_id": "kitty", "name": "front", "pid": 1, "visible": true,
+                             "rect": {"x": 0, "y": 30, "width": 1920, "height": 1050}, "nodes": []},
+                            {"type": "con", "app_id": "kitty", "name": "back", "pid": 2, "visible": false,
+                             "rect": {"x": 0, "y": 30, "width": 1920, "height": 1050}, "nodes": []}
+                        ]
+                    }],
+                    "floating_nodes": [
+                        {"type": "floating_con", "app_id": "pavucontrol", "name": "shown", "pid": 3,
+                         "scratchpad_state": "changed", "visible": true,
+                         "rect": {"x": 660, "y": 290, "width": 600, "height": 500}, "nodes": []}
+                    ]
+                }]
+            }
+        ]
+    });
+    // A workspace list that (wrongly) reports the scratchpad must not expose it.
+    let workspaces = serde_json::json!([
+        {"name": "1", "visible": true},
+        {"name": "__i3_scratch", "visible": true}
+    ]);
+    let boxes = sway_window_boxes(&workspaces, &tree, false);
+    assert_eq!(boxes.len(), 2, "{boxes:?}");
+    assert!(boxes.iter().any(|b| b.contains("front")), "{boxes:?}");
+    assert!(boxes.iter().any(|b| b.contains("shown")), "{boxes:?}");
+}
+
+#[test]
+fn sway_focused_window_geometry_finds_focused_node() {
+    use crate::compositor::sway_focused_window_geometry;
+    use crate::geometry::Geometry;
+
+    match sway_focused_window_geometry(&sway_tree_fixture(), false) {
+        Ok(geometry) => assert_eq!(
+            geometry,
+            Geometry {
+                x: 0,
+                y: 0,
+                width: 960,
+                height: 1080
+            }
+        ),
+        Err(err) => panic!("expected geometry, got error: {err}"),
+    }
+
+    let unfocused = serde_json::json!({"type": "root", "nodes": []});
+    if sway_focused_window_geometry(&unfocused, false).is_ok() {
+        panic!("expected error without a focused window");
+    }
+}
+
+#[cfg(feature = "freeze")]
+#[test]
+fn rotated_outputs_map_buffers_to_logical_orientation() {
+    use crate::backend::CapturedImage;
+    use crate::capture::mode_logical_size;
+    use crate::shm::untransform;
+    use wayland_client::protocol::wl_output::Transform;
+
+    // A 3x2 buffer whose red channel numbers its pixels row by row.
+    let buffer = || CapturedImage {
+        data: (0..6u8).flat_map(|i| [i, 0, 0, 255]).collect(),
+        width: 3,
+        height: 2,
+    };
+    let shown = |transform| {
+        let image = untransform(buffer(), transform);
+        let reds: Vec<u8> = image.data.chunks_exact(4).map(|px| px[0]).collect();
+        (image.width, image.height, reds)
+    };
+    assert_eq!(shown(Transform::Normal), (3, 2, vec![0, 1, 2, 3, 4, 5]));
+    assert_eq!(shown(Transform::_90), (2, 3, vec![3, 0, 4, 1, 5, 2]));
+    assert_eq!(shown(Transform::_180), (3, 2, vec![5, 4, 3, 2, 1, 0]));
+    assert_eq!(shown(Transform::_270), (2, 3, vec![2, 5, 1, 4, 0, 3]));
+    assert_eq!(shown(Transform::Flipped), (3, 2, vec![2, 1, 0, 5, 4, 3]));
+    assert_eq!(shown(Transform::Flipped90), (2, 3, vec![0, 3, 1, 4, 2, 5]));
+    assert_eq!(shown(Transform::Flipped180), (3, 2, vec![3, 4, 5, 0, 1, 2]));
+    assert_eq!(shown(Transform::Flipped270), (2, 3, vec![5, 2, 4, 1, 3, 0]));
+
+    // Without xdg-output the logical size comes from the sideways mode.
+    assert_eq!(mode_logical_size((2560, 1440), 2, true), (720, 1280));
+    assert_eq!(mode_logical_size((2560, 1440), 2, false), (1280, 720));
+}
+
+#[cfg(feature = "grim")]
+#[test]
+fn encoded_pngs_are_marked_srgb() {
+    let image = crate::backend::CapturedImage {
+        data: vec![1, 2, 3, 255, 4, 5, 6, 128],
+        width: 2,
+        height: 1,
+    };
+    let tagged = match crate::png_encoder::encode(&image, 6) {
+        Ok(png) => png,
+        Err(err) => panic!("encoding the test PNG failed: {err}"),
+    };
+    match png::Decoder::new(tagged.as_slice()).read_info() {
+        Ok(reader) => assert_eq!(
+            reader.info().srgb,
+            Some(png::SrgbRenderingIntent::Perceptual)
+        ),
+        Err(err) => panic!("tagged PNG doesn't decode: {err}"),
+    }
+    match crate::editor::decode_png(&tagged) {
+        Ok(decoded) => assert_eq!(decoded.data, image.data),
+        Err(err) => panic!("tagged PNG pixels don't decode: {err}"),
+    }
+    assert_eq!(
+        crate::backend::insert_png_chunks(b"MOCKPNG".to_vec(), b"chunk"),
+        b"MOCKPNG".to_vec()
+    );
+}
+
+#[test]
+fn encoded_jpegs_carry_an_srgb_profile() {
+    let image = crate::backend::CapturedImage {
+        data: vec![1, 2, 3, 255, 4, 5, 6, 128],
+        width: 2,
+        height: 1,
+    };
+    let options = crate::save::SaveOptions {
+        format: crate::config::ImageFormat::Jpeg,
+        quality: 90,
+        ..Default::default()
+    };
+    let geometry = geometry_or_panic(0, 0, 2, 1);
+    let jpeg = match crate::save::encode(&MockBackend::new(), &image, &options, &geometry, None) {
+        Ok(jpeg) => jpeg,
+        Err(err) => panic!("encoding the test JPEG failed: {err}"),
+    };
+
+    // APP2 segment: marker, length, "ICC_PROFILE\0", chunk 1 of 1, profile.
+    let marker = b"ICC_PROFILE\0\x01\x01";
+    let Some(start) = jpeg.windows(marker.len()).position(|w| w == marker) else {
+        panic!("no ICC_PROFILE segment in the JPEG");
+    };
+    assert_eq!(&jpeg[start - 4..start - 2], &[0xff, 0xe2]);
+    let length = usize::from(u16::from_be_bytes([jpeg[start - 2], jpeg[start - 1]]));
+    let profile = &jpeg[start + marker.len()..start - 2 + length];
+    assert_eq!(profile, crate::icc::srgb_profile().as_slice());
+
+    assert_eq!(
+        u32::from_be_bytes([profile[0], profile[1], profile[2], profile[3]]) as usize,
+        profile.len()
+    );
+    assert_eq!(&profile[12..24], b"mntrRGB XYZ ");
+    assert_eq!(&profile[36..40], b"acsp");
+    let tags = u32::from_be_bytes([profile[128], profile[129], profile[130], profile[131]]);
+    let signatures: Vec<&[u8]> = (0..tags as usize)
+        .map(|i| &profile[132 + 12 * i..][..4])
+        .collect();
+    for required in [
+        b"desc", b"cprt", b"wtpt", b"rXYZ", b"gXYZ", b"bXYZ", b"rTRC", b"gTRC", b"bTRC",
+    ] {
+        assert!(signatures.contains(&required.as_slice()), "{:?}", required);
+    }
+}
+
+#[test]
+fn png_compression_levels_and_optimize_round_trip() {
+    use crate::config::PngCompression;
+
+    let config: crate::config::Config = match toml::from_str("[capture]\npng_compression = 3\n") {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to deserialize config: {}", err),
+    };
+    assert_eq!(config.capture.png_compression, PngCompression::Level(3));
+    assert!(toml::from_str::<crate::config::Config>("[capture]\npng_compression = 10\n").is_err());
+    match toml::to_string(&config) {
+        Ok(written) => assert!(written.contains("png_compression = 3")),
+        Err(err) => panic!("Failed to serialize config: {}", err),
+    }
+    assert_eq!(
+        "Best".parse::<PngCompression>().ok(),
+        Some(PngCompression::Best)
+    );
+    assert_eq!(
+        "0".parse::<PngCompression>().ok(),
+        Some(PngCompression::Level(0))
+    );
+    assert!("fastest".parse::<PngCompression>().is_err());
+    let args = Args::parse_from(["hyprshot-rs", "--png-compression", "9", "--optimize"]);
+    assert_eq!(args.png_compression, Some(PngCompression::Level(9)));
+    assert!(args.optimize);
+
+    let (width, height) = (37u32, 23u32);
+    let image = |pixel: &dyn Fn(u32, u32) -> [u8; 4]| crate::backend::CapturedImage {
+        data: (0..height)
+            .flat_map(|y| (0..width).flat_map(move |x| pixel(x, y)))
+            .collect(),
+        width,
+        height,
+    };
+    let images = [
+        // Two colors with transparency: a 1-bit palette with tRNS.
+        image(&|x, y| {
+            if (x + y) % 3 == 0 {
+                [200, 10, 10, 255]
+            } else {
+                [0, 0, 0, 0]
+            }
+        }),
+        // Ten colors: a 4-bit palette.
+        image(&|x, _| [(x % 10) as u8 * 20, 50, 90, 255]),
+        // Opaque gray ramp: 8-bit grayscale.
+        image(&|x, y| {
+            let v = (x * 7 + y) as u8;
+            [v, v, v, 255]
+        }),
+        // Many opaque colors: RGB without alpha.
+        image(&|x, y| [(x * 7) as u8, (y * 11) as u8, (x * y) as u8, 255]),
+        // Many translucent colors: RGBA stays.
+        image(&|x, y| {
+            [
+                (x * 7) as u8,
+                (y * 11) as u8,
+                (x ^ y) as u8,
+                (x * 5 + y) as u8,
+            ]
+        }),
+    ];
+    for image in &images {
+        for level in [0, 1, 9] {
+            match crate::png_encoder::encode(image, level)
+                .and_then(|png| crate::editor::decode_png(&png))
+            {
+                Ok(decoded) => assert_eq!(decoded.data, image.data),
+                Err(err) => panic!("level {level} PNG doesn't round-trip: {err}"),
+            }
+        }
+        let (plain, optimized) = match (
+            crate::png_encoder::encode(image, 9),
+            crate::png_encoder::optimize(image),
+        ) {
+            (Ok(plain), Ok(optimized)) => (plain, optimized),
+            (Err(err), _) | (_, Err(err)) => panic!("encoding failed: {err}"),
+        };
+        assert!(optimized.len() <= plain.len());
+        match crate::editor::decode_png(&optimized) {
+            Ok(decoded) => assert_eq!(decoded.data, image.data),
+            Err(err) => panic!("optimized PNG doesn't round-trip: {err}"),
+        }
+    }
+}
+
+#[test]
+fn metadata_levels_embed_text_chunks_and_exif() {
+    use crate::config::MetadataLevel;
+    use crate::metadata::Metadata;
+    use chrono::TimeZone;
+
+    let time = match chrono::Local.with_ymd_and_hms(2026, 3, 4, 5, 6, 7) {
+        chrono::LocalResult::Single(time) => time,
+        _ => panic!("test time is ambiguous"),
+    };
+    assert!(Metadata::new(MetadataLevel::None, time).is_none());
+    let geometry = geometry_or_panic(10, 20, 300, 200);
+    let Some(basic) = Metadata::new(MetadataLevel::Basic, time) else {
+        panic!("basic metadata should be embedded");
+    };
+    let Some(mut full) = Metadata::new(MetadataLevel::Full, time) else {
+        panic!("full metadata should be embedded");
+    };
+    full.title = Some("Résumé — Firefox".to_string());
+    full.class = Some("firefox".to_string());
+    full.workspace = Some("2".to_string());
+    assert_eq!(basic.entries(&geometry, Some("DP-1")).len(), 2);
+
+    let mut plain = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut plain, 1, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        let written = encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&[1, 2, 3, 255]));
+        if let Err(err) = written {
+            panic!("encoding the test PNG failed: {err}");
+        }
+    }
+    let tagged = full.embed_png(plain, &geometry, Some("DP-1"));
+    match png::Decoder::new(tagged.as_slice()).read_info() {
+        Ok(reader) => {
+            let info = reader.info();
+            let latin1: Vec<(&str, &str)> = info
+                .uncompressed_latin1_text
+                .iter()
+                .map(|chunk| (chunk.keyword.as_str(), chunk.text.as_str()))
+                .collect();
+            assert!(latin1.contains(&("Window Class", "firefox")));
+            assert!(latin1.contains(&("Output", "DP-1")));
+            assert!(latin1.contains(&("Geometry", "10,20 300x200")));
+            assert!(latin1.contains(&("Workspace", "2")));
+            assert!(
+                latin1
+                    .iter()
+                    .any(|(key, text)| *key == "Software" && text.starts_with("hyprshot-rs "))
+            );
+            match info.utf8_text.first().map(|chunk| chunk.get_text()) {
+                Some(Ok(title)) => assert_eq!(title, "Résumé — Firefox"),
+                other => panic!("window title should be an iTXt chunk, got {other:?}"),
+            }
+        }
+        Err(err) => panic!("PNG with metadata doesn't decode: {err}"),
+    }
+
+    let exif = full.exif(&geometry, Some("DP-1"));
+    assert!(exif.starts_with(b"Exif\0\0II*\0"));
+    let tiff = &exif[6..];
+    assert_eq!(u16::from_le_bytes([tiff[8], tiff[9]]), 3);
+    let find = |needle: &[u8]| tiff.windows(needle.len()).any(|window| window == needle);
+    assert!(find(b"2026:03:04 05:06:07\0"));
+    assert!(find(
+        "Window Title: Résumé — Firefox; Window Class: firefox".as_bytes()
+    ));
+}
+
+#[cfg(feature = "freeze")]
+#[test]
+fn shm_formats_convert_to_rgba() {
+    use wayland_client::protocol::wl_shm::Format;
+
+    // Little-endian ARGB8888 is stored as B, G, R, A.
+    let buffer = [0x10, 0x20, 0x30, 0x40];
+    assert_eq!(
+        crate::shm::to_rgba(&buffer, Format::Argb8888),
+        vec![0x30, 0x20, 0x10, 0x40]
+    );
+    assert_eq!(
+        crate::shm::to_rgba(&buffer, Format::Xrgb8888),
+        vec![0x30, 0x20, 0x10, 0xff]
+    );
+    assert_eq!(
+        crate::shm::to_rgba(&buffer, Format::Abgr8888),
+        vec![0x10, 0x20, 0x30, 0x40]
+    );
+
+    // 10-bit channels round to 8 bits: 2:10:10:10 with 1023, 512 and 0.
+    let opaque = (0b11u32 << 30 | 1023 << 20 | 512 << 10).to_le_bytes();
+    assert_eq!(
+        crate::shm::to_rgba(&opaque, Format::Xrgb2101010),
+        vec![255, 128, 0, 0xff]
+    );
+    assert_eq!(
+        crate::shm::to_rgba(&opaque, Format::Abgr2101010),
+        vec![0, 128, 255, 0xff]
+    );
+    let clear = (1023u32 << 20).to_le_bytes();
+    assert_eq!(
+        crate::shm::to_rgba(&clear, Format::Argb2101010),
+        vec![255, 0, 0, 0]
+    );
+    assert!(crate::shm::is_deep_color(Format::Xbgr2101010));
+    assert!(!crate::shm::is_deep_color(Format::Xrgb8888));
+}
+
+#[cfg(feature = "dmabuf")]
+#[test]
+fn dmabuf_formats_map_to_shm_layouts() {
+    use crate::dmabuf::{LINEAR, device_id, modifiers, pick_format, shm_format};
+    use wayland_client::protocol::wl_shm::Format;
+
+    const XR24: u32 = 0x3432_5258;
+    const AR24: u32 = 0x3432_5241;
+    const AB24: u32 = 0x3432_4241;
+    const NV12: u32 = 0x3231_564e;
+
+    assert_eq!(device_id(&0xe280u64.to_ne_bytes()), Some(0xe280));
+    assert_eq!(device_id(&[1, 2, 3]), None);
+    let tiled = 0x0100_0000_0000_0001u64;
+    let bytes: Vec<u8> = [tiled, LINEAR]
+        .iter()
+        .flat_map(|m| m.to_ne_bytes())
+        .collect();
+    assert_eq!(modifiers(&bytes), vec![tiled, LINEAR]);
+
+    assert_eq!(shm_format(AR24), Some(Format::Argb8888));
+    assert_eq!(shm_format(XR24), Some(Format::Xrgb8888));
+    assert_eq!(shm_format(AB24), Some(Format::Abgr8888));
+    assert_eq!(shm_format(NV12), None);
+
+    let offered = vec![
+        (NV12, vec![LINEAR]),
+        (AR24, vec![tiled, LINEAR]),
+        (XR24, vec![tiled, LINEAR]),
+        (AB24, Vec::new()),
+    ];
+    match pick_format(&offered, false) {
+        Some((fourcc, format, mods)) => {
+            assert_eq!((fourcc, format), (XR24, Format::Xrgb8888));
+            assert_eq!(mods, vec![LINEAR, tiled]);
+        }
+        None => panic!("expected a format"),
+    }
+    match pick_format(&offered, true) {
+        Some((fourcc, _, _)) => assert_eq!(fourcc, AR24),
+        None => panic!("expected a format with alpha"),
+    }
+    assert!(pick_format(&offered[..1], false).is_none());
+}
+
+#[cfg(feature = "freeze")]
+#[test]
+fn overlay_debug_boxes_map_logical_rects_onto_scaled_buffers() {
+    use crate::geometry::Geometry;
+    use crate::overlay_text::{draw_outline, logical_to_buffer};
+
+    let geo = |x, y, width, height| Geometry {
+        x,
+        y,
+        width,
+        height,
+    };
+    // Second output at logical x=1920, buffer at scale 2.
+    let output = geo(1920, 0, 100, 50);
+    assert_eq!(
+        logical_to_buffer(geo(1930, 10, 20, 5), output, 200, 100),
+        Some((20, 20, 40, 10))
+    );
+    // Windows hanging off the output are clipped; others are skipped.
+    assert_eq!(
+        logical_to_buffer(geo(1900, 40, 40, 40), output, 200, 100),
+        Some((0, 80, 40, 20))
+    );
+    assert_eq!(
+        logical_to_buffer(geo(0, 0, 100, 100), output, 200, 100),
+        None
+    );
+
+    let (width, height) = (8u32, 6u32);
+    let mut data = [0u8, 0, 0, 0xff].repeat((width * height) as usize);
+    draw_outline(&mut data, width, height, (1, 1, 5, 4), 1);
+    let pixel = |x: u32, y: u32| {
+        let offset = ((y * width + x) * 4) as usize;
+        [data[offset], data[offset + 1], data[offset + 2]]
+    };
+    assert_eq!(pixel(1, 1), [0xff, 0x00, 0xff]);
+    assert_eq!(pixel(5, 4), [0xff, 0x00, 0xff]);
+    assert_eq!(pixel(3, 2), [0, 0, 0]);
+    assert_eq!(pixel(0, 0), [0, 0, 0]);
+    assert_eq!(pixel(6, 5), [0, 0, 0]);
+}
+
+#[cfg(feature = "freeze")]
+#[test]
+fn overlay_hint_draws_text_box_at_requested_position() {
+    use crate::config::FreezeHintPosition;
+
+    let (width, height) = (200u32, 100u32);
+    let background = [0x80, 0x80, 0x80, 0xff];
+    let blank = background.repeat((width * height) as usize);
+    let row_changed = |data: &[u8], y: u32| {
+        data[(y * width * 4) as usize..((y + 1) * width * 4) as usize]
+            .chunks_exact(4)
+            .any(|px| px != background)
+    };
+
+    let mut top = blank.clone();
+    crate::overlay_text::draw_hint(&mut top, width, height, "Hi", FreezeHintPosition::Top, 1);
+    assert!(row_changed(&top, 20));
+    assert!(!row_changed(&top, 90));
+    // Text pixels are white, the box around them is dimmed.
+    assert!(top.chunks_exact(4).any(|px| px == [0xff, 0xff, 0xff, 0xff]));
+    assert!(top.chunks_exact(4).any(|px| px == [0x33, 0x33, 0x33, 0xff]));
+
+    let mut bottom = blank.clone();
+    crate::overlay_text::draw_hint(
+        &mut bottom,
+        width,
+        height,
+        "Hi",
+        FreezeHintPosition::Bottom,
+        1,
+    );
+    assert!(!row_changed(&bottom, 20));
+    assert!(row_changed(&bottom, 70));
+
+    // Oversized text is clipped instead of panicking.
+    let mut clipped = blank;
+    crate::overlay_text::draw_hint(
+        &mut clipped,
+        width,
+        height,
+        "Drag to select a region - Esc to cancel",
+        FreezeHintPosition::Center,
+        4,
+    );
+}
+
+#[test]
+fn flash_is_off_unless_configured() {
+    assert!(!crate::config::Config::default().capture.flash);
+    let config: crate::config::Config = match toml::from_str("[capture]\nflash = true\n") {
+        Ok(v) => v,
+        Err(err) => panic!("Failed to deserialize config: {}", err),
+    };
+    assert!(config.capture.flash);
+}
+
+#[test]
+fn exit_codes_tell_cancellation_from_failures() {
+    use crate::exit::{self, Stage};
+
+    let cancelled = crate::selector::map_api_error(
+        slurp_rs::SlurpError::Cancelled,
+        crate::selector::SelectionTarget::Region,
+    )
+    .context("Region selection");
+    assert_eq!(exit::exit_code(&cancelled), exit::CANCELLED);
+
+    let capture = anyhow::anyhow!("screencopy failed")
+        .context(Stage::Capture)
+        .context("while capturing DP-1");
+    assert_eq!(exit::exit_code(&capture), exit::CAPTURE_FAILED);
+    assert_eq!(
+        exit::exit_code(&anyhow::anyhow!("Invalid capture.quality 0")),
+        exit::OTHER
+    );
+
+    let geometry = geometry_or_panic(0, 0, 2, 2);
+    let dir = test_output_dir("exit-save");
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        panic!("create_dir_all failed: {}", err);
+    }
+    let blocker = dir.join("not-a-dir");
+    if let Err(err) = std::fs::write(&blocker, b"") {
+        panic!("write failed: {}", err);
+    }
+    let options = crate::save::SaveOptions {
+        silent: true,
+        ..Default::default()
+    };
+    match crate::save::save_geometry(
+        &mut MockBackend::new(),
+        &geometry,
+        None,
+        &blocker.join("shot.png"),
+        &options,
+    ) {
+        Ok(_) => panic!("expected saving under a file to fail"),
+        Err(err) => assert_eq!(exit::exit_code(&err), exit::SAVE_FAILED),
+    }
+}
+
+#[test]
+fn split_conflicts_with_single_file_options() {
+    let args = match Args::try_parse_from(["hyprshot-rs", "-m", "all", "--split"]) {
+        Ok(v) => v,
+        Err(err) => panic!("--split failed to parse: {}", err),
+    };
+    assert!(args.split);
+    for conflicting in [
+        &["-f", "shot.png"][..],
+        &["--raw"],
+        &["--clipboard-only"],
+        &["--skip-unchanged"],
+    ] {
+        let argv = ["hyprshot-rs", "-m", "all", "--split"]
+            .into_iter()
+            .chain(conflicting.iter().copied());
+        if Args::try_parse_from(argv).is_ok() {
+            panic!("--split should conflict with {:?}", conflicting);
+        }
+    }
+}
+
+#[test]
+fn still_recording_saves_numbered_frames_on_an_interval() {
+    use crate::record::{record_stills, still_frame_name};
+
+    assert_eq!(still_frame_name(7), "frame-00007.png");
+    let dir = test_output_dir("stills").join("timelapse");
+    let geometry = geometry_or_panic(0, 0, 2, 2);
+    let mut backend = MockBackend::new();
+    let frames = match record_stills(
+        &mut backend,
+        &geometry,
+        &dir,
+        Duration::from_millis(10),
+        Some(Duration::from_millis(25)),
+        crate::config::PngCompression::Fast,
+        false,
+    ) {
+        Ok(v) => v,
+        Err(err) => panic!("record_stills failed: {}", err),
+    };
+    assert_eq!(frames, 3);
+    assert_eq!(backend.captured.len(), 3);
+    for index in 1..=3 {
+        assert!(dir.join(still_frame_name(index)).is_file());
+    }
+    assert!(!dir.join(still_frame_name(4)).exists());
+}
+
+#[test]
+fn decorations_expand_windows_by_border_and_shadow() {
+    use crate::compositor::{Extents, option_int, option_vec2};
+
+    let border_only = Extents::new(2, None);
+    assert_eq!(
+        border_only,
+        Extents {
+            left: 2,
+            top: 2,
+            right: 2,
+            bottom: 2
+        }
+    );
+    // A shadow pushed down-right reaches further on those sides.
+    let shadow = Extents::new(2, Some((20, (0, 8))));
+    assert_eq!((shadow.left, shadow.right), (22, 22));
+    assert_eq!((shadow.top, shadow.bottom), (14, 30));
+    match shadow.expand(geometry_or_panic(100, 100, 400, 300)) {
+        Ok(geometry) => assert_eq!(geometry, geometry_or_panic(78, 86, 444, 344)),
+        Err(err) => panic!("expand failed: {}", err),
+    }
+
+    assert_eq!(
+        option_int(&serde_json::json!({"option": "general:border_size", "int": 2})),
+        Some(2)
+    );
+    assert_eq!(
+        option_vec2(&serde_json::json!({"vec2": [0.0, 8.0]})),
+        Some((0.0, 8.0))
+    );
+    assert_eq!(
+        option_vec2(&serde_json::json!({"custom": "3 -4"})),
+        Some((3.0, -4.0))
+    );
+}
+
+#[test]
+fn transparent_conflicts_with_screen_based_options() {
+    if Args::try_parse_from(["hyprshot-rs", "-m", "window", "--transparent"]).is_err() {
+        panic!("--transparent failed to parse");
+    }
+    for conflicting in ["--decorations", "--record"] {
+        if Args::try_parse_from(["hyprshot-rs", "-m", "window", "--transparent", conflicting])
+            .is_ok()
+        {
+            panic!("--transparent should conflict with {}", conflicting);
+        }
+    }
+    // Without a Hyprland address, the window can only be found by title.
+    #[cfg(not(feature = "ext-capture"))]
+    {
+        let window = crate::compositor::Window {
+            geometry: geometry_or_panic(0, 0, 100, 100),
+            title: "Terminal".to_string(),
+            class: "foot".to_string(),
+            address: None,
+            id: Some(7),
+            pid: None,
+            workspace: None,
+        };
+        match crate::backend::create_window_backend(&window, false, Box::new(MockBackend::new())) {
+            Ok(_) => panic!("expected --transparent to need the ext-capture feature"),
+            Err(err) => assert!(err.to_string().contains("ext-capture")),
+        }
+    }
+}
+
+#[cfg(feature = "freeze")]
+#[test]
+fn hyprland_window_addresses_become_export_handles() {
+    use crate::toplevel_export::window_handle;
+
+    // hyprland-toplevel-export takes the low 32 bits of the address.
+    match window_handle("0x55d1c2a3b4c0") {
+        Ok(handle) => assert_eq!(handle, 0xc2a3_b4c0),
+        Err(err) => panic!("address rejected: {err}"),
+    }
+    match window_handle("0x1f") {
+        Ok(handle) => assert_eq!(handle, 0x1f),
+        Err(err) => panic!("address rejected: {err}"),
+    }
+    for invalid in ["55d1c2a3b4c0", "0xnothex", ""] {
+        if window_handle(invalid).is_ok() {
+            panic!("{invalid:?} should not be a window address");
+        }
+    }
+}
+
+#[test]
+fn only_selecting_modes_delay_after_the_freeze() {
+    use crate::app::freeze_hint_text;
+
+    // These wait for a selection, so a delay runs after the freeze starts.
+    assert!(freeze_hint_text(&Mode::Region, false, false).is_some());
+    assert!(freeze_hint_text(&Mode::Window, false, false).is_some());
+    // These resolve their geometry at once: the delay runs first, so the
+    // active window is looked up (and frozen) where it is after the delay.
+    assert!(freeze_hint_text(&Mode::Window, true, false).is_none());
+    assert!(freeze_hint_text(&Mode::Output, true, false).is_none());
+    assert!(freeze_hint_text(&Mode::Output, false, true).is_none());
+    assert!(freeze_hint_text(&Mode::All, false, false).is_none());
+}
+
+#[test]
+fn paste_subcommand_takes_an_optional_path() {
+    use crate::cli::Action;
+
+    match Args::try_parse_from(["hyprshot-rs", "paste", "/tmp/shot.png"]) {
+        Ok(args) => match args.action {
+            Some(Action::Paste { path }) => {
+                assert_eq!(path, Some(PathBuf::from("/tmp/shot.png")))
+            }
+            other => panic!("expected paste, got {:?}", other),
+        },
+        Err(err) => panic!("paste should parse: {}", err),
+    }
+    match Args::try_parse_from(["hyprshot-rs", "--format", "jpeg", "paste"]) {
+        Ok(args) => assert!(matches!(args.action, Some(Action::Paste { path: None }))),
+        Err(err) => panic!("paste without a path should parse: {}", err),
+    }
 }
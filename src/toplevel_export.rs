@@ -0,0 +1,374 @@
+//! `--transparent` on Hyprland: copy a window's own buffer through
+//! `hyprland-toplevel-export-v1`, which names windows by their address, so
+//! windows with the same title can still be told apart.
+//!
+//! No crate ships this protocol; the bindings are generated from the copy in
+//! `protocols/`.
+
+use anyhow::{Context, Result, bail};
+use std::io::Read;
+use std::os::fd::{AsRawFd, BorrowedFd};
+use wayland_client::{
+    Connection, Dispatch, EventQueue, QueueHandle, WEnum,
+    protocol::{
+        wl_buffer::WlBuffer,
+        wl_registry::{self, WlRegistry},
+        wl_shm::{self, WlShm},
+        wl_shm_pool::WlShmPool,
+    },
+};
+
+use crate::backend::{CaptureBackend, CapturedImage};
+use crate::config::PngCompression;
+use crate::geometry::Geometry;
+use crate::shm::{SUPPORTED_FORMATS, to_rgba};
+
+#[allow(
+    dead_code,
+    non_camel_case_types,
+    non_upper_case_globals,
+    unused_imports
+)]
+#[allow(missing_docs, clippy::all)]
+mod protocol {
+    use wayland_client;
+    use wayland_client::protocol::*;
+
+    pub mod __interfaces {
+        use wayland_client::protocol::__interfaces::*;
+        wayland_scanner::generate_interfaces!("protocols/hyprland-toplevel-export-v1.xml");
+    }
+    use self::__interfaces::*;
+
+    wayland_scanner::generate_client_code!("protocols/hyprland-toplevel-export-v1.xml");
+}
+
+use protocol::{
+    hyprland_toplevel_export_frame_v1::{self, HyprlandToplevelExportFrameV1},
+    hyprland_toplevel_export_manager_v1::HyprlandToplevelExportManagerV1,
+};
+
+/// The protocol's handle for the window at Hyprland address `address`
+/// (`0x...`): the low 32 bits of the address.
+pub(crate) fn window_handle(address: &str) -> Result<u32> {
+    let digits = address
+        .strip_prefix("0x")
+        .with_context(|| format!("Window address '{}' doesn't start with 0x", address))?;
+    let address = u64::from_str_radix(digits, 16)
+        .with_context(|| format!("Window address '0x{}' isn't hexadecimal", digits))?;
+    Ok(address as u32)
+}
+
+/// One `buffer` event: an shm layout the compositor can copy into.
+struct ShmLayout {
+    format: wl_shm::Format,
+    width: u32,
+    height: u32,
+    stride: u32,
+}
+
+enum FrameStatus {
+    Pending,
+    Ready,
+    Failed,
+}
+
+struct State {
+    shm: Option<WlShm>,
+    manager: Option<HyprlandToplevelExportManagerV1>,
+    layouts: Vec<ShmLayout>,
+    buffers_done: bool,
+    y_invert: bool,
+    frame: FrameStatus,
+}
+
+/// Every region capture is the one window's own buffer, so the geometry only
+/// decided which window it is, not what gets cropped. Whole outputs still come
+/// from `live`.
+pub struct WindowBackend {
+    _conn: Connection,
+    queue: EventQueue<State>,
+    state: State,
+    address: String,
+    handle: u32,
+    overlay_cursor: bool,
+    live: Box<dyn CaptureBackend>,
+}
+
+impl WindowBackend {
+    pub fn new(address: &str, overlay_cursor: bool, live: Box<dyn CaptureBackend>) -> Result<Self> {
+        let handle = window_handle(address)?;
+        let conn = Connection::connect_to_env().context("Failed to connect to Wayland")?;
+        let mut queue = conn.new_event_queue();
+        let qh = queue.handle();
+        let _registry = conn.display().get_registry(&qh, ());
+
+        let mut state = State {
+            shm: None,
+            manager: None,
+            layouts: Vec::new(),
+            buffers_done: false,
+            y_invert: false,
+            frame: FrameStatus::Pending,
+        };
+        queue
+            .roundtrip(&mut state)
+            .context("Failed to initialize Wayland globals")?;
+        if state.manager.is_none() {
+            bail!(
+                "--transparent needs hyprland-toplevel-export-v1, which this compositor doesn't offer"
+            );
+        }
+        if state.shm.is_none() {
+            bail!("wl_shm not available");
+        }
+        Ok(Self {
+            _conn: conn,
+            queue,
+            state,
+            address: address.to_string(),
+            handle,
+            overlay_cursor,
+            live,
+        })
+    }
+
+    /// Copy the window into an shm buffer, alpha included.
+    fn capture_window(&mut self) -> Result<CapturedImage> {
+        let qh = self.queue.handle();
+        let (Some(manager), Some(shm)) = (self.state.manager.clone(), self.state.shm.clone())
+        else {
+            bail!("hyprland-toplevel-export globals are missing");
+        };
+        self.state.layouts.clear();
+        self.state.buffers_done = false;
+        self.state.y_invert = false;
+        self.state.frame = FrameStatus::Pending;
+        let frame = manager.capture_toplevel(i32::from(self.overlay_cursor), self.handle, &qh, ());
+        while !self.state.buffers_done && matches!(self.state.frame, FrameStatus::Pending) {
+            self.queue
+                .blocking_dispatch(&mut self.state)
+                .context("Failed to receive the window's buffer layout")?;
+        }
+        if matches!(self.state.frame, FrameStatus::Failed) {
+            frame.destroy();
+            bail!(
+                "Hyprland couldn't export window {}; it may have closed",
+                self.address
+            );
+        }
+
+        // Keep the alpha channel when the compositor offers one.
+        let alpha = [wl_shm::Format::Argb8888, wl_shm::Format::Abgr8888];
+        let layout = self
+            .state
+            .layouts
+            .iter()
+            .filter(|layout| alpha.contains(&layout.format))
+            .chain(&self.state.layouts)
+            .find(|layout| SUPPORTED_FORMATS.contains(&layout.format));
+        let Some(&ShmLayout {
+            format,
+            width,
+            height,
+            stride,
+        }) = layout
+        else {
+            frame.destroy();
+            let offered: Vec<wl_shm::Format> = self
+                .state
+                .layouts
+                .iter()
+                .map(|layout| layout.format)
+                .collect();
+            bail!("No supported shm format offered (got {:?})", offered);
+        };
+
+        let size = stride as usize * height as usize;
+        let tmp_file = tempfile::NamedTempFile::new()
+            .context("Failed to create temporary file for shm buffer")?;
+        tmp_file
+            .as_file()
+            .set_len(size as u64)
+            .context("Failed to resize shm buffer file")?;
+        let pool = shm.create_pool(
+            unsafe { BorrowedFd::borrow_raw(tmp_file.as_file().as_raw_fd()) },
+            i32::try_from(size).context("Window is too large for a shm buffer")?,
+            &qh,
+            (),
+        );
+        let buffer = pool.create_buffer(
+            0,
+            width as i32,
+            height as i32,
+            stride as i32,
+            format,
+            &qh,
+            (),
+        );
+        pool.destroy();
+
+        frame.copy(&buffer, 1);
+        while matches!(self.state.frame, FrameStatus::Pending) {
+            self.queue
+                .blocking_dispatch(&mut self.state)
+                .context("Failed to wait for the window's frame")?;
+        }
+        frame.destroy();
+        buffer.destroy();
+        if matches!(self.state.frame, FrameStatus::Failed) {
+            bail!("Hyprland failed to copy window {}", self.address);
+        }
+
+        let mut pixels = Vec::with_capacity(size);
+        tmp_file
+            .reopen()
+            .and_then(|mut file| file.read_to_end(&mut pixels))
+            .context("Failed to read shm buffer")?;
+        let row = width as usize * 4;
+        let mut rows: Vec<&[u8]> = pixels
+            .chunks(stride as usize)
+            .map(|line| &line[..row])
+            .take(height as usize)
+            .collect();
+        if self.state.y_invert {
+            rows.reverse();
+        }
+        Ok(CapturedImage {
+            data: to_rgba(&rows.concat(), format),
+            width,
+            height,
+        })
+    }
+}
+
+impl CaptureBackend for WindowBackend {
+    fn capture_region(&mut self, _geometry: &Geometry) -> Result<CapturedImage> {
+        self.capture_window()
+    }
+
+    fn capture_output(&mut self, name: &str) -> Result<CapturedImage> {
+        self.live.capture_output(name)
+    }
+
+    fn to_png(&self, image: &CapturedImage, compression: PngCompression) -> Result<Vec<u8>> {
+        crate::png_encoder::encode(image, compression.level())
+            .context("Failed to encode screenshot as PNG")
+    }
+}
+
+impl Dispatch<WlRegistry, ()> for State {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name, interface, ..
+        } = event
+        {
+            match interface.as_str() {
+                "wl_shm" => {
+                    state.shm = Some(registry.bind(name, 1, qh, ()));
+                }
+                "hyprland_toplevel_export_manager_v1" => {
+                    state.manager = Some(registry.bind(name, 1, qh, ()));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Dispatch<HyprlandToplevelExportFrameV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &HyprlandToplevelExportFrameV1,
+        event: hyprland_toplevel_export_frame_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            hyprland_toplevel_export_frame_v1::Event::Buffer {
+                format: WEnum::Value(format),
+                width,
+                height,
+                stride,
+            } => {
+                state.layouts.push(ShmLayout {
+                    format,
+                    width,
+                    height,
+                    stride,
+                });
+            }
+            hyprland_toplevel_export_frame_v1::Event::BufferDone => {
+                state.buffers_done = true;
+            }
+            hyprland_toplevel_export_frame_v1::Event::Flags {
+                flags: WEnum::Value(flags),
+            } => {
+                state.y_invert = flags.contains(hyprland_toplevel_export_frame_v1::Flags::YInvert);
+            }
+            hyprland_toplevel_export_frame_v1::Event::Ready { .. } => {
+                state.frame = FrameStatus::Ready;
+            }
+            hyprland_toplevel_export_frame_v1::Event::Failed => {
+                state.frame = FrameStatus::Failed;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<HyprlandToplevelExportManagerV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &HyprlandToplevelExportManagerV1,
+        _: protocol::hyprland_toplevel_export_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlShm, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlShm,
+        _: wl_shm::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlShmPool, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlShmPool,
+        _: wayland_client::protocol::wl_shm_pool::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlBuffer, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlBuffer,
+        _: wayland_client::protocol::wl_buffer::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
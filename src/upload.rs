@@ -0,0 +1,211 @@
+//! `--upload`: send the saved screenshot to an HTTP endpoint from
+//! `[upload.targets.NAME]` with `curl` and pull the public URL out of the reply.
+
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use serde_json::Value;
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::config::{UploadConfig, UploadTarget};
+
+/// Give up on a stalled upload instead of hanging the keybind.
+const UPLOAD_TIMEOUT_SECS: u32 = 60;
+
+/// The target named by `--upload NAME`, or for a bare `--upload` (`name` empty)
+/// `upload.default`, or the only configured target.
+pub(crate) fn resolve_target<'a>(
+    config: &'a UploadConfig,
+    name: &str,
+) -> Result<(&'a str, &'a UploadTarget)> {
+    if config.targets.is_empty() {
+        bail!("--upload needs a target; add one under [upload.targets.NAME] in the config");
+    }
+    let name = match (name, config.default.as_deref()) {
+        ("", Some(default)) => default,
+        ("", None) if config.targets.len() == 1 => config
+            .targets
+            .keys()
+            .next()
+            .map(String::as_str)
+            .unwrap_or(""),
+        ("", None) => bail!(
+            "Several upload targets are configured; pass one to --upload or set upload.default ({})",
+            known_targets(config)
+        ),
+        (name, _) => name,
+    };
+    let (name, target) = config.targets.get_key_value(name).with_context(|| {
+        format!(
+            "Unknown upload target '{}' (configured: {})",
+            name,
+            known_targets(config)
+        )
+    })?;
+    if let Some(pattern) = &target.url_regex {
+        Regex::new(pattern)
+            .with_context(|| format!("Invalid url_regex for upload target '{}'", name))?;
+    }
+    Ok((name, target))
+}
+
+fn known_targets(config: &UploadConfig) -> String {
+    config
+        .targets
+        .keys()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `curl` arguments that send `file` to `target` and print the reply body.
+/// Headers are read from stdin ([`curl_headers`]) rather than passed here,
+/// since anything on the command line shows up in `/proc/PID/cmdline`.
+pub(crate) fn curl_args(target: &UploadTarget, file: &Path) -> Vec<String> {
+    let mut args: Vec<String> = ["--silent", "--show-error", "--fail-with-body", "--max-time"]
+        .map(String::from)
+        .into();
+    args.push(UPLOAD_TIMEOUT_SECS.to_string());
+    args.push("--request".into());
+    args.push(target.method.to_ascii_uppercase());
+    if !target.headers.is_empty() {
+        args.push("--header".into());
+        args.push("@-".into());
+    }
+    match &target.form_field {
+        Some(field) => {
+            args.push("--form".into());
+            // Quoted, so a ';' or ',' in the path isn't read as a curl option.
+            args.push(format!("{}=@\"{}\"", field, file.display()));
+        }
+        None => {
+            args.push("--data-binary".into());
+            args.push(format!("@{}", file.display()));
+        }
+    }
+    args.push(target.url.clone());
+    args
+}
+
+/// `target`'s headers, one per line, for `--header @-`. A line break in a
+/// name or value would smuggle in another header, so it is an error.
+pub(crate) fn curl_headers(target: &UploadTarget) -> Result<String> {
+    let mut lines = String::new();
+    for (name, value) in &target.headers {
+        if name.contains(['\r', '\n']) || value.contains(['\r', '\n']) {
+            bail!("Upload header '{}' contains a line break", name.trim());
+        }
+        lines.push_str(&format!("{}: {}\n", name, value));
+    }
+    Ok(lines)
+}
+
+/// The URL in `reply`: at `json_path`, else the `url_regex` match, else the
+/// whole reply when it is a bare URL (as from 0x0.st).
+pub(crate) fn extract_url(target: &UploadTarget, reply: &str) -> Result<String> {
+    let reply = reply.trim();
+    if let Some(path) = &target.json_path {
+        let json: Value = serde_json::from_str(reply)
+            .with_context(|| format!("Upload reply is not JSON: {}", truncate(reply)))?;
+        let mut value = &json;
+        for key in path.split('.').filter(|key| !key.is_empty()) {
+            let next = match key.parse::<usize>() {
+                Ok(index) => value.get(index),
+                Err(_) => value.get(key),
+            };
+            value = next
+                .with_context(|| format!("Upload reply has no '{}': {}", path, truncate(reply)))?;
+        }
+        return value
+            .as_str()
+            .map(str::to_string)
+            .with_context(|| format!("'{}' in the upload reply is not a string", path));
+    }
+    if let Some(pattern) = &target.url_regex {
+        let regex = Regex::new(pattern).context("Invalid url_regex")?;
+        let captures = regex.captures(reply).with_context(|| {
+            format!(
+                "No match for '{}' in the upload reply: {}",
+                pattern,
+                truncate(reply)
+            )
+        })?;
+        let url = captures.get(1).or_else(|| captures.get(0));
+        return Ok(url.map(|m| m.as_str().to_string()).unwrap_or_default());
+    }
+    if (reply.starts_with("https://") || reply.starts_with("http://"))
+        && !reply.contains(char::is_whitespace)
+    {
+        return Ok(reply.to_string());
+    }
+    bail!(
+        "Upload reply is not a URL; set json_path or url_regex for this target: {}",
+        truncate(reply)
+    )
+}
+
+fn truncate(reply: &str) -> String {
+    const MAX_CHARS: usize = 200;
+    if reply.chars().count() > MAX_CHARS {
+        format!("{}...", reply.chars().take(MAX_CHARS).collect::<String>())
+    } else {
+        reply.to_string()
+    }
+}
+
+/// Upload `file` and return its URL.
+pub(crate) fn upload(
+    name: &str,
+    target: &UploadTarget,
+    file: &Path,
+    debug: bool,
+) -> Result<String> {
+    let args = curl_args(target, file);
+    let headers = curl_headers(target)?;
+    // Headers often carry API keys, so only the endpoint is logged.
+    if debug {
+        eprintln!(
+            "Uploading {} to '{}': {} {}",
+            file.display(),
+            name,
+            target.method.to_ascii_uppercase(),
+            target.url
+        );
+    }
+    let mut child = Command::new("curl")
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| match err.kind() {
+            ErrorKind::NotFound => anyhow::anyhow!("--upload needs curl in PATH"),
+            _ => anyhow::Error::new(err).context("Failed to start curl"),
+        })?;
+    // Dropping stdin closes it, so curl sees the end of the header list.
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(headers.as_bytes())
+            .context("Failed to pass upload headers to curl")?;
+    }
+    let output = child
+        .wait_with_output()
+        .context("Failed to wait for curl")?;
+    let reply = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!(
+            "Upload to '{}' failed ({}): {}",
+            name,
+            stderr.trim(),
+            truncate(reply.trim())
+        );
+    }
+    let url = extract_url(target, &reply)
+        .with_context(|| format!("Upload to '{}' returned no URL", name))?;
+    if debug {
+        eprintln!("Uploaded to {}", url);
+    }
+    Ok(url)
+}
@@ -6,308 +6,83 @@ use std::time::{Duration, Instant};
 
 use crate::geometry::Geometry;
 
-#[cfg(feature = "freeze")]
-use wayland_client::{
-    Connection, Dispatch, QueueHandle,
-    protocol::{wl_output::Mode as WlOutputMode, wl_output::WlOutput, wl_registry::WlRegistry},
-};
-#[cfg(feature = "freeze")]
-use wayland_protocols::xdg::xdg_output::zv1::client::{
-    zxdg_output_manager_v1::ZxdgOutputManagerV1, zxdg_output_v1::ZxdgOutputV1,
-};
-
 pub fn trim(geometry: &Geometry, debug: bool) -> Result<Geometry> {
     if debug {
         eprintln!("Input geometry: {}", geometry);
     }
-    let x = geometry.x;
-    let y = geometry.y;
-    let width = geometry.width;
-    let height = geometry.height;
-
-    let mut mon_x = 0;
-    let mut mon_y = 0;
-    let mut mon_width = 0;
-    let mut mon_height = 0;
-
-    let mut found = false;
 
     #[cfg(feature = "freeze")]
-    if let Some((mx, my, mw, mh)) = wayland_monitor_bounds(x, y)? {
-        mon_x = mx;
-        mon_y = my;
-        mon_width = mw;
-        mon_height = mh;
-        found = true;
-    }
+    let monitors = wayland_monitors()?;
+    #[cfg(not(feature = "freeze"))]
+    let monitors: Vec<Geometry> = Vec::new();
 
-    if !found {
+    if monitors.is_empty() {
         if debug {
             eprintln!("Warning: could not determine monitor bounds; using raw geometry");
         }
         return Ok(*geometry);
     }
 
-    let mut cropped_x = x;
-    let mut cropped_y = y;
-    let mut cropped_width = width;
-    let mut cropped_height = height;
-
-    if x + width > mon_x + mon_width {
-        cropped_width = mon_x + mon_width - x;
-    }
-    if y + height > mon_y + mon_height {
-        cropped_height = mon_y + mon_height - y;
-    }
-    if x < mon_x {
-        cropped_x = mon_x;
-        cropped_width -= mon_x - x;
-    }
-    if y < mon_y {
-        cropped_y = mon_y;
-        cropped_height -= mon_y - y;
-    }
-
-    if cropped_width <= 0 || cropped_height <= 0 {
-        return Err(anyhow::anyhow!(
-            "Invalid cropped dimensions: width={} or height={}",
-            cropped_width,
-            cropped_height
-        ));
-    }
-
-    let cropped = Geometry::new(cropped_x, cropped_y, cropped_width, cropped_height)?;
+    let cropped = crop_to_monitors(geometry, &monitors)
+        .with_context(|| format!("Geometry {} is not on any monitor", geometry))?;
     if debug {
         eprintln!("Cropped geometry: {}", cropped);
     }
     Ok(cropped)
 }
 
-#[cfg(feature = "freeze")]
-fn wayland_monitor_bounds(x: i32, y: i32) -> Result<Option<(i32, i32, i32, i32)>> {
-    let conn = Connection::connect_to_env().context("Failed to connect to Wayland")?;
-    let mut event_queue = conn.new_event_queue();
-    let qh = event_queue.handle();
-
-    let _registry = conn.display().get_registry(&qh, ());
-
-    #[derive(Debug)]
-    struct OutputKey(usize);
-
-    struct OutputEntry {
-        output: WlOutput,
-        xdg_output: Option<ZxdgOutputV1>,
-        pos_x: Option<i32>,
-        pos_y: Option<i32>,
-        mode_width: Option<i32>,
-        mode_height: Option<i32>,
-        scale: i32,
-        logical_x: Option<i32>,
-        logical_y: Option<i32>,
-        logical_width: Option<i32>,
-        logical_height: Option<i32>,
-    }
-
-    struct State {
-        outputs: Vec<OutputEntry>,
-        xdg_output_manager: Option<ZxdgOutputManagerV1>,
-    }
-
-    impl Dispatch<WlRegistry, ()> for State {
-        fn event(
-            state: &mut Self,
-            registry: &WlRegistry,
-            event: wayland_client::protocol::wl_registry::Event,
-            _: &(),
-            _: &Connection,
-            qh: &QueueHandle<Self>,
-        ) {
-            if let wayland_client::protocol::wl_registry::Event::Global {
-                name,
-                interface,
-                version,
-            } = event
-            {
-                match interface.as_str() {
-                    "wl_output" => {
-                        let idx = state.outputs.len();
-                        let output = registry.bind::<WlOutput, _, _>(
-                            name,
-                            version.min(4),
-                            qh,
-                            OutputKey(idx),
-                        );
-                        state.outputs.push(OutputEntry {
-                            output,
-                            xdg_output: None,
-                            pos_x: None,
-                            pos_y: None,
-                            mode_width: None,
-                            mode_height: None,
-                            scale: 1,
-                            logical_x: None,
-                            logical_y: None,
-                            logical_width: None,
-                            logical_height: None,
-                        });
-                    }
-                    "zxdg_output_manager_v1" => {
-                        state.xdg_output_manager =
-                            Some(registry.bind(name, version.min(3), qh, ()));
-                    }
-                    _ => {}
-                }
-            }
+/// Crop `geometry` to the monitor it overlaps the most, so a window hanging
+/// off the edge of one output (or straddling two) is still captured from the
+/// output that shows most of it. Ties go to the first monitor.
+pub(crate) fn crop_to_monitors(geometry: &Geometry, monitors: &[Geometry]) -> Option<Geometry> {
+    let area = |g: &Geometry| g.width as i64 * g.height as i64;
+    let mut best: Option<Geometry> = None;
+    for monitor in monitors {
+        if let Some(overlap) = geometry.intersect(*monitor)
+            && best.is_none_or(|b| area(&overlap) > area(&b))
+        {
+            best = Some(overlap);
         }
     }
+    best
+}
 
-    impl Dispatch<WlOutput, OutputKey> for State {
-        fn event(
-            state: &mut Self,
-            _: &WlOutput,
-            event: wayland_client::protocol::wl_output::Event,
-            data: &OutputKey,
-            _: &Connection,
-            _: &QueueHandle<Self>,
-        ) {
-            let Some(entry) = state.outputs.get_mut(data.0) else {
-                return;
-            };
-            match event {
-                wayland_client::protocol::wl_output::Event::Geometry { x, y, .. } => {
-                    entry.pos_x = Some(x);
-                    entry.pos_y = Some(y);
-                }
-                wayland_client::protocol::wl_output::Event::Mode {
-                    flags,
-                    width,
-                    height,
-                    ..
-                } => {
-                    let is_current = match flags {
-                        wayland_client::WEnum::Value(f) => f.contains(WlOutputMode::Current),
-                        wayland_client::WEnum::Unknown(_) => false,
-                    };
-                    if is_current {
-                        entry.mode_width = Some(width);
-                        entry.mode_height = Some(height);
-                    }
-                }
-                wayland_client::protocol::wl_output::Event::Scale { factor } => {
-                    entry.scale = factor.max(1);
-                }
-                _ => {}
-            }
-        }
-    }
-
-    impl Dispatch<ZxdgOutputV1, OutputKey> for State {
-        fn event(
-            state: &mut Self,
-            _: &ZxdgOutputV1,
-            event: wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1::Event,
-            data: &OutputKey,
-            _: &Connection,
-            _: &QueueHandle<Self>,
-        ) {
-            let Some(entry) = state.outputs.get_mut(data.0) else {
-                return;
-            };
-            match event {
-                wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1::Event::LogicalPosition { x, y } => {
-                    entry.logical_x = Some(x);
-                    entry.logical_y = Some(y);
-                }
-                wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1::Event::LogicalSize { width, height } => {
-                    entry.logical_width = Some(width);
-                    entry.logical_height = Some(height);
-                }
-                _ => {}
-            }
-        }
-    }
-
-    impl Dispatch<ZxdgOutputManagerV1, ()> for State {
-        fn event(
-            _: &mut Self,
-            _: &ZxdgOutputManagerV1,
-            _: wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_manager_v1::Event,
-            _: &(),
-            _: &Connection,
-            _: &QueueHandle<Self>,
-        ) {
-        }
-    }
-
-    let mut state = State {
-        outputs: Vec::new(),
-        xdg_output_manager: None,
-    };
-
-    event_queue
-        .roundtrip(&mut state)
-        .context("Failed to initialize Wayland outputs")?;
-
-    if let Some(manager) = &state.xdg_output_manager {
-        for (idx, entry) in state.outputs.iter_mut().enumerate() {
-            let xdg_output = manager.get_xdg_output(&entry.output, &qh, OutputKey(idx));
-            entry.xdg_output = Some(xdg_output);
-        }
-        event_queue
-            .roundtrip(&mut state)
-            .context("Failed to receive Wayland output geometry")?;
-    }
-
-    fn output_logical_size(output: &OutputEntry) -> Option<(i32, i32)> {
-        if let (Some(width), Some(height)) = (output.logical_width, output.logical_height) {
-            return Some((width, height));
-        }
-        let mode_width = output.mode_width?;
-        let mode_height = output.mode_height?;
-        let scale = output.scale.max(1);
-        Some((
-            ((mode_width as f64) / (scale as f64)).round() as i32,
-            ((mode_height as f64) / (scale as f64)).round() as i32,
-        ))
-    }
+#[cfg(feature = "freeze")]
+pub(crate) fn wayland_monitors() -> Result<Vec<Geometry>> {
+    Ok(crate::outputs::enumerate()?
+        .iter()
+        .filter_map(crate::outputs::OutputEntry::geometry)
+        .collect())
+}
 
-    for output in &state.outputs {
-        let (ox, oy) = match (
-            output.logical_x.or(output.pos_x),
-            output.logical_y.or(output.pos_y),
-        ) {
-            (Some(ox), Some(oy)) => (ox, oy),
-            _ => continue,
-        };
-        let (ow, oh) = match output_logical_size(output) {
-            Some(v) => v,
-            None => continue,
-        };
-        if x >= ox && x < ox + ow && y >= oy && y < oy + oh {
-            return Ok(Some((ox, oy, ow, oh)));
+// Wait for a spawned process with a hard timeout; used for wl-copy in save.rs.
+pub fn wait_with_timeout(child: &mut Child, timeout: Duration) -> Result<ExitStatus> {
+    match wait_for_exit(child, timeout)? {
+        Some(status) => Ok(status),
+        None => {
+            let _ = child.kill();
+            let _ = child.wait();
+            Err(anyhow::anyhow!("Process timed out after {:?}", timeout))
         }
     }
-
-    Ok(None)
 }
 
-// Wait for a spawned process with a hard timeout; used for wl-copy in save.rs.
-pub fn wait_with_timeout(child: &mut Child, timeout: Duration) -> Result<ExitStatus> {
+// Wait up to `timeout` for a process to exit; `None` means it is still running
+// and is left alone. Used for the post-capture command in save.rs.
+pub fn wait_for_exit(child: &mut Child, timeout: Duration) -> Result<Option<ExitStatus>> {
     let start = Instant::now();
     loop {
         if let Some(status) = child.try_wait().context("Failed to poll process status")? {
-            return Ok(status);
+            return Ok(Some(status));
         }
         if start.elapsed() >= timeout {
-            let _ = child.kill();
-            let _ = child.wait();
-            return Err(anyhow::anyhow!("Process timed out after {:?}", timeout));
+            return Ok(None);
         }
         thread::sleep(Duration::from_millis(10));
     }
 }
 
-// Run a short-lived command with a timeout and capture Output; used for hyprctl/swaymsg.
+// Run a short-lived command with a timeout and capture Output (wallpaper tools).
 pub fn output_with_timeout(mut cmd: Command, timeout: Duration) -> Result<Output> {
     cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
     let mut child = cmd.spawn().context("Failed to spawn command")?;
@@ -351,3 +126,25 @@ pub fn output_with_timeout(mut cmd: Command, timeout: Duration) -> Result<Output
         stderr,
     })
 }
+
+/// Standard base64 (RFC 4648) with padding.
+pub(crate) fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for group in data.chunks(3) {
+        let bytes = [
+            group[0],
+            *group.get(1).unwrap_or(&0),
+            *group.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+        for (i, shift) in [18, 12, 6, 0].into_iter().enumerate() {
+            if i <= group.len() {
+                out.push(char::from(ALPHABET[(bits >> shift & 0x3f) as usize]));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
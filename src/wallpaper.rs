@@ -0,0 +1,103 @@
+//! `--set-wallpaper`: hand the saved screenshot to a running wallpaper daemon.
+
+use anyhow::{Context, Result};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::utils::output_with_timeout;
+
+const IPC_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Wallpaper tools hyprshot-rs knows how to drive, in detection order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WallpaperTool {
+    Swww,
+    Hyprpaper,
+    Swaybg,
+}
+
+impl WallpaperTool {
+    const ALL: [Self; 3] = [Self::Swww, Self::Hyprpaper, Self::Swaybg];
+
+    /// Process name that shows the tool is running.
+    fn process_name(self) -> &'static str {
+        match self {
+            Self::Swww => "swww-daemon",
+            Self::Hyprpaper => "hyprpaper",
+            Self::Swaybg => "swaybg",
+        }
+    }
+}
+
+/// Command that shows `path` on every output with `tool`.
+pub(crate) fn wallpaper_command(tool: WallpaperTool, path: &Path) -> Vec<String> {
+    let path = path.to_string_lossy().into_owned();
+    match tool {
+        WallpaperTool::Swww => vec!["swww".into(), "img".into(), path],
+        // An empty monitor name applies to all outputs.
+        WallpaperTool::Hyprpaper => vec![
+            "hyprctl".into(),
+            "hyprpaper".into(),
+            "reload".into(),
+            format!(",{}", path),
+        ],
+        WallpaperTool::Swaybg => vec![
+            "swaybg".into(),
+            "-i".into(),
+            path,
+            "-m".into(),
+            "fill".into(),
+        ],
+    }
+}
+
+fn is_running(process: &str) -> bool {
+    let mut cmd = Command::new("pgrep");
+    cmd.arg("-x").arg(process);
+    output_with_timeout(cmd, IPC_TIMEOUT).is_ok_and(|output| output.status.success())
+}
+
+fn detect_tool() -> Option<WallpaperTool> {
+    WallpaperTool::ALL
+        .into_iter()
+        .find(|tool| is_running(tool.process_name()))
+}
+
+/// Set `path` as the wallpaper with the first running tool.
+pub fn set_wallpaper(path: &Path, debug: bool) -> Result<()> {
+    let tool = detect_tool()
+        .context("No running wallpaper tool found (looked for swww-daemon, hyprpaper, swaybg)")?;
+    let argv = wallpaper_command(tool, path);
+    if debug {
+        eprintln!("Setting wallpaper with {:?}: {:?}", tool, argv);
+    }
+
+    let mut cmd = Command::new(&argv[0]);
+    cmd.args(&argv[1..]);
+    if tool == WallpaperTool::Swaybg {
+        // swaybg has no IPC: replace the running instance with one showing the new image.
+        let mut pkill = Command::new("pkill");
+        pkill.arg("-x").arg("swaybg");
+        let _ = output_with_timeout(pkill, IPC_TIMEOUT);
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn()
+            .context("Failed to start swaybg")?;
+        return Ok(());
+    }
+
+    let output = output_with_timeout(cmd, IPC_TIMEOUT)
+        .with_context(|| format!("Failed to run {}", argv[0]))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "{} failed: {}",
+            argv[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}